@@ -0,0 +1,90 @@
+//! Watcher event throughput on a 1M-entry index.
+//!
+//! Run with `cargo bench --bench index_store`. Simulates the create/modify/remove
+//! bursts produced by `git checkout` or `npm install` against `IndexStore`, and
+//! against the old `Vec<FileEntry>` + `retain` approach for comparison.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use trace_lib::indexer::{EntryKind, FileEntry, IndexStore};
+
+const INDEX_SIZE: usize = 1_000_000;
+const EVENTS: usize = 100_000;
+/// The Vec baseline is O(n) per event, so only a handful of events are timed.
+const VEC_EVENTS: usize = 200;
+
+fn entry(i: usize) -> FileEntry {
+    FileEntry {
+        name: format!("file_{}.rs", i),
+        path: format!("/home/bench/project_{}/src/file_{}.rs", i % 1000, i),
+        kind: EntryKind::File,
+        size: i as u64,
+        modified: 1_700_000_000,
+        icon_path: None,
+        keywords: None,
+        generic_name: None,
+    }
+}
+
+/// Spread event targets across the whole index with a cheap LCG.
+fn target(n: usize) -> usize {
+    (n.wrapping_mul(2_654_435_761) + 12_345) % INDEX_SIZE
+}
+
+fn report(label: &str, events: usize, elapsed: Duration) {
+    let per_sec = events as f64 / elapsed.as_secs_f64();
+    println!(
+        "{:<28} {:>8} events in {:>10.2?}  ({:>12.0} events/s)",
+        label, events, elapsed, per_sec
+    );
+}
+
+fn bench_store() {
+    let mut store = IndexStore::new();
+    store.extend((0..INDEX_SIZE).map(entry));
+
+    // Modify: replace existing entries in place
+    let start = Instant::now();
+    for n in 0..EVENTS {
+        let mut e = entry(target(n));
+        e.size += 1;
+        black_box(store.upsert(e));
+    }
+    report("IndexStore modify", EVENTS, start.elapsed());
+
+    // Remove
+    let start = Instant::now();
+    for n in 0..EVENTS {
+        black_box(store.remove(&entry(target(n)).path));
+    }
+    report("IndexStore remove", EVENTS, start.elapsed());
+
+    // Create: re-insert into the freed slots
+    let start = Instant::now();
+    for n in 0..EVENTS {
+        black_box(store.upsert(entry(target(n))));
+    }
+    report("IndexStore create", EVENTS, start.elapsed());
+
+    assert_eq!(store.len(), INDEX_SIZE);
+}
+
+fn bench_vec_baseline() {
+    let mut vec: Vec<FileEntry> = (0..INDEX_SIZE).map(entry).collect();
+
+    let start = Instant::now();
+    for n in 0..VEC_EVENTS {
+        let e = entry(target(n));
+        let path = e.path.clone();
+        vec.retain(|x| x.path != path);
+        vec.push(e);
+    }
+    report("Vec retain+push (old)", VEC_EVENTS, start.elapsed());
+    black_box(vec.len());
+}
+
+fn main() {
+    println!("Index size: {} entries", INDEX_SIZE);
+    bench_store();
+    bench_vec_baseline();
+}
@@ -1,9 +1,22 @@
 /// Math expression evaluator & graph point sampler.
-/// Handles natural-language patterns like "15% of 300", implicit
-/// multiplication ("2(3)", "3x"), and all standard math functions.
+/// Expressions are parsed into a tree by `calc_parse` — implicit
+/// multiplication ("2(3)", "3x"), percentages ("15% of 300", "200 + 10%"),
+/// units and conversions ("60 mph in km/h") and the standard math
+/// functions — then compiled once, with dimensions checked, into a program
+/// that can be evaluated for many values of x. Variables, user functions
+/// and `ans` come from a [`Memory`] that committed calculations update.
+/// Values without x are also worked out exactly, or to many digits, by
+/// `calc_exact`. Whole-number and bitwise math goes to `calc_int` instead.
 
+use num_rational::BigRational;
+use num_traits::ToPrimitive;
 use serde::{Deserialize, Serialize};
 
+use crate::calc_exact::{self, Real};
+use crate::calc_int::{self, Bases, Whole};
+use crate::calc_parse::{self, BinOp, Expr, ExprKind, ParseError, Statement, Tok};
+use crate::calc_units::{self, Dim, Unit};
+
 // ─── Types ───────────────────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,6 +25,18 @@ pub struct CalcResult {
     pub result: f64,
     pub display: String,
     pub has_variable: bool,
+    /// Unit of `result` (also shown in `display`), e.g. "km/h"
+    pub unit: Option<String>,
+    /// Why an expression that looks like math could not be read
+    pub error: Option<String>,
+    /// Character offset the error points at
+    pub error_position: Option<usize>,
+    /// Programmer-mode results in every base
+    pub bases: Option<Bases>,
+    /// The exact fraction when `display` is rounded, e.g. "1/3"
+    pub fraction: Option<String>,
+    /// More digits than `display`, e.g. all of `50!`
+    pub precise: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,82 +45,851 @@ pub struct GraphPoint {
     pub y: f64,
 }
 
-// ─── Preprocessor ────────────────────────────────────────
+/// A remembered value: a variable, or the previous result (`ans`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stored {
+    pub name: String,
+    /// In SI base units
+    pub value: f64,
+    pub dim: Dim,
+    /// Unit it was shown in, so later results can use it too
+    pub unit: Option<Unit>,
+    /// As shown when stored, e.g. "5 km"
+    pub display: String,
+    /// The value as an exact fraction in SI base units, e.g. "1/3"
+    #[serde(default)]
+    pub exact: Option<String>,
+    /// The value to more digits than `value` holds, when it isn't exact
+    #[serde(default)]
+    pub approx: Option<String>,
+}
+
+impl Stored {
+    /// The value to full precision, in SI base units.
+    fn real(&self) -> Option<Real> {
+        match (&self.exact, &self.approx) {
+            (Some(exact), _) => Real::parse(exact, true),
+            (None, Some(approx)) => Real::parse(approx, false),
+            (None, None) => Real::from_f64(self.value),
+        }
+    }
+}
+
+/// `f(x) = x^2 + 1`, kept as source and compiled wherever it is called.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserFunction {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: String,
+}
+
+/// Variables, functions and `ans` visible to expressions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Memory {
+    pub variables: Vec<Stored>,
+    pub functions: Vec<UserFunction>,
+    pub ans: Option<Stored>,
+}
+
+impl Memory {
+    pub fn variable(&self, name: &str) -> Option<&Stored> {
+        if name == "ans" {
+            return self.ans.as_ref();
+        }
+        self.variables.iter().find(|v| v.name == name)
+    }
+
+    fn function(&self, name: &str) -> Option<&UserFunction> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+
+    /// Forget a variable or function; returns whether there was one.
+    pub fn forget(&mut self, name: &str) -> bool {
+        let before = self.variables.len() + self.functions.len();
+        self.variables.retain(|v| v.name != name);
+        self.functions.retain(|f| f.name != name);
+        before != self.variables.len() + self.functions.len()
+    }
+}
+
+// ─── Functions & Constants ───────────────────────────────
+
+#[derive(Debug, Clone, Copy)]
+enum Func {
+    Unary(fn(f64) -> f64),
+    Binary(fn(f64, f64) -> f64),
+    /// Any number of arguments, folded left: min, max
+    Fold(fn(f64, f64) -> f64),
+}
+
+const FUNCTIONS: &[(&str, Func)] = &[
+    ("sin", Func::Unary(f64::sin)),
+    ("cos", Func::Unary(f64::cos)),
+    ("tan", Func::Unary(f64::tan)),
+    ("asin", Func::Unary(f64::asin)),
+    ("acos", Func::Unary(f64::acos)),
+    ("atan", Func::Unary(f64::atan)),
+    ("sinh", Func::Unary(f64::sinh)),
+    ("cosh", Func::Unary(f64::cosh)),
+    ("tanh", Func::Unary(f64::tanh)),
+    ("asinh", Func::Unary(f64::asinh)),
+    ("acosh", Func::Unary(f64::acosh)),
+    ("atanh", Func::Unary(f64::atanh)),
+    ("sqrt", Func::Unary(f64::sqrt)),
+    ("cbrt", Func::Unary(f64::cbrt)),
+    ("abs", Func::Unary(f64::abs)),
+    ("exp", Func::Unary(f64::exp)),
+    ("ln", Func::Unary(f64::ln)),
+    // "log" is base 10, the calculator-button convention
+    ("log", Func::Unary(f64::log10)),
+    ("log10", Func::Unary(f64::log10)),
+    ("log2", Func::Unary(f64::log2)),
+    ("floor", Func::Unary(f64::floor)),
+    ("ceil", Func::Unary(f64::ceil)),
+    ("round", Func::Unary(f64::round)),
+    ("trunc", Func::Unary(f64::trunc)),
+    ("signum", Func::Unary(f64::signum)),
+    ("sign", Func::Unary(f64::signum)),
+    ("atan2", Func::Binary(f64::atan2)),
+    ("hypot", Func::Binary(f64::hypot)),
+    ("pow", Func::Binary(f64::powf)),
+    ("factorial", Func::Unary(factorial)),
+    ("min", Func::Fold(f64::min)),
+    ("max", Func::Fold(f64::max)),
+];
+
+const CONSTANTS: &[(&str, f64)] = &[
+    ("pi", std::f64::consts::PI),
+    ("e", std::f64::consts::E),
+    ("tau", std::f64::consts::TAU),
+];
+
+/// A built-in function and its name as listed.
+fn function(name: &str) -> Option<(&'static str, Func)> {
+    FUNCTIONS
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .copied()
+}
+
+fn constant(name: &str) -> Option<f64> {
+    CONSTANTS
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| *v)
+}
+
+/// `n!`, for whole n; past 170! it's infinite.
+fn factorial(n: f64) -> f64 {
+    if n < 0.0 || n.fract() != 0.0 {
+        return f64::NAN;
+    }
+    (1..=n.min(171.0) as u32).map(f64::from).product()
+}
+
+fn is_variable(name: &str) -> bool {
+    name.eq_ignore_ascii_case("x")
+}
+
+// ─── Compiler ────────────────────────────────────────────
+
+/// An expression with every name resolved, ready to evaluate.
+/// Quantities are carried in SI base units; dimensions are checked here.
+#[derive(Debug, Clone)]
+enum Node {
+    /// A number, with its exact or high-precision value when known
+    Const(f64, Option<Real>),
+    X,
+    Neg(Box<Node>),
+    Binary(BinOp, Box<Node>, Box<Node>),
+    /// `15%` on its own is 0.15
+    Percent(Box<Node>),
+    /// `200 + 10%` / `200 - 10%`: the percentage is of the left-hand side
+    Markup {
+        base: Box<Node>,
+        percent: Box<Node>,
+        sign: f64,
+    },
+    Call(&'static str, Func, Vec<Node>),
+}
+
+/// A number known to full precision.
+fn precise(real: Real) -> Node {
+    Node::Const(real.to_f64(), Some(real))
+}
+
+/// A unit's factor or offset as the exact fraction it stands for.
+fn unit_real(v: f64) -> Option<Real> {
+    calc_exact::rationalize(v).map(Real::exact)
+}
 
-/// Convert human-friendly math into meval-compatible expressions.
-fn preprocess(input: &str) -> String {
-    let mut s = input.trim().to_lowercase();
+fn unit_value(v: f64) -> Node {
+    Node::Const(v, unit_real(v))
+}
 
-    // Replace unicode operators
-    s = s.replace('×', "*").replace('÷', "/");
+/// A compiled expression: parse once, evaluate for as many x as needed.
+#[derive(Debug, Clone)]
+pub struct Program {
+    root: Node,
+    uses_x: bool,
+    /// Unit the result is shown in; `None` for plain numbers
+    unit: Option<Unit>,
+    /// `72 °F to °C`: the value is a temperature reading, not a difference
+    absolute: bool,
+}
 
-    // Handle "log(" → "log10(" (common convention)
-    // Protect existing log10/log2 first
-    s = s
-        .replace("log10(", "\x01LOGTEN\x02")
-        .replace("log2(", "\x01LOGTWO\x02")
-        .replace("log(", "log10(")
-        .replace("\x01LOGTEN\x02", "log10(")
-        .replace("\x01LOGTWO\x02", "log2(");
+impl Program {
+    pub fn uses_x(&self) -> bool {
+        self.uses_x
+    }
 
-    // Handle "X% of Y" → "(X/100)*(Y)"
-    if let Some(pct_idx) = s.find('%') {
-        let before = s[..pct_idx].trim().to_string();
-        let after = s[pct_idx + 1..].trim().to_string();
+    /// Label of the unit results are in, e.g. "km/h".
+    pub fn unit(&self) -> Option<&str> {
+        self.unit.as_ref().map(|u| u.symbol.as_str())
+    }
 
-        if after.starts_with("of ") {
-            let y_part = after[3..].trim();
-            s = format!("({})/100*({})", before, y_part);
-        } else if after.is_empty() {
-            // "15%" → "(15)/100"
-            s = format!("({})/100", before);
-        } else if after.starts_with('+')
-            || after.starts_with('-')
-            || after.starts_with('*')
-            || after.starts_with('/')
-        {
-            // "50% * 200" → "(50/100) * 200"
-            s = format!("({})/100{}", before, after);
+    /// Value at `x`, in the result unit.
+    pub fn eval(&self, x: f64) -> f64 {
+        let v = self.eval_base(x);
+        match &self.unit {
+            None => v,
+            Some(u) if self.absolute => v / u.factor - u.offset,
+            Some(u) => v / u.factor,
         }
     }
 
-    // Insert implicit multiplication where appropriate:
-    //   digit(  → digit*(
-    //   )(      → )*(
-    //   )digit  → )*digit
-    //   digit x → digit*x   (variable)
-    //   digit pi → digit*pi (constant)
-    let mut result = String::with_capacity(s.len() + 16);
-    let chars: Vec<char> = s.chars().collect();
-    let len = chars.len();
+    /// Value at `x` in SI base units.
+    fn eval_base(&self, x: f64) -> f64 {
+        eval_node(&self.root, x)
+    }
+
+    /// The value to full precision in SI base units, when it doesn't use x
+    /// and stays within what `calc_exact` can work out.
+    fn precise_base(&self) -> Option<Real> {
+        if self.uses_x {
+            return None;
+        }
+        precise_node(&self.root)
+    }
+
+    /// A full-precision value in SI base units, in the result unit.
+    fn in_unit(&self, base: &Real) -> Option<Real> {
+        let Some(unit) = &self.unit else {
+            return Some(base.clone());
+        };
+        let v = base.div(&unit_real(unit.factor)?)?;
+        if self.absolute {
+            v.sub(&unit_real(unit.offset)?)
+        } else {
+            Some(v)
+        }
+    }
+}
+
+/// Parse and resolve an expression against `memory`.
+pub fn compile(src: &str, memory: &Memory) -> Result<Program, ParseError> {
+    compile_expr(&calc_parse::parse(src)?, src, memory)
+}
+
+fn compile_expr(expr: &Expr, src: &str, memory: &Memory) -> Result<Program, ParseError> {
+    compile_in(Compiler::new(memory), expr, src)
+}
+
+/// Compile a function body being defined. A single parameter becomes the
+/// graph variable, so `f(t) = t^2` plots like `x^2`.
+fn compile_definition(
+    name: &str,
+    params: &[String],
+    body: &Expr,
+    src: &str,
+    memory: &Memory,
+) -> Result<Program, ParseError> {
+    let mut compiler = Compiler::new(memory);
+    compiler.expanding.push(name.to_string());
+    for param in params {
+        let node = if params.len() == 1 {
+            Node::X
+        } else {
+            Node::Const(0.0, None)
+        };
+        compiler.bindings.push((param.clone(), node, Dim::NONE));
+    }
+    compile_in(compiler, body, src)
+}
 
-    for i in 0..len {
-        result.push(chars[i]);
-        if i + 1 < len {
-            let c = chars[i];
-            let n = chars[i + 1];
+fn compile_in(mut compiler: Compiler, expr: &Expr, src: &str) -> Result<Program, ParseError> {
+    let (value, target) = match &expr.kind {
+        ExprKind::Convert(value, target) => (value.as_ref(), Some(target.as_ref())),
+        _ => (expr, None),
+    };
+
+    let (root, dim, absolute) = match compiler.temperature_reading(value)? {
+        Some((root, dim)) => (root, dim, true),
+        None => {
+            let (root, dim) = compiler.lower(value)?;
+            (root, dim, false)
+        }
+    };
+
+    let unit = match target {
+        Some(target) => Some(compiler.target_unit(target, dim, src)?),
+        // Without a target, show the result in the first unit written with
+        // its dimension — `2 hours + 45 min` in hours — or in SI base units
+        None if !dim.is_none() => Some(
+            compiler
+                .seen
+                .iter()
+                .find(|u| u.dim == dim)
+                .cloned()
+                .unwrap_or(Unit {
+                    symbol: dim.base_label(),
+                    factor: 1.0,
+                    offset: 0.0,
+                    dim,
+                }),
+        ),
+        None => None,
+    };
+
+    Ok(Program {
+        root,
+        uses_x: compiler.uses_x,
+        unit,
+        absolute,
+    })
+}
+
+/// Names that keep the unit of their arguments, because scaling the
+/// argument scales the result: `abs(-3 m)`, `max(1 h, 50 min)`.
+const UNIT_PRESERVING: &[&str] = &["abs", "min", "max", "hypot"];
+
+struct Compiler<'a> {
+    memory: &'a Memory,
+    uses_x: bool,
+    /// Units in the order they were written, for picking a display unit
+    seen: Vec<Unit>,
+    /// Parameters of the user function being expanded, bound to its arguments
+    bindings: Vec<(String, Node, Dim)>,
+    /// User functions being expanded, to catch recursion
+    expanding: Vec<String>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(memory: &'a Memory) -> Self {
+        Compiler {
+            memory,
+            uses_x: false,
+            seen: Vec::new(),
+            bindings: Vec::new(),
+            expanding: Vec::new(),
+        }
+    }
+
+    fn lower(&mut self, expr: &Expr) -> Result<(Node, Dim), ParseError> {
+        let at = expr.span.0;
+        Ok(match &expr.kind {
+            ExprKind::Number(text) => {
+                let value = calc_exact::parse_decimal(text)
+                    .or_else(|| calc_int::based_literal(text).map(BigRational::from_integer))
+                    .ok_or_else(|| ParseError::new(format!("Bad number '{}'", text), at))?;
+                (precise(Real::exact(value)), Dim::NONE)
+            }
+            ExprKind::Name(name) => self.lower_name(name, at)?,
+            ExprKind::Neg(inner) => {
+                let (node, dim) = self.lower(inner)?;
+                (Node::Neg(Box::new(node)), dim)
+            }
+            ExprKind::Percent(inner) => {
+                let node = self.plain(inner, "A percentage")?;
+                (Node::Percent(Box::new(node)), Dim::NONE)
+            }
+            ExprKind::Binary(op @ (BinOp::Add | BinOp::Sub), lhs, rhs) => {
+                let (base, dim) = self.lower(lhs)?;
+                if let ExprKind::Percent(percent) = &rhs.kind {
+                    let percent = self.plain(percent, "A percentage")?;
+                    let sign = if *op == BinOp::Add { 1.0 } else { -1.0 };
+                    let node = Node::Markup {
+                        base: Box::new(base),
+                        percent: Box::new(percent),
+                        sign,
+                    };
+                    return Ok((node, dim));
+                }
+                let (rhs_node, rhs_dim) = self.lower(rhs)?;
+                if rhs_dim != dim {
+                    let message = if *op == BinOp::Add {
+                        format!("Can't add {} and {}", dim.name(), rhs_dim.name())
+                    } else {
+                        format!("Can't subtract {} from {}", rhs_dim.name(), dim.name())
+                    };
+                    return Err(ParseError::new(message, rhs.span.0));
+                }
+                (Node::Binary(*op, Box::new(base), Box::new(rhs_node)), dim)
+            }
+            ExprKind::Binary(BinOp::Mixed, lhs, rhs) => {
+                let (lhs_node, dim) = self.lower(lhs)?;
+                let (rhs_node, rhs_dim) = self.lower(rhs)?;
+                if dim.is_none() || rhs_dim != dim {
+                    return Err(ParseError::new("Missing operator", rhs.span.0));
+                }
+                let node = Node::Binary(BinOp::Add, Box::new(lhs_node), Box::new(rhs_node));
+                (node, dim)
+            }
+            ExprKind::Binary(BinOp::Pow, lhs, rhs) => {
+                let (base, dim) = self.lower(lhs)?;
+                let exponent = self.plain(rhs, "An exponent")?;
+                let dim = if dim.is_none() {
+                    dim
+                } else {
+                    // A quantity can only be raised to a fixed power: m^2, not m^x
+                    constant_value(&exponent)
+                        .and_then(|e| dim.pow(e))
+                        .ok_or_else(|| {
+                            ParseError::new(
+                                format!("Can't raise {} to this power", dim.name()),
+                                rhs.span.0,
+                            )
+                        })?
+                };
+                (
+                    Node::Binary(BinOp::Pow, Box::new(base), Box::new(exponent)),
+                    dim,
+                )
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                let (lhs_node, lhs_dim) = self.lower(lhs)?;
+                let (rhs_node, rhs_dim) = self.lower(rhs)?;
+                let dim = match op {
+                    BinOp::Div => lhs_dim / rhs_dim,
+                    _ => lhs_dim * rhs_dim,
+                };
+                (
+                    Node::Binary(*op, Box::new(lhs_node), Box::new(rhs_node)),
+                    dim,
+                )
+            }
+            ExprKind::Not(_) | ExprKind::Bitwise(..) => {
+                return Err(ParseError::new("Bitwise math only takes whole numbers", at))
+            }
+            ExprKind::Call(name, args) => self.lower_call(name, args, at)?,
+            ExprKind::Convert(_, target) => {
+                return Err(ParseError::new("Conversions go at the end", target.span.0))
+            }
+        })
+    }
+
+    /// Lower an operand that must be a plain number.
+    fn plain(&mut self, expr: &Expr, what: &str) -> Result<Node, ParseError> {
+        let (node, dim) = self.lower(expr)?;
+        if !dim.is_none() {
+            return Err(ParseError::new(
+                format!("{} can't be a {}", what, dim.name()),
+                expr.span.0,
+            ));
+        }
+        Ok(node)
+    }
+
+    fn lower_name(&mut self, name: &str, at: usize) -> Result<(Node, Dim), ParseError> {
+        if let Some((_, node, dim)) = self.bindings.iter().rev().find(|(n, ..)| n == name) {
+            if matches!(node, Node::X) {
+                self.uses_x = true;
+            }
+            return Ok((node.clone(), *dim));
+        }
+        if is_variable(name) {
+            self.uses_x = true;
+            return Ok((Node::X, Dim::NONE));
+        }
+        // Stored names shadow constants and units: `d = 5` makes `d` 5
+        if let Some(stored) = self.memory.variable(name) {
+            let resolved = (Node::Const(stored.value, stored.real()), stored.dim);
+            if let Some(unit) = &stored.unit {
+                self.seen.push(unit.clone());
+            }
+            return Ok(resolved);
+        }
+        if name == "ans" {
+            return Err(ParseError::new("There is no previous result yet", at));
+        }
+        if let Some(v) = constant(name) {
+            let real = calc_exact::constant(&name.to_lowercase());
+            return Ok((Node::Const(v, real), Dim::NONE));
+        }
+        if let Some(unit) = calc_units::lookup(name) {
+            let resolved = (unit_value(unit.factor), unit.dim);
+            self.seen.push(unit);
+            return Ok(resolved);
+        }
+        if function(name).is_some() {
+            return Err(ParseError::new(format!("'{}' needs parentheses", name), at));
+        }
+        Err(ParseError::new(format!("Unknown name '{}'", name), at))
+    }
+
+    fn lower_call(
+        &mut self,
+        name: &str,
+        args: &[Expr],
+        at: usize,
+    ) -> Result<(Node, Dim), ParseError> {
+        let memory = self.memory;
+        if let Some(user) = memory.function(name) {
+            return self.expand(user, args, at);
+        }
+        let Some((listed, func)) = function(name) else {
+            // `x(2+1)` and `pi(2)` multiply
+            let unknown = || ParseError::new(format!("Unknown function '{}'", name), at);
+            if args.len() != 1 {
+                return Err(unknown());
+            }
+            let (lhs, lhs_dim) = self.lower_name(name, at).map_err(|_| unknown())?;
+            let (rhs, rhs_dim) = self.lower(&args[0])?;
+            let node = Node::Binary(BinOp::Mul, Box::new(lhs), Box::new(rhs));
+            return Ok((node, lhs_dim * rhs_dim));
+        };
+        let arity_ok = match func {
+            Func::Unary(_) => args.len() == 1,
+            Func::Binary(_) => args.len() == 2,
+            Func::Fold(_) => !args.is_empty(),
+        };
+        let name = name.to_lowercase();
+        if !arity_ok {
+            let wanted = match func {
+                Func::Unary(_) => "1 argument",
+                Func::Binary(_) => "2 arguments",
+                Func::Fold(_) => "at least 1 argument",
+            };
+            return Err(ParseError::new(format!("'{}' takes {}", name, wanted), at));
+        }
+
+        let mut nodes = Vec::with_capacity(args.len());
+        let mut dims = Vec::with_capacity(args.len());
+        for arg in args {
+            let (node, dim) = self.lower(arg)?;
+            nodes.push(node);
+            dims.push(dim);
+        }
+        let dim = dims[0];
+        let root = match name.as_str() {
+            "sqrt" => Some(2.0),
+            "cbrt" => Some(3.0),
+            _ => None,
+        };
+        let result = if let Some(n) = root {
+            dim.pow(1.0 / n)
+        } else if UNIT_PRESERVING.contains(&name.as_str()) {
+            dims.iter().all(|d| *d == dim).then_some(dim)
+        } else {
+            dims.iter().all(|d| d.is_none()).then_some(Dim::NONE)
+        };
+        let Some(result) = result else {
+            let bad = args
+                .iter()
+                .zip(&dims)
+                .find(|(_, d)| !d.is_none())
+                .map_or(at, |(a, _)| a.span.0);
+            return Err(ParseError::new(
+                format!("'{}' can't take {}", name, dim.name()),
+                bad,
+            ));
+        };
+        Ok((Node::Call(listed, func, nodes), result))
+    }
+
+    /// Inline a user function, its parameters bound to the lowered arguments.
+    fn expand(
+        &mut self,
+        func: &UserFunction,
+        args: &[Expr],
+        at: usize,
+    ) -> Result<(Node, Dim), ParseError> {
+        let name = &func.name;
+        if args.len() != func.params.len() {
+            let plural = if func.params.len() == 1 { "" } else { "s" };
+            return Err(ParseError::new(
+                format!("'{}' takes {} argument{}", name, func.params.len(), plural),
+                at,
+            ));
+        }
+        if self.expanding.contains(name) {
+            return Err(ParseError::new(format!("'{}' can't call itself", name), at));
+        }
+        // Errors inside the body point at the call
+        let in_body = |e: ParseError| ParseError::new(format!("In '{}': {}", name, e.message), at);
+        let body = calc_parse::parse(&func.body).map_err(in_body)?;
+
+        let mut bound = Vec::with_capacity(args.len());
+        for (param, arg) in func.params.iter().zip(args) {
+            let (node, dim) = self.lower(arg)?;
+            bound.push((param.clone(), node, dim));
+        }
+        // The body sees its own parameters, not the caller's
+        let outer = std::mem::replace(&mut self.bindings, bound);
+        self.expanding.push(name.clone());
+        let lowered = self.lower(&body).map_err(in_body);
+        self.expanding.pop();
+        self.bindings = outer;
+        lowered
+    }
+
+    /// `72 °F` or `300 K` on its own is a reading on the scale, converted from
+    /// its zero point; anywhere else temperatures are differences.
+    fn temperature_reading(&mut self, expr: &Expr) -> Result<Option<(Node, Dim)>, ParseError> {
+        // `-40 °C` parses as -(40 °C), but the sign belongs to the reading
+        let (expr, negative) = match &expr.kind {
+            ExprKind::Neg(inner) => (inner.as_ref(), true),
+            _ => (expr, false),
+        };
+        let ExprKind::Binary(BinOp::Mul, amount, unit) = &expr.kind else {
+            return Ok(None);
+        };
+        let ExprKind::Name(name) = &unit.kind else {
+            return Ok(None);
+        };
+        let Some(unit) = calc_units::lookup(name).filter(|u| u.dim == Dim::TEMPERATURE) else {
+            return Ok(None);
+        };
+        let mut amount = self.plain(amount, "A temperature reading")?;
+        if negative {
+            amount = Node::Neg(Box::new(amount));
+        }
+        let kelvin = Node::Binary(
+            BinOp::Mul,
+            Box::new(Node::Binary(
+                BinOp::Add,
+                Box::new(amount),
+                Box::new(unit_value(unit.offset)),
+            )),
+            Box::new(unit_value(unit.factor)),
+        );
+        let dim = unit.dim;
+        self.seen.push(unit);
+        Ok(Some((kelvin, dim)))
+    }
 
-            // digit or ')' followed by '(' or letter → insert '*'
-            let curr_is_value = c.is_ascii_digit() || c == '.' || c == ')';
-            let next_is_start = n == '(' || n == 'x';
+    /// Resolve the unit after `in` / `to` / `as` and check it fits the value.
+    fn target_unit(&mut self, target: &Expr, dim: Dim, src: &str) -> Result<Unit, ParseError> {
+        let (node, target_dim) = self.lower(target)?;
+        let factor = constant_value(&node)
+            .filter(|f| *f != 0.0 && f.is_finite() && !target_dim.is_none())
+            .ok_or_else(|| ParseError::new("Expected a unit to convert to", target.span.0))?;
+        if target_dim != dim {
+            return Err(ParseError::new(
+                format!("Can't convert {} to {}", dim.name(), target_dim.name()),
+                target.span.0,
+            ));
+        }
+        Ok(match &target.kind {
+            ExprKind::Name(name) => calc_units::lookup(name).unwrap_or(Unit {
+                symbol: name.clone(),
+                factor,
+                offset: 0.0,
+                dim,
+            }),
+            _ => Unit {
+                symbol: src
+                    .chars()
+                    .skip(target.span.0)
+                    .take(target.span.1 - target.span.0)
+                    .collect(),
+                factor,
+                offset: 0.0,
+                dim,
+            },
+        })
+    }
+}
 
-            // ')' followed by digit
-            let paren_digit = c == ')' && n.is_ascii_digit();
+/// The value of a node that doesn't depend on x.
+fn constant_value(node: &Node) -> Option<f64> {
+    fn uses_x(node: &Node) -> bool {
+        match node {
+            Node::X => true,
+            Node::Const(..) => false,
+            Node::Neg(a) | Node::Percent(a) => uses_x(a),
+            Node::Binary(_, a, b) => uses_x(a) || uses_x(b),
+            Node::Markup { base, percent, .. } => uses_x(base) || uses_x(percent),
+            Node::Call(.., args) => args.iter().any(uses_x),
+        }
+    }
+    (!uses_x(node)).then(|| eval_node(node, 0.0))
+}
 
-            if (curr_is_value && next_is_start) || paren_digit {
-                result.push('*');
+fn eval_node(node: &Node, x: f64) -> f64 {
+    match node {
+        Node::Const(v, _) => *v,
+        Node::X => x,
+        Node::Neg(inner) => -eval_node(inner, x),
+        Node::Percent(inner) => eval_node(inner, x) / 100.0,
+        Node::Markup {
+            base,
+            percent,
+            sign,
+        } => {
+            let base = eval_node(base, x);
+            base + sign * base * eval_node(percent, x) / 100.0
+        }
+        Node::Binary(op, lhs, rhs) => {
+            let (a, b) = (eval_node(lhs, x), eval_node(rhs, x));
+            match op {
+                BinOp::Add | BinOp::Mixed => a + b,
+                BinOp::Sub => a - b,
+                BinOp::Mul | BinOp::Of => a * b,
+                BinOp::Div => a / b,
+                BinOp::Pow => a.powf(b),
             }
         }
+        Node::Call(_, func, args) => match func {
+            Func::Unary(f) => f(eval_node(&args[0], x)),
+            Func::Binary(f) => f(eval_node(&args[0], x), eval_node(&args[1], x)),
+            Func::Fold(f) => args
+                .iter()
+                .map(|a| eval_node(a, x))
+                .reduce(*f)
+                .unwrap_or(f64::NAN),
+        },
     }
+}
 
-    result
+/// The value of a node without x to full precision; `None` when some part
+/// of it is only known as an f64.
+fn precise_node(node: &Node) -> Option<Real> {
+    let hundred = || Real::exact(BigRational::from_integer(100.into()));
+    match node {
+        Node::Const(_, real) => real.clone(),
+        Node::X => None,
+        Node::Neg(inner) => Some(precise_node(inner)?.neg()),
+        Node::Percent(inner) => precise_node(inner)?.div(&hundred()),
+        Node::Markup {
+            base,
+            percent,
+            sign,
+        } => {
+            let base = precise_node(base)?;
+            let share = base.mul(&precise_node(percent)?)?.div(&hundred())?;
+            if *sign > 0.0 {
+                base.add(&share)
+            } else {
+                base.sub(&share)
+            }
+        }
+        Node::Binary(op, lhs, rhs) => {
+            let (a, b) = (precise_node(lhs)?, precise_node(rhs)?);
+            match op {
+                BinOp::Add | BinOp::Mixed => a.add(&b),
+                BinOp::Sub => a.sub(&b),
+                BinOp::Mul | BinOp::Of => a.mul(&b),
+                BinOp::Div => a.div(&b),
+                BinOp::Pow => a.pow(&b),
+            }
+        }
+        Node::Call(name, _, args) => {
+            let args = args.iter().map(precise_node).collect::<Option<Vec<_>>>()?;
+            calc_exact::call(name, &args)
+        }
+    }
 }
 
 // ─── Evaluator ───────────────────────────────────────────
 
+/// Whether a word means something to the calculator.
+fn is_known(name: &str, memory: &Memory) -> bool {
+    is_variable(name)
+        || constant(name).is_some()
+        || function(name).is_some()
+        || calc_units::lookup(name).is_some()
+        || memory.variable(name).is_some()
+        || memory.function(name).is_some()
+        || name == "ans"
+        || calc_parse::is_operator_word(name)
+        || calc_parse::NUMBER_FORMATS
+            .iter()
+            .any(|f| name.eq_ignore_ascii_case(f))
+}
+
+/// Whether text that failed to parse was meant as math: it lexes, has an
+/// operator or paren, and every word in it is one the calculator knows or
+/// is being defined. Anything else is a search and shouldn't be answered
+/// with an error.
+fn looks_like_math(raw: &str, memory: &Memory) -> bool {
+    let Ok(tokens) = calc_parse::tokenize(raw) else {
+        return false;
+    };
+    let defining = tokens
+        .iter()
+        .position(|t| t.tok == Tok::Op('='))
+        .unwrap_or(0);
+    let mut has_operator = false;
+    for (i, token) in tokens.iter().enumerate() {
+        match &token.tok {
+            Tok::Ident(name) => {
+                if ["in", "to", "as"]
+                    .iter()
+                    .any(|w| name.eq_ignore_ascii_case(w))
+                {
+                    has_operator = true;
+                    continue;
+                }
+                let params = &tokens[..defining];
+                let defined = i < defining
+                    || params
+                        .iter()
+                        .any(|t| matches!(&t.tok, Tok::Ident(p) if p == name));
+                if !defined && !is_known(name, memory) {
+                    return false;
+                }
+            }
+            Tok::Op(_) | Tok::LParen | Tok::RParen => has_operator = true,
+            Tok::Number(_) | Tok::Comma => {}
+        }
+    }
+    has_operator
+}
+
+/// What committing a calculation changes in memory.
+enum Effect {
+    /// A plain result, which becomes `ans`
+    Answer(Stored),
+    /// `rate = 0.07`, which also becomes `ans`
+    Assign(Stored),
+    Define(UserFunction),
+    /// Graphs change nothing
+    Nothing,
+}
+
 /// Try to evaluate a string as a math expression.
-/// Returns `None` if the string isn't valid math or is just a bare number.
-pub fn evaluate(input: &str) -> Option<CalcResult> {
+/// Returns `None` if the string isn't math or is just a bare number, and a
+/// result carrying `error` if it is math but malformed. Assignments and
+/// definitions are previewed but not stored; see [`execute`].
+pub fn evaluate(input: &str, memory: &Memory) -> Option<CalcResult> {
+    run(input, memory).map(|(result, _)| result)
+}
+
+/// Evaluate and commit: assignments and definitions are stored, and a
+/// value becomes `ans`.
+pub fn execute(input: &str, memory: &mut Memory) -> Option<CalcResult> {
+    let (result, effect) = run(input, memory)?;
+    match effect {
+        Effect::Answer(stored) => memory.ans = Some(stored),
+        Effect::Assign(stored) => {
+            memory.forget(&stored.name);
+            memory.variables.push(stored.clone());
+            memory.ans = Some(Stored {
+                name: "ans".to_string(),
+                ..stored
+            });
+        }
+        Effect::Define(func) => {
+            memory.forget(&func.name);
+            memory.functions.push(func);
+        }
+        Effect::Nothing => {}
+    }
+    Some(result)
+}
+
+fn run(input: &str, memory: &Memory) -> Option<(CalcResult, Effect)> {
     let raw = input.trim();
     if raw.is_empty() || raw.len() < 2 {
         return None;
@@ -106,143 +900,328 @@ pub fn evaluate(input: &str) -> Option<CalcResult> {
         return None;
     }
 
-    // Must contain at least one digit
-    if !raw.chars().any(|c| c.is_ascii_digit()) {
+    // Must contain a digit, a definition or something remembered
+    let tokens = calc_parse::tokenize(raw).unwrap_or_default();
+    let remembered = tokens.iter().any(|t| match &t.tok {
+        Tok::Ident(name) => memory.variable(name).is_some() || memory.function(name).is_some(),
+        Tok::Op(op) => *op == '=',
+        _ => false,
+    });
+    if !remembered && !raw.chars().any(|c| c.is_ascii_digit()) {
         return None;
     }
 
-    let processed = preprocess(raw);
-
-    // Check for variable 'x' (graphable equation)
-    let has_x = processed.contains('x');
-
-    if has_x {
-        // Validate by evaluating at x = 1
-        let test = processed.replace('x', "(1)");
-        match meval::eval_str(&test) {
-            Ok(v) if v.is_finite() => Some(CalcResult {
+    let outcome = calc_parse::parse_statement(raw).and_then(|statement| match statement {
+        Statement::Expr(expr) if is_bare(&expr) => Ok(None),
+        Statement::Expr(expr) => {
+            if let Some(whole) = programmer(&expr, memory) {
+                let (result, stored) = whole_result(raw, "ans", whole?);
+                return Ok(Some((result, Effect::Answer(stored))));
+            }
+            let program = compile_expr(&expr, raw, memory)?;
+            Ok(answer(raw, &program))
+        }
+        Statement::Assign { name, value } => {
+            check_definable(&name)?;
+            if let Some(whole) = programmer(&value, memory) {
+                let (mut result, stored) = whole_result(raw, &name, whole?);
+                result.display = format!("{} = {}", name, result.display);
+                return Ok(Some((result, Effect::Assign(stored))));
+            }
+            let program = compile_expr(&value, raw, memory)?;
+            if program.uses_x() {
+                return Err(ParseError::new(
+                    "Use f(x) = … to define a function of x",
+                    value.span.0,
+                ));
+            }
+            Ok(assign(raw, name, &program))
+        }
+        Statement::Define {
+            name,
+            params,
+            body,
+            source,
+        } => {
+            check_definable(&name)?;
+            for (i, param) in params.iter().enumerate() {
+                if params[..i].contains(param) || constant(param).is_some() {
+                    return Err(ParseError::new(
+                        format!("'{}' can't be a parameter", param),
+                        0,
+                    ));
+                }
+            }
+            let program = compile_definition(&name, &params, &body, raw, memory)?;
+            let result = CalcResult {
                 expression: raw.to_string(),
                 result: 0.0,
-                display: format!("f(x) = {}", raw),
-                has_variable: true,
-            }),
-            _ => None,
+                display: format!("{}({}) = {}", name, params.join(", "), source),
+                has_variable: program.uses_x(),
+                unit: program.unit().map(str::to_string),
+                error: None,
+                error_position: None,
+                bases: None,
+                fraction: None,
+                precise: None,
+            };
+            let func = UserFunction {
+                name,
+                params,
+                body: source,
+            };
+            Ok(Some((result, Effect::Define(func))))
         }
-    } else {
-        let val = match meval::eval_str(&processed) {
-            Ok(v) if v.is_finite() => v,
-            _ => return None,
-        };
+    });
 
-        // Reject bare numbers (no operator / function) — "42" should search, not calc
-        let has_operator = processed.contains('+')
-            || processed.contains('-')
-            || processed.contains('*')
-            || processed.contains('/')
-            || processed.contains('^')
-            || processed.contains('(');
-        let is_constant = processed == "pi" || processed == "e";
-        let had_percent = raw.contains('%');
-
-        if !has_operator && !is_constant && !had_percent {
-            return None;
+    match outcome {
+        Ok(outcome) => outcome,
+        Err(err) if looks_like_math(raw, memory) => {
+            let result = CalcResult {
+                expression: raw.to_string(),
+                result: 0.0,
+                display: err.message.clone(),
+                has_variable: false,
+                unit: None,
+                error: Some(err.to_string()),
+                error_position: Some(err.position),
+                bases: None,
+                fraction: None,
+                precise: None,
+            };
+            Some((result, Effect::Nothing))
         }
+        Err(_) => None,
+    }
+}
+
+/// The programmer-mode value of `expr`, or `None` to leave it to the
+/// decimal calculator.
+fn programmer(expr: &Expr, memory: &Memory) -> Option<Result<Whole, ParseError>> {
+    if !calc_int::is_programmer(expr) {
+        return None;
+    }
+    match calc_int::evaluate(expr, memory) {
+        Err(_) if !calc_int::is_bitwise(expr) => None,
+        result => Some(result),
+    }
+}
 
-        Some(CalcResult {
+/// Built-in names can't be redefined.
+fn check_definable(name: &str) -> Result<(), ParseError> {
+    if is_variable(name) || name == "ans" || constant(name).is_some() || function(name).is_some() {
+        return Err(ParseError::new(format!("Can't redefine '{}'", name), 0));
+    }
+    Ok(())
+}
+
+/// Result of an expression: a graph if it uses x, else its value.
+fn answer(raw: &str, program: &Program) -> Option<(CalcResult, Effect)> {
+    if program.uses_x() {
+        let result = CalcResult {
             expression: raw.to_string(),
-            result: val,
-            display: format_number(val),
-            has_variable: false,
-        })
+            result: 0.0,
+            display: format!("f(x) = {}", raw),
+            has_variable: true,
+            unit: program.unit().map(str::to_string),
+            error: None,
+            error_position: None,
+            bases: None,
+            fraction: None,
+            precise: None,
+        };
+        return Some((result, Effect::Nothing));
     }
+    let (result, stored) = value_result(raw, "ans", program)?;
+    Some((result, Effect::Answer(stored)))
+}
+
+fn assign(raw: &str, name: String, program: &Program) -> Option<(CalcResult, Effect)> {
+    let (mut result, stored) = value_result(raw, &name, program)?;
+    result.display = format!("{} = {}", name, result.display);
+    Some((result, Effect::Assign(stored)))
 }
 
-/// Sample a function f(x) over [x_min, x_max] for graphing.
-pub fn evaluate_graph(expr: &str, x_min: f64, x_max: f64, steps: usize) -> Vec<GraphPoint> {
-    let processed = preprocess(expr.trim());
-    let steps = steps.min(1000).max(10); // clamp to sane range
+/// The value of a program without x, and how it would be stored as `name`.
+fn value_result(raw: &str, name: &str, program: &Program) -> Option<(CalcResult, Stored)> {
+    // Exact or to many digits where possible, else as an f64
+    let base = program.precise_base();
+    let real = base
+        .as_ref()
+        .and_then(|b| program.in_unit(b))
+        .or_else(|| Real::from_f64(program.eval(0.0)))?;
+
+    let shown = calc_exact::show(&real);
+    let with_unit = |text: String| match program.unit() {
+        Some(unit) => format!("{} {}", text, unit),
+        None => text,
+    };
+    let display = with_unit(shown.display);
+    let stored = Stored {
+        name: name.to_string(),
+        value: finite(
+            base.as_ref()
+                .map_or_else(|| program.eval_base(0.0), Real::to_f64),
+        ),
+        dim: program.unit.as_ref().map_or(Dim::NONE, |u| u.dim),
+        unit: program.unit.clone(),
+        display: display.clone(),
+        exact: base.as_ref().filter(|b| b.exact).map(Real::to_string),
+        approx: base.as_ref().filter(|b| !b.exact).map(Real::to_string),
+    };
+    let result = CalcResult {
+        expression: raw.to_string(),
+        result: finite(real.to_f64()),
+        display,
+        has_variable: false,
+        unit: program.unit().map(str::to_string),
+        error: None,
+        error_position: None,
+        bases: None,
+        fraction: shown.fraction.map(with_unit),
+        precise: shown.precise.map(with_unit),
+    };
+    Some((result, stored))
+}
+
+/// A programmer-mode result, and how it would be stored as `name`.
+fn whole_result(raw: &str, name: &str, whole: Whole) -> (CalcResult, Stored) {
+    let value = finite(whole.value.to_f64().unwrap_or(f64::NAN));
+    let stored = Stored {
+        name: name.to_string(),
+        value,
+        dim: Dim::NONE,
+        unit: None,
+        display: whole.display.clone(),
+        exact: Some(whole.value.to_string()),
+        approx: None,
+    };
+    let result = CalcResult {
+        expression: raw.to_string(),
+        result: value,
+        display: whole.display,
+        has_variable: false,
+        unit: None,
+        error: None,
+        error_position: None,
+        bases: Some(whole.bases),
+        fraction: None,
+        precise: None,
+    };
+    (result, stored)
+}
+
+/// Results too large for an f64 are kept as its largest value, as JSON has
+/// no infinity; the full value lives in `display` and `Stored::exact`.
+fn finite(v: f64) -> f64 {
+    v.clamp(f64::MIN, f64::MAX)
+}
+
+/// A lone number, name or quantity: `42`, `(42)`, `rate`, `5 kg`. A lone
+/// `0xff` is worth answering with its other bases.
+fn is_bare(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Number(text) => !calc_int::is_based(text),
+        ExprKind::Name(_) => true,
+        ExprKind::Binary(BinOp::Mul, amount, unit) => {
+            matches!(amount.kind, ExprKind::Number(_))
+                && matches!(&unit.kind, ExprKind::Name(n) if calc_units::lookup(n).is_some())
+        }
+        _ => false,
+    }
+}
+
+/// Sample a function f(x) over [x_min, x_max] for graphing. `expr` may also
+/// be a one-parameter definition like `f(t) = t^2`, which plots its body.
+pub fn evaluate_graph(
+    expr: &str,
+    x_min: f64,
+    x_max: f64,
+    steps: usize,
+    memory: &Memory,
+) -> Vec<GraphPoint> {
+    let raw = expr.trim();
+    let program = match calc_parse::parse_statement(raw) {
+        Ok(Statement::Expr(expr)) => compile_expr(&expr, raw, memory),
+        Ok(Statement::Define {
+            name, params, body, ..
+        }) if params.len() == 1 => compile_definition(&name, &params, &body, raw, memory),
+        _ => return Vec::new(),
+    };
+    let Ok(program) = program else {
+        return Vec::new();
+    };
+    let steps = steps.clamp(10, 1000); // clamp to sane range
     let mut points = Vec::with_capacity(steps + 1);
 
     let step_size = (x_max - x_min) / steps as f64;
 
     for i in 0..=steps {
         let x = x_min + step_size * i as f64;
-        let substituted = processed.replace('x', &format!("({})", x));
-
-        if let Ok(y) = meval::eval_str(&substituted) {
-            if y.is_finite() {
-                points.push(GraphPoint { x, y });
-            }
+        let y = program.eval(x);
+        if y.is_finite() {
+            points.push(GraphPoint { x, y });
         }
     }
 
     points
 }
 
-// ─── Formatting ──────────────────────────────────────────
-
-fn format_number(val: f64) -> String {
-    if val == val.floor() && val.abs() < 1e15 {
-        format!("{}", val as i64)
-    } else {
-        // Up to 10 decimal places, strip trailing zeros
-        let s = format!("{:.10}", val);
-        let s = s.trim_end_matches('0');
-        let s = s.trim_end_matches('.');
-        s.to_string()
-    }
-}
-
 // ─── Tests ───────────────────────────────────────────────
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Evaluate with nothing remembered.
+    fn eval(input: &str) -> Option<CalcResult> {
+        evaluate(input, &Memory::default())
+    }
+
     #[test]
     fn basic_arithmetic() {
-        let r = evaluate("2+2").unwrap();
+        let r = eval("2+2").unwrap();
         assert_eq!(r.display, "4");
         assert!(!r.has_variable);
     }
 
     #[test]
     fn percentage_of() {
-        let r = evaluate("15% of 300").unwrap();
+        let r = eval("15% of 300").unwrap();
         assert!((r.result - 45.0).abs() < 0.001);
     }
 
     #[test]
     fn trig_function() {
-        let r = evaluate("sin(0)").unwrap();
+        let r = eval("sin(0)").unwrap();
         assert!((r.result - 0.0).abs() < 0.001);
     }
 
     #[test]
     fn power() {
-        let r = evaluate("2^10").unwrap();
+        let r = eval("2^10").unwrap();
         assert_eq!(r.display, "1024");
     }
 
     #[test]
     fn graphable() {
-        let r = evaluate("x^2 + 1").unwrap();
+        let r = eval("x^2 + 1").unwrap();
         assert!(r.has_variable);
     }
 
     #[test]
     fn bare_number_rejected() {
-        assert!(evaluate("42").is_none());
+        assert!(eval("42").is_none());
     }
 
     #[test]
     fn plain_text_rejected() {
-        assert!(evaluate("hello world").is_none());
+        assert!(eval("hello world").is_none());
     }
 
     #[test]
     fn graph_points() {
-        let pts = evaluate_graph("x^2", -2.0, 2.0, 100);
+        let pts = evaluate_graph("x^2", -2.0, 2.0, 100, &Memory::default());
         assert!(pts.len() == 101); // 100 steps → 101 points
         // First point: x=-2 → y=4
         assert!((pts[0].x - (-2.0)).abs() < 0.001);
@@ -251,4 +1230,227 @@ mod tests {
         assert!((pts[100].x - 2.0).abs() < 0.001);
         assert!((pts[100].y - 4.0).abs() < 0.001);
     }
+
+    #[test]
+    fn percent_semantics() {
+        assert!((eval("200 + 10%").unwrap().result - 220.0).abs() < 1e-9);
+        assert!((eval("80 - 25%").unwrap().result - 60.0).abs() < 1e-9);
+        assert!((eval("50% * 200").unwrap().result - 100.0).abs() < 1e-9);
+        assert_eq!(eval("15%").unwrap().display, "0.15");
+    }
+
+    #[test]
+    fn implicit_multiplication_and_precedence() {
+        assert_eq!(eval("2(3+4)").unwrap().display, "14");
+        assert_eq!(eval("(1+1)(2+3)").unwrap().display, "10");
+        assert_eq!(eval("-2^2").unwrap().display, "-4");
+        assert_eq!(eval("2^3^2").unwrap().display, "512");
+        assert_eq!(eval("log(1000) + ln(1)").unwrap().display, "3");
+        assert_eq!(eval("max(3, 9, 4) - 1").unwrap().display, "8");
+    }
+
+    #[test]
+    fn malformed_math_reports_position() {
+        let r = eval("2 + * 3").unwrap();
+        assert_eq!(r.error.as_deref(), Some("Unexpected '*' at column 5"));
+        assert_eq!(r.error_position, Some(4));
+
+        let r = eval("sqrt(2, 3)").unwrap();
+        assert_eq!(
+            r.error.as_deref(),
+            Some("'sqrt' takes 1 argument at column 1")
+        );
+
+        // Words the calculator doesn't know mean it's a search
+        assert!(eval("report 2023 (final)").is_none());
+        assert!(eval("foo(2)").is_none());
+    }
+
+    fn display(input: &str) -> String {
+        eval(input).unwrap().display
+    }
+
+    #[test]
+    fn programmer_mode() {
+        let r = eval("0xff").unwrap();
+        assert_eq!(r.display, "0xff");
+        let bases = r.bases.unwrap();
+        assert_eq!(
+            (bases.dec.as_str(), bases.bin.as_str()),
+            ("255", "0b1111_1111")
+        );
+        assert_eq!(eval("-1 in i32").unwrap().bases.unwrap().hex, "0xffffffff");
+        // Hex literals alone don't make arithmetic whole-number only
+        assert_eq!(eval("0x10 * 1.5").unwrap().display, "24");
+        assert!(eval("0x10 * 1.5").unwrap().bases.is_none());
+        assert_eq!(eval("2.5 & 1").unwrap().error_position, Some(0));
+
+        let mut memory = Memory::default();
+        let r = execute("mask = 0xf0", &mut memory).unwrap();
+        assert_eq!(r.display, "mask = 0xf0");
+        assert_eq!(evaluate("mask | 0x0f", &memory).unwrap().display, "0xff");
+        assert_eq!(evaluate("mask / 16", &memory).unwrap().display, "15");
+        // Wide results stay exact through `ans`
+        execute("1 << 80", &mut memory).unwrap();
+        let r = evaluate("ans | 1 in dec", &memory).unwrap();
+        assert_eq!(r.display, "1208925819614629174706177");
+    }
+
+    #[test]
+    fn exact_arithmetic() {
+        assert_eq!(display("0.1 + 0.2"), "0.3");
+        assert_eq!(display("0.1 * 3 - 0.3"), "0");
+        assert_eq!(display("2^53 + 1"), "9007199254740993");
+        assert_eq!(display("1e20 + 1"), "100000000000000000001");
+        let r = eval("1/3 + 1/7").unwrap();
+        assert_eq!(r.display, "0.4761904762");
+        assert_eq!(r.fraction.as_deref(), Some("10/21"));
+
+        let r = eval("25!").unwrap();
+        assert_eq!(r.display, "15511210043330985984000000");
+        let r = eval("2^200").unwrap();
+        assert_eq!(r.display, "1.6069380443e60");
+        assert_eq!(r.precise.unwrap().len(), 61);
+        assert_eq!(r.result, 2f64.powi(200));
+        // Too large for an f64, but not for the display
+        assert_eq!(display("200!").len(), 16);
+        assert_eq!(eval("10^400").unwrap().result, f64::MAX);
+
+        let r = eval("sqrt(2) * 1").unwrap();
+        assert_eq!(r.display, "1.4142135624");
+        assert_eq!(r.fraction, None);
+        assert!(r
+            .precise
+            .unwrap()
+            .starts_with("1.41421356237309504880168872420969807"));
+        assert_eq!(display("sqrt(8) * sqrt(2)"), "4");
+        assert_eq!(
+            eval("72 f to c").unwrap().fraction.as_deref(),
+            Some("200/9 °C")
+        );
+
+        // Exact through variables and `ans`
+        let mut memory = Memory::default();
+        execute("third = 1/3", &mut memory).unwrap();
+        assert_eq!(evaluate("third * 3", &memory).unwrap().display, "1");
+        execute("2^64", &mut memory).unwrap();
+        let r = evaluate("ans + 1", &memory).unwrap();
+        assert_eq!(r.display, "18446744073709551617");
+    }
+
+    #[test]
+    fn unit_conversions() {
+        assert_eq!(display("3.5 GiB in MB"), "3758.096384 MB");
+        assert_eq!(display("72 f to c"), "22.2222222222 °C");
+        assert_eq!(display("-40 °C in °F"), "-40 °F");
+        assert_eq!(display("0 K in C"), "-273.15 °C");
+        assert_eq!(display("300 K in F"), "80.33 °F");
+        assert_eq!(display("25 C in K"), "298.15 K");
+        assert_eq!(display("60 mph in km/h"), "96.56064 km/h");
+        assert_eq!(display("5 ft 11 in to cm"), "180.34 cm");
+        assert_eq!(display("12 in in cm"), "30.48 cm");
+        assert_eq!(display("2 hours + 45 min"), "2.75 h");
+        assert_eq!(display("3 m * 4 m"), "12 m^2");
+        assert_eq!(display("1 acre in m^2"), "4046.8564224 m^2");
+        assert_eq!(display("2 L in cup"), "8.4535056755 cup");
+        assert_eq!(display("1 kWh to kJ"), "3600 kJ");
+        assert_eq!(display("10 km / 30 min in km/h"), "20 km/h");
+        assert_eq!(eval("10 m / 2 m").unwrap().unit, None);
+        // A temperature change converts by scale only
+        assert_eq!(display("10 C + 5 C in F"), "27 °F");
+    }
+
+    #[test]
+    fn dimension_errors() {
+        let r = eval("2 hours + 3 kg").unwrap();
+        assert_eq!(
+            r.error.as_deref(),
+            Some("Can't add time and mass at column 11")
+        );
+        let r = eval("5 km in kg").unwrap();
+        assert_eq!(
+            r.error.as_deref(),
+            Some("Can't convert length to mass at column 9")
+        );
+        let r = eval("sin(3 m) + 1").unwrap();
+        assert_eq!(r.error_position, Some(4));
+        // Lone quantities are searches, like bare numbers
+        assert!(eval("5 kg").is_none());
+        assert!(eval("5 km in kg").unwrap().error.is_some());
+    }
+
+    #[test]
+    fn variables_ans_and_functions() {
+        let mut memory = Memory::default();
+        // Previewing stores nothing
+        assert_eq!(
+            evaluate("rate = 7%", &memory).unwrap().display,
+            "rate = 0.07"
+        );
+        assert!(memory.variables.is_empty());
+
+        execute("rate = 7%", &mut memory).unwrap();
+        let total = execute("200 * (1 + rate)", &mut memory).unwrap();
+        assert_eq!(total.display, "214");
+        assert_eq!(execute("ans / 2", &mut memory).unwrap().display, "107");
+        execute("d = 5 km", &mut memory).unwrap();
+        assert_eq!(evaluate("d * 2", &memory).unwrap().display, "10 km");
+
+        let f = execute("f(x) = x^2 + 1", &mut memory).unwrap();
+        assert!(f.has_variable);
+        assert_eq!(f.display, "f(x) = x^2 + 1");
+        assert_eq!(evaluate("f(3) + rate", &memory).unwrap().display, "10.07");
+        assert!(evaluate("f(x) * 2", &memory).unwrap().has_variable);
+        let pts = evaluate_graph("f(x)", 0.0, 1.0, 10, &memory);
+        assert_eq!(pts[10].y, 2.0);
+        let pts = evaluate_graph("g(t) = 2t", 0.0, 1.0, 10, &memory);
+        assert_eq!(pts[10].y, 2.0);
+
+        execute("area(w, h) = w h", &mut memory).unwrap();
+        assert_eq!(
+            evaluate("area(2 m, 3 m)", &memory).unwrap().display,
+            "6 m^2"
+        );
+        assert!(memory.forget("area"));
+        assert!(evaluate("area(2 m, 3 m)", &memory).is_none());
+    }
+
+    #[test]
+    fn definition_errors() {
+        let mut memory = Memory::default();
+        let error = |input: &str, memory: &Memory| evaluate(input, memory).unwrap().error;
+        assert_eq!(
+            error("pi = 3", &memory).as_deref(),
+            Some("Can't redefine 'pi' at column 1")
+        );
+        assert_eq!(
+            error("ans + 1", &memory).as_deref(),
+            Some("There is no previous result yet at column 1")
+        );
+        assert!(error("y = 2x", &memory).is_some());
+
+        execute("f(x) = x + 1", &mut memory).unwrap();
+        assert_eq!(
+            error("f(1, 2)", &memory).as_deref(),
+            Some("'f' takes 1 argument at column 1")
+        );
+        assert_eq!(
+            error("f(x) = f(x) + 1", &memory).as_deref(),
+            Some("'f' can't call itself at column 8")
+        );
+        execute("g(x) = f(x) * 2", &mut memory).unwrap();
+        assert!(error("f(x) = g(x)", &memory).is_some());
+        assert_eq!(evaluate("g(1)", &memory).unwrap().display, "4");
+    }
+
+    #[test]
+    fn compiled_once_for_graphing() {
+        let program = compile("3x^2 - 2x + 1", &Memory::default()).unwrap();
+        assert!(program.uses_x());
+        assert_eq!(program.eval(2.0), 9.0);
+        assert_eq!(program.eval(-1.0), 6.0);
+        assert!(evaluate_graph("sqrt(x)", -1.0, 1.0, 10, &Memory::default())
+            .iter()
+            .all(|p| p.x >= 0.0));
+    }
 }
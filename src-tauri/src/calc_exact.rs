@@ -0,0 +1,816 @@
+/// Exact and high-precision numbers for the calculator.
+///
+/// Arithmetic that stays rational — `0.1 + 0.2`, `1/3 * 3`, `2^100`, `50!` —
+/// is done exactly on big rationals. Everything else (`sqrt(2)`, `sin(1)`,
+/// `pi`) is computed in fixed point to [`WORK_DIGITS`] decimal places, so a
+/// result can be shown to far more digits than an f64 holds, and as a
+/// fraction when that's what it is.
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+/// Decimal places kept for values that aren't exact.
+const WORK_DIGITS: u32 = 60;
+
+/// Extra places carried through a series so its rounding stays out of sight.
+const GUARD_DIGITS: u32 = 15;
+
+/// Exact values wider than this fall back to f64, so `9^9^9` can't stall the bar.
+const MAX_BITS: u64 = 65_536;
+
+/// Largest `n!` worked out exactly.
+const MAX_FACTORIAL: u32 = 5000;
+
+/// Decimal places in an everyday result, as before exact arithmetic.
+const DISPLAY_DECIMALS: usize = 10;
+
+/// Whole numbers up to this many digits are shown in full.
+const DISPLAY_DIGITS: usize = 30;
+
+/// Significant digits in [`Shown::precise`].
+const PRECISE_DIGITS: usize = 40;
+
+/// Longer whole numbers aren't written out in [`Shown::precise`] at all.
+const MAX_WRITTEN_DIGITS: usize = 1000;
+
+/// Fractions are offered while the denominator stays this short.
+const FRACTION_DIGITS: usize = 6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Real {
+    pub value: BigRational,
+    /// False once the value has been rounded, as by `sqrt(2)`
+    pub exact: bool,
+}
+
+/// How a value is presented.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shown {
+    /// The everyday form, e.g. "0.3333333333"
+    pub display: String,
+    /// The exact fraction when `display` is rounded, e.g. "1/3"
+    pub fraction: Option<String>,
+    /// More digits when `display` doesn't tell the whole story
+    pub precise: Option<String>,
+}
+
+fn ten_pow(exponent: u32) -> BigInt {
+    BigInt::from(10u32).pow(exponent)
+}
+
+fn within_limits(value: &BigRational) -> bool {
+    value.numer().bits() <= MAX_BITS && value.denom().bits() <= MAX_BITS
+}
+
+impl Real {
+    pub fn exact(value: BigRational) -> Self {
+        Real { value, exact: true }
+    }
+
+    /// A value known only approximately, rounded to [`WORK_DIGITS`] places.
+    pub fn approx(value: BigRational) -> Self {
+        let scale = ten_pow(WORK_DIGITS);
+        let rounded = (value * BigRational::from_integer(scale.clone())).round();
+        Real {
+            value: BigRational::new(rounded.to_integer(), scale),
+            exact: false,
+        }
+    }
+
+    fn integer(n: i64) -> Self {
+        Real::exact(BigRational::from_integer(n.into()))
+    }
+
+    /// An f64 taken at its shortest decimal reading, as inexact.
+    pub fn from_f64(v: f64) -> Option<Self> {
+        v.is_finite()
+            .then(|| parse_decimal(&format!("{:e}", v)))
+            .flatten()
+            .map(|value| Real {
+                value,
+                exact: false,
+            })
+    }
+
+    /// Read a value saved with `to_string`.
+    pub fn parse(text: &str, exact: bool) -> Option<Self> {
+        let value: BigRational = text.parse().ok()?;
+        Some(Real { value, exact })
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.value.to_f64().unwrap_or(f64::NAN)
+    }
+
+    /// Combine two values, keeping the result exact only if both were.
+    fn with(&self, other: &Real, value: BigRational) -> Option<Real> {
+        let result = if self.exact && other.exact {
+            Real::exact(value)
+        } else {
+            Real::approx(value)
+        };
+        within_limits(&result.value).then_some(result)
+    }
+
+    pub fn neg(&self) -> Real {
+        Real {
+            value: -&self.value,
+            exact: self.exact,
+        }
+    }
+
+    pub fn add(&self, other: &Real) -> Option<Real> {
+        self.with(other, &self.value + &other.value)
+    }
+
+    pub fn sub(&self, other: &Real) -> Option<Real> {
+        self.with(other, &self.value - &other.value)
+    }
+
+    pub fn mul(&self, other: &Real) -> Option<Real> {
+        self.with(other, &self.value * &other.value)
+    }
+
+    pub fn div(&self, other: &Real) -> Option<Real> {
+        if other.value.is_zero() {
+            return None;
+        }
+        self.with(other, &self.value / &other.value)
+    }
+
+    pub fn pow(&self, exponent: &Real) -> Option<Real> {
+        let base = &self.value;
+        if exponent.value.is_integer() {
+            let n = exponent.value.to_integer().to_i32()?;
+            let width = base.numer().bits() + base.denom().bits();
+            if (n < 0 && base.is_zero()) || width * u64::from(n.unsigned_abs()) > 2 * MAX_BITS {
+                return None;
+            }
+            return self.with(exponent, base.pow(n));
+        }
+        if base.is_zero() {
+            return exponent.value.is_positive().then(|| Real::integer(0));
+        }
+        // `8^(1/3)`, `4^1.5`: a whole root stays exact
+        if self.exact && exponent.exact {
+            let q = exponent.value.denom().to_u32().filter(|q| *q <= 64);
+            if let Some(root) = q.and_then(|q| root(base, q)) {
+                return Real::exact(root).pow(&Real::exact(BigRational::from_integer(
+                    exponent.value.numer().clone(),
+                )));
+            }
+        }
+        if base.is_negative() {
+            return None;
+        }
+        let f = Fixed::new();
+        let ln = f.ln(base)?;
+        let product = f.mul(&ln, &f.from(&exponent.value));
+        Some(f.real(f.exp(&product)?))
+    }
+}
+
+impl std::fmt::Display for Real {
+    /// `p/q`, as [`Real::parse`] reads it back.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+/// The exact value of a decimal literal like `0.1`, `2e3` or `.5`.
+pub fn parse_decimal(text: &str) -> Option<BigRational> {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(i) => (&text[..i], text[i + 1..].parse::<i32>().ok()?),
+        None => (text, 0),
+    };
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, mantissa),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", whole, fraction);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let exponent = exponent - i32::try_from(fraction.len()).ok()?;
+    if exponent.unsigned_abs() > 10_000 {
+        return None;
+    }
+    let numer: BigInt = digits.parse::<BigInt>().ok()? * sign;
+    let scale = ten_pow(exponent.unsigned_abs());
+    Some(if exponent >= 0 {
+        BigRational::from_integer(numer * scale)
+    } else {
+        BigRational::new(numer, scale)
+    })
+}
+
+/// The rational an f64 was meant to be: its shortest decimal reading, or the
+/// simplest fraction that rounds to it, so `5.0 / 9.0` gives back 5/9.
+pub fn rationalize(v: f64) -> Option<BigRational> {
+    let decimal = parse_decimal(&format!("{:e}", v))?;
+    let significant = format!("{:e}", v.abs())
+        .split('e')
+        .next()
+        .map_or(0, |m| m.len() - usize::from(m.contains('.')));
+    if significant <= 15 {
+        return Some(decimal);
+    }
+    // Continued-fraction convergents until one is within rounding of `v`
+    let tolerance = decimal.abs() * BigRational::new(1.into(), ten_pow(15));
+    let (mut h, mut h_prev) = (BigInt::one(), BigInt::zero());
+    let (mut k, mut k_prev) = (BigInt::zero(), BigInt::one());
+    let mut rest = decimal.clone();
+    for _ in 0..64 {
+        let a = rest.floor().to_integer();
+        (h, h_prev) = (&a * &h + &h_prev, h);
+        (k, k_prev) = (&a * &k + &k_prev, k);
+        let convergent = BigRational::new(h.clone(), k.clone());
+        let fract = &rest - BigRational::from_integer(a);
+        if (&convergent - &decimal).abs() <= tolerance || fract.is_zero() {
+            return Some(convergent);
+        }
+        rest = fract.recip();
+    }
+    Some(decimal)
+}
+
+/// The exact `n`th root of a rational, if it has one.
+fn root(value: &BigRational, n: u32) -> Option<BigRational> {
+    if value.is_negative() && n.is_multiple_of(2) {
+        return None;
+    }
+    let exact = |v: &BigInt| {
+        let r = v.nth_root(n);
+        (r.pow(n) == *v).then_some(r)
+    };
+    Some(BigRational::new(
+        exact(value.numer())?,
+        exact(value.denom())?,
+    ))
+}
+
+// ─── Constants & Functions ───────────────────────────────
+
+/// A named constant to [`WORK_DIGITS`] places.
+pub fn constant(name: &str) -> Option<Real> {
+    let f = Fixed::new();
+    let value = match name {
+        "pi" => f.pi(),
+        "tau" => f.pi() * 2,
+        "e" => f.exp(&f.one)?,
+        _ => return None,
+    };
+    Some(f.real(value))
+}
+
+/// Call a built-in function exactly where the answer is rational and to
+/// [`WORK_DIGITS`] places otherwise; `None` outside its domain or range.
+pub fn call(name: &str, args: &[Real]) -> Option<Real> {
+    let x = args.first()?;
+    let v = &x.value;
+    let keep = |value: BigRational| Real {
+        value,
+        exact: x.exact,
+    };
+    let f = Fixed::new();
+
+    // Points where the answer is a whole number: sin(0), cos(0), ln(1)…
+    if x.exact && args.len() == 1 {
+        let at_zero = match name {
+            "sin" | "tan" | "asin" | "atan" | "sinh" | "tanh" | "asinh" | "atanh" => Some(0),
+            "cos" | "cosh" | "exp" => Some(1),
+            _ => None,
+        };
+        if let (true, Some(n)) = (v.is_zero(), at_zero) {
+            return Some(Real::integer(n));
+        }
+        if v.is_one() && matches!(name, "ln" | "acos" | "acosh") {
+            return Some(Real::integer(0));
+        }
+    }
+
+    Some(match name {
+        "abs" => keep(v.abs()),
+        "floor" => keep(v.floor()),
+        "ceil" => keep(v.ceil()),
+        "round" => keep(v.round()),
+        "trunc" => keep(v.trunc()),
+        "signum" | "sign" => keep(v.signum()),
+        "min" => args.iter().min_by(|a, b| a.value.cmp(&b.value))?.clone(),
+        "max" => args.iter().max_by(|a, b| a.value.cmp(&b.value))?.clone(),
+        "pow" => x.pow(args.get(1)?)?,
+        "sqrt" | "cbrt" => {
+            let n = if name == "sqrt" { 2 } else { 3 };
+            match root(v, n) {
+                Some(r) => keep(r),
+                None if n == 2 && v.is_negative() => return None,
+                None => f.real(f.root(&f.from(v), n)),
+            }
+        }
+        "hypot" => {
+            let y = args.get(1)?;
+            let sum = x.mul(x)?.add(&y.mul(y)?)?;
+            call("sqrt", &[sum])?
+        }
+        "factorial" => {
+            let n = v
+                .to_integer()
+                .to_u32()
+                .filter(|n| v.is_integer() && *n <= MAX_FACTORIAL)?;
+            let product = (2..=n).fold(BigInt::one(), |acc, i| acc * i);
+            keep(BigRational::from_integer(product))
+        }
+        "exp" => f.real(f.exp(&f.from(v))?),
+        "ln" => f.real(f.ln(v)?),
+        "log" | "log10" => log(x, 10)?,
+        "log2" => log(x, 2)?,
+        "sin" | "cos" | "tan" => {
+            let (sin, cos) = f.sin_cos(&f.from(v))?;
+            f.real(match name {
+                "sin" => sin,
+                "cos" => cos,
+                // tan(pi/2): a cosine this small is zero but for rounding
+                _ if cos.abs() <= ten_pow(GUARD_DIGITS) => return None,
+                _ => f.div(&sin, &cos),
+            })
+        }
+        "asin" | "acos" => {
+            if v.abs() > BigRational::one() {
+                return None;
+            }
+            let fx = f.from(v);
+            let half_pi: BigInt = f.pi() / 2;
+            let asin = if v.abs().is_one() {
+                half_pi.clone() * v.signum().to_integer()
+            } else {
+                let cos = f.root(&(&f.one - f.mul(&fx, &fx)), 2);
+                f.atan(&f.div(&fx, &cos))
+            };
+            f.real(if name == "asin" { asin } else { half_pi - asin })
+        }
+        "atan" => f.real(f.atan(&f.from(v))),
+        "atan2" => {
+            // f64::atan2 is called on y with x as its argument
+            let (y, x) = (v, &args.get(1)?.value);
+            let pi = f.pi();
+            let angle = if x.is_zero() {
+                pi / 2 * y.signum().to_integer()
+            } else {
+                let a = f.atan(&f.from(&(y / x)));
+                match (x.is_negative(), y.is_negative()) {
+                    (false, _) => a,
+                    (true, false) => a + pi,
+                    (true, true) => a - pi,
+                }
+            };
+            f.real(angle)
+        }
+        "sinh" | "cosh" | "tanh" => {
+            let e = f.exp(&f.from(v))?;
+            let inverse = f.div(&f.one, &e);
+            f.real(match name {
+                "sinh" => (e - inverse) / 2,
+                "cosh" => (e + inverse) / 2,
+                _ => f.div(&(&e - &inverse), &(e + inverse)),
+            })
+        }
+        "asinh" | "acosh" => {
+            let fx = f.from(v);
+            let shift = if name == "asinh" { &f.one } else { &-&f.one };
+            let square = f.mul(&fx, &fx) + shift;
+            if square.is_negative() || (name == "acosh" && v.is_negative()) {
+                return None;
+            }
+            // asinh(-x) = -asinh(x) keeps the logarithm's argument positive
+            let sum = fx.abs() + f.root(&square, 2);
+            let ln = f.ln(&BigRational::new(sum, f.one.clone()))?;
+            f.real(ln * v.signum().to_integer().max(BigInt::from(name == "acosh")))
+        }
+        "atanh" => {
+            if v.abs() >= BigRational::one() {
+                return None;
+            }
+            let one = BigRational::one();
+            f.real(f.ln(&((&one + v) / (&one - v)))? / 2)
+        }
+        _ => return None,
+    })
+}
+
+/// `log10(1000)` is exactly 3; other logarithms are worked out.
+fn log(x: &Real, base: u32) -> Option<Real> {
+    if !x.value.is_positive() {
+        return None;
+    }
+    if x.exact {
+        let guess = x.to_f64().log(f64::from(base)).round();
+        if guess.is_finite() {
+            let n = guess as i32;
+            if BigRational::from_integer(base.into()).pow(n) == x.value {
+                return Some(Real::integer(n.into()));
+            }
+        }
+    }
+    let f = Fixed::new();
+    let ln = f.ln(&x.value)?;
+    let ln_base = f.ln(&BigRational::from_integer(base.into()))?;
+    Some(f.real(f.div(&ln, &ln_base)))
+}
+
+// ─── Fixed Point ─────────────────────────────────────────
+
+/// Fixed-point arithmetic on integers scaled by `one`.
+struct Fixed {
+    one: BigInt,
+}
+
+impl Fixed {
+    fn new() -> Self {
+        Fixed {
+            one: ten_pow(WORK_DIGITS + GUARD_DIGITS),
+        }
+    }
+
+    fn from(&self, value: &BigRational) -> BigInt {
+        (value * BigRational::from_integer(self.one.clone()))
+            .round()
+            .to_integer()
+    }
+
+    fn real(&self, value: BigInt) -> Real {
+        Real::approx(BigRational::new(value, self.one.clone()))
+    }
+
+    fn mul(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        a * b / &self.one
+    }
+
+    fn div(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        a * &self.one / b
+    }
+
+    fn root(&self, x: &BigInt, n: u32) -> BigInt {
+        (x * self.one.pow(n - 1)).nth_root(n)
+    }
+
+    /// e^x, by halving x until the series converges fast and squaring back.
+    fn exp(&self, x: &BigInt) -> Option<BigInt> {
+        if x.abs() > &self.one * 10_000 {
+            return None;
+        }
+        if x.is_negative() {
+            return Some(self.div(&self.one, &self.exp(&-x)?));
+        }
+        let half = &self.one / 2;
+        let (mut r, mut halvings) = (x.clone(), 0);
+        while r > half {
+            r >>= 1;
+            halvings += 1;
+        }
+        let (mut sum, mut term) = (self.one.clone(), self.one.clone());
+        for n in 1u32.. {
+            term = self.mul(&term, &r) / n;
+            if term.is_zero() {
+                break;
+            }
+            sum += &term;
+        }
+        for _ in 0..halvings {
+            sum = self.mul(&sum, &sum);
+        }
+        Some(sum)
+    }
+
+    /// ln of a positive rational, as ln(numerator) - ln(denominator) so tiny
+    /// and huge values keep their digits.
+    fn ln(&self, value: &BigRational) -> Option<BigInt> {
+        if !value.is_positive() {
+            return None;
+        }
+        let ln2 = self.atanh(&(&self.one / 3)) * 2;
+        // n = m·2^k with m in [1/2, 1): ln n = k·ln 2 + 2·atanh((m-1)/(m+1))
+        let ln_int = |n: &BigInt| {
+            let k = n.bits();
+            let m = (n * &self.one) >> k;
+            let z = self.div(&(&m - &self.one), &(&m + &self.one));
+            &ln2 * k + self.atanh(&z) * 2
+        };
+        Some(ln_int(value.numer()) - ln_int(value.denom()))
+    }
+
+    fn atanh(&self, z: &BigInt) -> BigInt {
+        let square = self.mul(z, z);
+        let (mut sum, mut power) = (z.clone(), z.clone());
+        for n in 1u32.. {
+            power = self.mul(&power, &square);
+            let term = &power / (2 * n + 1);
+            if term.is_zero() {
+                break;
+            }
+            sum += term;
+        }
+        sum
+    }
+
+    /// The Taylor series, for |x| well below 1.
+    fn atan_series(&self, x: &BigInt) -> BigInt {
+        let square = self.mul(x, x);
+        let (mut sum, mut power) = (x.clone(), x.clone());
+        for n in 1u32.. {
+            power = -self.mul(&power, &square);
+            let term = &power / (2 * n + 1);
+            if term.is_zero() {
+                break;
+            }
+            sum += term;
+        }
+        sum
+    }
+
+    fn atan(&self, x: &BigInt) -> BigInt {
+        if x.is_negative() {
+            return -self.atan(&-x);
+        }
+        if *x > self.one {
+            return self.pi() / 2 - self.atan(&self.div(&self.one, x));
+        }
+        // atan(x) = 2·atan(x / (1 + √(1 + x²))), twice, brings x under 0.2
+        let mut x = x.clone();
+        for _ in 0..2 {
+            let hyp = self.root(&(&self.one + self.mul(&x, &x)), 2);
+            x = self.div(&x, &(&self.one + hyp));
+        }
+        self.atan_series(&x) * 4
+    }
+
+    /// Machin's formula: π = 16·atan(1/5) - 4·atan(1/239).
+    fn pi(&self) -> BigInt {
+        let fifth = &self.one / 5;
+        let small = &self.one / 239;
+        self.atan_series(&fifth) * 16 - self.atan_series(&small) * 4
+    }
+
+    fn sin_cos(&self, x: &BigInt) -> Option<(BigInt, BigInt)> {
+        if x.abs() > &self.one * 1_000_000 {
+            return None;
+        }
+        // Reduce |x| into [-π, π]
+        let tau = self.pi() * 2;
+        let mut r = x.abs() % &tau;
+        if r > &tau / 2 {
+            r -= &tau;
+        }
+        let square = self.mul(&r, &r);
+        let series = |first: BigInt, offset: u32| {
+            let (mut sum, mut term) = (first.clone(), first);
+            for n in 1u32.. {
+                let k = 2 * n + offset;
+                term = -self.mul(&term, &square) / ((k - 1) * k);
+                if term.is_zero() {
+                    break;
+                }
+                sum += &term;
+            }
+            sum
+        };
+        let sin = series(r.clone(), 1);
+        let cos = series(self.one.clone(), 0);
+        let sin = if x.is_negative() { -sin } else { sin };
+        Some((sin, cos))
+    }
+}
+
+// ─── Formatting ──────────────────────────────────────────
+
+/// floor(log10 |v|) for a nonzero value.
+fn exponent(v: &BigRational) -> i64 {
+    let digits = |n: &BigInt| n.abs().to_string().len() as i64;
+    let e = digits(v.numer()) - digits(v.denom());
+    if v.abs() >= power_of_ten(e) {
+        e
+    } else {
+        e - 1
+    }
+}
+
+fn power_of_ten(e: i64) -> BigRational {
+    let p = ten_pow(e.unsigned_abs() as u32);
+    if e >= 0 {
+        BigRational::from_integer(p)
+    } else {
+        BigRational::new(BigInt::one(), p)
+    }
+}
+
+/// |v| rounded to a whole number of units of 10^-places.
+fn scaled(v: &BigRational, places: i64) -> BigInt {
+    (v.abs() * power_of_ten(places)).round().to_integer()
+}
+
+fn sign(v: &BigRational) -> &'static str {
+    if v.is_negative() {
+        "-"
+    } else {
+        ""
+    }
+}
+
+/// `v` rounded to `places` decimals, without trailing zeros.
+fn positional(v: &BigRational, places: usize) -> String {
+    let digits = scaled(v, places as i64).to_string();
+    let digits = format!("{:0>1$}", digits, places + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - places);
+    let fraction = fraction.trim_end_matches('0');
+    let sign = if whole.chars().chain(fraction.chars()).all(|c| c == '0') {
+        ""
+    } else {
+        sign(v)
+    };
+    if fraction.is_empty() {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+/// `v` to `significant` digits as `1.5e-12`.
+fn scientific(v: &BigRational, significant: usize) -> String {
+    let mut e = exponent(v);
+    let mut digits = scaled(v, significant as i64 - 1 - e).to_string();
+    // Rounding 9.99… up adds a digit
+    if digits.len() > significant {
+        e += 1;
+        digits.truncate(significant);
+    }
+    let (first, rest) = digits.split_at(1);
+    let rest = rest.trim_end_matches('0');
+    let mantissa = if rest.is_empty() {
+        first.to_string()
+    } else {
+        format!("{}.{}", first, rest)
+    };
+    format!("{}{}e{}", sign(v), mantissa, e)
+}
+
+/// `v` to `significant` digits, positional while that stays readable.
+fn to_digits(v: &BigRational, significant: usize) -> String {
+    let e = exponent(v);
+    if (-10..significant as i64).contains(&e) {
+        positional(v, (significant as i64 - 1 - e).max(0) as usize)
+    } else {
+        scientific(v, significant)
+    }
+}
+
+pub fn show(r: &Real) -> Shown {
+    let v = &r.value;
+    if v.is_zero() {
+        return Shown {
+            display: "0".to_string(),
+            fraction: None,
+            precise: None,
+        };
+    }
+
+    // Exact whole numbers are written out unless they're enormous
+    if r.exact && v.is_integer() {
+        let digits = v.to_integer().to_string();
+        if digits.trim_start_matches('-').len() <= DISPLAY_DIGITS {
+            return Shown {
+                display: digits,
+                fraction: None,
+                precise: None,
+            };
+        }
+        return Shown {
+            display: scientific(v, DISPLAY_DECIMALS + 1),
+            fraction: None,
+            precise: (digits.len() <= MAX_WRITTEN_DIGITS).then_some(digits),
+        };
+    }
+
+    let display = if (-(DISPLAY_DECIMALS as i64)..15).contains(&exponent(v)) {
+        positional(v, DISPLAY_DECIMALS)
+    } else {
+        scientific(v, DISPLAY_DECIMALS + 1)
+    };
+    let complete = r.exact && parse_decimal(&display).as_ref() == Some(v);
+    let precise = Some(to_digits(v, PRECISE_DIGITS)).filter(|p| !complete && *p != display);
+    let short = v.denom().to_string().len() <= FRACTION_DIGITS;
+    let fraction = (r.exact && !complete && short).then(|| v.to_string());
+    Shown {
+        display,
+        fraction,
+        precise,
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn real(text: &str) -> Real {
+        Real::exact(parse_decimal(text).unwrap())
+    }
+
+    fn digits(r: &Real) -> String {
+        to_digits(&r.value, PRECISE_DIGITS)
+    }
+
+    #[test]
+    fn decimals_and_rationalizing() {
+        assert_eq!(real("0.1").add(&real("0.2")).unwrap(), real("0.3"));
+        assert_eq!(parse_decimal("2.5e-3"), parse_decimal("0.0025"));
+        assert_eq!(parse_decimal(".5").unwrap().to_string(), "1/2");
+        assert!(parse_decimal("1.2.3").is_none());
+
+        assert_eq!(rationalize(0.3048).unwrap().to_string(), "381/1250");
+        assert_eq!(rationalize(5.0 / 9.0).unwrap().to_string(), "5/9");
+        assert_eq!(rationalize(1852.0 / 3600.0).unwrap().to_string(), "463/900");
+        assert_eq!(
+            rationalize(1e-6 * 1e-3).unwrap().to_string(),
+            "1/1000000000"
+        );
+        assert_eq!(
+            rationalize(1.478_676_478_125e-5),
+            parse_decimal("1.478676478125e-5")
+        );
+    }
+
+    #[test]
+    fn exact_powers_and_roots() {
+        let two = Real::integer(2);
+        let r = two.pow(&Real::integer(100)).unwrap();
+        assert_eq!(show(&r).precise.unwrap(), "1267650600228229401496703205376");
+        assert!(!real("8").pow(&real("0.5")).unwrap().exact);
+        let r = real("8").pow(&Real::exact(BigRational::new(2.into(), 3.into())));
+        assert_eq!(r.unwrap(), Real::integer(4));
+        assert_eq!(call("sqrt", &[real("2.25")]).unwrap(), real("1.5"));
+        assert!(two.pow(&Real::integer(1_000_000)).is_none());
+        assert!(Real::integer(0).pow(&Real::integer(-1)).is_none());
+    }
+
+    #[test]
+    fn high_precision_functions() {
+        let pi = constant("pi").unwrap();
+        assert_eq!(digits(&pi), "3.141592653589793238462643383279502884197");
+        let e = constant("e").unwrap();
+        assert_eq!(digits(&e), "2.718281828459045235360287471352662497757");
+        let sqrt2 = call("sqrt", &[Real::integer(2)]).unwrap();
+        assert_eq!(digits(&sqrt2), "1.41421356237309504880168872420969807857");
+        let ln2 = call("ln", &[Real::integer(2)]).unwrap();
+        assert_eq!(digits(&ln2), "0.6931471805599453094172321214581765680755");
+        let sin1 = call("sin", &[Real::integer(1)]).unwrap();
+        assert_eq!(digits(&sin1), "0.8414709848078965066525023216302989996226");
+        let atan1 = call("atan", &[Real::integer(1)]).unwrap();
+        assert_eq!(digits(&atan1), digits(&pi.div(&Real::integer(4)).unwrap()));
+
+        // Whole answers stay exact
+        assert_eq!(call("log10", &[real("1000")]).unwrap(), Real::integer(3));
+        assert_eq!(call("log2", &[real("0.125")]).unwrap(), Real::integer(-3));
+        assert_eq!(call("cos", &[Real::integer(0)]).unwrap(), Real::integer(1));
+        let sin_pi = call("sin", &[pi]).unwrap();
+        assert_eq!(show(&sin_pi).display, "0");
+        assert_eq!(show(&sin_pi).precise, None);
+        assert!(call("ln", &[Real::integer(-1)]).is_none());
+        assert!(call("asin", &[Real::integer(2)]).is_none());
+        let half_pi = constant("pi").unwrap().div(&Real::integer(2)).unwrap();
+        assert!(call("tan", &[half_pi]).is_none());
+    }
+
+    #[test]
+    fn factorials() {
+        let r = call("factorial", &[Real::integer(25)]).unwrap();
+        assert_eq!(show(&r).display, "15511210043330985984000000");
+        let r = call("factorial", &[Real::integer(100)]).unwrap();
+        let shown = show(&r);
+        assert_eq!(shown.display, "9.3326215444e157");
+        assert_eq!(shown.precise.unwrap().len(), 158);
+        assert!(call("factorial", &[real("2.5")]).is_none());
+    }
+
+    #[test]
+    fn shown_forms() {
+        let third = Real::integer(1).div(&Real::integer(3)).unwrap();
+        let shown = show(&third);
+        assert_eq!(shown.display, "0.3333333333");
+        assert_eq!(shown.fraction.as_deref(), Some("1/3"));
+        assert_eq!(shown.precise.unwrap(), format!("0.{}", "3".repeat(40)));
+
+        let shown = show(&real("0.3"));
+        assert_eq!((shown.fraction, shown.precise), (None, None));
+        assert_eq!(show(&real("-2.5")).display, "-2.5");
+        assert_eq!(show(&real("1e20")).display, "100000000000000000000");
+        assert_eq!(show(&real("1.5e-12")).display, "1.5e-12");
+        assert_eq!(
+            show(&real("123456789012345.5")).display,
+            "123456789012345.5"
+        );
+        assert_eq!(show(&real("1234567890123456.5")).display, "1.2345678901e15");
+        assert_eq!(show(&Real::from_f64(1e20).unwrap()).display, "1e20");
+        assert_eq!(show(&real("-0.00000000001")).display, "-1e-11");
+    }
+}
@@ -0,0 +1,174 @@
+/// Calculator history and memory: past calculations committed with Enter,
+/// plus the variables, user functions and `ans` they left behind.
+///
+/// Both persist across launches in one file, so `rate = 0.07` typed
+/// yesterday still works today.
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::calc::{CalcResult, Memory};
+use crate::json_store::{self, JsonStore};
+
+/// Calculations kept; the oldest are dropped first.
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalcEntry {
+    pub id: u64,
+    /// The input as typed
+    pub expression: String,
+    /// The result as shown, e.g. "96.56064 km/h" or "rate = 0.07"
+    pub display: String,
+    /// Unix timestamp of the latest time it was committed
+    pub created: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CalcHistory {
+    version: u32,
+    next_id: u64,
+    /// Oldest first
+    entries: Vec<CalcEntry>,
+    pub memory: Memory,
+}
+
+pub type SharedCalcHistory = Arc<RwLock<CalcHistory>>;
+
+impl JsonStore for CalcHistory {
+    const VERSION: u32 = 1;
+    const NAME: &'static str = "calculator history";
+
+    /// Lives in the data dir next to the shell history.
+    fn path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("trace")
+            .join("calc_history.json")
+    }
+
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+impl CalcHistory {
+    pub fn new() -> Self {
+        Self {
+            version: Self::VERSION,
+            next_id: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Load the history from disk, or start empty if it is missing or outdated.
+    pub fn load() -> Self {
+        json_store::load().unwrap_or_else(Self::new)
+    }
+
+    /// Remember a committed calculation; repeating the latest one only
+    /// refreshes it. Returns the id of its entry.
+    pub fn record(&mut self, result: &CalcResult, now: u64) -> u64 {
+        if let Some(last) = self.entries.last_mut() {
+            if last.expression == result.expression {
+                last.display = result.display.clone();
+                last.created = now;
+                return last.id;
+            }
+        }
+
+        let id = self.next_id.max(1);
+        self.next_id = id + 1;
+        self.entries.push(CalcEntry {
+            id,
+            expression: result.expression.clone(),
+            display: result.display.clone(),
+            created: now,
+        });
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..excess);
+        }
+        id
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.entries.retain(|e| e.id != id);
+    }
+
+    /// Entries whose input or result contains every word of `query`, newest
+    /// first. An empty query lists everything.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<CalcEntry> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        self.entries
+            .iter()
+            .rev()
+            .filter(|e| {
+                let text = format!("{}\n{}", e.expression, e.display).to_lowercase();
+                words.iter().all(|w| text.contains(w.as_str()))
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calc;
+
+    const T0: u64 = 1_700_000_000;
+
+    fn commit(history: &mut CalcHistory, input: &str, now: u64) -> u64 {
+        let result = calc::execute(input, &mut history.memory).unwrap();
+        history.record(&result, now)
+    }
+
+    #[test]
+    fn records_and_searches() {
+        let mut history = CalcHistory::new();
+        let a = commit(&mut history, "rate = 7%", T0);
+        let b = commit(&mut history, "60 mph in km/h", T0 + 1);
+        let c = commit(&mut history, "200 * rate", T0 + 2);
+        // Committing the latest calculation again refreshes it
+        assert_eq!(commit(&mut history, "200 * rate", T0 + 3), c);
+
+        let ids = |found: Vec<CalcEntry>| found.iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(ids(history.search("", 10)), [c, b, a]);
+        assert_eq!(ids(history.search("RATE", 10)), [c, a]);
+        assert_eq!(ids(history.search("km/h 96", 10)), [b]);
+        assert_eq!(history.search("", 10)[0].display, "14");
+
+        history.remove(b);
+        assert_eq!(ids(history.search("", 1)), [c]);
+        assert_eq!(history.search("", 10).len(), 2);
+    }
+
+    #[test]
+    fn memory_round_trips() {
+        let mut history = CalcHistory::new();
+        commit(&mut history, "d = 5 km", T0);
+        commit(&mut history, "f(x) = x^2 + 1", T0 + 1);
+        commit(&mut history, "f(3)", T0 + 2);
+
+        let json = serde_json::to_string(&history).unwrap();
+        let restored: CalcHistory = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.memory, history.memory);
+        let r = calc::evaluate("d / 2 + ans m", &restored.memory).unwrap();
+        assert_eq!(r.display, "2.51 km");
+    }
+
+    #[test]
+    fn trims_oldest() {
+        let mut history = CalcHistory::new();
+        for i in 0..MAX_ENTRIES + 3 {
+            commit(&mut history, &format!("{} + 1", i), T0 + i as u64);
+        }
+        assert_eq!(history.entries.len(), MAX_ENTRIES);
+        assert_eq!(history.entries[0].expression, "3 + 1");
+    }
+}
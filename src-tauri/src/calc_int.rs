@@ -0,0 +1,463 @@
+/// Programmer mode: exact arithmetic on arbitrary-width integers.
+///
+/// Expressions with `0x` / `0b` / `0o` literals, bitwise operators or a base
+/// or width target (`255 in hex`, `-1 in i8`) are evaluated here instead of
+/// in f64. Results are shown in hex, decimal, octal and binary at once, and
+/// negative numbers in two's complement at 8, 16, 32 or 64 bits.
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Num, One, Signed, ToPrimitive, Zero};
+use serde::{Deserialize, Serialize};
+
+use crate::calc::Memory;
+use crate::calc_parse::{BinOp, BitOp, Expr, ExprKind, ParseError};
+
+/// Results wider than this are refused, so `1 << 10^9` can't stall the bar.
+const MAX_BITS: u64 = 8192;
+
+/// Two's-complement widths negative results are shown at.
+const WIDTHS: &[u32] = &[8, 16, 32, 64];
+
+/// A whole number written in every base programmers use.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bases {
+    pub hex: String,
+    pub dec: String,
+    pub oct: String,
+    pub bin: String,
+    /// Width of the two's complement shown in hex, octal and binary;
+    /// `None` when they carry a sign instead
+    pub bits: Option<u32>,
+}
+
+/// A programmer-mode result.
+#[derive(Debug, Clone)]
+pub struct Whole {
+    pub value: BigInt,
+    pub bases: Bases,
+    /// The base the result reads best in: the one asked for, else the one
+    /// the first literal was written in
+    pub display: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Radix {
+    Hex,
+    Dec,
+    Oct,
+    Bin,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Radix(Radix),
+    /// `in i8`, `in u32`: wrap to that many bits
+    Width {
+        bits: u32,
+        signed: bool,
+    },
+}
+
+fn target(name: &str) -> Option<Target> {
+    let name = name.to_ascii_lowercase();
+    let radix = match name.as_str() {
+        "hex" => Radix::Hex,
+        "dec" => Radix::Dec,
+        "oct" => Radix::Oct,
+        "bin" => Radix::Bin,
+        _ => {
+            let signed = name.starts_with('i');
+            let bits = name.strip_prefix(['i', 'u'])?.parse().ok()?;
+            return WIDTHS
+                .contains(&bits)
+                .then_some(Target::Width { bits, signed });
+        }
+    };
+    Some(Target::Radix(radix))
+}
+
+/// A whole-number literal and the base it was written in; `None` for
+/// decimals like `1.5` or `2e3`.
+fn literal(text: &str) -> Option<(BigInt, Radix)> {
+    let lower = text.to_ascii_lowercase();
+    let (digits, radix, base) = if let Some(d) = lower.strip_prefix("0x") {
+        (d, Radix::Hex, 16)
+    } else if let Some(d) = lower.strip_prefix("0o") {
+        (d, Radix::Oct, 8)
+    } else if let Some(d) = lower.strip_prefix("0b") {
+        (d, Radix::Bin, 2)
+    } else {
+        (lower.as_str(), Radix::Dec, 10)
+    };
+    let digits = digits.replace('_', "");
+    if digits.is_empty() {
+        return None;
+    }
+    BigInt::from_str_radix(&digits, base)
+        .ok()
+        .map(|v| (v, radix))
+}
+
+/// The value of a `0x` / `0b` / `0o` literal, for the decimal calculator.
+pub fn based_literal(text: &str) -> Option<BigInt> {
+    match literal(text)? {
+        (_, Radix::Dec) => None,
+        (value, _) => Some(value),
+    }
+}
+
+/// Whether a literal is written in hex, octal or binary.
+pub fn is_based(text: &str) -> bool {
+    text.len() > 2
+        && matches!(
+            &text.as_bytes()[..2],
+            b"0x" | b"0X" | b"0o" | b"0O" | b"0b" | b"0B"
+        )
+}
+
+/// Whether an expression is meant for programmer mode.
+pub fn is_programmer(expr: &Expr) -> bool {
+    uses(expr, true)
+}
+
+/// Whether an expression only makes sense in programmer mode; `0x10 * 1.5`
+/// is just a number written in hex.
+pub fn is_bitwise(expr: &Expr) -> bool {
+    uses(expr, false)
+}
+
+/// Whether `expr` has bitwise operators or a base target, or, with
+/// `literals`, a based literal.
+fn uses(expr: &Expr, literals: bool) -> bool {
+    let any = |e: &Expr| uses(e, literals);
+    match &expr.kind {
+        ExprKind::Number(text) => literals && is_based(text),
+        ExprKind::Name(_) => false,
+        ExprKind::Not(_) | ExprKind::Bitwise(..) => true,
+        ExprKind::Neg(a) | ExprKind::Percent(a) => any(a),
+        ExprKind::Binary(_, a, b) => any(a) || any(b),
+        ExprKind::Call(_, args) => args.iter().any(any),
+        ExprKind::Convert(value, to) => {
+            matches!(&to.kind, ExprKind::Name(n) if target(n).is_some()) || any(value)
+        }
+    }
+}
+
+/// Evaluate an expression exactly, as whole numbers.
+pub fn evaluate(expr: &Expr, memory: &Memory) -> Result<Whole, ParseError> {
+    let (value, to) = match &expr.kind {
+        ExprKind::Convert(value, to) => {
+            let target = match &to.kind {
+                ExprKind::Name(name) => target(name),
+                _ => None,
+            };
+            let target = target.ok_or_else(|| {
+                ParseError::new("Expected hex, dec, oct, bin or a width like i8", to.span.0)
+            })?;
+            (value.as_ref(), Some(target))
+        }
+        _ => (expr, None),
+    };
+
+    let mut eval = Evaluator {
+        memory,
+        radix: None,
+    };
+    let mut value = eval.eval(value)?;
+    let (radix, bits) = match to {
+        Some(Target::Radix(radix)) => (radix, None),
+        Some(Target::Width { bits, signed }) => {
+            value = wrap(&value, bits, signed);
+            (Radix::Dec, Some(bits))
+        }
+        None => (eval.radix.unwrap_or(Radix::Dec), None),
+    };
+    // Negative numbers read as their bit pattern at the narrowest width that holds them
+    let bits = match bits {
+        None if value.is_negative() => {
+            let min = |bits: u32| -(BigInt::one() << (bits - 1));
+            WIDTHS.iter().copied().find(|b| value >= min(*b))
+        }
+        bits => bits,
+    };
+
+    let bases = bases(&value, bits);
+    let display = match radix {
+        Radix::Hex => bases.hex.clone(),
+        Radix::Dec => bases.dec.clone(),
+        Radix::Oct => bases.oct.clone(),
+        Radix::Bin => bases.bin.clone(),
+    };
+    Ok(Whole {
+        value,
+        bases,
+        display,
+    })
+}
+
+struct Evaluator<'a> {
+    memory: &'a Memory,
+    /// Base of the first literal written in one
+    radix: Option<Radix>,
+}
+
+impl Evaluator<'_> {
+    fn eval(&mut self, expr: &Expr) -> Result<BigInt, ParseError> {
+        let at = expr.span.0;
+        let value = match &expr.kind {
+            ExprKind::Number(text) => {
+                let (value, radix) = literal(text).ok_or_else(|| {
+                    let message = if is_based(text) {
+                        format!("Bad number '{}'", text)
+                    } else {
+                        "Bitwise math only takes whole numbers".to_string()
+                    };
+                    ParseError::new(message, at)
+                })?;
+                if radix != Radix::Dec {
+                    self.radix.get_or_insert(radix);
+                }
+                value
+            }
+            ExprKind::Name(name) => self.name(name, at)?,
+            ExprKind::Neg(inner) => -self.eval(inner)?,
+            ExprKind::Not(inner) => !self.eval(inner)?,
+            ExprKind::Binary(op, lhs, rhs) => {
+                let a = self.eval(lhs)?;
+                let b = self.eval(rhs)?;
+                match op {
+                    BinOp::Add => a + b,
+                    BinOp::Sub => a - b,
+                    BinOp::Mul => a * b,
+                    // Rounds toward zero, like integer division in C
+                    BinOp::Div if b.is_zero() => {
+                        return Err(ParseError::new("Division by zero", rhs.span.0))
+                    }
+                    BinOp::Div => a / b,
+                    BinOp::Pow => {
+                        let exponent = b
+                            .to_u32()
+                            .filter(|e| a.bits().saturating_mul(u64::from(*e)) <= MAX_BITS)
+                            .ok_or_else(|| {
+                                let message = if b.is_negative() {
+                                    "A negative power isn't a whole number"
+                                } else {
+                                    "Result is too large"
+                                };
+                                ParseError::new(message, rhs.span.0)
+                            })?;
+                        a.pow(exponent)
+                    }
+                    BinOp::Of | BinOp::Mixed => {
+                        return Err(ParseError::new(
+                            "Bitwise math only takes whole numbers",
+                            rhs.span.0,
+                        ))
+                    }
+                }
+            }
+            ExprKind::Bitwise(op, lhs, rhs) => {
+                let a = self.eval(lhs)?;
+                let b = self.eval(rhs)?;
+                match op {
+                    BitOp::Or => a | b,
+                    BitOp::Xor => a ^ b,
+                    BitOp::And => a & b,
+                    BitOp::Shl | BitOp::Shr => {
+                        let shift = b
+                            .to_u64()
+                            .filter(|s| *op == BitOp::Shr || a.bits() + s <= MAX_BITS)
+                            .ok_or_else(|| {
+                                let message = if b.is_negative() {
+                                    "Can't shift by a negative amount"
+                                } else {
+                                    "Result is too large"
+                                };
+                                ParseError::new(message, rhs.span.0)
+                            })?;
+                        if *op == BitOp::Shl {
+                            a << shift
+                        } else {
+                            // Rounds toward minus infinity, so -1 >> 1 stays -1
+                            a >> shift
+                        }
+                    }
+                }
+            }
+            ExprKind::Percent(_) => {
+                return Err(ParseError::new(
+                    "Percentages don't work with bitwise math",
+                    at,
+                ))
+            }
+            ExprKind::Call(name, _) => {
+                return Err(ParseError::new(
+                    format!("'{}' doesn't work with bitwise math", name),
+                    at,
+                ))
+            }
+            ExprKind::Convert(_, to) => {
+                return Err(ParseError::new("Conversions go at the end", to.span.0))
+            }
+        };
+        if value.bits() > MAX_BITS {
+            return Err(ParseError::new("Result is too large", at));
+        }
+        Ok(value)
+    }
+
+    /// A remembered whole number.
+    fn name(&self, name: &str, at: usize) -> Result<BigInt, ParseError> {
+        let Some(stored) = self.memory.variable(name) else {
+            let message = if name == "ans" {
+                "There is no previous result yet".to_string()
+            } else {
+                format!("'{}' doesn't work with bitwise math", name)
+            };
+            return Err(ParseError::new(message, at));
+        };
+        let value = match &stored.exact {
+            _ if !stored.dim.is_none() => None,
+            Some(exact) => exact.parse::<BigInt>().ok(),
+            None if stored.value.fract() == 0.0 => BigInt::from_f64(stored.value),
+            None => None,
+        };
+        value.ok_or_else(|| ParseError::new(format!("'{}' isn't a whole number", name), at))
+    }
+}
+
+/// `value` cut to its low `bits`, read as signed or unsigned.
+fn wrap(value: &BigInt, bits: u32, signed: bool) -> BigInt {
+    let modulus = BigInt::one() << bits;
+    let low: BigInt = value & (&modulus - 1u32);
+    if signed && low.bit(u64::from(bits) - 1) {
+        low - modulus
+    } else {
+        low
+    }
+}
+
+fn bases(value: &BigInt, bits: Option<u32>) -> Bases {
+    let (sign, magnitude, width) = match bits {
+        // The bit pattern, padded to the full width
+        Some(bits) => ("", wrap(value, bits, false), bits as usize),
+        None if value.is_negative() => ("-", -value, 0),
+        None => ("", value.clone(), 0),
+    };
+    let digits = |radix: u32, per_digit: usize| {
+        let text = magnitude.to_str_radix(radix);
+        format!("{:0>1$}", text, width / per_digit)
+    };
+    Bases {
+        hex: format!("{}0x{}", sign, digits(16, 4)),
+        dec: value.to_string(),
+        oct: format!("{}0o{}", sign, magnitude.to_str_radix(8)),
+        bin: format!("{}0b{}", sign, group(&digits(2, 1))),
+        bits,
+    }
+}
+
+/// Binary digits in groups of four, as `0b1010_0101` is typed.
+fn group(digits: &str) -> String {
+    let mut out = String::with_capacity(digits.len() * 5 / 4);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(4) {
+            out.push('_');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calc_parse;
+
+    fn eval(src: &str) -> Result<Whole, String> {
+        let expr = calc_parse::parse(src).unwrap();
+        assert!(is_programmer(&expr), "{}", src);
+        evaluate(&expr, &Memory::default()).map_err(|e| e.message)
+    }
+
+    fn shown(src: &str) -> String {
+        eval(src).unwrap().display
+    }
+
+    #[test]
+    fn literals_and_operators() {
+        assert_eq!(shown("0xF0 | 0x0F"), "0xff");
+        assert_eq!(shown("0b1100 & 0b1010"), "0b1000");
+        assert_eq!(shown("0o17 xor 5"), "0o12");
+        assert_eq!(shown("1 << 4"), "16");
+        assert_eq!(shown("0x100 >> 4 + 1"), "0x8");
+        assert_eq!(shown("7 / 2 & 0xf"), "0x3");
+        assert_eq!(shown("255 in hex"), "0xff");
+        assert_eq!(shown("10 as bin"), "0b1010");
+        assert_eq!(shown("0xff_ff in dec"), "65535");
+    }
+
+    #[test]
+    fn arbitrary_width() {
+        let r = eval("1 << 100 | 1").unwrap();
+        assert_eq!(r.bases.dec, "1267650600228229401496703205377");
+        assert_eq!(r.bases.hex, format!("0x1{}1", "0".repeat(24)));
+        assert_eq!(shown("0x1 << 64 in dec"), "18446744073709551616");
+        assert_eq!(eval("1 << 9000").unwrap_err(), "Result is too large");
+        assert_eq!(eval("0x2 ^ 10000").unwrap_err(), "Result is too large");
+    }
+
+    #[test]
+    fn twos_complement() {
+        let r = eval("-1 in i8").unwrap();
+        assert_eq!((r.display.as_str(), r.bases.hex.as_str()), ("-1", "0xff"));
+        assert_eq!(r.bases.bin, "0b1111_1111");
+        assert_eq!(r.bases.bits, Some(8));
+        assert_eq!(shown("-1 in u16"), "65535");
+        assert_eq!(shown("200 in i8"), "-56");
+        assert_eq!(shown("0x1ff in u8"), "255");
+
+        // Negative results pick the narrowest width that holds them
+        let r = eval("~0xf0").unwrap();
+        assert_eq!(r.value, BigInt::from(-241));
+        assert_eq!((r.display.as_str(), r.bases.bits), ("0xff0f", Some(16)));
+        assert_eq!(r.bases.oct, "0o177417");
+        let r = eval("-(1 << 70) & ~0").unwrap();
+        assert_eq!(
+            (r.bases.bits, r.bases.hex.as_str()),
+            (None, "-0x400000000000000000")
+        );
+        assert_eq!(shown("-1 >> 1"), "-1");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            eval("1.5 & 1").unwrap_err(),
+            "Bitwise math only takes whole numbers"
+        );
+        assert_eq!(eval("0b102 + 1").unwrap_err(), "Bad number '0b102'");
+        assert_eq!(eval("1 / 0 | 1").unwrap_err(), "Division by zero");
+        assert_eq!(
+            eval("2 ^ -1 & 1").unwrap_err(),
+            "A negative power isn't a whole number"
+        );
+        assert_eq!(
+            eval("1 << -1").unwrap_err(),
+            "Can't shift by a negative amount"
+        );
+        assert_eq!(
+            eval("sqrt(4) & 1").unwrap_err(),
+            "'sqrt' doesn't work with bitwise math"
+        );
+        assert_eq!(
+            eval("pi & 1").unwrap_err(),
+            "'pi' doesn't work with bitwise math"
+        );
+        assert_eq!(
+            eval("0x5 to km").unwrap_err(),
+            "Expected hex, dec, oct, bin or a width like i8"
+        );
+    }
+}
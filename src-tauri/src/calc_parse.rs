@@ -0,0 +1,815 @@
+/// Lexer and parser for calculator expressions.
+///
+/// Produces an [`Expr`] tree with implicit multiplication (`2x`, `3(4+1)`,
+/// `(1+2)(3+4)`), postfix percent (`15%`), the `of` operator, mixed-unit
+/// quantities (`5 ft 11 in`), bitwise operators (`0xf0 | 0x0f`, `1 << 4`)
+/// and `in` / `to` / `as` conversions made explicit,
+/// so the evaluator never has to guess at the text. Names are kept as written;
+/// deciding what they mean is left to the compiler in `calc`.
+use std::fmt;
+
+/// Character span of a node in the expression it was parsed from.
+pub type Span = (usize, usize);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    /// Numeric literal as written, so later stages can read it exactly;
+    /// may be `0x`, `0b` or `0o` prefixed
+    Number(String),
+    Name(String),
+    Neg(Box<Expr>),
+    /// `~5` / `not 5`
+    Not(Box<Expr>),
+    /// `15%`
+    Percent(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Bitwise(BitOp, Box<Expr>, Box<Expr>),
+    /// `name(args…)` — may turn out to be a variable times a group
+    Call(String, Vec<Expr>),
+    /// `3.5 GiB in MB`: the value, then the unit to show it in
+    Convert(Box<Expr>, Box<Expr>),
+}
+
+/// A line of calculator input: an expression, or a definition to remember.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Expr(Expr),
+    /// `rate = 0.07`
+    Assign {
+        name: String,
+        value: Expr,
+    },
+    /// `f(x) = x^2 + 1`; the body is also kept as written, for storing
+    Define {
+        name: String,
+        params: Vec<String>,
+        body: Expr,
+        source: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    /// `15% of 300`
+    Of,
+    /// `5 ft 11 in`: adjacent quantities, summed
+    Mixed,
+}
+
+/// Operators on whole numbers, loosest first: `|`, `xor`, `&`, shifts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOp {
+    Or,
+    Xor,
+    And,
+    Shl,
+    Shr,
+}
+
+/// Conversion targets that show a whole number another way: `255 in hex`,
+/// `-1 in i8`.
+pub const NUMBER_FORMATS: &[&str] = &[
+    "hex", "dec", "oct", "bin", "i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// Character offset in the expression
+    pub position: usize,
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>, position: usize) -> Self {
+        ParseError {
+            message: message.into(),
+            position,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+// ─── Lexer ───────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tok {
+    Number(String),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+    Comma,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub tok: Tok,
+    pub span: Span,
+}
+
+fn is_ident_start(c: char) -> bool {
+    // `°` starts unit names like `°C`
+    c.is_alphabetic() || c == '_' || c == '°'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Split an expression into tokens.
+pub fn tokenize(src: &str) -> Result<Vec<Token>, ParseError> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let radix = match chars.get(i + 1) {
+            Some('x' | 'X') => 16,
+            Some('o' | 'O') => 8,
+            Some('b' | 'B') => 2,
+            _ => 0,
+        };
+        let tok = if c == '0' && radix > 0 && chars.get(i + 2).is_some_and(|d| d.is_digit(radix)) {
+            // `0xff`, `0b1010_0101`; bad digits are caught when it's read
+            i += 2;
+            while i < chars.len() && is_ident_char(chars[i]) {
+                i += 1;
+            }
+            Tok::Number(chars[start..i].iter().collect())
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit()))
+        {
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            if i < chars.len() && chars[i] == '.' {
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            // Exponent only when digits follow, so `2e` stays 2·e
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            Tok::Number(chars[start..i].iter().collect())
+        } else if c == 'π' {
+            i += 1;
+            Tok::Ident("pi".to_string())
+        } else if is_ident_start(c) {
+            i += 1;
+            while i < chars.len() && is_ident_char(chars[i]) {
+                i += 1;
+            }
+            Tok::Ident(chars[start..i].iter().collect())
+        } else {
+            i += 1;
+            match c {
+                '(' => Tok::LParen,
+                ')' => Tok::RParen,
+                ',' => Tok::Comma,
+                '+' | '-' | '/' | '^' | '%' | '!' | '=' | '&' | '|' | '~' => Tok::Op(c),
+                // `<<` and `>>` shift; the lexer keeps one character of each
+                '<' | '>' if chars.get(i) == Some(&c) => {
+                    i += 1;
+                    Tok::Op(c)
+                }
+                '−' => Tok::Op('-'),
+                '÷' => Tok::Op('/'),
+                '×' | '·' => Tok::Op('*'),
+                // `**` is another spelling of `^`
+                '*' if chars.get(i) == Some(&'*') => {
+                    i += 1;
+                    Tok::Op('^')
+                }
+                '*' => Tok::Op('*'),
+                _ => {
+                    return Err(ParseError::new(
+                        format!("Unexpected character '{}'", c),
+                        start,
+                    ))
+                }
+            }
+        };
+        tokens.push(Token {
+            tok,
+            span: (start, i),
+        });
+    }
+    Ok(tokens)
+}
+
+// ─── Parser ──────────────────────────────────────────────
+
+/// Parse an expression into a tree.
+pub fn parse(src: &str) -> Result<Expr, ParseError> {
+    parse_tokens(tokenize(src)?, src.chars().count())
+}
+
+/// Parse a line that may also assign a variable or define a function.
+pub fn parse_statement(src: &str) -> Result<Statement, ParseError> {
+    let tokens = tokenize(src)?;
+    let end = src.chars().count();
+    let Some(eq) = tokens.iter().position(|t| t.tok == Tok::Op('=')) else {
+        return parse_tokens(tokens, end).map(Statement::Expr);
+    };
+
+    let source: String = src.chars().skip(tokens[eq].span.1).collect();
+    let source = source.trim().to_string();
+    let head: Vec<Tok> = tokens[..eq].iter().map(|t| t.tok.clone()).collect();
+    let body = parse_tokens(tokens[eq + 1..].to_vec(), end)?;
+
+    match head.as_slice() {
+        [Tok::Ident(name)] => Ok(Statement::Assign {
+            name: name.clone(),
+            value: body,
+        }),
+        // `f(x, y) =`: names separated by commas
+        [Tok::Ident(name), Tok::LParen, inner @ .., Tok::RParen]
+            if inner.iter().enumerate().all(|(i, t)| match t {
+                Tok::Ident(_) => i % 2 == 0,
+                Tok::Comma => i % 2 == 1,
+                _ => false,
+            }) && inner.len() % 2 == 1 =>
+        {
+            let params = inner
+                .iter()
+                .filter_map(|t| match t {
+                    Tok::Ident(p) => Some(p.clone()),
+                    _ => None,
+                })
+                .collect();
+            Ok(Statement::Define {
+                name: name.clone(),
+                params,
+                body,
+                source,
+            })
+        }
+        _ => Err(ParseError::new(
+            "Only a name or a function like f(x) can be assigned",
+            tokens[eq].span.0,
+        )),
+    }
+}
+
+fn parse_tokens(tokens: Vec<Token>, end: usize) -> Result<Expr, ParseError> {
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        end,
+    };
+    if parser.tokens.is_empty() {
+        return Err(ParseError::new("Empty expression", end));
+    }
+    let expr = parser.conversion()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(Tok::RParen) => Err(ParseError::new("Unmatched ')'", parser.position())),
+        Some(_) => Err(ParseError::new(
+            format!("Unexpected {}", describe(&parser.tokens[parser.pos].tok)),
+            parser.position(),
+        )),
+    }
+}
+
+fn describe(tok: &Tok) -> String {
+    match tok {
+        Tok::Number(n) => format!("number '{}'", n),
+        Tok::Ident(name) => format!("'{}'", name),
+        Tok::Op(op @ ('<' | '>')) => format!("'{}{}'", op, op),
+        Tok::Op(op) => format!("'{}'", op),
+        Tok::LParen => "'('".to_string(),
+        Tok::RParen => "')'".to_string(),
+        Tok::Comma => "','".to_string(),
+    }
+}
+
+/// Words that act as operators rather than names.
+pub fn is_operator_word(name: &str) -> bool {
+    ["of", "and", "or", "xor", "not"]
+        .iter()
+        .any(|w| name.eq_ignore_ascii_case(w))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Length of the source, for errors at the end of input
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos).map(|t| &t.tok)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |t| t.span.0)
+    }
+
+    fn eat_op(&mut self, ops: &[char]) -> Option<char> {
+        match self.peek() {
+            Some(Tok::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        match self.peek() {
+            Some(Tok::Ident(name)) if name.eq_ignore_ascii_case(word) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn binary(op: BinOp, lhs: Expr, rhs: Expr) -> Expr {
+        let span = (lhs.span.0, rhs.span.1);
+        Expr {
+            kind: ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)),
+            span,
+        }
+    }
+
+    /// Whether the next word starts a unit conversion. `in` is also inches,
+    /// so it only converts after a unit, a unit's power or `)`:
+    /// `3 GiB in MB`, `12 in in cm`.
+    fn at_conversion(&self) -> bool {
+        let Some(Tok::Ident(word)) = self.peek() else {
+            return false;
+        };
+        let target_follows = matches!(
+            self.tokens.get(self.pos + 1).map(|t| &t.tok),
+            Some(Tok::Ident(_) | Tok::LParen)
+        );
+        let after_unit = match self.pos.checked_sub(1).map(|p| &self.tokens[p].tok) {
+            Some(Tok::Ident(_) | Tok::RParen) => true,
+            // `ft^2 in in^2`
+            Some(Tok::Number(_)) => self.pos >= 2 && self.tokens[self.pos - 2].tok == Tok::Op('^'),
+            _ => false,
+        };
+        let format_follows = matches!(
+            self.tokens.get(self.pos + 1).map(|t| &t.tok),
+            Some(Tok::Ident(name)) if NUMBER_FORMATS.iter().any(|f| name.eq_ignore_ascii_case(f))
+        );
+        match word.to_ascii_lowercase().as_str() {
+            "to" | "as" => target_follows,
+            "in" => target_follows && (after_unit || format_follows),
+            _ => false,
+        }
+    }
+
+    fn conversion(&mut self) -> Result<Expr, ParseError> {
+        let value = self.bit_or()?;
+        if !self.at_conversion() {
+            return Ok(value);
+        }
+        self.pos += 1;
+        let target = self.multiplicative()?;
+        let span = (value.span.0, target.span.1);
+        Ok(Expr {
+            kind: ExprKind::Convert(Box::new(value), Box::new(target)),
+            span,
+        })
+    }
+
+    fn bitwise(op: BitOp, lhs: Expr, rhs: Expr) -> Expr {
+        let span = (lhs.span.0, rhs.span.1);
+        Expr {
+            kind: ExprKind::Bitwise(op, Box::new(lhs), Box::new(rhs)),
+            span,
+        }
+    }
+
+    fn bit_or(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.bit_xor()?;
+        while self.eat_op(&['|']).is_some() || self.eat_word("or") {
+            let rhs = self.bit_xor()?;
+            lhs = Self::bitwise(BitOp::Or, lhs, rhs);
+        }
+        Ok(lhs)
+    }
+
+    /// `^` is already power, so exclusive or is spelled out.
+    fn bit_xor(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.bit_and()?;
+        while self.eat_word("xor") {
+            let rhs = self.bit_and()?;
+            lhs = Self::bitwise(BitOp::Xor, lhs, rhs);
+        }
+        Ok(lhs)
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.shift()?;
+        while self.eat_op(&['&']).is_some() || self.eat_word("and") {
+            let rhs = self.shift()?;
+            lhs = Self::bitwise(BitOp::And, lhs, rhs);
+        }
+        Ok(lhs)
+    }
+
+    fn shift(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.additive()?;
+        while let Some(op) = self.eat_op(&['<', '>']) {
+            let op = if op == '<' { BitOp::Shl } else { BitOp::Shr };
+            let rhs = self.additive()?;
+            lhs = Self::bitwise(op, lhs, rhs);
+        }
+        Ok(lhs)
+    }
+
+    fn additive(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.multiplicative()?;
+        loop {
+            let op = match self.eat_op(&['+', '-']) {
+                Some('+') => BinOp::Add,
+                Some(_) => BinOp::Sub,
+                // A number right after a unit continues the quantity: `5 ft 11 in`
+                None if self.mixed_follows() => BinOp::Mixed,
+                None => break,
+            };
+            let rhs = self.multiplicative()?;
+            lhs = Self::binary(op, lhs, rhs);
+        }
+        Ok(lhs)
+    }
+
+    fn mixed_follows(&self) -> bool {
+        matches!(self.peek(), Some(Tok::Number(_)))
+            && self.pos > 0
+            && matches!(self.tokens[self.pos - 1].tok, Tok::Ident(_))
+    }
+
+    /// Whether the next token starts an operand that multiplies the previous
+    /// one without an operator: `2x`, `2(3)`, `(1)(2)`, `(2)3`.
+    fn implicit_follows(&self) -> bool {
+        match self.peek() {
+            Some(Tok::Ident(name)) => !is_operator_word(name) && !self.at_conversion(),
+            Some(Tok::LParen) => true,
+            // A number straight after a closing paren, never `2 3`
+            Some(Tok::Number(_)) => self.pos > 0 && self.tokens[self.pos - 1].tok == Tok::RParen,
+            _ => false,
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if let Some(op) = self.eat_op(&['*', '/']) {
+                if op == '*' {
+                    BinOp::Mul
+                } else {
+                    BinOp::Div
+                }
+            } else if self.eat_word("of") {
+                BinOp::Of
+            } else if self.implicit_follows() {
+                // Binds like `*` but not to a leading sign: `-2x` is -(2x) either way
+                let rhs = self.power()?;
+                lhs = Self::binary(BinOp::Mul, lhs, rhs);
+                continue;
+            } else {
+                break;
+            };
+            let rhs = self.unary()?;
+            lhs = Self::binary(op, lhs, rhs);
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        let start = self.position();
+        if self.eat_op(&['~']).is_some() || self.eat_word("not") {
+            let operand = self.unary()?;
+            let span = (start, operand.span.1);
+            return Ok(Expr {
+                kind: ExprKind::Not(Box::new(operand)),
+                span,
+            });
+        }
+        match self.eat_op(&['-', '+']) {
+            Some(op) => {
+                let operand = self.unary()?;
+                if op == '+' {
+                    return Ok(operand);
+                }
+                let span = (start, operand.span.1);
+                Ok(Expr {
+                    kind: ExprKind::Neg(Box::new(operand)),
+                    span,
+                })
+            }
+            None => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.postfix()?;
+        // A number and the name after it form one term, so quantities hold
+        // together: `10 km / 30 min`, `1/2x` is 1/(2x), `2x^2` is 2·x²
+        if matches!(base.kind, ExprKind::Number(_))
+            && matches!(self.peek(), Some(Tok::Ident(_)))
+            && self.implicit_follows()
+        {
+            let rhs = self.power()?;
+            return Ok(Self::binary(BinOp::Mul, base, rhs));
+        }
+        if self.eat_op(&['^']).is_some() {
+            let exponent = self.exponent()?;
+            return Ok(Self::binary(BinOp::Pow, base, exponent));
+        }
+        Ok(base)
+    }
+
+    /// Right-associative, may carry a sign (`2^-1`), and ends before any
+    /// name so `2 ft^2 in in^2` keeps its units apart.
+    fn exponent(&mut self) -> Result<Expr, ParseError> {
+        let start = self.position();
+        if let Some(op) = self.eat_op(&['-', '+']) {
+            let operand = self.exponent()?;
+            if op == '+' {
+                return Ok(operand);
+            }
+            let span = (start, operand.span.1);
+            return Ok(Expr {
+                kind: ExprKind::Neg(Box::new(operand)),
+                span,
+            });
+        }
+        let base = self.postfix()?;
+        if self.eat_op(&['^']).is_some() {
+            let exponent = self.exponent()?;
+            return Ok(Self::binary(BinOp::Pow, base, exponent));
+        }
+        Ok(base)
+    }
+
+    /// `7%` and `5!`; a factorial is a call to `factorial`.
+    fn postfix(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.primary()?;
+        while let Some(op) = self.eat_op(&['%', '!']) {
+            let span = (expr.span.0, self.tokens[self.pos - 1].span.1);
+            let kind = if op == '%' {
+                ExprKind::Percent(Box::new(expr))
+            } else {
+                ExprKind::Call("factorial".to_string(), vec![expr])
+            };
+            expr = Expr { kind, span };
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            return Err(ParseError::new("Expression ends early", self.end));
+        };
+        self.pos += 1;
+        match token.tok {
+            Tok::Number(text) => Ok(Expr {
+                kind: ExprKind::Number(text),
+                span: token.span,
+            }),
+            Tok::Ident(name) if self.peek() == Some(&Tok::LParen) => {
+                self.pos += 1;
+                let mut args = Vec::new();
+                if self.peek() != Some(&Tok::RParen) {
+                    loop {
+                        args.push(self.bit_or()?);
+                        if self.peek() != Some(&Tok::Comma) {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                let end = self.close(token.span.0)?;
+                Ok(Expr {
+                    kind: ExprKind::Call(name, args),
+                    span: (token.span.0, end),
+                })
+            }
+            Tok::Ident(name) => Ok(Expr {
+                kind: ExprKind::Name(name),
+                span: token.span,
+            }),
+            Tok::LParen => {
+                let inner = self.bit_or()?;
+                let end = self.close(token.span.0)?;
+                Ok(Expr {
+                    kind: inner.kind,
+                    span: (token.span.0, end),
+                })
+            }
+            tok => Err(ParseError::new(
+                format!("Unexpected {}", describe(&tok)),
+                token.span.0,
+            )),
+        }
+    }
+
+    /// Consume the `)` matching the `(` at `open`, returning the end of the group.
+    fn close(&mut self, open: usize) -> Result<usize, ParseError> {
+        match self.tokens.get(self.pos) {
+            Some(Token {
+                tok: Tok::RParen,
+                span,
+            }) => {
+                self.pos += 1;
+                Ok(span.1)
+            }
+            None => Err(ParseError::new("Missing ')'", open)),
+            Some(token) => Err(ParseError::new(
+                format!("Expected ')' but found {}", describe(&token.tok)),
+                token.span.0,
+            )),
+        }
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Render a tree in prefix form for compact assertions.
+    fn sexp(e: &Expr) -> String {
+        match &e.kind {
+            ExprKind::Number(n) => n.clone(),
+            ExprKind::Name(n) => n.clone(),
+            ExprKind::Neg(a) => format!("(neg {})", sexp(a)),
+            ExprKind::Not(a) => format!("(not {})", sexp(a)),
+            ExprKind::Percent(a) => format!("(% {})", sexp(a)),
+            ExprKind::Binary(op, a, b) => format!("({:?} {} {})", op, sexp(a), sexp(b)),
+            ExprKind::Bitwise(op, a, b) => format!("({:?} {} {})", op, sexp(a), sexp(b)),
+            ExprKind::Call(f, args) => {
+                let args: Vec<String> = args.iter().map(sexp).collect();
+                format!("({} {})", f, args.join(" "))
+            }
+            ExprKind::Convert(a, b) => format!("(-> {} {})", sexp(a), sexp(b)),
+        }
+    }
+
+    fn tree(src: &str) -> String {
+        sexp(&parse(src).unwrap())
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(tree("1 + 2 * 3"), "(Add 1 (Mul 2 3))");
+        assert_eq!(tree("2^3^2"), "(Pow 2 (Pow 3 2))");
+        assert_eq!(tree("-2^2"), "(neg (Pow 2 2))");
+        assert_eq!(tree("2^-1"), "(Pow 2 (neg 1))");
+        assert_eq!(tree("8 / 2 / 2"), "(Div (Div 8 2) 2)");
+        assert_eq!(tree("2 ** 3"), "(Pow 2 3)");
+    }
+
+    #[test]
+    fn implicit_multiplication() {
+        assert_eq!(tree("2x"), "(Mul 2 x)");
+        assert_eq!(tree("3x^2"), "(Mul 3 (Pow x 2))");
+        assert_eq!(tree("2(3+4)"), "(Mul 2 (Add 3 4))");
+        assert_eq!(tree("(1)(2)"), "(Mul 1 2)");
+        assert_eq!(tree("(2)3"), "(Mul 2 3)");
+        assert_eq!(tree("2pi"), "(Mul 2 pi)");
+        assert_eq!(tree("2e"), "(Mul 2 e)");
+        assert_eq!(tree("2e3"), "2e3");
+        assert_eq!(tree("1/2x"), "(Div 1 (Mul 2 x))");
+        assert_eq!(tree("1/2(x)"), "(Mul (Div 1 2) x)");
+        assert!(parse("2 3").is_err());
+    }
+
+    #[test]
+    fn percent_and_of() {
+        assert_eq!(tree("15% of 300"), "(Of (% 15) 300)");
+        assert_eq!(tree("200 + 10%"), "(Add 200 (% 10))");
+        assert_eq!(tree("50% * 200"), "(Mul (% 50) 200)");
+    }
+
+    #[test]
+    fn units_and_conversions() {
+        assert_eq!(tree("3.5 GiB in MB"), "(-> (Mul 3.5 GiB) MB)");
+        assert_eq!(tree("60 mph in km/h"), "(-> (Mul 60 mph) (Div km h))");
+        assert_eq!(
+            tree("5 ft 11 in to cm"),
+            "(-> (Mixed (Mul 5 ft) (Mul 11 in)) cm)"
+        );
+        assert_eq!(tree("12 in in cm"), "(-> (Mul 12 in) cm)");
+        assert_eq!(tree("72 °F as °C"), "(-> (Mul 72 °F) °C)");
+        assert_eq!(tree("2 hours + 45 min"), "(Add (Mul 2 hours) (Mul 45 min))");
+        assert_eq!(tree("10 km / 30 min"), "(Div (Mul 10 km) (Mul 30 min))");
+        assert_eq!(tree("2 ft^2 in in^2"), "(-> (Mul 2 (Pow ft 2)) (Pow in 2))");
+    }
+
+    #[test]
+    fn bitwise_and_bases() {
+        assert_eq!(tree("0xFF & 0b1010_0101"), "(And 0xFF 0b1010_0101)");
+        assert_eq!(
+            tree("1 | 2 xor 3 & 4 << 1 + 1"),
+            "(Or 1 (Xor 2 (And 3 (Shl 4 (Add 1 1)))))"
+        );
+        assert_eq!(tree("~0o17 >> 2"), "(Shr (not 0o17) 2)");
+        assert_eq!(tree("5 and not 1"), "(And 5 (not 1))");
+        assert_eq!(tree("255 in hex"), "(-> 255 hex)");
+        assert_eq!(tree("-1 in i8"), "(-> (neg 1) i8)");
+        // Not a base prefix unless a digit of that base follows
+        assert_eq!(tree("0b"), "(Mul 0 b)");
+        assert_eq!(tree("0bit"), "(Mul 0 bit)");
+        assert_eq!(parse("1 < 2").unwrap_err().position, 2);
+        assert_eq!(parse("1 << ").unwrap_err().position, 5);
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(tree("max(1, 2, 3)"), "(max 1 2 3)");
+        assert_eq!(tree("sin(x)cos(x)"), "(Mul (sin x) (cos x))");
+        assert_eq!(tree("2 × 3 ÷ π"), "(Div (Mul 2 3) pi)");
+        assert_eq!(tree("-3!"), "(neg (factorial 3))");
+        assert_eq!(tree("2^3!"), "(Pow 2 (factorial 3))");
+        assert_eq!(tree("(1+2)!!"), "(factorial (factorial (Add 1 2)))");
+    }
+
+    #[test]
+    fn statements() {
+        match parse_statement("rate = 7%").unwrap() {
+            Statement::Assign { name, value } => {
+                assert_eq!(name, "rate");
+                assert_eq!(sexp(&value), "(% 7)");
+            }
+            other => panic!("{:?}", other),
+        }
+        match parse_statement("f(x, y) = x^2 + y").unwrap() {
+            Statement::Define {
+                name,
+                params,
+                body,
+                source,
+            } => {
+                assert_eq!(
+                    (name.as_str(), params),
+                    ("f", vec!["x".to_string(), "y".to_string()])
+                );
+                assert_eq!(sexp(&body), "(Add (Pow x 2) y)");
+                assert_eq!(body.span.0, 10);
+                assert_eq!(source, "x^2 + y");
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(parse_statement("2 + 2"), Ok(Statement::Expr(_))));
+        assert_eq!(parse_statement("2 + 2 = 4").unwrap_err().position, 6);
+        assert_eq!(parse_statement("f(x,) = 1").unwrap_err().position, 6);
+        assert_eq!(parse_statement("a = ").unwrap_err().position, 4);
+        assert_eq!(parse("a = 1").unwrap_err().message, "Unexpected '='");
+    }
+
+    #[test]
+    fn error_positions() {
+        let err = parse("2 + * 3").unwrap_err();
+        assert_eq!(err.position, 4);
+        assert_eq!(err.to_string(), "Unexpected '*' at column 5");
+
+        assert_eq!(parse("sqrt(2").unwrap_err().message, "Missing ')'");
+        assert_eq!(parse("sqrt(2").unwrap_err().position, 0);
+        assert_eq!(parse("1 + 2)").unwrap_err().position, 5);
+        assert_eq!(parse("3 +").unwrap_err().position, 3);
+        assert_eq!(parse("2 @ 3").unwrap_err().position, 2);
+    }
+}
@@ -0,0 +1,480 @@
+/// Units for the calculator: a table of lengths, masses, times, data sizes,
+/// temperatures, speeds, areas, volumes and energies, with SI and IEC
+/// prefixes, resolved to a factor against SI base units and a dimension.
+use serde::{Deserialize, Serialize};
+
+/// Exponents of the base dimensions: length, mass, time, data, temperature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Dim(pub [i8; 5]);
+
+const BASE_SYMBOLS: [&str; 5] = ["m", "kg", "s", "bit", "K"];
+
+impl Dim {
+    pub const NONE: Dim = Dim([0; 5]);
+    const LENGTH: Dim = Dim([1, 0, 0, 0, 0]);
+    const MASS: Dim = Dim([0, 1, 0, 0, 0]);
+    const TIME: Dim = Dim([0, 0, 1, 0, 0]);
+    const DATA: Dim = Dim([0, 0, 0, 1, 0]);
+    pub const TEMPERATURE: Dim = Dim([0, 0, 0, 0, 1]);
+    const SPEED: Dim = Dim([1, 0, -1, 0, 0]);
+    const AREA: Dim = Dim([2, 0, 0, 0, 0]);
+    const VOLUME: Dim = Dim([3, 0, 0, 0, 0]);
+    const ENERGY: Dim = Dim([2, 1, -2, 0, 0]);
+    const POWER: Dim = Dim([2, 1, -3, 0, 0]);
+
+    pub fn is_none(self) -> bool {
+        self == Dim::NONE
+    }
+
+    /// Raise to a power; `None` when an exponent would stop being whole.
+    pub fn pow(self, exponent: f64) -> Option<Dim> {
+        let mut out = [0i8; 5];
+        for (o, e) in out.iter_mut().zip(self.0) {
+            let v = e as f64 * exponent;
+            if v.fract() != 0.0 || v.abs() > 24.0 {
+                return None;
+            }
+            *o = v as i8;
+        }
+        Some(Dim(out))
+    }
+
+    /// Human name for error messages: "length", "speed", "m^2/s".
+    pub fn name(self) -> String {
+        let known = [
+            (Dim::NONE, "a plain number"),
+            (Dim::LENGTH, "length"),
+            (Dim::MASS, "mass"),
+            (Dim::TIME, "time"),
+            (Dim::DATA, "data size"),
+            (Dim::TEMPERATURE, "temperature"),
+            (Dim::SPEED, "speed"),
+            (Dim::AREA, "area"),
+            (Dim::VOLUME, "volume"),
+            (Dim::ENERGY, "energy"),
+            (Dim::POWER, "power"),
+        ];
+        match known.iter().find(|(d, _)| *d == self) {
+            Some((_, name)) => name.to_string(),
+            None => self.base_label(),
+        }
+    }
+
+    /// The dimension written in SI base units, e.g. "kg·m^2/s^2".
+    pub fn base_label(self) -> String {
+        let part = |i: usize, e: i8| match e {
+            1 => BASE_SYMBOLS[i].to_string(),
+            _ => format!("{}^{}", BASE_SYMBOLS[i], e),
+        };
+        let num: Vec<String> = (0..5)
+            .filter(|&i| self.0[i] > 0)
+            .map(|i| part(i, self.0[i]))
+            .collect();
+        let den: Vec<String> = (0..5)
+            .filter(|&i| self.0[i] < 0)
+            .map(|i| part(i, -self.0[i]))
+            .collect();
+        let num = if num.is_empty() {
+            "1".to_string()
+        } else {
+            num.join("·")
+        };
+        if den.is_empty() {
+            num
+        } else {
+            format!("{}/{}", num, den.join("/"))
+        }
+    }
+}
+
+impl std::ops::Mul for Dim {
+    type Output = Dim;
+
+    fn mul(self, other: Dim) -> Dim {
+        Dim(std::array::from_fn(|i| self.0[i] + other.0[i]))
+    }
+}
+
+impl std::ops::Div for Dim {
+    type Output = Dim;
+
+    fn div(self, other: Dim) -> Dim {
+        Dim(std::array::from_fn(|i| self.0[i] - other.0[i]))
+    }
+}
+
+// ─── Unit Table ──────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Prefixes {
+    None,
+    /// n µ m c d k M G T P
+    Si,
+    /// k M G T P E and Ki Mi Gi Ti Pi Ei
+    Data,
+}
+
+struct UnitDef {
+    /// Symbols (matched exactly) followed by long names (any case, plurals)
+    names: &'static [&'static str],
+    /// How results in this unit are labelled
+    symbol: &'static str,
+    factor: f64,
+    /// For temperatures: kelvin = (value + offset) × factor
+    offset: f64,
+    dim: Dim,
+    prefixes: Prefixes,
+}
+
+const fn unit(
+    names: &'static [&'static str],
+    symbol: &'static str,
+    factor: f64,
+    dim: Dim,
+    prefixes: Prefixes,
+) -> UnitDef {
+    UnitDef {
+        names,
+        symbol,
+        factor,
+        offset: 0.0,
+        dim,
+        prefixes,
+    }
+}
+
+const YEAR: f64 = 365.25 * 86400.0;
+
+const UNITS: &[UnitDef] = &[
+    // Length
+    unit(
+        &["m", "meter", "metre"],
+        "m",
+        1.0,
+        Dim::LENGTH,
+        Prefixes::Si,
+    ),
+    unit(
+        &["in", "inch", "inches"],
+        "in",
+        0.0254,
+        Dim::LENGTH,
+        Prefixes::None,
+    ),
+    unit(
+        &["ft", "foot", "feet"],
+        "ft",
+        0.3048,
+        Dim::LENGTH,
+        Prefixes::None,
+    ),
+    unit(&["yd", "yard"], "yd", 0.9144, Dim::LENGTH, Prefixes::None),
+    unit(&["mi", "mile"], "mi", 1609.344, Dim::LENGTH, Prefixes::None),
+    unit(&["nmi"], "nmi", 1852.0, Dim::LENGTH, Prefixes::None),
+    // Mass
+    unit(&["g", "gram", "gramme"], "g", 1e-3, Dim::MASS, Prefixes::Si),
+    unit(&["t", "tonne"], "t", 1000.0, Dim::MASS, Prefixes::None),
+    unit(
+        &["lb", "lbs", "pound"],
+        "lb",
+        0.453_592_37,
+        Dim::MASS,
+        Prefixes::None,
+    ),
+    unit(
+        &["oz", "ounce"],
+        "oz",
+        0.028_349_523_125,
+        Dim::MASS,
+        Prefixes::None,
+    ),
+    unit(
+        &["st", "stone"],
+        "st",
+        6.350_293_18,
+        Dim::MASS,
+        Prefixes::None,
+    ),
+    // Time
+    unit(&["s", "sec", "second"], "s", 1.0, Dim::TIME, Prefixes::Si),
+    unit(&["min", "minute"], "min", 60.0, Dim::TIME, Prefixes::None),
+    unit(&["h", "hr", "hour"], "h", 3600.0, Dim::TIME, Prefixes::None),
+    unit(&["d", "day"], "d", 86400.0, Dim::TIME, Prefixes::None),
+    unit(&["wk", "week"], "wk", 604_800.0, Dim::TIME, Prefixes::None),
+    unit(
+        &["mo", "month"],
+        "mo",
+        YEAR / 12.0,
+        Dim::TIME,
+        Prefixes::None,
+    ),
+    unit(&["yr", "year"], "yr", YEAR, Dim::TIME, Prefixes::None),
+    // Data
+    unit(&["b", "bit"], "b", 1.0, Dim::DATA, Prefixes::Data),
+    unit(&["B", "byte"], "B", 8.0, Dim::DATA, Prefixes::Data),
+    // Temperature (see `offset`)
+    unit(&["K", "kelvin"], "K", 1.0, Dim::TEMPERATURE, Prefixes::None),
+    UnitDef {
+        names: &["C", "c", "°C", "degC", "celsius"],
+        symbol: "°C",
+        factor: 1.0,
+        offset: 273.15,
+        dim: Dim::TEMPERATURE,
+        prefixes: Prefixes::None,
+    },
+    UnitDef {
+        names: &["F", "f", "°F", "degF", "fahrenheit"],
+        symbol: "°F",
+        factor: 5.0 / 9.0,
+        offset: 459.67,
+        dim: Dim::TEMPERATURE,
+        prefixes: Prefixes::None,
+    },
+    // Speed
+    unit(&["mph"], "mph", 0.447_04, Dim::SPEED, Prefixes::None),
+    unit(
+        &["kph", "kmh"],
+        "km/h",
+        1.0 / 3.6,
+        Dim::SPEED,
+        Prefixes::None,
+    ),
+    unit(
+        &["kn", "kt", "knot"],
+        "kn",
+        1852.0 / 3600.0,
+        Dim::SPEED,
+        Prefixes::None,
+    ),
+    // Area
+    unit(&["ha", "hectare"], "ha", 1e4, Dim::AREA, Prefixes::None),
+    unit(
+        &["acre"],
+        "acre",
+        4_046.856_422_4,
+        Dim::AREA,
+        Prefixes::None,
+    ),
+    // Volume
+    unit(
+        &["L", "l", "liter", "litre"],
+        "L",
+        1e-3,
+        Dim::VOLUME,
+        Prefixes::Si,
+    ),
+    unit(
+        &["gal", "gallon"],
+        "gal",
+        3.785_411_784e-3,
+        Dim::VOLUME,
+        Prefixes::None,
+    ),
+    unit(
+        &["qt", "quart"],
+        "qt",
+        9.463_529_46e-4,
+        Dim::VOLUME,
+        Prefixes::None,
+    ),
+    unit(
+        &["pt", "pint"],
+        "pt",
+        4.731_764_73e-4,
+        Dim::VOLUME,
+        Prefixes::None,
+    ),
+    unit(
+        &["cup"],
+        "cup",
+        2.365_882_365e-4,
+        Dim::VOLUME,
+        Prefixes::None,
+    ),
+    unit(
+        &["floz"],
+        "fl oz",
+        2.957_352_956_25e-5,
+        Dim::VOLUME,
+        Prefixes::None,
+    ),
+    unit(
+        &["tbsp"],
+        "tbsp",
+        1.478_676_478_125e-5,
+        Dim::VOLUME,
+        Prefixes::None,
+    ),
+    unit(
+        &["tsp"],
+        "tsp",
+        4.928_921_593_75e-6,
+        Dim::VOLUME,
+        Prefixes::None,
+    ),
+    // Energy & power
+    unit(&["J", "joule"], "J", 1.0, Dim::ENERGY, Prefixes::Si),
+    unit(&["cal", "calorie"], "cal", 4.184, Dim::ENERGY, Prefixes::Si),
+    unit(&["Cal"], "kcal", 4184.0, Dim::ENERGY, Prefixes::None),
+    unit(&["Wh"], "Wh", 3600.0, Dim::ENERGY, Prefixes::Si),
+    unit(&["eV"], "eV", 1.602_176_634e-19, Dim::ENERGY, Prefixes::Si),
+    unit(
+        &["BTU", "btu"],
+        "BTU",
+        1055.056,
+        Dim::ENERGY,
+        Prefixes::None,
+    ),
+    unit(&["W", "watt"], "W", 1.0, Dim::POWER, Prefixes::Si),
+];
+
+const SI_PREFIXES: &[(&str, &str, f64)] = &[
+    ("n", "nano", 1e-9),
+    ("µ", "micro", 1e-6),
+    ("u", "micro", 1e-6),
+    ("m", "milli", 1e-3),
+    ("c", "centi", 1e-2),
+    ("d", "deci", 1e-1),
+    ("k", "kilo", 1e3),
+    ("M", "mega", 1e6),
+    ("G", "giga", 1e9),
+    ("T", "tera", 1e12),
+    ("P", "peta", 1e15),
+];
+
+const DATA_PREFIXES: &[(&str, &str, f64)] = &[
+    ("Ki", "kibi", 1024.0),
+    ("Mi", "mebi", 1_048_576.0),
+    ("Gi", "gibi", 1_073_741_824.0),
+    ("Ti", "tebi", 1_099_511_627_776.0),
+    ("Pi", "pebi", 1_125_899_906_842_624.0),
+    ("Ei", "exbi", 1_152_921_504_606_846_976.0),
+    ("k", "kilo", 1e3),
+    ("K", "kilo", 1e3),
+    ("M", "mega", 1e6),
+    ("G", "giga", 1e9),
+    ("T", "tera", 1e12),
+    ("P", "peta", 1e15),
+    ("E", "exa", 1e18),
+];
+
+/// A resolved unit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Unit {
+    /// Label for results shown in this unit
+    pub symbol: String,
+    /// Size of one of this unit in SI base units
+    pub factor: f64,
+    /// Non-zero for °C and °F, whose zero isn't absolute zero
+    pub offset: f64,
+    pub dim: Dim,
+}
+
+impl Unit {
+    fn from_def(def: &UnitDef, prefix: &str, scale: f64) -> Unit {
+        Unit {
+            symbol: format!("{}{}", prefix, def.symbol),
+            factor: def.factor * scale,
+            offset: def.offset,
+            dim: def.dim,
+        }
+    }
+}
+
+fn prefixes(kind: Prefixes) -> &'static [(&'static str, &'static str, f64)] {
+    match kind {
+        Prefixes::None => &[],
+        Prefixes::Si => SI_PREFIXES,
+        Prefixes::Data => DATA_PREFIXES,
+    }
+}
+
+/// A symbol (`km`, `GiB`, `°F`) or a long name (`Hours`, `kilometres`).
+pub fn lookup(name: &str) -> Option<Unit> {
+    // Symbols are case-sensitive: `Mb` is a megabit, `MB` a megabyte
+    for def in UNITS {
+        if def.names.contains(&name) {
+            return Some(Unit::from_def(def, "", 1.0));
+        }
+    }
+    for def in UNITS {
+        for &(prefix, _, scale) in prefixes(def.prefixes) {
+            if let Some(rest) = name.strip_prefix(prefix) {
+                if def.names.iter().any(|n| *n == rest && n.len() <= 3) {
+                    return Some(Unit::from_def(def, prefix, scale));
+                }
+            }
+        }
+    }
+
+    // Long names in any case, singular or plural, with spelled-out prefixes
+    let lower = name.to_lowercase();
+    let singular = [
+        lower.as_str(),
+        lower.strip_suffix("es").unwrap_or(""),
+        lower.strip_suffix('s').unwrap_or(""),
+    ];
+    for word in singular.iter().filter(|w| w.len() >= 2) {
+        for def in UNITS {
+            if def
+                .names
+                .iter()
+                .any(|n| n.len() > 1 && n.eq_ignore_ascii_case(word))
+            {
+                return Some(Unit::from_def(def, "", 1.0));
+            }
+            for &(prefix, long, scale) in prefixes(def.prefixes) {
+                let Some(rest) = word.strip_prefix(long) else {
+                    continue;
+                };
+                if def.names.iter().any(|n| n.len() > 3 && *n == rest) {
+                    return Some(Unit::from_def(def, prefix, scale));
+                }
+            }
+        }
+    }
+    None
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols_and_prefixes() {
+        assert_eq!(lookup("km").unwrap().factor, 1000.0);
+        assert_eq!(lookup("MB").unwrap().factor, 8e6);
+        assert_eq!(lookup("Mb").unwrap().factor, 1e6);
+        assert_eq!(lookup("GiB").unwrap().factor, 8.0 * 1_073_741_824.0);
+        assert_eq!(lookup("ms").unwrap().symbol, "ms");
+        assert_eq!(lookup("min").unwrap().factor, 60.0);
+        assert_eq!(lookup("mi").unwrap().factor, 1609.344);
+        assert_eq!(lookup("kg").unwrap().factor, 1.0);
+        assert!(lookup("kft").is_none());
+        assert!(lookup("mB").is_none());
+    }
+
+    #[test]
+    fn long_names() {
+        assert_eq!(lookup("Hours").unwrap().symbol, "h");
+        assert_eq!(lookup("inches").unwrap().symbol, "in");
+        assert_eq!(lookup("kilometres").unwrap().symbol, "km");
+        assert_eq!(lookup("gigabytes").unwrap().symbol, "GB");
+        assert_eq!(lookup("mins").unwrap().symbol, "min");
+        assert!(lookup("x").is_none());
+        assert!(lookup("pi").is_none());
+    }
+
+    #[test]
+    fn dimensions() {
+        let speed = lookup("m").unwrap().dim / lookup("s").unwrap().dim;
+        assert_eq!(speed.name(), "speed");
+        assert_eq!(lookup("mph").unwrap().dim, speed);
+        assert_eq!(Dim::LENGTH.pow(3.0), Some(Dim::VOLUME));
+        assert_eq!(Dim::AREA.pow(0.5), Some(Dim::LENGTH));
+        assert_eq!(Dim::LENGTH.pow(0.5), None);
+        assert_eq!(Dim([1, 1, -2, 0, 0]).base_label(), "m·kg/s^2");
+    }
+}
@@ -1,22 +1,49 @@
 /// Tauri command handlers — the bridge between the frontend and Rust backend.
-use crate::calc::{self, CalcResult, GraphPoint};
-use crate::doc_chat;
+use crate::calc::{self, CalcResult, GraphPoint, Memory};
+use crate::calc_history::{CalcEntry, SharedCalcHistory};
+use crate::content_index::{self, ContentMatch, SharedContentIndex};
+use crate::doc_chat::{self, ChatReply, ChatSession, SharedChat};
+use crate::frecency::{self, SharedFrecency};
+use crate::ignore_rules::IgnoreRules;
 use crate::indexer::FileIndex;
+use crate::json_store;
 use crate::launcher;
-use crate::llm::LlmClient;
-use crate::search::{self, SearchResult};
-use crate::settings::Settings;
-use crate::shell_cmd::{self, ShellOutput, ShellTranslation};
-use std::sync::Arc;
-use tauri::State;
+use crate::llm::{self, ActiveStreams, CancelToken, ChatMessage, LlmClient};
+use crate::query::Query;
+use crate::search::{self, SearchCache, SearchGate, SearchResult};
+use crate::settings::{Settings, KEY_PROVIDERS};
+use crate::runner::{self, ActiveRuns, RunControl, RunOptions, ShellOutput, Stream};
+use crate::shell_cmd::{self, ShellTranslation};
+use crate::shell_history::{HistoryEntry, SharedShellHistory};
+use crate::shell_explain::{self, Step};
+use crate::shell_preview::{self, PreviewAction};
+use crate::vault::{self, Protection, Vault, VaultStatus};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, State};
 use tokio::sync::RwLock;
 
 /// Shared app state passed to every Tauri command.
 pub struct AppState {
     pub index: FileIndex,
+    /// The indexer's ignore rules, also used to expand folders for chat
+    pub rules: Arc<IgnoreRules>,
+    pub content: SharedContentIndex,
     pub settings: Arc<RwLock<Settings>>,
+    /// Encrypted API keys; `settings` holds the decrypted copies the LLM client reads
+    pub vault: Arc<RwLock<Vault>>,
+    pub frecency: SharedFrecency,
+    /// Remembered shell translations and favorites
+    pub shell_history: SharedShellHistory,
+    /// Committed calculations, variables and user functions
+    pub calc_history: SharedCalcHistory,
+    pub search_cache: Arc<Mutex<SearchCache>>,
+    pub search_gate: Arc<SearchGate>,
     pub llm: LlmClient,
-    pub chat_file_content: Arc<RwLock<Option<(String, String)>>>, // (path, content)
+    pub streams: ActiveStreams,
+    pub runs: ActiveRuns,
+    pub chat: SharedChat,
 }
 
 // ─── SEARCH ──────────────────────────────────────────────
@@ -26,22 +53,92 @@ pub async fn search_files(
     query: String,
     state: State<'_, AppState>,
 ) -> Result<Vec<SearchResult>, String> {
-    let settings = state.settings.read().await;
-    let results = search::fuzzy_search(&state.index, &query, settings.max_results).await;
-    Ok(results)
+    let query = Query::parse(&query)?;
+    let max_results = state.settings.read().await.max_results;
+
+    // Scoring is CPU-bound: run it on the blocking pool, where it fans out to rayon
+    let ticket = state.search_gate.begin();
+    let gate = state.search_gate.clone();
+    let index = state.index.clone();
+    let frecency = state.frecency.clone();
+    let cache = state.search_cache.clone();
+    tokio::task::spawn_blocking(move || {
+        // Searches queue here; a superseded one bails out as soon as it gets in
+        let mut cache = cache.lock().map_err(|e| e.to_string())?;
+        let idx = index.blocking_read();
+        let frecency = frecency.blocking_read();
+        let cancelled = || !gate.is_current(ticket);
+        search::fuzzy_search(&idx, &query, &frecency, &mut cache, max_results, &cancelled)
+            .ok_or_else(|| "Search cancelled".to_string())
+    })
+    .await
+    .map_err(|e| format!("Search failed: {}", e))?
+}
+
+/// Full-text search over document contents, with matching lines as snippets.
+#[tauri::command]
+pub async fn search_content(
+    query: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<ContentMatch>, String> {
+    let max_results = {
+        let settings = state.settings.read().await;
+        if !settings.content_index_enabled {
+            return Err(
+                "Content search is off — enable it in Settings and restart Trace".to_string(),
+            );
+        }
+        settings.max_results
+    };
+    Ok(content_index::search(&state.content, &query, max_results).await)
 }
 
 // ─── FILE OPEN / APP LAUNCH ──────────────────────────────
 
+/// Open a result and remember it for ranking. `query` is the search text that found
+/// it (filters stripped), so the same prefix ranks it higher next time.
 #[tauri::command]
-pub async fn open_result(path: String, kind: String) -> Result<(), String> {
+pub async fn open_result(
+    path: String,
+    kind: String,
+    query: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
     match kind.as_str() {
-        "App" => open_app(&path),
+        "App" => open_app(&path)?,
         _ => {
             // Open file with default application
-            open::that(&path).map_err(|e| format!("Failed to open: {}", e))
+            open::that(&path).map_err(|e| format!("Failed to open: {}", e))?
         }
     }
+
+    let text = query
+        .and_then(|q| Query::parse(&q).ok())
+        .map(|q| q.text())
+        .unwrap_or_default();
+    state
+        .frecency
+        .write()
+        .await
+        .record(&path, &text, frecency::now());
+    frecency::persist(&state.frecency).await;
+    Ok(())
+}
+
+/// Drop everything learned about one result from the ranking.
+#[tauri::command]
+pub async fn forget_result(path: String, state: State<'_, AppState>) -> Result<(), String> {
+    state.frecency.write().await.forget(&path);
+    frecency::persist(&state.frecency).await;
+    Ok(())
+}
+
+/// Clear all launch history used for ranking.
+#[tauri::command]
+pub async fn reset_frecency(state: State<'_, AppState>) -> Result<(), String> {
+    state.frecency.write().await.reset();
+    frecency::persist(&state.frecency).await;
+    Ok(())
 }
 
 /// Open an app entry. On Linux, re-parse the .desktop file for Exec=.
@@ -78,40 +175,428 @@ fn open_app(path: &str) -> Result<(), String> {
 
 // ─── SETTINGS ────────────────────────────────────────────
 
+/// Settings as the panel sees them: API keys only as masked values.
+#[derive(Serialize)]
+pub struct SettingsView {
+    #[serde(flatten)]
+    settings: Settings,
+    /// provider → masked key, for every key that is set
+    api_keys: BTreeMap<String, String>,
+    vault: VaultStatus,
+}
+
 #[tauri::command]
-pub async fn get_settings(state: State<'_, AppState>) -> Result<Settings, String> {
+pub async fn get_settings(state: State<'_, AppState>) -> Result<SettingsView, String> {
+    let vault = state.vault.read().await;
     let settings = state.settings.read().await;
-    Ok(settings.clone())
+    Ok(SettingsView {
+        settings: settings.clone(),
+        api_keys: vault.masked(),
+        vault: vault.status(),
+    })
 }
 
+/// Keys left `None` are kept; submitted ones go to the vault (empty = remove).
 #[tauri::command]
 pub async fn save_settings(
     new_settings: Settings,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
+    let mut new_settings = new_settings;
+    let mut vault = state.vault.write().await;
+    let mut keys_changed = false;
+    for provider in KEY_PROVIDERS {
+        if let Some(key) = new_settings.key_slot(provider).and_then(Option::take) {
+            vault.set(provider, key.trim())?;
+            keys_changed = true;
+        }
+    }
+    // Also retry a save that failed earlier, such as during migration, so
+    // settings.json is never rewritten while keys live only in memory
+    if keys_changed || vault.has_unsaved_keys() {
+        vault.save()?;
+    }
+    vault.apply_to(&mut new_settings);
+
     new_settings.save()?;
     let mut settings = state.settings.write().await;
     *settings = new_settings;
     Ok(())
 }
 
+/// Copy freshly unlocked keys into settings, moving any plaintext leftovers
+/// from an old `settings.json` into the vault on the way.
+async fn refresh_keys(state: &AppState) -> Result<VaultStatus, String> {
+    let mut vault = state.vault.write().await;
+    let mut settings = state.settings.write().await;
+    if !vault.is_locked() {
+        vault::migrate_plaintext_keys(&mut settings, &mut vault)?;
+    }
+    vault.apply_to(&mut settings);
+    Ok(vault.status())
+}
+
+/// Unlock a passphrase-protected vault. Key derivation is deliberately slow,
+/// so it runs off the async workers.
+#[tauri::command]
+pub async fn unlock_vault(
+    passphrase: String,
+    state: State<'_, AppState>,
+) -> Result<VaultStatus, String> {
+    let vault = state.vault.clone();
+    tokio::task::spawn_blocking(move || vault.blocking_write().unlock(&passphrase))
+        .await
+        .map_err(|e| format!("Unlock task failed: {}", e))??;
+    refresh_keys(&state).await
+}
+
+/// Re-encrypt the vault under the OS keyring, a keyfile or a passphrase.
+#[tauri::command]
+pub async fn set_vault_protection(
+    protection: Protection,
+    passphrase: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<VaultStatus, String> {
+    let vault = state.vault.clone();
+    tokio::task::spawn_blocking(move || {
+        vault
+            .blocking_write()
+            .set_protection(protection, passphrase.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Vault task failed: {}", e))??;
+    refresh_keys(&state).await
+}
+
+/// Models served by a local provider ("ollama" or "custom"), queried from its
+/// endpoint. Takes the panel's unsaved URL/key so the list follows edits; without
+/// a typed key the saved custom key is used, but only for the saved custom URL so
+/// it is never sent to another host.
+#[tauri::command]
+pub async fn list_models(
+    provider: String,
+    base_url: String,
+    api_key: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, String> {
+    let api_key = match api_key.filter(|k| !k.is_empty()) {
+        Some(key) => Some(key),
+        None if provider == "custom" => {
+            let settings = state.settings.read().await;
+            let saved_url = settings.custom_base_url.trim_end_matches('/');
+            if base_url.trim_end_matches('/') == saved_url {
+                settings.custom_key.clone()
+            } else {
+                None
+            }
+        }
+        None => None,
+    };
+    state
+        .llm
+        .list_models(&provider, &base_url, api_key.as_deref())
+        .await
+}
+
 // ─── NLP-TO-SHELL (Phase 3) ─────────────────────────────
 
+/// Translate natural language to a command. With a `stream_id`, the raw reply is
+/// streamed as `llm-delta` events and can be stopped with `cancel_stream`.
+/// A request translated before is answered from the shell history unless `fresh`.
 #[tauri::command]
 pub async fn translate_command(
     input: String,
+    stream_id: Option<String>,
+    fresh: Option<bool>,
+    app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<ShellTranslation, String> {
+    if !fresh.unwrap_or(false) {
+        if let Some(entry) = state.shell_history.read().await.cached(&input) {
+            eprintln!("[trace][shell] Reusing translation #{} from history", entry.id);
+            let mut translation = ShellTranslation::assess(entry.command.clone());
+            translation.history_id = Some(entry.id);
+            translation.cached = true;
+            translation.pinned = entry.pinned;
+            return Ok(translation);
+        }
+    }
+
     let settings = state.settings.read().await;
-    shell_cmd::translate_to_command(&state.llm, &settings, &input).await
+    let stream = StreamHandle::open(&state.streams, stream_id);
+    let mut emit = stream.emitter(&app);
+    let mut translation = shell_cmd::translate_to_command(
+        &state.llm,
+        &settings,
+        &input,
+        &mut emit,
+        Some(&stream.token),
+    )
+    .await?;
+    if !translation.command.is_empty() {
+        let id = state.shell_history.write().await.record_translation(
+            &input,
+            &translation.command,
+            frecency::now(),
+        );
+        translation.history_id = Some(id);
+        json_store::persist(&state.shell_history).await;
+    }
+    Ok(translation)
 }
 
+/// Event name for streamed shell output.
+const SHELL_OUTPUT_EVENT: &str = "shell-output";
+
+#[derive(Clone, Serialize)]
+struct ShellChunk<'a> {
+    run_id: &'a str,
+    stream: Stream,
+    text: &'a str,
+}
+
+/// A running command, registered under its id until it exits.
+struct RunHandle {
+    id: String,
+    runs: ActiveRuns,
+}
+
+impl Drop for RunHandle {
+    fn drop(&mut self) {
+        if let Ok(mut runs) = self.runs.lock() {
+            runs.remove(&self.id);
+        }
+    }
+}
+
+/// Run a shell command in `cwd` (default: home), streaming its output as
+/// `shell-output` events tagged with `run_id`. A bare `cd` only resolves the new
+/// directory, which comes back as `cwd`. `timeout_secs` overrides the setting.
+/// The exit code is recorded on the shell history entry `history_id`.
+#[tauri::command]
+pub async fn execute_shell(
+    command: String,
+    run_id: String,
+    cwd: Option<String>,
+    timeout_secs: Option<u64>,
+    history_id: Option<u64>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ShellOutput, String> {
+    let output = run_shell(command, run_id, cwd, timeout_secs, &app, &state).await;
+    if let (Some(id), Ok(output)) = (history_id, &output) {
+        let recorded = state
+            .shell_history
+            .write()
+            .await
+            .record_run(id, output.exit_code, frecency::now());
+        match recorded {
+            Ok(()) => json_store::persist(&state.shell_history).await,
+            Err(e) => eprintln!("[trace][shell] Not recording run #{}: {}", id, e),
+        }
+    }
+    output
+}
+
+async fn run_shell(
+    command: String,
+    run_id: String,
+    cwd: Option<String>,
+    timeout_secs: Option<u64>,
+    app: &AppHandle,
+    state: &AppState,
+) -> Result<ShellOutput, String> {
+    let home = dirs::home_dir().unwrap_or_else(|| std::path::PathBuf::from("/"));
+    let cwd = match cwd {
+        Some(dir) => runner::resolve_dir(&dir, &home)?,
+        None => home,
+    };
+
+    if let Some(target) = runner::parse_cd(&command) {
+        let dir = runner::resolve_dir(target, &cwd)?;
+        return Ok(ShellOutput {
+            stdout: String::new(),
+            stderr: String::new(),
+            exit_code: 0,
+            duration_ms: 0,
+            cwd: dir.to_string_lossy().to_string(),
+            timed_out: false,
+            killed: false,
+            truncated: false,
+        });
+    }
+
+    let timeout_secs = match timeout_secs {
+        Some(secs) => secs,
+        None => state.settings.read().await.shell_timeout_secs,
+    };
+    let options = RunOptions {
+        cwd,
+        timeout: (timeout_secs > 0).then(|| std::time::Duration::from_secs(timeout_secs)),
+    };
+
+    let (stdin, stdin_rx) = tokio::sync::mpsc::unbounded_channel();
+    let control = RunControl {
+        stdin,
+        kill: CancelToken::default(),
+    };
+    let kill = control.kill.clone();
+    if let Ok(mut runs) = state.runs.lock() {
+        runs.insert(run_id.clone(), control);
+    }
+    let _handle = RunHandle {
+        id: run_id.clone(),
+        runs: state.runs.clone(),
+    };
+
+    let mut emit = |stream: Stream, text: &str| {
+        let chunk = ShellChunk {
+            run_id: &run_id,
+            stream,
+            text,
+        };
+        if let Err(e) = app.emit(SHELL_OUTPUT_EVENT, chunk) {
+            eprintln!("[trace][shell] Failed to emit output: {}", e);
+        }
+    };
+    runner::run(&command, options, stdin_rx, kill, &mut emit).await
+}
+
+fn running(state: &AppState, run_id: &str) -> Result<RunControl, String> {
+    state
+        .runs
+        .lock()
+        .ok()
+        .and_then(|runs| runs.get(run_id).cloned())
+        .ok_or_else(|| "Command is no longer running".to_string())
+}
+
+/// Send text to a running command's stdin; `close` then signals end of input.
+#[tauri::command]
+pub fn write_shell_stdin(
+    run_id: String,
+    data: String,
+    close: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let control = running(&state, &run_id)?;
+    if !data.is_empty() {
+        let _ = control.stdin.send(Some(data));
+    }
+    if close {
+        let _ = control.stdin.send(None);
+    }
+    Ok(())
+}
+
+/// Kill a running command and everything it started.
+#[tauri::command]
+pub fn kill_shell(run_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    running(&state, &run_id)?.kill.cancel();
+    Ok(())
+}
+
+/// Break a command into steps with per-flag explanations, without running it.
+#[tauri::command]
+pub fn explain_command(command: String) -> Result<Vec<Step>, String> {
+    shell_explain::explain(&command)
+}
+
+/// List the existing paths a command would delete, move, copy or chmod.
+#[tauri::command]
+pub async fn preview_command(
+    command: String,
+    cwd: Option<String>,
+) -> Result<Vec<PreviewAction>, String> {
+    let home = dirs::home_dir().unwrap_or_else(|| std::path::PathBuf::from("/"));
+    let cwd = match cwd {
+        Some(dir) => runner::resolve_dir(&dir, &home)?,
+        None => home,
+    };
+    // Globbing and counting directory trees touch the disk
+    tokio::task::spawn_blocking(move || shell_preview::preview(&command, &cwd))
+        .await
+        .map_err(|e| format!("Preview failed: {}", e))?
+}
+
+// ─── SHELL HISTORY ───────────────────────────────────────
+
+/// Remembered translations matching `query`, favorites first.
 #[tauri::command]
-pub async fn execute_shell(command: String) -> Result<ShellOutput, String> {
-    // Run on blocking thread since Command::output blocks
-    tokio::task::spawn_blocking(move || shell_cmd::execute_command(&command))
+pub async fn search_shell_history(
+    query: String,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<HistoryEntry>, String> {
+    Ok(state
+        .shell_history
+        .read()
         .await
-        .map_err(|e| format!("Task failed: {}", e))?
+        .search(&query, limit.unwrap_or(8)))
+}
+
+/// Load a history entry as the pending command, without asking the LLM.
+#[tauri::command]
+pub async fn use_shell_history(
+    id: u64,
+    state: State<'_, AppState>,
+) -> Result<ShellTranslation, String> {
+    let history = state.shell_history.read().await;
+    let entry = history.get(id).ok_or("No such history entry")?;
+    let mut translation = ShellTranslation::assess(entry.command.clone());
+    translation.history_id = Some(id);
+    translation.cached = true;
+    translation.pinned = entry.pinned;
+    Ok(translation)
+}
+
+/// Switch the pending command to another of the model's candidates, remembering
+/// it for `input` so the choice is reused next time.
+#[tauri::command]
+pub async fn use_shell_candidate(
+    input: String,
+    command: String,
+    state: State<'_, AppState>,
+) -> Result<ShellTranslation, String> {
+    let id = state
+        .shell_history
+        .write()
+        .await
+        .record_translation(&input, &command, frecency::now());
+    json_store::persist(&state.shell_history).await;
+    let mut translation = ShellTranslation::assess(command);
+    translation.history_id = Some(id);
+    Ok(translation)
+}
+
+#[tauri::command]
+pub async fn pin_shell_history(
+    id: u64,
+    pinned: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.shell_history.write().await.set_pinned(id, pinned)?;
+    json_store::persist(&state.shell_history).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_shell_history(id: u64, state: State<'_, AppState>) -> Result<(), String> {
+    state.shell_history.write().await.remove(id);
+    json_store::persist(&state.shell_history).await;
+    Ok(())
+}
+
+/// Write the history (or only favorites) as a runnable script; returns its path.
+#[tauri::command]
+pub async fn export_shell_history(
+    destination: Option<String>,
+    pinned_only: bool,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let history = state.shell_history.read().await;
+    let path = history.export(destination.as_deref(), pinned_only)?;
+    Ok(path.to_string_lossy().to_string())
 }
 
 /// Return lightweight OS/user context for the terminal title bar in the UI.
@@ -146,51 +631,224 @@ pub fn get_shell_context() -> serde_json::Value {
             .unwrap_or_else(|_| "bash".to_string())
     };
 
+    let home = dirs::home_dir()
+        .map(|h| h.to_string_lossy().to_string())
+        .unwrap_or_default();
+
     serde_json::json!({
         "username": username,
         "hostname": hostname,
         "shell": shell,
+        "home": home,
     })
 }
 
 // ─── DOCUMENT CHAT (Phase 3) ────────────────────────────
 
+/// Open a chat on one or more files and folders. Folders are expanded through the
+/// index's ignore rules. Returns a preview of a single file, or a summary of what
+/// was loaded.
 #[tauri::command]
 pub async fn enter_chat_mode(
-    path: String,
+    paths: Vec<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
-    let content = doc_chat::read_file_content(&path)?;
-    let preview = if content.len() > 500 {
-        format!("{}...", &content[..500])
-    } else {
-        content.clone()
+    if paths.is_empty() {
+        return Err("Nothing selected to chat about".to_string());
+    }
+
+    // Large files take a moment to extract and chunk
+    let loaded = {
+        let paths = paths.clone();
+        let rules = state.rules.clone();
+        tokio::task::spawn_blocking(move || doc_chat::load_selection(&paths, &rules))
+            .await
+            .map_err(|e| format!("Task failed: {}", e))??
     };
 
-    let mut chat = state.chat_file_content.write().await;
-    *chat = Some((path, content));
+    let mut preview = match (paths.as_slice(), loaded.documents.as_slice()) {
+        ([picked], [doc]) if &doc.path == picked => match doc.text.char_indices().nth(500) {
+            Some((end, _)) => format!("{}...", &doc.text[..end]),
+            None => doc.text.clone(),
+        },
+        (_, docs) => {
+            let bytes: usize = docs.iter().map(|d| d.text.len()).sum();
+            let mut summary = format!("Loaded {} files ({} KB):\n", docs.len(), bytes.div_ceil(1024));
+            for doc in docs.iter().take(20) {
+                summary.push_str(&format!("- `{}`\n", doc.path));
+            }
+            if docs.len() > 20 {
+                summary.push_str(&format!("- … and {} more\n", docs.len() - 20));
+            }
+            summary
+        }
+    };
+    if !loaded.skipped.is_empty() {
+        preview.push_str(&format!("\n\nSkipped: {}", loaded.skipped.join("; ")));
+    }
+
+    // Reopening the selection of the last chat picks its conversation back up
+    let mut chat = state.chat.write().await;
+    match chat.as_mut() {
+        Some(session) if session.selection == paths => session.resume(loaded.documents),
+        _ => *chat = Some(ChatSession::new(paths, loaded.documents)),
+    }
 
     Ok(preview)
 }
 
+/// Ask about the open files; earlier turns of the conversation go along with it.
+/// With a `stream_id`, the answer is streamed as `llm-delta` events and can be
+/// stopped with `cancel_stream` — a stopped answer is kept as far as it got.
+/// Files too long for their share of the context are answered from retrieved
+/// excerpts, listed in `sources`.
 #[tauri::command]
 pub async fn chat_message(
     question: String,
+    stream_id: Option<String>,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<String, String> {
-    let chat = state.chat_file_content.read().await;
-    let (file_path, file_content) = chat
-        .as_ref()
-        .ok_or("Not in chat mode. Select a file first.")?;
+) -> Result<ChatReply, String> {
+    // Work on a copy so the history can be viewed or cleared mid-answer
+    let (session_id, documents, history) = {
+        let chat = state.chat.read().await;
+        let session = chat
+            .as_ref()
+            .filter(|s| s.open)
+            .ok_or("Not in chat mode. Select a file first.")?;
+        (session.id, session.documents.clone(), session.history.clone())
+    };
 
-    let settings = state.settings.read().await;
-    doc_chat::chat_about_file(&state.llm, &settings, file_path, file_content, &question).await
+    let settings = state.settings.read().await.clone();
+    let stream = StreamHandle::open(&state.streams, stream_id);
+    let mut emit = stream.emitter(&app);
+    let mut partial = String::new();
+    let mut on_delta = |delta: &str| {
+        partial.push_str(delta);
+        emit(delta);
+    };
+    let result = doc_chat::chat_about_files(
+        &state.llm,
+        &settings,
+        &documents,
+        &history,
+        &question,
+        &mut on_delta,
+        Some(&stream.token),
+    )
+    .await;
+
+    let answer = match &result {
+        Ok(reply) => Some(reply.answer.as_str()),
+        Err(e) if e == llm::CANCELLED && !partial.is_empty() => Some(partial.as_str()),
+        Err(_) => None,
+    };
+    if let Some(answer) = answer {
+        let mut chat = state.chat.write().await;
+        if let Some(session) = chat.as_mut().filter(|s| s.id == session_id) {
+            session.record(&question, answer);
+        }
+    }
+    result
+}
+
+/// The conversation so far in the current (or last) chat, oldest first.
+#[tauri::command]
+pub async fn get_chat_history(state: State<'_, AppState>) -> Result<Vec<ChatMessage>, String> {
+    let chat = state.chat.read().await;
+    Ok(chat.as_ref().map(|s| s.history.clone()).unwrap_or_default())
+}
+
+/// Forget the conversation but stay on the same file.
+#[tauri::command]
+pub async fn clear_chat_history(state: State<'_, AppState>) -> Result<(), String> {
+    let mut chat = state.chat.write().await;
+    if let Some(session) = chat.as_mut() {
+        session.history.clear();
+    }
+    Ok(())
+}
+
+/// Save the conversation as Markdown; returns the file written.
+#[tauri::command]
+pub async fn export_chat_history(
+    destination: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let chat = state.chat.read().await;
+    let session = chat.as_ref().ok_or("No conversation to export")?;
+    if session.history.is_empty() {
+        return Err("No conversation to export".to_string());
+    }
+    let path = session.export(destination.as_deref())?;
+    Ok(path.to_string_lossy().to_string())
 }
 
 #[tauri::command]
 pub async fn exit_chat_mode(state: State<'_, AppState>) -> Result<(), String> {
-    let mut chat = state.chat_file_content.write().await;
-    *chat = None;
+    let mut chat = state.chat.write().await;
+    if let Some(session) = chat.as_mut() {
+        session.close();
+    }
+    Ok(())
+}
+
+// ─── LLM STREAMING ───────────────────────────────────────
+
+/// Event name for streamed LLM output.
+const LLM_DELTA_EVENT: &str = "llm-delta";
+
+#[derive(Clone, Serialize)]
+struct LlmDelta<'a> {
+    stream_id: &'a str,
+    delta: &'a str,
+}
+
+/// A cancellable stream, registered under its id for as long as the command runs.
+struct StreamHandle {
+    id: Option<String>,
+    token: CancelToken,
+    streams: ActiveStreams,
+}
+
+impl StreamHandle {
+    fn open(streams: &ActiveStreams, id: Option<String>) -> Self {
+        let token = CancelToken::default();
+        if let (Some(id), Ok(mut active)) = (&id, streams.lock()) {
+            active.insert(id.clone(), token.clone());
+        }
+        Self {
+            id,
+            token,
+            streams: streams.clone(),
+        }
+    }
+
+    /// Callback that forwards each delta to the frontend (a no-op without an id).
+    fn emitter<'a>(&'a self, app: &'a AppHandle) -> impl FnMut(&str) + Send + 'a {
+        move |delta| {
+            if let Some(stream_id) = &self.id {
+                let _ = app.emit(LLM_DELTA_EVENT, LlmDelta { stream_id, delta });
+            }
+        }
+    }
+}
+
+impl Drop for StreamHandle {
+    fn drop(&mut self) {
+        if let (Some(id), Ok(mut active)) = (&self.id, self.streams.lock()) {
+            active.remove(id);
+        }
+    }
+}
+
+/// Stop a streaming `chat_message` / `translate_command`; it returns "Cancelled".
+#[tauri::command]
+pub fn cancel_stream(stream_id: String, state: State<'_, AppState>) -> Result<(), String> {
+    let active = state.streams.lock().map_err(|e| e.to_string())?;
+    if let Some(token) = active.get(&stream_id) {
+        token.cancel();
+    }
     Ok(())
 }
 
@@ -210,18 +868,89 @@ pub fn get_registered_shortcut() -> Result<String, String> {
 // ─── MATH CALCULATOR ─────────────────────────────────────
 
 #[tauri::command]
-pub fn evaluate_math(query: String) -> Option<CalcResult> {
-    calc::evaluate(&query)
+pub async fn evaluate_math(
+    query: String,
+    state: State<'_, AppState>,
+) -> Result<Option<CalcResult>, String> {
+    let history = state.calc_history.read().await;
+    Ok(calc::evaluate(&query, &history.memory))
 }
 
 #[tauri::command]
-pub fn evaluate_graph(
+pub async fn evaluate_graph(
     query: String,
     x_min: f64,
     x_max: f64,
     steps: usize,
-) -> Vec<GraphPoint> {
-    calc::evaluate_graph(&query, x_min, x_max, steps)
+    state: State<'_, AppState>,
+) -> Result<Vec<GraphPoint>, String> {
+    let history = state.calc_history.read().await;
+    Ok(calc::evaluate_graph(
+        &query,
+        x_min,
+        x_max,
+        steps,
+        &history.memory,
+    ))
+}
+
+/// Commit a calculation: store its assignment or function definition, make
+/// its value `ans` and add it to the history.
+#[tauri::command]
+pub async fn record_calculation(
+    query: String,
+    state: State<'_, AppState>,
+) -> Result<Option<CalcResult>, String> {
+    let mut history = state.calc_history.write().await;
+    let Some(result) = calc::execute(&query, &mut history.memory) else {
+        return Ok(None);
+    };
+    if result.error.is_some() {
+        return Ok(Some(result));
+    }
+    history.record(&result, frecency::now());
+    drop(history);
+    json_store::persist(&state.calc_history).await;
+    Ok(Some(result))
+}
+
+/// Past calculations matching `query`, newest first.
+#[tauri::command]
+pub async fn search_calc_history(
+    query: String,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<Vec<CalcEntry>, String> {
+    Ok(state
+        .calc_history
+        .read()
+        .await
+        .search(&query, limit.unwrap_or(8)))
+}
+
+#[tauri::command]
+pub async fn delete_calc_history(id: u64, state: State<'_, AppState>) -> Result<(), String> {
+    state.calc_history.write().await.remove(id);
+    json_store::persist(&state.calc_history).await;
+    Ok(())
+}
+
+/// Stored variables, user functions and `ans`.
+#[tauri::command]
+pub async fn get_calc_memory(state: State<'_, AppState>) -> Result<Memory, String> {
+    Ok(state.calc_history.read().await.memory.clone())
+}
+
+#[tauri::command]
+pub async fn forget_calc_definition(
+    name: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if !state.calc_history.write().await.memory.forget(&name) {
+        return Err(format!("No variable or function named '{}'", name));
+    }
+    json_store::persist(&state.calc_history).await;
+    Ok(())
 }
 
 // ─── APP ICON DATA ───────────────────────────────────────
@@ -0,0 +1,438 @@
+/// Full-text search over the contents of indexed documents.
+///
+/// When `Settings::content_index_enabled` is on, an inverted index (term → documents)
+/// is built from the text, source and PDF files in the file index, persisted under the
+/// cache dir, and kept current by the watcher. Only term frequencies are stored;
+/// snippets are produced at query time by re-reading the top matches.
+use crate::extract;
+use crate::indexer::{EntryKind, FileIndex};
+use crate::json_store::{self, JsonStore};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Files larger than this are left out of the content index.
+const MAX_INDEXED_BYTES: u64 = 10_000_000;
+
+/// Terms outside this length range are not indexed.
+const MIN_TERM_LEN: usize = 2;
+const MAX_TERM_LEN: usize = 64;
+
+/// Snippets returned per matching file.
+const MAX_SNIPPETS: usize = 3;
+
+/// Snippet lines are clipped to roughly this many characters around the match.
+const SNIPPET_CHARS: usize = 160;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Doc {
+    path: String,
+    modified: u64,
+    size: u64,
+    /// Distinct terms in the document, kept so removal can clean up postings.
+    terms: Vec<String>,
+}
+
+/// Inverted index over document contents.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ContentIndex {
+    version: u32,
+    docs: Vec<Option<Doc>>,
+    /// term → (doc slot, term frequency)
+    postings: HashMap<String, Vec<(u32, u32)>>,
+    #[serde(skip)]
+    by_path: HashMap<String, u32>,
+    #[serde(skip)]
+    free: Vec<u32>,
+}
+
+/// The shared content index, alongside `FileIndex`.
+pub type SharedContentIndex = Arc<RwLock<ContentIndex>>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Snippet {
+    /// 1-based line number.
+    pub line: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentMatch {
+    pub name: String,
+    pub path: String,
+    pub score: f64,
+    pub snippets: Vec<Snippet>,
+}
+
+impl JsonStore for ContentIndex {
+    /// Also bumped whenever the tokenizer changes.
+    const VERSION: u32 = 1;
+    const NAME: &'static str = "content index";
+
+    fn path() -> PathBuf {
+        dirs::cache_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("trace")
+            .join("content.json")
+    }
+
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+/// Split text into lowercase alphanumeric terms.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|t| (MIN_TERM_LEN..=MAX_TERM_LEN).contains(&t.chars().count()))
+        .map(|t| t.to_lowercase())
+}
+
+/// Returns true if the file's format is one the content index understands.
+pub fn is_indexable(path: &Path, size: u64) -> bool {
+    size <= MAX_INDEXED_BYTES && extract::registry().supports(path)
+}
+
+impl ContentIndex {
+    pub fn new() -> Self {
+        Self {
+            version: Self::VERSION,
+            ..Self::default()
+        }
+    }
+
+    /// Load the index from disk, or start empty if it is missing or outdated.
+    pub fn load() -> Self {
+        let Some(mut index) = json_store::load::<Self>() else {
+            return Self::new();
+        };
+
+        for (slot, doc) in index.docs.iter().enumerate() {
+            match doc {
+                Some(doc) => {
+                    index.by_path.insert(doc.path.clone(), slot as u32);
+                }
+                None => index.free.push(slot as u32),
+            }
+        }
+        index
+    }
+
+    /// Number of indexed documents.
+    pub fn len(&self) -> usize {
+        self.by_path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_path.is_empty()
+    }
+
+    /// Returns true if `path` is indexed with this exact mtime and size.
+    pub fn is_current(&self, path: &str, modified: u64, size: u64) -> bool {
+        self.by_path
+            .get(path)
+            .and_then(|&slot| self.docs[slot as usize].as_ref())
+            .is_some_and(|d| d.modified == modified && d.size == size)
+    }
+
+    /// Index (or re-index) a document's text.
+    pub fn insert(&mut self, path: &str, modified: u64, size: u64, text: &str) {
+        self.remove(path);
+
+        let mut freqs: HashMap<String, u32> = HashMap::new();
+        for term in tokenize(text) {
+            *freqs.entry(term).or_default() += 1;
+        }
+
+        let slot = self.free.pop().unwrap_or_else(|| {
+            self.docs.push(None);
+            (self.docs.len() - 1) as u32
+        });
+        for (term, tf) in &freqs {
+            self.postings.entry(term.clone()).or_default().push((slot, *tf));
+        }
+
+        self.docs[slot as usize] = Some(Doc {
+            path: path.to_string(),
+            modified,
+            size,
+            terms: freqs.into_keys().collect(),
+        });
+        self.by_path.insert(path.to_string(), slot);
+    }
+
+    /// Drop a document from the index.
+    pub fn remove(&mut self, path: &str) {
+        let Some(slot) = self.by_path.remove(path) else {
+            return;
+        };
+        if let Some(doc) = self.docs[slot as usize].take() {
+            for term in doc.terms {
+                if let Some(list) = self.postings.get_mut(&term) {
+                    list.retain(|(s, _)| *s != slot);
+                    if list.is_empty() {
+                        self.postings.remove(&term);
+                    }
+                }
+            }
+        }
+        self.free.push(slot);
+    }
+
+    /// Follow a file that moved without its contents changing.
+    pub fn rename(&mut self, from: &str, to: &str) {
+        let Some(slot) = self.by_path.remove(from) else {
+            return;
+        };
+        self.remove(to);
+        if let Some(doc) = self.docs[slot as usize].as_mut() {
+            doc.path = to.to_string();
+        }
+        self.by_path.insert(to.to_string(), slot);
+    }
+
+    /// Paths of indexed documents containing every query term, best first.
+    /// Scored with a log-scaled tf·idf sum.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<(String, f64)> {
+        let terms: HashSet<String> = tokenize(query).collect();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut lists = Vec::with_capacity(terms.len());
+        for term in &terms {
+            match self.postings.get(term) {
+                Some(list) => lists.push(list),
+                None => return Vec::new(),
+            }
+        }
+        // Start from the rarest term so the candidate set stays small
+        lists.sort_by_key(|l| l.len());
+
+        let n = self.len().max(1) as f64;
+        let weight = |tf: u32, df: usize| (1.0 + (tf as f64).ln()) * (1.0 + n / df as f64).ln();
+
+        let mut scores: HashMap<u32, f64> = lists[0]
+            .iter()
+            .map(|&(slot, tf)| (slot, weight(tf, lists[0].len())))
+            .collect();
+        for list in &lists[1..] {
+            let tfs: HashMap<u32, u32> = list.iter().copied().collect();
+            scores.retain(|slot, score| match tfs.get(slot) {
+                Some(&tf) => {
+                    *score += weight(tf, list.len());
+                    true
+                }
+                None => false,
+            });
+        }
+
+        let mut ranked: Vec<(u32, f64)> = scores.into_iter().collect();
+        ranked.sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+            .into_iter()
+            .take(limit)
+            .filter_map(|(slot, score)| {
+                let doc = self.docs[slot as usize].as_ref()?;
+                Some((doc.path.clone(), score))
+            })
+            .collect()
+    }
+}
+
+/// Bring the content index in line with the file index: extract new or changed
+/// documents and drop ones that no longer exist. Blocking — run on a worker thread.
+pub fn sync_with_files(content: &SharedContentIndex, index: &FileIndex) {
+    let files: Vec<(String, u64, u64)> = index
+        .blocking_read()
+        .entries()
+        .filter(|e| e.kind == EntryKind::File && is_indexable(Path::new(&e.path), e.size))
+        .map(|e| (e.path.clone(), e.modified, e.size))
+        .collect();
+
+    let stale: Vec<String> = {
+        let idx = content.blocking_read();
+        let live: HashSet<&str> = files.iter().map(|(p, _, _)| p.as_str()).collect();
+        idx.by_path
+            .keys()
+            .filter(|p| !live.contains(p.as_str()))
+            .cloned()
+            .collect()
+    };
+    if !stale.is_empty() {
+        let mut idx = content.blocking_write();
+        for path in &stale {
+            idx.remove(path);
+        }
+    }
+
+    let mut updated = 0usize;
+    for (path, modified, size) in files {
+        if content.blocking_read().is_current(&path, modified, size) {
+            continue;
+        }
+        // Extraction happens outside the lock; unreadable files are simply skipped
+        if let Ok(text) = extract::registry().extract(Path::new(&path)) {
+            content.blocking_write().insert(&path, modified, size, &text);
+            updated += 1;
+        }
+    }
+
+    let idx = content.blocking_read();
+    println!(
+        "[trace][content] {} documents indexed ({} updated, {} removed)",
+        idx.len(),
+        updated,
+        stale.len()
+    );
+    if let Err(e) = json_store::save(&*idx) {
+        eprintln!("[trace][content] {}", e);
+    }
+}
+
+/// Re-extract a single file after a live change (watcher path).
+pub fn update_file(content: &SharedContentIndex, path: &str, modified: u64, size: u64) {
+    if !is_indexable(Path::new(path), size) {
+        content.blocking_write().remove(path);
+        return;
+    }
+    if content.blocking_read().is_current(path, modified, size) {
+        return;
+    }
+    match extract::registry().extract(Path::new(path)) {
+        Ok(text) => content.blocking_write().insert(path, modified, size, &text),
+        Err(_) => content.blocking_write().remove(path),
+    }
+}
+
+/// Run a content query and attach line snippets for each matching file.
+pub async fn search(content: &SharedContentIndex, query: &str, limit: usize) -> Vec<ContentMatch> {
+    let hits = content.read().await.search(query, limit);
+    let terms: Vec<String> = tokenize(query).collect();
+
+    tokio::task::spawn_blocking(move || {
+        hits.into_iter()
+            .map(|(path, score)| {
+                let name = Path::new(&path)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.clone());
+                let snippets = extract::registry()
+                    .extract(Path::new(&path))
+                    .map(|text| find_snippets(&text, &terms))
+                    .unwrap_or_default();
+                ContentMatch {
+                    name,
+                    path,
+                    score,
+                    snippets,
+                }
+            })
+            .collect()
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// Pick the lines mentioning the most query terms, in file order.
+fn find_snippets(text: &str, terms: &[String]) -> Vec<Snippet> {
+    let mut scored: Vec<(usize, usize, &str)> = text
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let lower = line.to_lowercase();
+            let hits = terms.iter().filter(|t| lower.contains(t.as_str())).count();
+            (hits > 0).then_some((hits, i, line))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.truncate(MAX_SNIPPETS);
+    scored.sort_by_key(|&(_, i, _)| i);
+
+    scored
+        .into_iter()
+        .map(|(_, i, line)| Snippet {
+            line: i + 1,
+            text: clip_around(line, terms),
+        })
+        .collect()
+}
+
+/// Trim a long line to a window around the first matching term.
+fn clip_around(line: &str, terms: &[String]) -> String {
+    let line = line.trim();
+    let chars: Vec<char> = line.chars().collect();
+    if chars.len() <= SNIPPET_CHARS {
+        return line.to_string();
+    }
+
+    let lower = line.to_lowercase();
+    let first_byte = terms
+        .iter()
+        .filter_map(|t| lower.find(t.as_str()))
+        .min()
+        .unwrap_or(0);
+    // Lowercasing can change the length of some scripts; clamp to be safe
+    let first = lower[..first_byte].chars().count().min(chars.len());
+    let start = first.saturating_sub(SNIPPET_CHARS / 3);
+    let end = (start + SNIPPET_CHARS).min(chars.len());
+
+    let mut out: String = chars[start..end].iter().collect();
+    if start > 0 {
+        out.insert(0, '…');
+    }
+    if end < chars.len() {
+        out.push('…');
+    }
+    out
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_requires_every_term() {
+        let mut idx = ContentIndex::new();
+        idx.insert("/a.md", 0, 0, "The quick brown fox");
+        idx.insert("/b.md", 0, 0, "A quick test of quick search");
+
+        let hits = idx.search("quick", 10);
+        assert_eq!(hits.len(), 2);
+        // Higher term frequency ranks first
+        assert_eq!(hits[0].0, "/b.md");
+
+        let hits = idx.search("QUICK fox", 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0, "/a.md");
+
+        assert!(idx.search("missing", 10).is_empty());
+    }
+
+    #[test]
+    fn remove_and_rename_update_postings() {
+        let mut idx = ContentIndex::new();
+        idx.insert("/a.rs", 1, 1, "fn parse_query() {}");
+        idx.rename("/a.rs", "/b.rs");
+        assert_eq!(idx.search("parse_query", 10)[0].0, "/b.rs");
+        assert!(idx.is_current("/b.rs", 1, 1));
+
+        idx.remove("/b.rs");
+        assert!(idx.search("parse_query", 10).is_empty());
+        assert!(idx.postings.is_empty());
+    }
+
+    #[test]
+    fn snippets_report_line_numbers() {
+        let text = "first line\nnothing here\nthe needle is here\n";
+        let snippets = find_snippets(text, &["needle".to_string()]);
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].line, 3);
+        assert_eq!(snippets[0].text, "the needle is here");
+    }
+}
@@ -1,9 +1,264 @@
-/// Document Chat (RAG-Lite): Read a file's content and chat about it via LLM.
-use crate::llm::LlmClient;
-use crate::settings::Settings;
-use std::path::Path;
+/// Document Chat (RAG-Lite): Read files' content and chat about them via LLM.
+/// A chat holds one or more files (folders are expanded). Each file gets a share
+/// of the model's context; files that fit their share are sent whole, longer ones
+/// go through `rag` retrieval and only the excerpts relevant to the question are sent.
+use crate::content_index;
+use crate::extract;
+use crate::ignore_rules::IgnoreRules;
+use crate::indexer::{self, EntryKind};
+use crate::llm::{CancelToken, ChatMessage, LlmClient, Role};
+use crate::rag::{self, ChunkEmbeddings, Citation, Document};
+use crate::settings::{IndexFilter, Settings};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
-const MAX_CONTENT_CHARS: usize = 100_000;
+/// Files above this size are refused outright.
+const MAX_FILE_BYTES: u64 = 50_000_000;
+
+/// Most files one chat can hold; folders with more are cut off (in path order).
+const MAX_SESSION_FILES: usize = 200;
+
+/// Combined text one chat can hold.
+const MAX_SESSION_BYTES: usize = 50_000_000;
+
+/// When file and conversation together overflow the context window, the
+/// conversation keeps at most 1/HISTORY_SHARE of it and the file the rest.
+const HISTORY_SHARE: usize = 4;
+
+/// An answer plus the excerpts it was given, when the file was too long to send whole.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatReply {
+    pub answer: String,
+    pub sources: Vec<Citation>,
+}
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// One document chat: the files being discussed and everything said so far.
+pub struct ChatSession {
+    /// Distinguishes sessions, so an answer never lands in a newer conversation
+    pub id: u64,
+    /// What the user picked (files and folders); reopening the same pick resumes the chat
+    pub selection: Vec<String>,
+    /// The chunked files; empty while the chat is closed
+    pub documents: Vec<Arc<Document>>,
+    pub history: Vec<ChatMessage>,
+    pub open: bool,
+}
+
+pub type SharedChat = Arc<RwLock<Option<ChatSession>>>;
+
+impl ChatSession {
+    pub fn new(selection: Vec<String>, documents: Vec<Document>) -> Self {
+        Self {
+            id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+            selection,
+            documents: documents.into_iter().map(Arc::new).collect(),
+            history: Vec::new(),
+            open: true,
+        }
+    }
+
+    /// Reopen a closed chat on the same selection, keeping its conversation.
+    pub fn resume(&mut self, documents: Vec<Document>) {
+        self.documents = documents.into_iter().map(Arc::new).collect();
+        self.open = true;
+    }
+
+    /// Leave chat mode; the conversation stays around until something else is opened.
+    pub fn close(&mut self) {
+        self.documents.clear();
+        self.open = false;
+    }
+
+    /// Append a finished question/answer exchange.
+    pub fn record(&mut self, question: &str, answer: &str) {
+        self.history.push(ChatMessage::user(question));
+        self.history.push(ChatMessage::assistant(answer));
+    }
+
+    /// The conversation as a Markdown document.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# Chat about {}\n\n", selection_title(&self.selection));
+        for path in &self.selection {
+            out.push_str(&format!("- `{}`\n", path));
+        }
+        for message in &self.history {
+            let heading = match message.role {
+                Role::User => "You",
+                Role::Assistant => "Assistant",
+            };
+            out.push_str(&format!("\n## {}\n\n{}\n", heading, message.content.trim_end()));
+        }
+        out
+    }
+
+    /// Write the conversation as Markdown to `destination`, or to a timestamped
+    /// file in the documents (else home) directory. Returns the path written.
+    pub fn export(&self, destination: Option<&str>) -> Result<PathBuf, String> {
+        let path = match destination {
+            Some(dest) => PathBuf::from(dest),
+            None => {
+                let dir = dirs::document_dir()
+                    .or_else(dirs::home_dir)
+                    .unwrap_or_else(std::env::temp_dir);
+                let stem = self
+                    .selection
+                    .first()
+                    .and_then(|p| Path::new(p).file_stem())
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "file".to_string());
+                let stamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                dir.join(format!("trace-chat-{}-{}.md", stem, stamp))
+            }
+        };
+        std::fs::write(&path, self.to_markdown())
+            .map_err(|e| format!("Failed to export chat to {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+/// "notes.md", or "3 items" for a multi-pick.
+fn selection_title(selection: &[String]) -> String {
+    match selection {
+        [one] => file_name(one),
+        many => format!("{} items", many.len()),
+    }
+}
+
+/// Files loaded for a chat, plus what was left out and why.
+pub struct LoadedFiles {
+    pub documents: Vec<Document>,
+    pub skipped: Vec<String>,
+}
+
+/// Turn a pick of files and folders into chat documents. Files are read as given;
+/// folders are walked with the indexer's ignore rules (or, outside every index
+/// root, rules rooted at the folder itself) and only text/PDF formats are kept.
+/// Blocking — run on a worker thread.
+pub fn load_selection(selection: &[String], rules: &IgnoreRules) -> Result<LoadedFiles, String> {
+    let mut files: Vec<String> = Vec::new();
+    let mut skipped = Vec::new();
+
+    for picked in selection {
+        let path = Path::new(picked);
+        if !path.is_dir() {
+            files.push(picked.clone());
+            continue;
+        }
+
+        let own_rules;
+        let rules = if rules.covers(path) {
+            rules
+        } else {
+            own_rules = IgnoreRules::new(&[path.to_path_buf()], &[IndexFilter::default()]);
+            &own_rules
+        };
+        let mut found: Vec<String> = indexer::scan_tree(path, rules)
+            .into_iter()
+            .filter(|e| {
+                e.kind == EntryKind::File && content_index::is_indexable(Path::new(&e.path), e.size)
+            })
+            .map(|e| e.path)
+            .collect();
+        found.sort();
+        files.extend(found);
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|f| seen.insert(f.clone()));
+    if files.len() > MAX_SESSION_FILES {
+        skipped.push(format!(
+            "{} more files (a chat holds at most {})",
+            files.len() - MAX_SESSION_FILES,
+            MAX_SESSION_FILES
+        ));
+        files.truncate(MAX_SESSION_FILES);
+    }
+
+    let mut documents = Vec::new();
+    let mut total = 0;
+    let mut last_error = None;
+    for file in files {
+        match read_file_content(&file) {
+            Ok(text) if total + text.len() > MAX_SESSION_BYTES => {
+                skipped.push(format!("{} (chat size limit reached)", file_name(&file)));
+            }
+            Ok(text) => {
+                total += text.len();
+                documents.push(Document::new(file, text));
+            }
+            Err(e) => {
+                skipped.push(format!("{} ({})", file_name(&file), e));
+                last_error = Some(e);
+            }
+        }
+    }
+
+    if documents.is_empty() {
+        return Err(match (selection, last_error) {
+            ([_], Some(e)) => e,
+            _ => "No readable text files in the selection".to_string(),
+        });
+    }
+    Ok(LoadedFiles { documents, skipped })
+}
+
+/// Short names for each document: paths relative to their deepest common folder,
+/// or the bare file name for a single document.
+fn source_labels(docs: &[Arc<Document>]) -> Vec<String> {
+    let paths: Vec<&Path> = docs.iter().map(|d| Path::new(&d.path)).collect();
+    let mut common = match paths.first().and_then(|p| p.parent()) {
+        Some(dir) => dir.to_path_buf(),
+        None => return docs.iter().map(|d| d.path.clone()).collect(),
+    };
+    for path in &paths[1..] {
+        while !path.starts_with(&common) {
+            if !common.pop() {
+                break;
+            }
+        }
+    }
+    paths
+        .iter()
+        .map(|p| {
+            p.strip_prefix(&common)
+                .unwrap_or(p)
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect()
+}
+
+/// Split `budget` bytes between files of the given sizes: files smaller than an
+/// equal share keep their full size, and what they leave over is shared out
+/// equally among the larger ones.
+fn allocate(sizes: &[usize], budget: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| sizes[i]);
+
+    let mut shares = vec![0; sizes.len()];
+    let mut remaining = budget;
+    for (k, &i) in order.iter().enumerate() {
+        let fair = remaining / (order.len() - k);
+        shares[i] = sizes[i].min(fair);
+        remaining -= shares[i];
+    }
+    shares
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
 
 /// Return the safe maximum character count for file content based on the active model.
 /// Prevents context-length overflow errors on small-context models like Gemma / Mistral 7B.
@@ -49,8 +304,8 @@ fn model_context_chars(model: &str) -> usize {
     20_000
 }
 
-/// Read a file's text content. Supports plain text, code, markdown, and PDF.
-/// For very large files, truncates to MAX_CONTENT_CHARS.
+/// Read a file's full text content: plain text, code, PDF, office documents,
+/// EPUB, HTML, notebooks and gzip'd files.
 pub fn read_file_content(path: &str) -> Result<String, String> {
     let p = Path::new(path);
 
@@ -62,85 +317,339 @@ pub fn read_file_content(path: &str) -> Result<String, String> {
     let metadata = std::fs::metadata(p)
         .map_err(|e| format!("Cannot read file metadata: {}", e))?;
 
-    if metadata.len() > 50_000_000 {
-        // 50MB limit
+    if metadata.len() > MAX_FILE_BYTES {
         return Err("File is too large (>50MB) for chat mode".to_string());
     }
 
-    // Detect PDF by extension and extract text
-    let ext = p
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("")
-        .to_lowercase();
-
-    let content = if ext == "pdf" {
-        let bytes = std::fs::read(p)
-            .map_err(|e| format!("Cannot read PDF file: {}", e))?;
-        pdf_extract::extract_text_from_mem(&bytes)
-            .map_err(|e| format!(
-                "Could not extract text from PDF (it may be a scanned/image-only document): {}",
-                e
-            ))?
-    } else {
-        std::fs::read_to_string(p)
-            .map_err(|e| format!("Cannot read file (may be binary): {}", e))?
+    extract_text(p)
+}
+
+/// Extract the text of a file without any size limits or truncation, using
+/// whichever `extract` registry entry claims it (see `extract::registry`).
+fn extract_text(p: &Path) -> Result<String, String> {
+    extract::registry().extract(p)
+}
+
+/// The most recent turns of `history` that fit in `budget` chars. Always starts
+/// on a user turn, since providers reject conversations opening with the assistant.
+fn recent_history(history: &[ChatMessage], budget: usize) -> &[ChatMessage] {
+    let mut used = 0;
+    let mut start = history.len();
+    for (i, message) in history.iter().enumerate().rev() {
+        used += message.content.len();
+        if used > budget {
+            break;
+        }
+        start = i;
+    }
+    while start < history.len() && history[start].role != Role::User {
+        start += 1;
+    }
+    &history[start..]
+}
+
+/// Split the model's context window between file and conversation. The file
+/// gets everything the conversation doesn't need, but never less than
+/// (HISTORY_SHARE-1)/HISTORY_SHARE of it; older turns are dropped first.
+/// Returns the file's budget and the turns that fit next to it.
+fn split_budget<'a>(
+    file_len: usize,
+    history: &'a [ChatMessage],
+    question: &str,
+    limit: usize,
+) -> (usize, &'a [ChatMessage]) {
+    let conversation: usize =
+        question.len() + history.iter().map(|m| m.content.len()).sum::<usize>();
+    let file_budget = limit - conversation.min(limit / HISTORY_SHARE);
+    let history_budget = limit.saturating_sub(file_len.min(file_budget) + question.len());
+    (file_budget, recent_history(history, history_budget))
+}
+
+/// Cosine similarity of the question to every chunk, embedding the document on
+/// first use. `None` when embeddings are off, the document is too large, or the
+/// provider fails — retrieval then falls back to BM25 alone.
+async fn semantic_scores(
+    llm: &LlmClient,
+    settings: &Settings,
+    doc: &Document,
+    query: &str,
+) -> Option<Vec<f64>> {
+    if !settings.embeddings_enabled() || doc.chunks.len() > rag::MAX_EMBEDDED_CHUNKS {
+        return None;
+    }
+    let model = settings.embedding_model.trim();
+
+    let embeddings = match doc.embeddings(model) {
+        Some(embeddings) => embeddings,
+        None => {
+            let texts: Vec<&str> = (0..doc.chunks.len()).map(|i| doc.chunk_text(i)).collect();
+            match llm.embed(settings, &texts).await {
+                Ok(vectors) => doc.set_embeddings(ChunkEmbeddings {
+                    model: model.to_string(),
+                    vectors,
+                }),
+                Err(e) => {
+                    eprintln!("[trace][chat] Embedding failed, using keyword ranking: {}", e);
+                    return None;
+                }
+            }
+        }
     };
 
-    // Truncate if needed
-    if content.len() > MAX_CONTENT_CHARS {
-        Ok(format!(
-            "{}\n\n[... truncated — showing first {} characters of {} total]",
-            &content[..MAX_CONTENT_CHARS],
-            MAX_CONTENT_CHARS,
-            content.len()
-        ))
-    } else {
-        Ok(content)
+    match llm.embed(settings, &[query]).await {
+        Ok(mut query_vector) if query_vector.len() == 1 => Some(rag::cosine_scores(
+            &query_vector.remove(0),
+            &embeddings.vectors,
+        )),
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("[trace][chat] Embedding failed, using keyword ranking: {}", e);
+            None
+        }
+    }
+}
+
+/// The excerpts of `doc` picked from `ranking` within `budget`, labelled
+/// `[first_label]`, `[first_label + 1]`, … in document order, plus their citations.
+fn excerpts(
+    doc: &Document,
+    ranking: &[usize],
+    budget: usize,
+    first_label: usize,
+) -> (String, Vec<Citation>) {
+    let mut text = String::new();
+    let mut citations = Vec::new();
+    for (n, i) in doc.select(ranking, budget).into_iter().enumerate() {
+        let chunk = &doc.chunks[i];
+        let label = first_label + n;
+        text.push_str(&format!("[{}] {}\n{}\n\n", label, chunk.describe(), doc.chunk_text(i)));
+        citations.push(Citation {
+            label,
+            path: doc.path.clone(),
+            first_line: chunk.first_line,
+            last_line: chunk.last_line,
+            pages: chunk.pages,
+            cited: false,
+        });
     }
+    (text, citations)
 }
 
-/// Send a question about a file to the LLM.
-pub async fn chat_about_file(
+/// Send a question about the chat's files to the LLM, with the earlier conversation,
+/// streaming the answer through `on_delta`. Old turns are dropped to fit the model's
+/// context, and files too long for their share are cut down to their most relevant
+/// excerpts, labelled `[n]` across all files.
+pub async fn chat_about_files(
     llm: &LlmClient,
     settings: &Settings,
-    file_path: &str,
-    file_content: &str,
+    docs: &[Arc<Document>],
+    history: &[ChatMessage],
     question: &str,
-) -> Result<String, String> {
-    let file_name = Path::new(file_path)
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| file_path.to_string());
-
-    // Trim content to the model's safe context window
+    on_delta: &mut (dyn FnMut(&str) + Send),
+    cancel: Option<&CancelToken>,
+) -> Result<ChatReply, String> {
     let limit = model_context_chars(&settings.active_model);
-    let trimmed_content: String;
-    let content_for_prompt = if file_content.len() > limit {
-        // Find a clean UTF-8 boundary
-        let boundary = file_content
-            .char_indices()
-            .map(|(i, _)| i)
-            .take_while(|&i| i <= limit)
-            .last()
-            .unwrap_or(limit);
-        trimmed_content = format!(
-            "{}\n\n[... content trimmed to {} chars — {} chars total. Ask about a specific section for more detail.]",
-            &file_content[..boundary],
-            limit,
-            file_content.len()
+    let sizes: Vec<usize> = docs.iter().map(|d| d.text.len()).collect();
+    let (budget, recent) = split_budget(sizes.iter().sum(), history, question, limit);
+    if recent.len() < history.len() {
+        eprintln!(
+            "[trace][chat] Dropped {} earlier messages to fit the context window",
+            history.len() - recent.len()
         );
-        &trimmed_content
-    } else {
-        file_content
+    }
+
+    // Follow-ups ("and the second one?") lean on the previous question's terms
+    let query = match recent.iter().rev().find(|m| m.role == Role::User) {
+        Some(previous) => format!("{}\n{}", question, previous.content),
+        None => question.to_string(),
     };
 
-    let system = format!(
-        "You are an expert code and document analyst. The user has opened the file '{}'. \
-         Below is the file's content. Answer the user's question about this file concisely and accurately.\n\n\
-         --- FILE CONTENT ---\n{}\n--- END FILE CONTENT ---",
-        file_name, content_for_prompt
-    );
+    let labels = source_labels(docs);
+    let shares = allocate(&sizes, budget);
+    let mut body = String::new();
+    let mut sources: Vec<Citation> = Vec::new();
+    let mut all_fit = true;
+    for ((doc, label), share) in docs.iter().zip(&labels).zip(shares) {
+        if doc.text.len() <= share {
+            body.push_str(&format!("--- FILE: {} ---\n{}\n--- END FILE ---\n\n", label, doc.text));
+            continue;
+        }
+        all_fit = false;
+        let similarity = semantic_scores(llm, settings, doc, &query).await;
+        let ranking = doc.rank(&query, similarity.as_deref());
+        let (text, cited) = excerpts(doc, &ranking, share, sources.len() + 1);
+        eprintln!(
+            "[trace][chat] Sending {} of {} chunks of {}",
+            cited.len(),
+            doc.chunks.len(),
+            label
+        );
+        if cited.is_empty() {
+            body.push_str(&format!("--- FILE: {} (too long; nothing relevant found) ---\n\n", label));
+        } else {
+            body.push_str(&format!("--- FILE: {} (excerpts) ---\n{}--- END FILE ---\n\n", label, text));
+        }
+        sources.extend(cited);
+    }
+
+    // A lone file is shown whole only if it fit; otherwise `body` holds what did
+    let system = match (docs, all_fit) {
+        ([doc], true) => format!(
+            "You are an expert code and document analyst. The user has opened the file '{}'. \
+             Below is the file's content. Answer the user's question about this file concisely and accurately.\n\n\
+             --- FILE CONTENT ---\n{}\n--- END FILE CONTENT ---",
+            labels[0], doc.text
+        ),
+        _ => format!(
+            "You are an expert code and document analyst. The user has opened {} file(s), shown below \
+             between --- FILE: name --- markers. Files too long to show in full are given as the excerpts \
+             most relevant to the question, each labelled [n] with its location. Answer concisely and \
+             accurately, naming the files you draw on and citing excerpts you used as [n]. If the material \
+             does not contain the answer, say so.\n\n{}",
+            docs.len(),
+            body
+        ),
+    };
+
+    let mut messages = recent.to_vec();
+    messages.push(ChatMessage::user(question));
+    let answer = llm
+        .prompt_stream(settings, &system, &messages, on_delta, cancel)
+        .await?;
+
+    for source in &mut sources {
+        source.cited = rag::is_cited(&answer, source.label);
+    }
+    Ok(ChatReply { answer, sources })
+}
 
-    llm.prompt(settings, &system, question).await
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turns(n: usize, len: usize) -> Vec<ChatMessage> {
+        (0..n)
+            .flat_map(|i| {
+                [
+                    ChatMessage::user(format!("{}{}", i, "q".repeat(len - 1))),
+                    ChatMessage::assistant("a".repeat(len)),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn small_chats_fit_entirely() {
+        let history = turns(3, 10);
+        let (budget, recent) = split_budget(4, &history, "next?", 1_000);
+        assert_eq!(budget, 1_000 - 65);
+        assert_eq!(recent, &history[..]);
+    }
+
+    #[test]
+    fn oldest_turns_are_dropped_first() {
+        let history = turns(10, 100);
+        // Room for the question and ~3.5 turns next to a tiny file
+        let (_, recent) = split_budget(1, &history, "q", 750);
+        assert_eq!(recent.len(), 6);
+        assert_eq!(recent[0].role, Role::User);
+        assert!(recent[0].content.starts_with('7'));
+        assert_eq!(recent.last(), history.last());
+    }
+
+    #[test]
+    fn large_file_keeps_a_share_for_the_conversation() {
+        let history = turns(20, 100);
+        let (budget, recent) = split_budget(10_000, &history, "q", 4_000);
+
+        assert_eq!(budget, 3_000);
+        let used: usize = recent.iter().map(|m| m.content.len()).sum();
+        assert!(used > 0 && used < 1_000, "{}", used);
+        assert_eq!(recent[0].role, Role::User);
+    }
+
+    #[test]
+    fn excerpts_are_labelled_in_document_order() {
+        let text: String = (1..=400).map(|i| format!("row {} plain\n", i)).collect();
+        let text = format!("{}needle here\n", text);
+        let doc = Document::new("/data.log".into(), text);
+
+        let ranking = doc.rank("needle", None);
+        let (text, sources) = excerpts(&doc, &ranking, 2_000, 3);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].last_line, 401);
+        assert!(text.starts_with(&format!("[3] lines {}–401\n", sources[0].first_line)));
+        assert!(text.contains("needle here"));
+    }
+
+    #[test]
+    fn history_never_opens_with_the_assistant() {
+        let history = vec![
+            ChatMessage::user("u".repeat(50)),
+            ChatMessage::assistant("a".repeat(10)),
+        ];
+        assert!(recent_history(&history, 20).is_empty());
+        assert_eq!(recent_history(&history, 60).len(), 2);
+    }
+
+    #[test]
+    fn markdown_export() {
+        let doc = Document::new("/tmp/notes.md".into(), "text".into());
+        let mut session = ChatSession::new(vec!["/tmp/notes.md".into()], vec![doc]);
+        session.record("What is this?", "A note.\n");
+        assert_eq!(
+            session.to_markdown(),
+            "# Chat about notes.md\n\n- `/tmp/notes.md`\n\n## You\n\nWhat is this?\n\n## Assistant\n\nA note.\n"
+        );
+    }
+
+    #[test]
+    fn small_files_keep_their_size_and_big_ones_share_the_rest() {
+        assert_eq!(allocate(&[100, 5_000, 200, 9_000], 4_300), vec![100, 2_000, 200, 2_000]);
+        assert_eq!(allocate(&[10, 20], 1_000), vec![10, 20]);
+        assert_eq!(allocate(&[], 1_000), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn labels_are_relative_to_the_common_folder() {
+        let docs: Vec<Arc<Document>> = ["/p/src/main.rs", "/p/src/util/io.rs", "/p/README.md"]
+            .iter()
+            .map(|p| Arc::new(Document::new(p.to_string(), String::new())))
+            .collect();
+        assert_eq!(source_labels(&docs), vec!["src/main.rs", "src/util/io.rs", "README.md"]);
+        assert_eq!(source_labels(&docs[1..2]), vec!["io.rs"]);
+    }
+
+    #[test]
+    fn folders_expand_through_ignore_rules() {
+        let dir = std::env::temp_dir().join(format!("trace-chat-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::create_dir_all(dir.join("target")).unwrap();
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "pub fn a() {}\n").unwrap();
+        std::fs::write(dir.join("notes.md"), "# notes\n").unwrap();
+        std::fs::write(dir.join("image.png"), [0u8, 1, 2]).unwrap();
+        std::fs::write(dir.join("target/out.rs"), "generated\n").unwrap();
+        std::fs::write(dir.join(".git/config"), "[core]\n").unwrap();
+
+        let rules = IgnoreRules::new(std::slice::from_ref(&dir), &[IndexFilter::default()]);
+        let loaded = load_selection(&[dir.to_string_lossy().to_string()], &rules).unwrap();
+        let names: Vec<String> = loaded
+            .documents
+            .iter()
+            .map(|d| Path::new(&d.path).strip_prefix(&dir).unwrap().to_string_lossy().to_string())
+            .collect();
+        assert!(names.contains(&"src/lib.rs".to_string()), "{:?}", names);
+        for left_out in ["target/out.rs", ".git/config", "image.png", ".gitignore"] {
+            assert!(!names.contains(&left_out.to_string()), "{:?}", names);
+        }
+
+        let missing = load_selection(&[dir.join("nope.txt").to_string_lossy().to_string()], &rules);
+        assert!(matches!(missing, Err(e) if e.starts_with("File not found")));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
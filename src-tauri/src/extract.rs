@@ -0,0 +1,960 @@
+/// Text extraction for every document format Trace understands.
+///
+/// Extractors are looked up by file extension first, then by the file's leading
+/// bytes, so a misnamed PDF or an extensionless zip still converts. Anything left
+/// over is read as UTF-8. Office tables come out as Markdown, spreadsheets as one
+/// TSV block per sheet, slides and notebook cells under their own headings.
+use crate::rag::PAGE_BREAK;
+use roxmltree::{Document as Xml, Node};
+use serde_json::Value;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Most bytes a compressed member (gzip stream or zip entry) may inflate to.
+const MAX_INFLATED_BYTES: u64 = 50_000_000;
+
+/// Gzip streams unwrapped inside one another before giving up.
+const MAX_GZIP_LAYERS: usize = 4;
+
+/// Leading bytes of a gzip stream.
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+
+/// Columns a worksheet can have (A through XFD).
+const MAX_COLUMNS: usize = 16_384;
+
+/// Longest run of spaces an OpenDocument `text:s` element may expand to; the
+/// count comes from the file itself.
+const MAX_SPACE_RUN: usize = 256;
+
+/// Characters of each notebook cell output kept after the cell's source.
+const MAX_OUTPUT_CHARS: usize = 2_000;
+
+/// Plain-text formats (source, markup, config, logs) read as UTF-8.
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "markdown", "rst", "org", "adoc", "tex", "log", "csv", "tsv", "json", "yaml",
+    "yml", "toml", "ini", "cfg", "conf", "env", "xml", "css", "scss", "less", "js", "jsx", "mjs",
+    "cjs", "ts", "tsx", "vue", "svelte", "rs", "py", "go", "java", "kt", "kts", "scala", "c", "h",
+    "cc", "cpp", "cxx", "hpp", "cs", "swift", "m", "rb", "php", "pl", "lua", "r", "jl", "dart",
+    "ex", "exs", "erl", "hs", "ml", "clj", "sql", "sh", "bash", "zsh", "fish", "ps1", "bat",
+    "cmake", "gradle", "proto", "graphql",
+];
+
+/// Converts one family of formats to text. `path` is a hint only (compressed
+/// files use it to find the inner format); the bytes are the whole file.
+/// `registry` is the one doing the lookup, so wrapper formats like gzip hand
+/// their contents back to the same set of extractors.
+pub type ExtractFn = fn(registry: &Registry, bytes: &[u8], path: &Path) -> Result<String, String>;
+
+pub struct Extractor {
+    pub name: &'static str,
+    /// Lowercase extensions, without the dot
+    pub extensions: &'static [&'static str],
+    /// Leading bytes that identify the format whatever the file is called
+    pub magic: &'static [u8],
+    pub extract: ExtractFn,
+}
+
+/// Extractors in lookup order; later registrations win on shared extensions.
+#[derive(Default)]
+pub struct Registry {
+    extractors: Vec<Extractor>,
+}
+
+impl Registry {
+    /// Every built-in format.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::default();
+        registry.register(Extractor {
+            name: "text",
+            extensions: TEXT_EXTENSIONS,
+            magic: b"",
+            extract: extract_utf8,
+        });
+        registry.register(Extractor {
+            name: "pdf",
+            extensions: &["pdf"],
+            magic: b"%PDF",
+            extract: extract_pdf,
+        });
+        registry.register(Extractor {
+            name: "html",
+            extensions: &["html", "htm", "xhtml"],
+            magic: b"",
+            extract: extract_html,
+        });
+        registry.register(Extractor {
+            name: "notebook",
+            extensions: &["ipynb"],
+            magic: b"",
+            extract: extract_notebook,
+        });
+        registry.register(Extractor {
+            name: "office",
+            extensions: &["docx", "xlsx", "pptx", "odt", "ods", "odp", "epub"],
+            magic: b"PK\x03\x04",
+            extract: extract_zip_document,
+        });
+        registry.register(Extractor {
+            name: "gzip",
+            extensions: &["gz"],
+            magic: GZIP_MAGIC,
+            extract: extract_gzip,
+        });
+        registry
+    }
+
+    pub fn register(&mut self, extractor: Extractor) {
+        self.extractors.push(extractor);
+    }
+
+    fn by_extension(&self, path: &Path) -> Option<&Extractor> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        self.extractors
+            .iter()
+            .rev()
+            .find(|e| e.extensions.contains(&ext.as_str()))
+    }
+
+    fn by_magic(&self, bytes: &[u8]) -> Option<&Extractor> {
+        self.extractors
+            .iter()
+            .rev()
+            .find(|e| !e.magic.is_empty() && bytes.starts_with(e.magic))
+    }
+
+    /// Returns true if some extractor claims the file's extension.
+    pub fn supports(&self, path: &Path) -> bool {
+        self.by_extension(path).is_some()
+    }
+
+    /// Convert an in-memory file to text.
+    pub fn extract_bytes(&self, bytes: &[u8], path: &Path) -> Result<String, String> {
+        match self.by_extension(path).or_else(|| self.by_magic(bytes)) {
+            Some(extractor) => (extractor.extract)(self, bytes, path),
+            None => extract_utf8(self, bytes, path),
+        }
+    }
+
+    /// Read and convert a file to text.
+    pub fn extract(&self, path: &Path) -> Result<String, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Cannot read file: {}", e))?;
+        self.extract_bytes(&bytes, path)
+    }
+}
+
+/// The shared registry of built-in extractors.
+pub fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::with_defaults)
+}
+
+// ─── Plain text, PDF, gzip ───────────────────────────────
+
+fn extract_utf8(_registry: &Registry, bytes: &[u8], _path: &Path) -> Result<String, String> {
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| "Cannot read file (may be binary): not valid UTF-8".to_string())
+}
+
+/// Pages are separated by `rag::PAGE_BREAK` so chat citations can name them.
+fn extract_pdf(_registry: &Registry, bytes: &[u8], _path: &Path) -> Result<String, String> {
+    pdf_extract::extract_text_from_mem_by_pages(bytes)
+        .map(|pages| pages.join(&PAGE_BREAK.to_string()))
+        .map_err(|e| {
+            format!(
+                "Could not extract text from PDF (it may be a scanned/image-only document): {}",
+                e
+            )
+        })
+}
+
+/// Decompress, then convert by the inner name (`app.log.gz` → `app.log`).
+/// When that name says nothing and the contents are gzip again, the layers are
+/// unwrapped here, at most `MAX_GZIP_LAYERS` deep, instead of recursing through
+/// the magic-byte lookup.
+fn extract_gzip(registry: &Registry, bytes: &[u8], path: &Path) -> Result<String, String> {
+    let inner = path.with_extension("");
+    let mut inflated = inflate_gzip(bytes)?;
+    let mut layers = 1;
+    while registry.by_extension(&inner).is_none() && inflated.starts_with(GZIP_MAGIC) {
+        if layers == MAX_GZIP_LAYERS {
+            return Err(format!("More than {} nested gzip layers", MAX_GZIP_LAYERS));
+        }
+        inflated = inflate_gzip(&inflated)?;
+        layers += 1;
+    }
+    registry.extract_bytes(&inflated, &inner)
+}
+
+fn inflate_gzip(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let mut inflated = Vec::new();
+    flate2::read::GzDecoder::new(bytes)
+        .take(MAX_INFLATED_BYTES)
+        .read_to_end(&mut inflated)
+        .map_err(|e| format!("Could not decompress gzip file: {}", e))?;
+    Ok(inflated)
+}
+
+// ─── HTML ────────────────────────────────────────────────
+
+/// Elements that start a new line of text.
+fn is_block(name: &str) -> bool {
+    matches!(
+        name,
+        "p" | "div"
+            | "br"
+            | "li"
+            | "tr"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "pre"
+            | "blockquote"
+            | "section"
+            | "article"
+            | "header"
+            | "footer"
+            | "table"
+            | "ul"
+            | "ol"
+            | "hr"
+            | "dt"
+            | "dd"
+    )
+}
+
+fn extract_html(_registry: &Registry, bytes: &[u8], _path: &Path) -> Result<String, String> {
+    Ok(html_to_text(&String::from_utf8_lossy(bytes)))
+}
+
+/// Visible text of an HTML page: tags dropped, scripts and styles skipped,
+/// block elements on their own lines, cells tab-separated, common entities decoded.
+pub fn html_to_text(html: &str) -> String {
+    let mut out = String::new();
+    let mut rest = html;
+    while let Some(lt) = rest.find('<') {
+        out.push_str(&decode_entities(&rest[..lt]));
+        rest = &rest[lt..];
+
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = after.find("-->").map_or("", |end| &after[end + 3..]);
+            continue;
+        }
+        let Some(gt) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[1..gt];
+        rest = &rest[gt + 1..];
+
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        if !closing && (name == "script" || name == "style") {
+            let end = format!("</{}", name);
+            let lower = rest.to_ascii_lowercase();
+            rest = lower.find(&end).map_or("", |i| &rest[i..]);
+            continue;
+        }
+        if name == "td" || name == "th" {
+            if closing {
+                out.push('\t');
+            }
+        } else if is_block(&name) && !out.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    out.push_str(&decode_entities(rest));
+    tidy_lines(&out)
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let ch = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" | "#39" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                    .and_then(char::from_u32),
+            }?;
+            Some((ch, end))
+        });
+        match decoded {
+            Some((ch, end)) => {
+                out.push(ch);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Collapse runs of spaces, trim each line and drop repeated blank lines.
+fn tidy_lines(text: &str) -> String {
+    let mut out = String::new();
+    let mut blank = true;
+    for line in text.lines() {
+        let line = line
+            .split(' ')
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let line = line.trim_matches(|c: char| c == ' ' || c == '\r');
+        if line.trim().is_empty() {
+            if !blank {
+                out.push('\n');
+            }
+            blank = true;
+            continue;
+        }
+        out.push_str(line.trim_end_matches('\t'));
+        out.push('\n');
+        blank = false;
+    }
+    out.trim_end().to_string()
+}
+
+// ─── Jupyter notebooks ───────────────────────────────────
+
+/// Each cell under its own heading: Markdown as-is, code fenced in the kernel's
+/// language, followed by its text output (clipped).
+fn extract_notebook(_registry: &Registry, bytes: &[u8], _path: &Path) -> Result<String, String> {
+    let nb: Value =
+        serde_json::from_slice(bytes).map_err(|e| format!("Invalid notebook JSON: {}", e))?;
+    let cells = nb["cells"]
+        .as_array()
+        .ok_or("Notebook has no cells (nbformat 4 expected)")?;
+    let language = nb["metadata"]["kernelspec"]["language"]
+        .as_str()
+        .or_else(|| nb["metadata"]["language_info"]["name"].as_str())
+        .unwrap_or("");
+
+    let mut out = String::new();
+    for (i, cell) in cells.iter().enumerate() {
+        let kind = cell["cell_type"].as_str().unwrap_or("raw");
+        let source = multiline(&cell["source"]);
+        out.push_str(&format!("## Cell {} ({})\n\n", i + 1, kind));
+        if kind == "code" {
+            out.push_str(&format!("```{}\n{}\n```\n\n", language, source.trim_end()));
+            let outputs = cell["outputs"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+            for output in outputs {
+                let text = match output["output_type"].as_str() {
+                    Some("stream") => multiline(&output["text"]),
+                    Some("execute_result") | Some("display_data") => {
+                        multiline(&output["data"]["text/plain"])
+                    }
+                    Some("error") => format!(
+                        "{}: {}",
+                        output["ename"].as_str().unwrap_or("Error"),
+                        output["evalue"].as_str().unwrap_or("")
+                    ),
+                    _ => String::new(),
+                };
+                if text.trim().is_empty() {
+                    continue;
+                }
+                let clipped: String = text.chars().take(MAX_OUTPUT_CHARS).collect();
+                out.push_str(&format!("Output:\n```\n{}\n```\n\n", clipped.trim_end()));
+            }
+        } else {
+            out.push_str(source.trim_end());
+            out.push_str("\n\n");
+        }
+    }
+    Ok(out.trim_end().to_string())
+}
+
+/// Notebook text fields are either a string or a list of lines.
+fn multiline(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(lines) => lines.iter().filter_map(|l| l.as_str()).collect(),
+        _ => String::new(),
+    }
+}
+
+// ─── Zip-based documents ─────────────────────────────────
+
+type Archive<'a> = zip::ZipArchive<Cursor<&'a [u8]>>;
+
+fn read_entry(archive: &mut Archive, name: &str) -> Result<Option<String>, String> {
+    let entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(format!("Cannot read {}: {}", name, e)),
+    };
+    let mut text = String::new();
+    entry
+        .take(MAX_INFLATED_BYTES)
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Cannot read {}: {}", name, e))?;
+    Ok(Some(text))
+}
+
+fn require_entry(archive: &mut Archive, name: &str) -> Result<String, String> {
+    read_entry(archive, name)?.ok_or_else(|| format!("Document is missing {}", name))
+}
+
+fn parse_xml<'a>(text: &'a str, name: &str) -> Result<Xml<'a>, String> {
+    Xml::parse(text).map_err(|e| format!("Malformed {}: {}", name, e))
+}
+
+/// Office Open XML, OpenDocument or EPUB — told apart by what the archive holds.
+fn extract_zip_document(
+    _registry: &Registry,
+    bytes: &[u8],
+    _path: &Path,
+) -> Result<String, String> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .map_err(|e| format!("Not a readable zip document: {}", e))?;
+    let has = |archive: &Archive, name: &str| archive.index_for_name(name).is_some();
+
+    if has(&archive, "word/document.xml") {
+        extract_docx(&mut archive)
+    } else if has(&archive, "xl/workbook.xml") {
+        extract_xlsx(&mut archive)
+    } else if has(&archive, "ppt/presentation.xml") {
+        extract_pptx(&mut archive)
+    } else if has(&archive, "META-INF/container.xml") {
+        extract_epub(&mut archive)
+    } else if has(&archive, "content.xml") {
+        extract_opendocument(&mut archive)
+    } else {
+        Err("Unsupported zip document (expected docx, xlsx, pptx, odt/ods/odp or epub)".to_string())
+    }
+}
+
+/// Element names that make up a word-processing document in one XML dialect.
+struct Flow {
+    paragraphs: &'static [&'static str],
+    text: &'static str,
+    tab: &'static str,
+    line_break: &'static str,
+    /// OpenDocument's `<text:s text:c="3"/>` run of spaces
+    spaces: Option<&'static str>,
+    table: &'static str,
+    row: &'static str,
+    cell: &'static str,
+}
+
+const WORD_FLOW: Flow = Flow {
+    paragraphs: &["p"],
+    text: "t",
+    tab: "tab",
+    line_break: "br",
+    spaces: None,
+    table: "tbl",
+    row: "tr",
+    cell: "tc",
+};
+
+const OPENDOCUMENT_FLOW: Flow = Flow {
+    paragraphs: &["p", "h"],
+    text: "",
+    tab: "tab",
+    line_break: "line-break",
+    spaces: Some("s"),
+    table: "table",
+    row: "table-row",
+    cell: "table-cell",
+};
+
+/// Paragraphs one per line and tables as Markdown, in document order.
+fn flow_text(node: Node, flow: &Flow, out: &mut String) {
+    for child in node.children().filter(Node::is_element) {
+        let name = child.tag_name().name();
+        if name == flow.table {
+            let rows: Vec<Vec<String>> = child
+                .descendants()
+                .filter(|n| n.tag_name().name() == flow.row)
+                .map(|row| {
+                    row.children()
+                        .filter(|c| c.tag_name().name() == flow.cell)
+                        .map(|cell| {
+                            let mut text = String::new();
+                            flow_text(cell, flow, &mut text);
+                            text.trim().replace('\n', " ")
+                        })
+                        .collect()
+                })
+                .collect();
+            out.push_str(&markdown_table(&rows));
+        } else if flow.paragraphs.contains(&name) {
+            inline_text(child, flow, out);
+            out.push('\n');
+        } else {
+            flow_text(child, flow, out);
+        }
+    }
+}
+
+fn inline_text(node: Node, flow: &Flow, out: &mut String) {
+    for child in node.children() {
+        if child.is_text() && flow.text.is_empty() {
+            out.push_str(child.text().unwrap_or(""));
+            continue;
+        }
+        if !child.is_element() {
+            continue;
+        }
+        let name = child.tag_name().name();
+        if !flow.text.is_empty() && name == flow.text {
+            out.push_str(child.text().unwrap_or(""));
+        } else if name == flow.tab {
+            out.push('\t');
+        } else if name == flow.line_break {
+            out.push('\n');
+        } else if Some(name) == flow.spaces {
+            let count = child
+                .attributes()
+                .find(|a| a.name() == "c")
+                .and_then(|a| a.value().parse().ok())
+                .unwrap_or(1usize);
+            out.push_str(&" ".repeat(count.min(MAX_SPACE_RUN)));
+        } else {
+            inline_text(child, flow, out);
+        }
+    }
+}
+
+/// Rows as a Markdown table, the first row as header.
+fn markdown_table(rows: &[Vec<String>]) -> String {
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if width == 0 {
+        return String::new();
+    }
+    let line = |cells: &[String]| {
+        let mut line = String::from("|");
+        for i in 0..width {
+            let cell = cells.get(i).map(String::as_str).unwrap_or("");
+            line.push_str(&format!(" {} |", cell.replace('|', "\\|")));
+        }
+        line.push('\n');
+        line
+    };
+    let mut out = String::from("\n");
+    out.push_str(&line(&rows[0]));
+    out.push_str(&format!("|{}\n", " --- |".repeat(width)));
+    for row in &rows[1..] {
+        out.push_str(&line(row));
+    }
+    out.push('\n');
+    out
+}
+
+fn extract_docx(archive: &mut Archive) -> Result<String, String> {
+    let xml = require_entry(archive, "word/document.xml")?;
+    let doc = parse_xml(&xml, "word/document.xml")?;
+    let mut out = String::new();
+    flow_text(doc.root_element(), &WORD_FLOW, &mut out);
+    Ok(tidy_blank_lines(&out))
+}
+
+/// OpenDocument text, spreadsheets and presentations all keep their body in content.xml.
+fn extract_opendocument(archive: &mut Archive) -> Result<String, String> {
+    let xml = require_entry(archive, "content.xml")?;
+    let doc = parse_xml(&xml, "content.xml")?;
+    let mut out = String::new();
+    flow_text(doc.root_element(), &OPENDOCUMENT_FLOW, &mut out);
+    Ok(tidy_blank_lines(&out))
+}
+
+/// Squeeze runs of blank lines down to one.
+fn tidy_blank_lines(text: &str) -> String {
+    let mut out = String::new();
+    let mut blanks = 0;
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            blanks += 1;
+            if blanks > 1 {
+                continue;
+            }
+        } else {
+            blanks = 0;
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out.trim().to_string()
+}
+
+/// Zero-based column of a cell reference like "AB12", or `None` past XFD.
+fn column_index(cell_ref: &str) -> Option<usize> {
+    let column = cell_ref
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .try_fold(0usize, |acc, c| {
+            acc.checked_mul(26)?
+                .checked_add(c.to_ascii_uppercase() as usize - 'A' as usize + 1)
+        })?;
+    (column <= MAX_COLUMNS).then(|| column.saturating_sub(1))
+}
+
+/// Every sheet as a TSV block under a `## Sheet: name` heading.
+fn extract_xlsx(archive: &mut Archive) -> Result<String, String> {
+    let shared: Vec<String> = match read_entry(archive, "xl/sharedStrings.xml")? {
+        Some(xml) => {
+            let doc = parse_xml(&xml, "xl/sharedStrings.xml")?;
+            doc.root_element()
+                .children()
+                .filter(|n| n.tag_name().name() == "si")
+                .map(|si| {
+                    si.descendants()
+                        .filter(|n| n.tag_name().name() == "t")
+                        .filter_map(|t| t.text())
+                        .collect()
+                })
+                .collect()
+        }
+        None => Vec::new(),
+    };
+
+    let rels_xml = require_entry(archive, "xl/_rels/workbook.xml.rels")?;
+    let rels = parse_xml(&rels_xml, "xl/_rels/workbook.xml.rels")?;
+    let target_of = |id: &str| {
+        rels.descendants()
+            .find(|n| n.attribute("Id") == Some(id))
+            .and_then(|n| n.attribute("Target"))
+            .map(|t| match t.strip_prefix('/') {
+                Some(absolute) => absolute.to_string(),
+                None => format!("xl/{}", t),
+            })
+    };
+
+    let workbook_xml = require_entry(archive, "xl/workbook.xml")?;
+    let workbook = parse_xml(&workbook_xml, "xl/workbook.xml")?;
+    let sheets: Vec<(String, String)> = workbook
+        .descendants()
+        .filter(|n| n.tag_name().name() == "sheet")
+        .filter_map(|n| {
+            let id = n.attributes().find(|a| a.name() == "id")?.value();
+            Some((n.attribute("name")?.to_string(), target_of(id)?))
+        })
+        .collect();
+
+    let mut out = String::new();
+    for (name, target) in sheets {
+        let Some(xml) = read_entry(archive, &target)? else {
+            continue;
+        };
+        let sheet = parse_xml(&xml, &target)?;
+        out.push_str(&format!("## Sheet: {}\n\n", name));
+        for row in sheet.descendants().filter(|n| n.tag_name().name() == "row") {
+            let mut cells: Vec<String> = Vec::new();
+            for cell in row.children().filter(|n| n.tag_name().name() == "c") {
+                // A reference past the last column is malformed; skip the cell
+                let Some(column) = cell.attribute("r").map_or(Some(cells.len()), column_index)
+                else {
+                    continue;
+                };
+                let value = cell
+                    .children()
+                    .find(|n| n.tag_name().name() == "v")
+                    .and_then(|v| v.text())
+                    .unwrap_or("");
+                let text = match cell.attribute("t") {
+                    Some("s") => value
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| shared.get(i).cloned())
+                        .unwrap_or_default(),
+                    Some("inlineStr") => cell
+                        .descendants()
+                        .filter(|n| n.tag_name().name() == "t")
+                        .filter_map(|t| t.text())
+                        .collect(),
+                    Some("b") => (if value == "1" { "TRUE" } else { "FALSE" }).to_string(),
+                    _ => value.to_string(),
+                };
+                if cells.len() <= column {
+                    cells.resize(column + 1, String::new());
+                }
+                cells[column] = text.replace(['\t', '\n'], " ");
+            }
+            out.push_str(&cells.join("\t"));
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    Ok(out.trim_end().to_string())
+}
+
+/// Slide text in slide order, each under a `## Slide n` heading.
+fn extract_pptx(archive: &mut Archive) -> Result<String, String> {
+    let mut slides: Vec<(u32, String)> = archive
+        .file_names()
+        .filter_map(|name| {
+            let number = name
+                .strip_prefix("ppt/slides/slide")?
+                .strip_suffix(".xml")?
+                .parse()
+                .ok()?;
+            Some((number, name.to_string()))
+        })
+        .collect();
+    slides.sort();
+
+    let mut out = String::new();
+    for (number, name) in slides {
+        let xml = require_entry(archive, &name)?;
+        let slide = parse_xml(&xml, &name)?;
+        out.push_str(&format!("## Slide {}\n\n", number));
+        for paragraph in slide.descendants().filter(|n| n.tag_name().name() == "p") {
+            let text: String = paragraph
+                .descendants()
+                .filter(|n| n.tag_name().name() == "t")
+                .filter_map(|t| t.text())
+                .collect();
+            if !text.trim().is_empty() {
+                out.push_str(text.trim_end());
+                out.push('\n');
+            }
+        }
+        out.push('\n');
+    }
+    Ok(out.trim_end().to_string())
+}
+
+/// Chapters in reading (spine) order, converted from XHTML.
+fn extract_epub(archive: &mut Archive) -> Result<String, String> {
+    let container_xml = require_entry(archive, "META-INF/container.xml")?;
+    let container = parse_xml(&container_xml, "META-INF/container.xml")?;
+    let opf_path = container
+        .descendants()
+        .find(|n| n.tag_name().name() == "rootfile")
+        .and_then(|n| n.attribute("full-path"))
+        .ok_or("EPUB container names no package file")?
+        .to_string();
+    let base = match opf_path.rfind('/') {
+        Some(slash) => &opf_path[..=slash],
+        None => "",
+    };
+
+    let opf_xml = require_entry(archive, &opf_path)?;
+    let opf = parse_xml(&opf_xml, &opf_path)?;
+    let href_of = |id: &str| {
+        opf.descendants()
+            .find(|n| n.tag_name().name() == "item" && n.attribute("id") == Some(id))
+            .and_then(|n| n.attribute("href"))
+    };
+    let chapters: Vec<String> = opf
+        .descendants()
+        .filter(|n| n.tag_name().name() == "itemref")
+        .filter_map(|n| href_of(n.attribute("idref")?))
+        .map(|href| format!("{}{}", base, href))
+        .collect();
+
+    let mut out = String::new();
+    for chapter in chapters {
+        if let Some(xhtml) = read_entry(archive, &chapter)? {
+            out.push_str(&html_to_text(&xhtml));
+            out.push_str("\n\n");
+        }
+    }
+    Ok(out.trim_end().to_string())
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(bytes: &[u8], name: &str) -> String {
+        registry()
+            .extract_bytes(bytes, Path::new(name))
+            .unwrap_or_else(|e| panic!("{}: {}", name, e))
+    }
+
+    #[test]
+    fn docx_paragraphs_and_tables() {
+        let text = extract(
+            include_bytes!("../tests/fixtures/sample.docx"),
+            "sample.docx",
+        );
+        assert_eq!(
+            text,
+            "Quarterly Report\nRevenue\tgrew 12%.\n\n| Region | Sales |\n| --- | --- |\n| North | 1200 |\n| South | 950 |\n\nEnd of report."
+        );
+    }
+
+    #[test]
+    fn odt_paragraphs_spaces_and_tables() {
+        let text = extract(include_bytes!("../tests/fixtures/sample.odt"), "sample.odt");
+        assert_eq!(
+            text,
+            "Meeting Notes\nAttendees:   Ann, Bo\n\n| Task | Owner |\n| --- | --- |\n| Budget | Ann |"
+        );
+    }
+
+    #[test]
+    fn xlsx_sheets_as_tsv() {
+        let text = extract(
+            include_bytes!("../tests/fixtures/sample.xlsx"),
+            "sample.xlsx",
+        );
+        assert_eq!(
+            text,
+            "## Sheet: Sales\n\nItem\tQty\tPaid\nWidget\t3\tTRUE\nGadget\t\t7.5\n\n## Sheet: Notes\n\ninline note"
+        );
+    }
+
+    #[test]
+    fn pptx_slides_in_order() {
+        let text = extract(
+            include_bytes!("../tests/fixtures/sample.pptx"),
+            "sample.pptx",
+        );
+        assert_eq!(
+            text,
+            "## Slide 1\n\nWelcome\nAgenda for today\n\n## Slide 2\n\nQuestions?"
+        );
+    }
+
+    #[test]
+    fn epub_chapters_in_spine_order() {
+        let text = extract(
+            include_bytes!("../tests/fixtures/sample.epub"),
+            "sample.epub",
+        );
+        assert_eq!(
+            text,
+            "Chapter One\nIt was a dark & stormy night.\n\nChapter Two\nThe end."
+        );
+    }
+
+    #[test]
+    fn notebook_cells_and_outputs() {
+        let text = extract(
+            include_bytes!("../tests/fixtures/sample.ipynb"),
+            "sample.ipynb",
+        );
+        assert_eq!(
+            text,
+            "## Cell 1 (markdown)\n\n# Analysis\n\n## Cell 2 (code)\n\n```python\nx = 2\nprint(x * 21)\n```\n\nOutput:\n```\n42\n```"
+        );
+    }
+
+    #[test]
+    fn gzip_uses_the_inner_format() {
+        let text = extract(include_bytes!("../tests/fixtures/app.log.gz"), "app.log.gz");
+        assert_eq!(
+            text,
+            "2024-01-01 INFO started\n2024-01-01 ERROR disk full\n"
+        );
+    }
+
+    #[test]
+    fn gzip_hands_back_to_its_own_registry() {
+        fn shout(_: &Registry, bytes: &[u8], _: &Path) -> Result<String, String> {
+            Ok(String::from_utf8_lossy(bytes).to_uppercase())
+        }
+        let mut custom = Registry::with_defaults();
+        custom.register(Extractor {
+            name: "shout",
+            extensions: &["log"],
+            magic: b"",
+            extract: shout,
+        });
+        let text = custom
+            .extract_bytes(
+                include_bytes!("../tests/fixtures/app.log.gz"),
+                Path::new("app.log.gz"),
+            )
+            .unwrap();
+        assert!(text.starts_with("2024-01-01 INFO STARTED"));
+    }
+
+    #[test]
+    fn nested_gzip_is_unwrapped_a_few_layers_deep() {
+        fn gzip(bytes: &[u8]) -> Vec<u8> {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+            std::io::Write::write_all(&mut encoder, bytes).unwrap();
+            encoder.finish().unwrap()
+        }
+        let mut bytes = gzip(b"inner text");
+        for _ in 1..MAX_GZIP_LAYERS {
+            bytes = gzip(&bytes);
+        }
+        assert_eq!(extract(&bytes, "rotated"), "inner text");
+
+        let too_deep = gzip(&bytes);
+        let err = registry()
+            .extract_bytes(&too_deep, Path::new("rotated"))
+            .unwrap_err();
+        assert!(err.contains("nested gzip"), "{}", err);
+    }
+
+    #[test]
+    fn odt_space_runs_are_capped() {
+        let xml = r#"<p xmlns:text="urn:t"><text:s text:c="2000000000"/>x</p>"#;
+        let doc = Xml::parse(xml).unwrap();
+        let mut out = String::new();
+        inline_text(doc.root_element(), &OPENDOCUMENT_FLOW, &mut out);
+        assert_eq!(out.len(), MAX_SPACE_RUN + 1);
+    }
+
+    #[test]
+    fn html_visible_text() {
+        let html = "<html><head><title>T</title><style>p{color:red}</style>\
+                    <script>var a = '<p>';</script></head><body>\
+                    <h1>Title</h1><p>Fish &amp; chips &#8212; &lt;cheap&gt;</p><!-- hidden -->\
+                    <table><tr><td>a</td><td>b</td></tr></table></body></html>";
+        assert_eq!(
+            extract(html.as_bytes(), "page.html"),
+            "T\nTitle\nFish & chips — <cheap>\na\tb"
+        );
+    }
+
+    #[test]
+    fn magic_bytes_beat_a_missing_extension() {
+        let docx = include_bytes!("../tests/fixtures/sample.docx");
+        assert!(extract(docx, "REPORT").starts_with("Quarterly Report"));
+        let gz = include_bytes!("../tests/fixtures/app.log.gz");
+        assert!(extract(gz, "rotated").contains("disk full"));
+        assert_eq!(extract(b"plain words", "README"), "plain words");
+        assert!(registry()
+            .extract_bytes(&[0xff, 0xfe, 0x00], Path::new("blob.bin"))
+            .is_err());
+    }
+
+    #[test]
+    fn column_letters() {
+        assert_eq!(column_index("A1"), Some(0));
+        assert_eq!(column_index("Z9"), Some(25));
+        assert_eq!(column_index("AA10"), Some(26));
+        assert_eq!(column_index("AB3"), Some(27));
+        assert_eq!(column_index("XFD1"), Some(16_383));
+        assert_eq!(column_index("XFE1"), None);
+        assert_eq!(column_index("ZZZZZZZZZZZZZZZZ1"), None);
+    }
+}
@@ -0,0 +1,243 @@
+/// Launch history used to rank results by what the user actually opens.
+///
+/// Every `open_result` records a visit for the entry, plus one for each prefix of
+/// the query that found it, so typing "fi" again surfaces what was opened from
+/// "fi", "fir" or "firefox". Scores decay exponentially with a fixed half-life, so
+/// a burst of launches last year counts for less than a few launches this week.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::json_store::{self, JsonStore};
+
+/// A visit loses half its weight after this many seconds (two weeks).
+const HALF_LIFE_SECS: f64 = 14.0 * 86_400.0;
+
+/// Only the first this-many characters of a query are remembered as prefixes.
+const MAX_PREFIX_CHARS: usize = 12;
+
+/// Entries remembered per query prefix; the weakest are dropped first.
+const MAX_PER_PREFIX: usize = 16;
+
+/// Decayed scores below this are forgotten when the store is saved.
+const MIN_SCORE: f64 = 0.01;
+
+/// Decaying launch counter.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Visits {
+    /// Score as of `last`.
+    score: f64,
+    /// Unix timestamp of the most recent launch.
+    last: u64,
+}
+
+impl Visits {
+    /// The score decayed to `now`.
+    pub fn at(&self, now: u64) -> f64 {
+        let elapsed = now.saturating_sub(self.last) as f64;
+        self.score * 0.5f64.powf(elapsed / HALF_LIFE_SECS)
+    }
+
+    fn bump(&mut self, now: u64) {
+        self.score = self.at(now) + 1.0;
+        self.last = self.last.max(now);
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FrecencyStore {
+    version: u32,
+    /// path → launches from any query
+    entries: HashMap<String, Visits>,
+    /// lowercased query prefix → path → launches from that prefix
+    prefixes: HashMap<String, HashMap<String, Visits>>,
+}
+
+pub type SharedFrecency = Arc<RwLock<FrecencyStore>>;
+
+impl JsonStore for FrecencyStore {
+    const VERSION: u32 = 1;
+    const NAME: &'static str = "launch history";
+
+    /// Lives in the data dir: unlike the index caches it cannot be rebuilt from
+    /// the filesystem.
+    fn path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("trace")
+            .join("frecency.json")
+    }
+
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+/// Normalise a query into the key used for prefix lookups.
+pub fn query_key(query: &str) -> String {
+    query
+        .trim()
+        .to_lowercase()
+        .chars()
+        .take(MAX_PREFIX_CHARS)
+        .collect()
+}
+
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl FrecencyStore {
+    pub fn new() -> Self {
+        Self {
+            version: Self::VERSION,
+            ..Self::default()
+        }
+    }
+
+    /// Load the store from disk, or start empty if it is missing or outdated.
+    pub fn load() -> Self {
+        json_store::load().unwrap_or_else(Self::new)
+    }
+
+    /// Record that `path` was opened from a search for `query`.
+    pub fn record(&mut self, path: &str, query: &str, now: u64) {
+        self.entries.entry(path.to_string()).or_default().bump(now);
+
+        let key = query_key(query);
+        if key.is_empty() {
+            return;
+        }
+        for (end, _) in key.char_indices().skip(1).chain([(key.len(), ' ')]) {
+            let per_prefix = self.prefixes.entry(key[..end].to_string()).or_default();
+            per_prefix.entry(path.to_string()).or_default().bump(now);
+
+            if per_prefix.len() > MAX_PER_PREFIX {
+                if let Some(weakest) = per_prefix
+                    .iter()
+                    .filter(|(p, _)| p.as_str() != path)
+                    .min_by(|a, b| a.1.at(now).total_cmp(&b.1.at(now)))
+                    .map(|(p, _)| p.clone())
+                {
+                    per_prefix.remove(&weakest);
+                }
+            }
+        }
+    }
+
+    /// Decayed launch score of `path` from any query.
+    pub fn entry_score(&self, path: &str, now: u64) -> f64 {
+        self.entries.get(path).map_or(0.0, |v| v.at(now))
+    }
+
+    /// Launches of each path from exactly this query prefix (see `query_key`).
+    pub fn prefix_scores(&self, key: &str) -> Option<&HashMap<String, Visits>> {
+        self.prefixes.get(key)
+    }
+
+    /// Decayed launch score of `path` within a map from `prefix_scores`.
+    pub fn score_in(scores: Option<&HashMap<String, Visits>>, path: &str, now: u64) -> f64 {
+        scores.and_then(|s| s.get(path)).map_or(0.0, |v| v.at(now))
+    }
+
+    /// Forget everything learned about `path`.
+    pub fn forget(&mut self, path: &str) {
+        self.entries.remove(path);
+        for per_prefix in self.prefixes.values_mut() {
+            per_prefix.remove(path);
+        }
+        self.prefixes.retain(|_, m| !m.is_empty());
+    }
+
+    /// Forget all launch history.
+    pub fn reset(&mut self) {
+        self.entries.clear();
+        self.prefixes.clear();
+    }
+
+    fn prune(&mut self, now: u64) {
+        self.entries.retain(|_, v| v.at(now) >= MIN_SCORE);
+        for per_prefix in self.prefixes.values_mut() {
+            per_prefix.retain(|_, v| v.at(now) >= MIN_SCORE);
+        }
+        self.prefixes.retain(|_, m| !m.is_empty());
+    }
+}
+
+/// Save the shared store from a worker thread, dropping anything that has decayed away.
+pub async fn persist(store: &SharedFrecency) {
+    store.write().await.prune(now());
+    json_store::persist(store).await;
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T0: u64 = 1_700_000_000;
+    const WEEK: u64 = 7 * 86_400;
+
+    #[test]
+    fn scores_decay_with_half_life() {
+        let mut store = FrecencyStore::new();
+        store.record("/a", "a", T0);
+        store.record("/a", "a", T0);
+
+        assert!((store.entry_score("/a", T0) - 2.0).abs() < 1e-9);
+        assert!((store.entry_score("/a", T0 + 2 * WEEK) - 1.0).abs() < 1e-9);
+        assert_eq!(store.entry_score("/b", T0), 0.0);
+    }
+
+    #[test]
+    fn records_every_query_prefix() {
+        let mut store = FrecencyStore::new();
+        store.record("/usr/share/applications/firefox.desktop", "  FireFox ", T0);
+
+        for key in ["f", "fi", "fire", "firefox"] {
+            let scores = store.prefix_scores(key);
+            let score =
+                FrecencyStore::score_in(scores, "/usr/share/applications/firefox.desktop", T0);
+            assert!(score > 0.0, "prefix {:?}", key);
+        }
+        assert!(store.prefix_scores("firefox ").is_none());
+        assert!(store.prefix_scores("x").is_none());
+    }
+
+    #[test]
+    fn prefix_keeps_strongest_entries() {
+        let mut store = FrecencyStore::new();
+        for i in 0..MAX_PER_PREFIX {
+            store.record(&format!("/{}", i), "q", T0 + i as u64);
+            store.record(&format!("/{}", i), "q", T0 + i as u64);
+        }
+        store.record("/new", "q", T0 + 100);
+
+        let scores = store.prefix_scores("q").unwrap();
+        assert_eq!(scores.len(), MAX_PER_PREFIX);
+        assert!(scores.contains_key("/new"));
+        assert!(!scores.contains_key("/0"));
+    }
+
+    #[test]
+    fn forget_and_reset() {
+        let mut store = FrecencyStore::new();
+        store.record("/a", "abc", T0);
+        store.record("/b", "abc", T0);
+
+        store.forget("/a");
+        assert_eq!(store.entry_score("/a", T0), 0.0);
+        assert_eq!(FrecencyStore::score_in(store.prefix_scores("ab"), "/a", T0), 0.0);
+        assert!(FrecencyStore::score_in(store.prefix_scores("ab"), "/b", T0) > 0.0);
+
+        store.reset();
+        assert_eq!(store.entry_score("/b", T0), 0.0);
+        assert!(store.prefix_scores("a").is_none());
+    }
+}
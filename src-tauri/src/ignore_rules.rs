@@ -0,0 +1,331 @@
+/// Gitignore-style filtering shared by the indexer and the filesystem watcher.
+///
+/// A path is checked against, in decreasing precedence:
+///   1. the per-root include/exclude globs from `Settings::index_filters`
+///   2. the built-in dotfile rule (hidden files and directories are skipped)
+///   3. `.gitignore` / `.ignore` files from the root down to the path (deeper wins)
+///   4. the global Trace ignore file (`~/.config/trace/ignore`)
+use crate::settings::IndexFilter;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::Match;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Per-directory ignore files, in increasing precedence.
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// Written to the global ignore file the first time Trace runs.
+const DEFAULT_GLOBAL_IGNORE: &str = "\
+# Trace ignore file — gitignore syntax, applied to every indexed root.
+# Changes take effect the next time Trace starts.
+node_modules/
+__pycache__/
+";
+
+struct RootRules {
+    root: PathBuf,
+    overrides: Override,
+}
+
+pub struct IgnoreRules {
+    roots: Vec<RootRules>,
+    global: Gitignore,
+    fingerprint: u64,
+    /// Lazily built `.gitignore`/`.ignore` matchers per directory (`None` = no files).
+    dir_rules: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl IgnoreRules {
+    /// Build the rules for `roots`; `filters[i]` holds the globs for `roots[i]`.
+    pub fn new(roots: &[PathBuf], filters: &[IndexFilter]) -> Self {
+        let global_path = Self::global_ignore_path();
+        if !global_path.exists() {
+            if let Some(parent) = global_path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            let _ = std::fs::write(&global_path, DEFAULT_GLOBAL_IGNORE);
+        }
+
+        let global_text = std::fs::read_to_string(&global_path).unwrap_or_default();
+        Self::with_global(roots, filters, &global_text, &global_path)
+    }
+
+    /// Build the rules with `global_text` as the contents of the global ignore file.
+    pub fn with_global(
+        roots: &[PathBuf],
+        filters: &[IndexFilter],
+        global_text: &str,
+        global_path: &Path,
+    ) -> Self {
+        let mut builder = GitignoreBuilder::new("");
+        for line in global_text.lines() {
+            if let Err(e) = builder.add_line(Some(global_path.to_path_buf()), line) {
+                eprintln!("[trace][ignore] Bad pattern in {:?}: {}", global_path, e);
+            }
+        }
+        let global = builder.build().unwrap_or_else(|e| {
+            eprintln!("[trace][ignore] Failed to load {:?}: {}", global_path, e);
+            Gitignore::empty()
+        });
+
+        let mut hasher = DefaultHasher::new();
+        global_text.hash(&mut hasher);
+
+        let roots = roots
+            .iter()
+            .enumerate()
+            .map(|(i, root)| {
+                let filter = filters.get(i).cloned().unwrap_or_default();
+                root.hash(&mut hasher);
+                filter.include.hash(&mut hasher);
+                filter.exclude.hash(&mut hasher);
+                RootRules {
+                    root: root.clone(),
+                    overrides: build_overrides(root, &filter),
+                }
+            })
+            .collect();
+
+        Self {
+            roots,
+            global,
+            fingerprint: hasher.finish(),
+            dir_rules: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Path to the user's global Trace ignore file.
+    pub fn global_ignore_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("trace")
+            .join("ignore")
+    }
+
+    /// Hash of the global ignore file and per-root globs. A cached index built under
+    /// different rules must be rescanned rather than reused.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Returns true if `path` lies under one of the roots these rules were built for.
+    pub fn covers(&self, path: &Path) -> bool {
+        self.roots.iter().any(|r| path.starts_with(&r.root))
+    }
+
+    /// Returns true if `path` should be left out of the index.
+    /// Paths outside every root (and the roots themselves) are never ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(rules) = self
+            .roots
+            .iter()
+            .filter(|r| path.starts_with(&r.root))
+            .max_by_key(|r| r.root.components().count())
+        else {
+            return false;
+        };
+        let rel = match path.strip_prefix(&rules.root) {
+            Ok(rel) if !rel.as_os_str().is_empty() => rel,
+            _ => return false,
+        };
+
+        // Explicit per-root globs beat everything else
+        match rules.overrides.matched(rel, is_dir) {
+            Match::Whitelist(_) => return false,
+            Match::Ignore(_) => return true,
+            Match::None => {}
+        }
+
+        // Dotfiles and anything inside a dot-directory
+        if rel
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        {
+            return true;
+        }
+
+        // Walk from the path's parent up to the root; the deepest decision wins
+        let mut dir = path.parent();
+        while let Some(d) = dir {
+            if !d.starts_with(&rules.root) {
+                break;
+            }
+            if let Some(gi) = self.dir_matcher(d) {
+                match gi.matched_path_or_any_parents(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+            dir = d.parent();
+        }
+
+        self.global
+            .matched_path_or_any_parents(rel, is_dir)
+            .is_ignore()
+    }
+
+    /// Returns true if `path` is one of the per-directory ignore files.
+    pub fn is_ignore_file(path: &Path) -> bool {
+        path.file_name()
+            .is_some_and(|n| IGNORE_FILES.iter().any(|f| n == *f))
+    }
+
+    /// Forget the cached matcher for `dir` after one of its ignore files changed.
+    pub fn invalidate(&self, dir: &Path) {
+        if let Ok(mut cache) = self.dir_rules.lock() {
+            cache.remove(dir);
+        }
+    }
+
+    /// Load (or fetch from cache) the combined ignore-file matcher for `dir`.
+    fn dir_matcher(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        if let Some(cached) = self.dir_rules.lock().ok()?.get(dir) {
+            return cached.clone();
+        }
+
+        let files: Vec<PathBuf> = IGNORE_FILES
+            .iter()
+            .map(|f| dir.join(f))
+            .filter(|p| p.is_file())
+            .collect();
+
+        let matcher = if files.is_empty() {
+            None
+        } else {
+            let mut builder = GitignoreBuilder::new(dir);
+            for file in &files {
+                if let Some(e) = builder.add(file) {
+                    eprintln!("[trace][ignore] Problem reading {:?}: {}", file, e);
+                }
+            }
+            builder.build().ok().map(Arc::new)
+        };
+
+        if let Ok(mut cache) = self.dir_rules.lock() {
+            cache.insert(dir.to_path_buf(), matcher.clone());
+        }
+        matcher
+    }
+}
+
+/// Compile a root's include/exclude globs into an override matcher.
+/// Includes whitelist paths (and restrict files to those matching); excludes ignore them.
+fn build_overrides(root: &Path, filter: &IndexFilter) -> Override {
+    let mut builder = OverrideBuilder::new(root);
+    let globs = filter
+        .include
+        .iter()
+        .cloned()
+        .chain(filter.exclude.iter().map(|g| format!("!{}", g)));
+
+    for glob in globs {
+        if let Err(e) = builder.add(&glob) {
+            eprintln!("[trace][ignore] Invalid glob '{}' for {:?}: {}", glob, root, e);
+        }
+    }
+
+    builder.build().unwrap_or_else(|e| {
+        eprintln!("[trace][ignore] Failed to build globs for {:?}: {}", root, e);
+        Override::empty()
+    })
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("trace-ignore-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, text: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+
+    fn filter(include: &[&str], exclude: &[&str]) -> IndexFilter {
+        IndexFilter {
+            include: include.iter().map(|g| g.to_string()).collect(),
+            exclude: exclude.iter().map(|g| g.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn layers_override_each_other_in_order() {
+        let dir = temp_dir("layers");
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        write(&a.join(".gitignore"), "*.log\n!keep.log\nbuild/\n");
+        write(&a.join("sub/.gitignore"), "!debug.log\n");
+        write(&b.join(".gitignore"), "*.tmp\n");
+        let rules = IgnoreRules::with_global(
+            &[a.clone(), b.clone()],
+            &[
+                filter(&[], &["secret.txt"]),
+                filter(&[".env", "*.tmp"], &[]),
+            ],
+            "*.tmp\nkeep.log\nnode_modules/\n",
+            &dir.join("ignore"),
+        );
+
+        let cases = [
+            // Nothing applies
+            (a.join("main.rs"), false, false),
+            (a.clone(), true, false),
+            (dir.join("elsewhere.log"), false, false),
+            // Global ignore file
+            (a.join("cache.tmp"), false, true),
+            (a.join("node_modules"), true, true),
+            // .gitignore beats global, deeper .gitignore beats shallower
+            (a.join("app.log"), false, true),
+            (a.join("keep.log"), false, false),
+            (a.join("build/out.rs"), false, true),
+            (a.join("sub/debug.log"), false, false),
+            (a.join("sub/other.log"), false, true),
+            // Dotfiles beat .gitignore whitelists
+            (a.join(".hidden"), false, true),
+            (a.join(".git"), true, true),
+            (a.join(".cache/keep.log"), false, true),
+            // Per-root globs beat everything
+            (a.join("secret.txt"), false, true),
+            (b.join(".env"), false, false),
+            (b.join("scratch.tmp"), false, false),
+            (b.join("notes.txt"), false, true),
+            (b.join("docs"), true, false),
+        ];
+        for (path, is_dir, ignored) in cases {
+            assert_eq!(rules.is_ignored(&path, is_dir), ignored, "{:?}", path);
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn invalidate_picks_up_an_edited_gitignore() {
+        let dir = temp_dir("invalidate");
+        write(&dir.join(".gitignore"), "a.txt\n");
+        let rules =
+            IgnoreRules::with_global(std::slice::from_ref(&dir), &[], "", &dir.join("ignore"));
+        assert!(rules.is_ignored(&dir.join("a.txt"), false));
+
+        write(&dir.join(".gitignore"), "b.txt\n");
+        // Still the cached matcher until told otherwise
+        assert!(rules.is_ignored(&dir.join("a.txt"), false));
+
+        rules.invalidate(&dir);
+        assert!(!rules.is_ignored(&dir.join("a.txt"), false));
+        assert!(rules.is_ignored(&dir.join("b.txt"), false));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,9 +1,18 @@
+use crate::ignore_rules::IgnoreRules;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::SystemTime;
 use tokio::sync::RwLock;
-use walkdir::WalkDir;
+
+/// Bump whenever `FileEntry` or `IndexCache` change shape so stale caches are discarded.
+const CACHE_VERSION: u32 = 2;
+
+/// Maximum directory depth below each root (the root itself is depth 0).
+const MAX_DEPTH: usize = 12;
 
 /// Represents a single indexed entry (file or app).
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,91 +40,663 @@ pub enum EntryKind {
     App,
 }
 
+/// Stable handle to an entry in an `IndexStore`.
+/// An id keeps pointing at the same path until that path is removed; after
+/// removal the slot may be reused for a different entry.
+pub type EntryId = u32;
+
+/// Path-keyed entry storage with O(1) lookup, insert and remove.
+///
+/// Entries live in a slot vector addressed by `EntryId`; a `path → id` map finds
+/// them by path, and freed slots are recycled so the vector never grows from churn.
+#[derive(Debug, Default)]
+pub struct IndexStore {
+    slots: Vec<Option<FileEntry>>,
+    by_path: HashMap<String, EntryId>,
+    free: Vec<EntryId>,
+    /// Bumped on every mutation, so derived data can tell when it is stale.
+    generation: u64,
+}
+
+impl IndexStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of live entries.
+    pub fn len(&self) -> usize {
+        self.by_path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_path.is_empty()
+    }
+
+    /// Changes whenever an entry is inserted, replaced, moved or removed.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Look up an entry by id.
+    pub fn get(&self, id: EntryId) -> Option<&FileEntry> {
+        self.slots.get(id as usize).and_then(|s| s.as_ref())
+    }
+
+    /// Look up the id of the entry at `path`.
+    pub fn id_of(&self, path: &str) -> Option<EntryId> {
+        self.by_path.get(path).copied()
+    }
+
+    /// Insert an entry, replacing (and keeping the id of) any entry with the same path.
+    pub fn upsert(&mut self, entry: FileEntry) -> EntryId {
+        self.generation += 1;
+        if let Some(&id) = self.by_path.get(&entry.path) {
+            self.slots[id as usize] = Some(entry);
+            return id;
+        }
+
+        let path = entry.path.clone();
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.slots[id as usize] = Some(entry);
+                id
+            }
+            None => {
+                self.slots.push(Some(entry));
+                (self.slots.len() - 1) as EntryId
+            }
+        };
+        self.by_path.insert(path, id);
+        id
+    }
+
+    /// Remove the entry at `path`, returning it if it was present.
+    pub fn remove(&mut self, path: &str) -> Option<FileEntry> {
+        let id = self.by_path.remove(path)?;
+        self.generation += 1;
+        self.free.push(id);
+        self.slots[id as usize].take()
+    }
+
+    /// Remove the entry at `path` and every entry below it, returning what was removed.
+    pub fn remove_tree(&mut self, path: &str) -> Vec<FileEntry> {
+        if !self.may_have_children(path) {
+            return self.remove(path).into_iter().collect();
+        }
+
+        let root = Path::new(path);
+        let ids: Vec<EntryId> = self
+            .iter()
+            .filter(|(_, e)| Path::new(&e.path).starts_with(root))
+            .map(|(id, _)| id)
+            .collect();
+
+        self.generation += 1;
+        let mut removed = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(entry) = self.slots[id as usize].take() {
+                self.by_path.remove(&entry.path);
+                self.free.push(id);
+                removed.push(entry);
+            }
+        }
+        removed
+    }
+
+    /// Re-key the entry at `from` and everything below it to live under `to`,
+    /// keeping their ids. Anything already at a destination path is replaced.
+    /// Returns the `(old path, new path)` of every moved entry.
+    pub fn move_tree(&mut self, from: &str, to: &str) -> Vec<(String, String)> {
+        let ids: Vec<EntryId> = if self.may_have_children(from) {
+            let root = Path::new(from);
+            self.iter()
+                .filter(|(_, e)| Path::new(&e.path).starts_with(root))
+                .map(|(id, _)| id)
+                .collect()
+        } else {
+            self.id_of(from).into_iter().collect()
+        };
+        let (from, to) = (Path::new(from), Path::new(to));
+
+        self.generation += 1;
+        let mut moved = Vec::with_capacity(ids.len());
+        for id in ids {
+            let Some(mut entry) = self.slots[id as usize].take() else {
+                continue;
+            };
+            self.by_path.remove(&entry.path);
+
+            let rel = Path::new(&entry.path)
+                .strip_prefix(from)
+                .unwrap_or(Path::new(""))
+                .to_path_buf();
+            let new_path = if rel.as_os_str().is_empty() {
+                // The moved root itself may also have been renamed
+                if let Some(name) = to.file_name() {
+                    entry.name = name.to_string_lossy().to_string();
+                }
+                to.to_path_buf()
+            } else {
+                to.join(rel)
+            };
+            let new_path = new_path.to_string_lossy().to_string();
+            let old_path = std::mem::replace(&mut entry.path, new_path);
+
+            self.remove(&entry.path);
+            self.by_path.insert(entry.path.clone(), id);
+            moved.push((old_path, entry.path.clone()));
+            self.slots[id as usize] = Some(entry);
+        }
+        moved
+    }
+
+    /// Only directory entries need the O(n) descendant scan of the tree operations.
+    fn may_have_children(&self, path: &str) -> bool {
+        self.id_of(path)
+            .and_then(|id| self.get(id))
+            .is_some_and(|e| e.kind == EntryKind::Directory)
+    }
+
+    /// Keep only the entries for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(&FileEntry) -> bool) {
+        self.generation += 1;
+        for (id, slot) in self.slots.iter_mut().enumerate() {
+            if slot.as_ref().is_some_and(|e| !keep(e)) {
+                if let Some(entry) = slot.take() {
+                    self.by_path.remove(&entry.path);
+                    self.free.push(id as EntryId);
+                }
+            }
+        }
+    }
+
+    /// Iterate over `(id, entry)` pairs in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (EntryId, &FileEntry)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(id, slot)| slot.as_ref().map(|e| (id as EntryId, e)))
+    }
+
+    /// Parallel version of `iter`.
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = (EntryId, &FileEntry)> {
+        self.slots
+            .par_iter()
+            .enumerate()
+            .filter_map(|(id, slot)| slot.as_ref().map(|e| (id as EntryId, e)))
+    }
+
+    /// Iterate over all live entries.
+    pub fn entries(&self) -> impl Iterator<Item = &FileEntry> {
+        self.slots.iter().flatten()
+    }
+}
+
+impl Extend<FileEntry> for IndexStore {
+    fn extend<I: IntoIterator<Item = FileEntry>>(&mut self, iter: I) {
+        for entry in iter {
+            self.upsert(entry);
+        }
+    }
+}
+
 /// The shared file index — an in-memory store behind an async RwLock.
-pub type FileIndex = Arc<RwLock<Vec<FileEntry>>>;
+pub type FileIndex = Arc<RwLock<IndexStore>>;
+
+/// Directory path → mtime (unix seconds) recorded when its children were last listed.
+pub type DirMtimes = HashMap<String, u64>;
+
+/// On-disk snapshot of the file index, used to warm-start search at launch.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexCache {
+    version: u32,
+    roots: Vec<String>,
+    /// `IgnoreRules::fingerprint` the cached entries were filtered with.
+    rules: u64,
+    dir_mtimes: DirMtimes,
+    pub entries: Vec<FileEntry>,
+}
+
+impl IndexCache {
+    /// Whether the cache was written by this version, for `roots`, under `rules`.
+    fn matches(&self, roots: &[PathBuf], rules: &IgnoreRules) -> bool {
+        self.version == CACHE_VERSION
+            && self.roots == root_strings(roots)
+            && self.rules == rules.fingerprint()
+    }
+}
 
 /// Create a new empty file index.
 pub fn new_index() -> FileIndex {
-    Arc::new(RwLock::new(Vec::new()))
+    Arc::new(RwLock::new(IndexStore::new()))
+}
+
+/// Path to the index cache file.
+fn cache_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("trace")
+        .join("index.json")
+}
+
+fn root_strings(roots: &[PathBuf]) -> Vec<String> {
+    roots.iter().map(|r| r.to_string_lossy().to_string()).collect()
+}
+
+/// Load the cached index from disk.
+/// Returns `None` if there is no cache, it is unreadable, or it was written by an
+/// incompatible version, for a different set of roots, or under different ignore rules.
+pub fn load_cache(roots: &[PathBuf], rules: &IgnoreRules) -> Option<IndexCache> {
+    let file = std::fs::File::open(cache_path()).ok()?;
+    let cache: IndexCache = match serde_json::from_reader(BufReader::new(file)) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("[trace] Ignoring unreadable index cache: {}", e);
+            return None;
+        }
+    };
+
+    if !cache.matches(roots, rules) {
+        println!("[trace] Index cache is stale — rebuilding from scratch");
+        return None;
+    }
+
+    Some(cache)
+}
+
+/// Persist the current index (minus apps, which are rescanned every launch) to disk.
+/// Writes to a temporary file first so a crash never leaves a half-written cache.
+pub async fn save_cache(
+    index: &FileIndex,
+    roots: &[PathBuf],
+    rules: &IgnoreRules,
+    dir_mtimes: &DirMtimes,
+) {
+    let entries: Vec<FileEntry> = {
+        let idx = index.read().await;
+        idx.entries()
+            .filter(|e| e.kind != EntryKind::App)
+            .cloned()
+            .collect()
+    };
+
+    let cache = IndexCache {
+        version: CACHE_VERSION,
+        roots: root_strings(roots),
+        rules: rules.fingerprint(),
+        dir_mtimes: dir_mtimes.clone(),
+        entries,
+    };
+
+    let result = tokio::task::spawn_blocking(move || write_cache(&cache))
+        .await
+        .map_err(|e| format!("Task failed: {}", e))
+        .and_then(|r| r);
+
+    if let Err(e) = result {
+        eprintln!("[trace] Failed to save index cache: {}", e);
+    }
+}
+
+fn write_cache(cache: &IndexCache) -> Result<(), String> {
+    let path = cache_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create cache dir: {}", e))?;
+    }
+
+    let tmp = path.with_extension("json.tmp");
+    let file = std::fs::File::create(&tmp)
+        .map_err(|e| format!("Failed to create cache file: {}", e))?;
+    serde_json::to_writer(BufWriter::new(file), cache)
+        .map_err(|e| format!("Failed to serialize index: {}", e))?;
+    std::fs::rename(&tmp, &path).map_err(|e| format!("Failed to replace cache file: {}", e))
 }
 
 /// Scan the given root directories and populate the index.
+///
+/// When a cache is supplied, directories whose mtime is unchanged reuse their cached
+/// children instead of being re-listed, so only directories that gained, lost or
+/// renamed entries since the last run touch the disk. App entries already in the
+/// index are preserved. Returns the directory mtimes for the next `save_cache`.
 /// Runs on a blocking thread pool to avoid starving the async runtime.
-pub async fn build_index(index: FileIndex, roots: Vec<PathBuf>) {
-    let entries = tokio::task::spawn_blocking(move || {
-        let mut results: Vec<FileEntry> = Vec::with_capacity(100_000);
-
-        for root in &roots {
-            let walker = WalkDir::new(root)
-                .follow_links(false)
-                .max_depth(12) // reasonable depth limit
-                .into_iter()
-                .filter_entry(|e| !is_hidden(e));
-
-            for entry in walker.flatten() {
-                let metadata = match entry.metadata() {
-                    Ok(m) => m,
-                    Err(_) => continue,
-                };
-
-                let modified = metadata
-                    .modified()
-                    .unwrap_or(SystemTime::UNIX_EPOCH)
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs();
-
-                let kind = if metadata.is_dir() {
-                    EntryKind::Directory
-                } else {
-                    EntryKind::File
-                };
-
-                let name = entry
-                    .file_name()
-                    .to_string_lossy()
-                    .to_string();
-
-                results.push(FileEntry {
-                    name,
-                    path: entry.path().to_string_lossy().to_string(),
-                    kind,
-                    size: metadata.len(),
-                    modified,
-                    icon_path: None,
-                    keywords: None,
-                    generic_name: None,
-                });
+pub async fn build_index(
+    index: FileIndex,
+    roots: Vec<PathBuf>,
+    rules: Arc<IgnoreRules>,
+    cache: Option<IndexCache>,
+) -> DirMtimes {
+    let roots_for_scan = roots.clone();
+    let rules_for_scan = rules.clone();
+    let (entries, dir_mtimes) =
+        tokio::task::spawn_blocking(move || reconcile(&roots_for_scan, &rules_for_scan, cache))
+            .await
+            .unwrap_or_default();
+
+    {
+        let mut idx = index.write().await;
+        idx.retain(|e| e.kind == EntryKind::App);
+        idx.extend(entries);
+        println!("[trace] Indexed {} entries", idx.len());
+    }
+
+    save_cache(&index, &roots, &rules, &dir_mtimes).await;
+    dir_mtimes
+}
+
+/// Walk the roots, re-listing only directories whose mtime differs from the cache.
+fn reconcile(
+    roots: &[PathBuf],
+    rules: &IgnoreRules,
+    cache: Option<IndexCache>,
+) -> (Vec<FileEntry>, DirMtimes) {
+    let (old_mtimes, mut cached_children) = match cache {
+        Some(cache) => {
+            let mut by_parent: HashMap<String, Vec<FileEntry>> = HashMap::new();
+            for entry in cache.entries {
+                let parent = Path::new(&entry.path)
+                    .parent()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                by_parent.entry(parent).or_default().push(entry);
+            }
+            (cache.dir_mtimes, by_parent)
+        }
+        None => (DirMtimes::new(), HashMap::new()),
+    };
+
+    let mut results: Vec<FileEntry> = Vec::with_capacity(100_000);
+    let mut dir_mtimes = DirMtimes::new();
+    let mut relisted = 0usize;
+
+    for root in roots {
+        // The root itself is indexed too, mirroring a plain recursive walk
+        let Ok(metadata) = std::fs::metadata(root) else {
+            continue;
+        };
+        if let Some(entry) = entry_from_metadata(root, &metadata) {
+            results.push(entry);
+        }
+        if !metadata.is_dir() {
+            continue;
+        }
+
+        let mut stack: Vec<(PathBuf, usize)> = vec![(root.clone(), 0)];
+        while let Some((dir, depth)) = stack.pop() {
+            let dir_str = dir.to_string_lossy().to_string();
+            let Ok(dir_meta) = std::fs::metadata(&dir) else {
+                continue;
+            };
+            let mtime = unix_mtime(&dir_meta);
+            dir_mtimes.insert(dir_str.clone(), mtime);
+
+            let children = if old_mtimes.get(&dir_str) == Some(&mtime) {
+                cached_children.remove(&dir_str).unwrap_or_default()
+            } else {
+                relisted += 1;
+                list_dir(&dir, rules)
+            };
+
+            for child in children {
+                if child.kind == EntryKind::Directory && depth + 1 < MAX_DEPTH {
+                    stack.push((PathBuf::from(&child.path), depth + 1));
+                }
+                results.push(child);
+            }
+        }
+    }
+
+    println!(
+        "[trace] Reconciled {} directories ({} re-listed)",
+        dir_mtimes.len(),
+        relisted
+    );
+    (results, dir_mtimes)
+}
+
+/// List everything below `dir` (excluding `dir` itself), applying the same
+/// ignore rules and depth limit as a full scan.
+pub fn scan_tree(dir: &Path, rules: &IgnoreRules) -> Vec<FileEntry> {
+    let mut results = Vec::new();
+    let mut stack: Vec<(PathBuf, usize)> = vec![(dir.to_path_buf(), 0)];
+
+    while let Some((dir, depth)) = stack.pop() {
+        for child in list_dir(&dir, rules) {
+            if child.kind == EntryKind::Directory && depth + 1 < MAX_DEPTH {
+                stack.push((PathBuf::from(&child.path), depth + 1));
             }
+            results.push(child);
         }
+    }
+    results
+}
+
+/// List the immediate children of a directory that aren't excluded by `rules`.
+fn list_dir(dir: &Path, rules: &IgnoreRules) -> Vec<FileEntry> {
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    read_dir
+        .flatten()
+        .filter_map(|e| {
+            // Like the old walker, don't follow symlinks
+            let metadata = e.metadata().ok()?;
+            let path = e.path();
+            if rules.is_ignored(&path, metadata.is_dir()) {
+                return None;
+            }
+            entry_from_metadata(&path, &metadata)
+        })
+        .collect()
+}
 
-        results
+/// Build an index entry from a path and its metadata.
+pub fn entry_from_metadata(path: &Path, metadata: &std::fs::Metadata) -> Option<FileEntry> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+
+    let kind = if metadata.is_dir() {
+        EntryKind::Directory
+    } else {
+        EntryKind::File
+    };
+
+    Some(FileEntry {
+        name,
+        path: path.to_string_lossy().to_string(),
+        kind,
+        size: metadata.len(),
+        modified: unix_mtime(metadata),
+        icon_path: None,
+        keywords: None,
+        generic_name: None,
     })
-    .await
-    .unwrap_or_default();
+}
 
-    let mut idx = index.write().await;
-    *idx = entries;
-    println!("[trace] Indexed {} entries", idx.len());
+fn unix_mtime(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
-/// Returns true for hidden files/dirs (dotfiles) and common junk directories.
-fn is_hidden(entry: &walkdir::DirEntry) -> bool {
-    let name = entry.file_name().to_string_lossy();
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::IndexFilter;
+
+    fn file(path: &str) -> FileEntry {
+        FileEntry {
+            name: path.rsplit('/').next().unwrap_or(path).to_string(),
+            path: path.to_string(),
+            kind: EntryKind::File,
+            size: 0,
+            modified: 0,
+            icon_path: None,
+            keywords: None,
+            generic_name: None,
+        }
+    }
+
+    #[test]
+    fn upsert_replaces_in_place() {
+        let mut store = IndexStore::new();
+        let id = store.upsert(file("/a/one.txt"));
+        let mut updated = file("/a/one.txt");
+        updated.size = 42;
+        assert_eq!(store.upsert(updated), id);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get(id).unwrap().size, 42);
+    }
+
+    #[test]
+    fn remove_frees_slot_for_reuse() {
+        let mut store = IndexStore::new();
+        let a = store.upsert(file("/a"));
+        let b = store.upsert(file("/b"));
+        assert!(store.remove("/a").is_some());
+        assert!(store.remove("/a").is_none());
+        assert_eq!(store.id_of("/b"), Some(b));
+        assert_eq!(store.upsert(file("/c")), a);
+        assert_eq!(store.len(), 2);
+    }
+
+    fn dir(path: &str) -> FileEntry {
+        FileEntry {
+            kind: EntryKind::Directory,
+            ..file(path)
+        }
+    }
+
+    #[test]
+    fn move_tree_rekeys_descendants() {
+        let mut store = IndexStore::new();
+        store.extend([dir("/a"), file("/a/x"), file("/a/sub/y"), file("/ab")]);
+        let x = store.id_of("/a/x").unwrap();
+
+        assert_eq!(store.move_tree("/a", "/b").len(), 3);
+        assert_eq!(store.id_of("/b/x"), Some(x));
+        assert!(store.id_of("/b/sub/y").is_some());
+        assert_eq!(store.get(store.id_of("/b").unwrap()).unwrap().name, "b");
+        // Sibling sharing a string prefix is untouched
+        assert!(store.id_of("/ab").is_some());
+        assert!(store.id_of("/a/x").is_none());
+    }
+
+    #[test]
+    fn remove_tree_removes_descendants() {
+        let mut store = IndexStore::new();
+        store.extend([dir("/a"), file("/a/x"), file("/a/sub/y"), file("/ab")]);
+        assert_eq!(store.remove_tree("/a").len(), 3);
+        assert_eq!(store.len(), 1);
+        // A plain file is removed without touching anything else
+        assert_eq!(store.remove_tree("/ab").len(), 1);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn retain_drops_from_path_map() {
+        let mut store = IndexStore::new();
+        store.extend(["/keep", "/drop"].map(file));
+        store.retain(|e| e.path == "/keep");
+        assert_eq!(store.len(), 1);
+        assert!(store.id_of("/drop").is_none());
+        assert_eq!(store.iter().count(), 1);
+    }
+
+    fn temp_tree(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("trace-index-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.txt"), "a").unwrap();
+        std::fs::write(dir.join("sub/b.txt"), "b").unwrap();
+        dir
+    }
+
+    fn no_rules(roots: &[PathBuf], filters: &[IndexFilter]) -> IgnoreRules {
+        IgnoreRules::with_global(roots, filters, "", Path::new("ignore"))
+    }
+
+    fn paths(entries: &[FileEntry]) -> Vec<String> {
+        let mut paths: Vec<String> = entries.iter().map(|e| e.path.clone()).collect();
+        paths.sort();
+        paths
+    }
 
-    // Always allow the root itself
-    if entry.depth() == 0 {
-        return false;
+    #[test]
+    fn cache_is_rejected_when_anything_it_depends_on_changed() {
+        let roots = vec![PathBuf::from("/home/u")];
+        let rules = no_rules(&roots, &[]);
+        let cache = |version, roots: &[PathBuf], rules: &IgnoreRules| IndexCache {
+            version,
+            roots: root_strings(roots),
+            rules: rules.fingerprint(),
+            dir_mtimes: DirMtimes::new(),
+            entries: Vec::new(),
+        };
+        assert!(cache(CACHE_VERSION, &roots, &rules).matches(&roots, &rules));
+
+        let other_roots = vec![PathBuf::from("/home/u"), PathBuf::from("/srv")];
+        let filtered = no_rules(
+            &roots,
+            &[IndexFilter {
+                include: Vec::new(),
+                exclude: vec!["*.log".into()],
+            }],
+        );
+        let stale = [
+            cache(CACHE_VERSION - 1, &roots, &rules),
+            cache(CACHE_VERSION, &other_roots, &rules),
+            cache(CACHE_VERSION, &roots, &filtered),
+        ];
+        for cache in stale {
+            assert!(!cache.matches(&roots, &rules), "{:?}", cache.version);
+        }
     }
 
-    // Skip dotfiles, node_modules, target, .git, __pycache__, etc.
-    name.starts_with('.')
-        || name == "node_modules"
-        || name == "target"
-        || name == "__pycache__"
-        || name == ".cache"
-        || name == ".local/share/Trash"
-        || name == "vendor"
-        || name == "dist"
-        || name == "build"
+    #[test]
+    fn reconcile_reuses_unchanged_directories_and_relists_changed_ones() {
+        let root = temp_tree("reconcile");
+        let roots = vec![root.clone()];
+        let rules = no_rules(&roots, &[]);
+        let sub = root.join("sub").to_string_lossy().to_string();
+
+        let (entries, dir_mtimes) = reconcile(&roots, &rules, None);
+        let first = paths(&entries);
+        assert!(first.contains(&format!("{}/b.txt", sub)));
+
+        // A cached child that no longer exists on disk stands in for "not re-listed"
+        std::fs::write(root.join("sub/c.txt"), "c").unwrap();
+        let sub_mtime = unix_mtime(&std::fs::metadata(root.join("sub")).unwrap());
+        let cached = |sub_mtime: u64| {
+            let mut dir_mtimes = dir_mtimes.clone();
+            dir_mtimes.insert(sub.clone(), sub_mtime);
+            let mut entries = entries.clone();
+            entries.push(file(&format!("{}/ghost.txt", sub)));
+            IndexCache {
+                version: CACHE_VERSION,
+                roots: root_strings(&roots),
+                rules: rules.fingerprint(),
+                dir_mtimes,
+                entries,
+            }
+        };
+
+        let (reused, _) = reconcile(&roots, &rules, Some(cached(sub_mtime)));
+        let reused = paths(&reused);
+        assert!(reused.contains(&format!("{}/ghost.txt", sub)));
+        assert!(!reused.contains(&format!("{}/c.txt", sub)));
+
+        let (relisted, mtimes) = reconcile(&roots, &rules, Some(cached(0)));
+        let relisted = paths(&relisted);
+        assert!(!relisted.contains(&format!("{}/ghost.txt", sub)));
+        assert!(relisted.contains(&format!("{}/c.txt", sub)));
+        assert_eq!(mtimes.get(&sub), Some(&sub_mtime));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
 }
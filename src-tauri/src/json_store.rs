@@ -0,0 +1,138 @@
+/// Versioned JSON files for state kept across launches: the shell and calculator
+/// histories, the launch history and the content index cache.
+///
+/// A file written with another `VERSION` is discarded rather than migrated, and
+/// saves go through a temporary file so a crash never leaves half of one behind.
+/// Saves of the same file take turns, since they share that temporary file.
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::RwLock;
+
+/// A value persisted as a single JSON file.
+pub trait JsonStore: Serialize + DeserializeOwned {
+    /// Bump whenever the on-disk layout changes.
+    const VERSION: u32;
+
+    /// What the file holds, for error messages, e.g. "shell history"
+    const NAME: &'static str;
+
+    fn path() -> PathBuf;
+
+    /// The `VERSION` this value was written with.
+    fn version(&self) -> u32;
+}
+
+/// Read the stored value, or `None` if it is missing, outdated or unreadable.
+pub fn load<T: JsonStore>() -> Option<T> {
+    let file = std::fs::File::open(T::path()).ok()?;
+    match serde_json::from_reader::<_, T>(BufReader::new(file)) {
+        Ok(value) if value.version() == T::VERSION => Some(value),
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!("[trace][json_store] Ignoring unreadable {}: {}", T::NAME, e);
+            None
+        }
+    }
+}
+
+/// The lock held while `path` is being written.
+fn file_lock(path: &Path) -> Result<Arc<Mutex<()>>, String> {
+    static LOCKS: OnceLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> = OnceLock::new();
+    let mut locks = LOCKS
+        .get_or_init(Default::default)
+        .lock()
+        .map_err(|e| e.to_string())?;
+    Ok(locks.entry(path.to_path_buf()).or_default().clone())
+}
+
+/// Write the value, replacing the file only once the new one is complete.
+pub fn save<T: JsonStore>(value: &T) -> Result<(), String> {
+    let path = T::path();
+    let lock = file_lock(&path)?;
+    let _saving = lock.lock().map_err(|e| e.to_string())?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {} dir: {}", T::NAME, e))?;
+    }
+
+    let tmp = path.with_extension("json.tmp");
+    let file = std::fs::File::create(&tmp)
+        .map_err(|e| format!("Failed to create {} file: {}", T::NAME, e))?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer(&mut writer, value)
+        .map_err(|e| format!("Failed to serialize {}: {}", T::NAME, e))?;
+    // A short write (disk full) must not replace the good file
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to write {} file: {}", T::NAME, e))?;
+    std::fs::rename(&tmp, &path).map_err(|e| format!("Failed to replace {} file: {}", T::NAME, e))
+}
+
+/// Save a shared value from a worker thread.
+pub async fn persist<T: JsonStore + Send + Sync + 'static>(shared: &Arc<RwLock<T>>) {
+    let shared = shared.clone();
+    let result = tokio::task::spawn_blocking(move || save(&*shared.blocking_read())).await;
+    if let Ok(Err(e)) = result {
+        eprintln!("[trace][json_store] {}", e);
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Notes {
+        version: u32,
+        lines: Vec<String>,
+    }
+
+    impl JsonStore for Notes {
+        const VERSION: u32 = 2;
+        const NAME: &'static str = "test notes";
+
+        fn path() -> PathBuf {
+            std::env::temp_dir()
+                .join(format!("trace-json-store-{}", std::process::id()))
+                .join("notes.json")
+        }
+
+        fn version(&self) -> u32 {
+            self.version
+        }
+    }
+
+    #[test]
+    fn concurrent_saves_leave_a_readable_file() {
+        let notes = Arc::new(Notes {
+            version: Notes::VERSION,
+            lines: (0..20_000).map(|i| format!("line {}", i)).collect(),
+        });
+        let savers: Vec<_> = (0..8)
+            .map(|_| {
+                let notes = notes.clone();
+                std::thread::spawn(move || save(&*notes))
+            })
+            .collect();
+        for saver in savers {
+            saver.join().unwrap().unwrap();
+        }
+        assert_eq!(load::<Notes>().as_ref(), Some(&*notes));
+
+        save(&Notes {
+            version: 1,
+            lines: Vec::new(),
+        })
+        .unwrap();
+        assert_eq!(load::<Notes>(), None);
+
+        let _ = std::fs::remove_dir_all(Notes::path().parent().unwrap());
+    }
+}
@@ -1,27 +1,68 @@
 mod autostart;
 mod calc;
+mod calc_exact;
+mod calc_history;
+mod calc_int;
+mod calc_parse;
+mod calc_units;
 mod commands;
+mod content_index;
 mod doc_chat;
-mod indexer;
+mod extract;
+mod frecency;
+mod ignore_rules;
+pub mod indexer;
+mod json_store;
 mod launcher;
 mod llm;
+mod query;
+mod rag;
+mod runner;
 mod search;
 mod settings;
 mod shell_cmd;
+mod shell_explain;
+mod shell_history;
+mod shell_parse;
+mod shell_preview;
+mod shell_risk;
+mod vault;
 mod watcher;
 
+use calc_history::CalcHistory;
 use commands::AppState;
+use content_index::ContentIndex;
+use frecency::FrecencyStore;
+use ignore_rules::IgnoreRules;
 use indexer::FileIndex;
 use llm::LlmClient;
+use search::{SearchCache, SearchGate};
 use settings::Settings;
+use shell_history::ShellHistory;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tauri::Manager;
 use tokio::sync::RwLock;
 
+/// How often the live index is written back to the on-disk cache.
+const CACHE_FLUSH_INTERVAL: Duration = Duration::from_secs(300);
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let settings = Settings::load();
+    let mut settings = Settings::load();
+    let mut vault = vault::Vault::open();
+    if vault.is_locked() {
+        eprintln!("[trace][vault] Key vault is locked; unlock it in Settings");
+    } else {
+        match vault::migrate_plaintext_keys(&mut settings, &mut vault) {
+            Ok(0) => {}
+            Ok(n) => eprintln!("[trace][vault] Moved {} API key(s) into the vault", n),
+            Err(e) => eprintln!("[trace][vault] Could not migrate plaintext API keys: {}", e),
+        }
+    }
+    vault.apply_to(&mut settings);
     let home = dirs::home_dir().unwrap_or_else(|| {
         // Platform-safe fallback
         if cfg!(windows) {
@@ -44,12 +85,32 @@ pub fn run() {
         })
         .collect();
 
+    let filters: Vec<_> = settings
+        .index_roots
+        .iter()
+        .map(|r| settings.index_filters.get(r).cloned().unwrap_or_default())
+        .collect();
+    let rules = Arc::new(IgnoreRules::new(&roots, &filters));
+
+    let content_enabled = settings.content_index_enabled;
+
     let index: FileIndex = indexer::new_index();
+    let content = Arc::new(RwLock::new(ContentIndex::new()));
     let state = AppState {
         index: index.clone(),
+        rules: rules.clone(),
+        content: content.clone(),
         settings: Arc::new(RwLock::new(settings)),
+        vault: Arc::new(RwLock::new(vault)),
+        frecency: Arc::new(RwLock::new(FrecencyStore::load())),
+        shell_history: Arc::new(RwLock::new(ShellHistory::load())),
+        calc_history: Arc::new(RwLock::new(CalcHistory::load())),
+        search_cache: Arc::new(Mutex::new(SearchCache::default())),
+        search_gate: Arc::new(SearchGate::default()),
         llm: LlmClient::new(),
-        chat_file_content: Arc::new(RwLock::new(None)),
+        streams: Arc::new(Mutex::new(HashMap::new())),
+        runs: Arc::new(Mutex::new(HashMap::new())),
+        chat: Arc::new(RwLock::new(None)),
     };
 
     let index_for_build = index.clone();
@@ -87,38 +148,120 @@ pub fn run() {
 
             // Build the file index in the background
             tauri::async_runtime::spawn(async move {
-                // Scan the filesystem first (this replaces the entire index)
-                indexer::build_index(index_for_build.clone(), roots_for_build).await;
+                // Serve the on-disk cache straight away so search works during the scan
+                let roots_for_cache = roots_for_build.clone();
+                let rules_for_cache = rules.clone();
+                let cache = tokio::task::spawn_blocking(move || {
+                    indexer::load_cache(&roots_for_cache, &rules_for_cache)
+                })
+                .await
+                .unwrap_or_default();
+                if let Some(cache) = &cache {
+                    let mut idx = index_for_build.write().await;
+                    idx.extend(cache.entries.iter().cloned());
+                    println!("[trace] Warm start — {} cached entries", idx.len());
+                }
 
-                // Now add desktop apps ON TOP of the file index (so they aren't overwritten)
+                // Desktop apps are always rescanned; build_index keeps them in place
                 let apps = launcher::scan_desktop_apps();
-                {
-                    let mut idx = index_for_build.write().await;
-                    idx.extend(apps);
-                    println!("[trace] Index ready — {} total entries", idx.len());
+                index_for_build.write().await.extend(apps);
+
+                // Reconcile the cache against the filesystem
+                let dir_mtimes = indexer::build_index(
+                    index_for_build.clone(),
+                    roots_for_build.clone(),
+                    rules.clone(),
+                    cache,
+                )
+                .await;
+                println!(
+                    "[trace] Index ready — {} total entries",
+                    index_for_build.read().await.len()
+                );
+
+                // Load the saved content index before the watcher starts updating it
+                if content_enabled {
+                    let content_for_load = content.clone();
+                    let _ = tokio::task::spawn_blocking(move || {
+                        *content_for_load.blocking_write() = ContentIndex::load();
+                    })
+                    .await;
                 }
 
                 // Start watching for changes
-                watcher::start_watcher(index_for_watch, roots_for_watch).await;
+                let content_for_watch = content_enabled.then(|| content.clone());
+                watcher::start_watcher(
+                    index_for_watch,
+                    roots_for_watch,
+                    rules.clone(),
+                    content_for_watch,
+                )
+                .await;
+
+                // Catch the content index up with the file index (optional). Extraction
+                // can take minutes on a first run, so it runs alongside the watcher and
+                // the cache flushes rather than ahead of them.
+                if content_enabled {
+                    let content_for_sync = content.clone();
+                    let index_for_sync = index_for_build.clone();
+                    tokio::task::spawn_blocking(move || {
+                        content_index::sync_with_files(&content_for_sync, &index_for_sync);
+                    });
+                }
+
+                // Periodically flush watcher updates so the next launch starts fresh
+                loop {
+                    tokio::time::sleep(CACHE_FLUSH_INTERVAL).await;
+                    indexer::save_cache(&index_for_build, &roots_for_build, &rules, &dir_mtimes)
+                        .await;
+                    if content_enabled {
+                        json_store::persist(&content).await;
+                    }
+                }
             });
 
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::search_files,
+            commands::search_content,
             commands::open_result,
+            commands::forget_result,
+            commands::reset_frecency,
             commands::get_settings,
             commands::save_settings,
+            commands::unlock_vault,
+            commands::set_vault_protection,
+            commands::list_models,
             commands::translate_command,
             commands::execute_shell,
+            commands::write_shell_stdin,
+            commands::kill_shell,
+            commands::explain_command,
+            commands::preview_command,
+            commands::search_shell_history,
+            commands::use_shell_history,
+            commands::use_shell_candidate,
+            commands::pin_shell_history,
+            commands::delete_shell_history,
+            commands::export_shell_history,
             commands::enter_chat_mode,
             commands::chat_message,
+            commands::get_chat_history,
+            commands::clear_chat_history,
+            commands::export_chat_history,
             commands::exit_chat_mode,
+            commands::cancel_stream,
             commands::get_system_info,
             commands::get_shell_context,
             commands::get_registered_shortcut,
             commands::evaluate_math,
             commands::evaluate_graph,
+            commands::record_calculation,
+            commands::search_calc_history,
+            commands::delete_calc_history,
+            commands::get_calc_memory,
+            commands::forget_calc_definition,
             commands::get_app_icon,
         ])
         .run(tauri::generate_context!())
@@ -1,12 +1,108 @@
 use crate::settings::Settings;
-use reqwest::Client;
-use serde_json::json;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
 
-/// Unified LLM client that dispatches to OpenAI, Anthropic, or Google APIs.
+/// Error returned when a streamed completion is stopped through its `CancelToken`.
+pub const CANCELLED: &str = "Cancelled";
+
+/// How long model listing waits for a local server before giving up.
+const LIST_MODELS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Chunks sent per embedding request.
+const EMBED_BATCH: usize = 64;
+
+/// Context window requested from Ollama, whose own default silently truncates long prompts.
+const OLLAMA_NUM_CTX: u32 = 8192;
+
+/// Unified LLM client that dispatches to the cloud APIs or a local server.
+/// Every provider is called in streaming mode; `prompt` simply collects the stream.
 pub struct LlmClient {
     http: Client,
 }
 
+/// Stops an in-flight streamed completion.
+#[derive(Clone)]
+pub struct CancelToken {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self {
+            tx: Arc::new(watch::channel(false).0),
+        }
+    }
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.tx.send_replace(true);
+    }
+
+    /// Resolves once `cancel` has been called (immediately if it already was).
+    pub async fn cancelled(&self) {
+        let mut rx = self.tx.subscribe();
+        let _ = rx.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+/// Who said a message in a conversation. The system prompt is passed separately.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// One turn of a conversation, oldest first in every list handed to `LlmClient`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: Role::Assistant,
+            content: content.into(),
+        }
+    }
+}
+
+/// In-flight streams by the id the frontend gave them, so they can be cancelled.
+pub type ActiveStreams = Arc<Mutex<HashMap<String, CancelToken>>>;
+
+/// How a provider frames its streamed events.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dialect {
+    /// `data: {"choices":[{"delta":{"content":…}}]}` … `data: [DONE]`
+    OpenAi,
+    /// `event: content_block_delta` / `data: {"delta":{"text":…}}` … `event: message_stop`
+    Anthropic,
+    /// `data: {"candidates":[{"content":{"parts":[{"text":…}]}}]}` until the body ends
+    Gemini,
+    /// Newline-delimited JSON: `{"message":{"content":…},"done":false}` … `{"done":true}`
+    Ollama,
+}
+
+impl Default for LlmClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LlmClient {
     pub fn new() -> Self {
         Self {
@@ -14,126 +110,322 @@ impl LlmClient {
         }
     }
 
-    /// Send a prompt to the active cloud provider and return the response text.
+    /// Send a prompt to the active provider and return the response text.
     pub async fn prompt(
         &self,
         settings: &Settings,
         system: &str,
         user_msg: &str,
     ) -> Result<String, String> {
-        let api_key = settings
-            .active_key()
-            .ok_or("No API key configured for the active provider")?;
+        let messages = [ChatMessage::user(user_msg)];
+        self.prompt_stream(settings, system, &messages, &mut |_| {}, None)
+            .await
+    }
+
+    /// Continue a conversation, handing each piece of text to `on_delta` as it
+    /// arrives. `messages` must start with a user turn and end with the new question.
+    /// Returns the full text, or `CANCELLED` if `cancel` fired first.
+    pub async fn prompt_stream(
+        &self,
+        settings: &Settings,
+        system: &str,
+        messages: &[ChatMessage],
+        on_delta: &mut (dyn FnMut(&str) + Send),
+        cancel: Option<&CancelToken>,
+    ) -> Result<String, String> {
+        let api_key = match settings.active_key() {
+            Some(key) => key.as_str(),
+            None if !settings.requires_key() => "",
+            None if settings.keys_locked => {
+                return Err("API keys are locked — unlock the key vault in Settings".to_string())
+            }
+            None => return Err("No API key configured for the active provider".to_string()),
+        };
+        let model = settings.active_model.as_str();
 
-        match settings.active_provider.as_str() {
-            "openai" => self.prompt_openai(api_key, &settings.active_model, system, user_msg).await,
-            "anthropic" => self.prompt_anthropic(api_key, &settings.active_model, system, user_msg).await,
-            "google" => self.prompt_google(api_key, &settings.active_model, system, user_msg).await,
-            "huggingface" => self.prompt_huggingface(api_key, &settings.active_model, system, user_msg).await,
-            "openrouter" => self.prompt_openrouter(api_key, &settings.active_model, system, user_msg).await,
-            other => Err(format!("Unknown provider: {}", other)),
+        let (request, dialect, provider) = match settings.active_provider.as_str() {
+            "openai" => (
+                self.request_openai(api_key, model, system, messages),
+                Dialect::OpenAi,
+                "OpenAI",
+            ),
+            "anthropic" => (
+                self.request_anthropic(api_key, model, system, messages),
+                Dialect::Anthropic,
+                "Anthropic",
+            ),
+            "google" => (
+                self.request_google(api_key, model, system, messages),
+                Dialect::Gemini,
+                "Google AI",
+            ),
+            "huggingface" => (
+                self.request_huggingface(api_key, model, system, messages),
+                Dialect::OpenAi,
+                "HuggingFace",
+            ),
+            "openrouter" => (
+                self.request_openrouter(api_key, model, system, messages),
+                Dialect::OpenAi,
+                "OpenRouter",
+            ),
+            "ollama" => (
+                self.request_ollama(&settings.ollama_base_url, model, system, messages),
+                Dialect::Ollama,
+                "Ollama",
+            ),
+            "custom" => (
+                self.request_custom(&settings.custom_base_url, api_key, model, system, messages),
+                Dialect::OpenAi,
+                "Custom endpoint",
+            ),
+            other => return Err(format!("Unknown provider: {}", other)),
+        };
+
+        let send = request.send();
+        let resp = match cancel {
+            Some(cancel) => tokio::select! {
+                _ = cancel.cancelled() => return Err(CANCELLED.to_string()),
+                resp = send => resp,
+            },
+            None => send.await,
         }
+        .map_err(|e| format!("{} request failed: {}", provider, e))?;
+
+        Self::read_stream(resp, dialect, provider, on_delta, cancel).await
     }
 
-    /// Parse a response: get raw text first so decode errors show the actual body.
-    async fn parse_response(
-        resp: reqwest::Response,
+    /// List the models a local provider serves: Ollama's `/api/tags`, or `/models`
+    /// on an OpenAI-compatible base URL. Takes the URL and key directly so the
+    /// settings panel can query values that have not been saved yet.
+    pub async fn list_models(
+        &self,
         provider: &str,
-    ) -> Result<serde_json::Value, String> {
+        base_url: &str,
+        api_key: Option<&str>,
+    ) -> Result<Vec<String>, String> {
+        let base = base_url.trim().trim_end_matches('/');
+        if base.is_empty() {
+            return Err("No base URL configured".to_string());
+        }
+
+        let (request, list, field) = match provider {
+            "ollama" => (
+                self.http.get(format!("{}/api/tags", base)),
+                "models",
+                "name",
+            ),
+            "custom" => (self.http.get(format!("{}/models", base)), "data", "id"),
+            other => return Err(format!("{} does not support model listing", other)),
+        };
+        let request = match api_key.filter(|k| !k.is_empty()) {
+            Some(key) => request.header("Authorization", format!("Bearer {}", key)),
+            None => request,
+        };
+
+        let resp = request
+            .timeout(LIST_MODELS_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| format!("Could not reach {}: {}", base, e))?;
         let status = resp.status();
-        let text = resp
-            .text()
+        if !status.is_success() {
+            return Err(format!("{} answered HTTP {}", base, status));
+        }
+        let body: Value = resp
+            .json()
             .await
+            .map_err(|e| format!("Unexpected model list from {}: {}", base, e))?;
+
+        Ok(parse_model_list(&body, list, field))
+    }
+
+    /// Embed `texts` with the configured embedding provider, one vector per text.
+    pub async fn embed(
+        &self,
+        settings: &Settings,
+        texts: &[&str],
+    ) -> Result<Vec<Vec<f32>>, String> {
+        let model = settings.embedding_model.trim();
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(EMBED_BATCH) {
+            let body = json!({ "model": model, "input": batch });
+            let (request, list) = match settings.embedding_provider.as_str() {
+                "openai" => (
+                    self.http
+                        .post("https://api.openai.com/v1/embeddings")
+                        .header(
+                            "Authorization",
+                            format!("Bearer {}", settings.openai_key.as_deref().unwrap_or("")),
+                        ),
+                    "data",
+                ),
+                "ollama" => (
+                    self.http.post(format!(
+                        "{}/api/embed",
+                        settings.ollama_base_url.trim().trim_end_matches('/')
+                    )),
+                    "embeddings",
+                ),
+                "custom" => {
+                    let request = self.http.post(format!(
+                        "{}/embeddings",
+                        settings.custom_base_url.trim().trim_end_matches('/')
+                    ));
+                    let request = match settings.custom_key.as_deref().filter(|k| !k.is_empty()) {
+                        Some(key) => request.header("Authorization", format!("Bearer {}", key)),
+                        None => request,
+                    };
+                    (request, "data")
+                }
+                "" => return Err("No embedding provider configured".to_string()),
+                other => return Err(format!("Unknown embedding provider: {}", other)),
+            };
+
+            let resp = request
+                .header("Content-Type", "application/json")
+                .json(&body)
+                .send()
+                .await
+                .map_err(|e| format!("Embedding request failed: {}", e))?;
+            let status = resp.status();
+            if !status.is_success() {
+                let body = resp.text().await.unwrap_or_default();
+                return Err(format!(
+                    "Embedding request failed (HTTP {}): {}",
+                    status, body
+                ));
+            }
+            let body: Value = resp
+                .json()
+                .await
+                .map_err(|e| format!("Unexpected embedding response: {}", e))?;
+
+            let batch_vectors = parse_embeddings(&body, list)?;
+            if batch_vectors.len() != batch.len() {
+                return Err(format!(
+                    "Embedding provider returned {} vectors for {} inputs",
+                    batch_vectors.len(),
+                    batch.len()
+                ));
+            }
+            vectors.extend(batch_vectors);
+        }
+        Ok(vectors)
+    }
+
+    /// Read a streamed response to the end, forwarding text deltas.
+    async fn read_stream(
+        mut resp: reqwest::Response,
+        dialect: Dialect,
+        provider: &str,
+        on_delta: &mut (dyn FnMut(&str) + Send),
+        cancel: Option<&CancelToken>,
+    ) -> Result<String, String> {
+        // Errors come back as a plain JSON body, not as a stream
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!(
+                "{} request failed (HTTP {}): {}",
+                provider, status, body
+            ));
+        }
+
+        let mut parser = match dialect {
+            Dialect::Ollama => SseParser::ndjson(),
+            _ => SseParser::default(),
+        };
+        let mut full = String::new();
+        loop {
+            let chunk = match cancel {
+                Some(cancel) => tokio::select! {
+                    _ = cancel.cancelled() => return Err(CANCELLED.to_string()),
+                    chunk = resp.chunk() => chunk,
+                },
+                None => resp.chunk().await,
+            }
             .map_err(|e| format!("{} response read failed: {}", provider, e))?;
-        serde_json::from_str(&text).map_err(|e| {
-            format!(
-                "{} response parse failed (HTTP {}): {} — body: {}",
-                provider, status, e, text
-            )
-        })
+
+            let Some(chunk) = chunk else {
+                break;
+            };
+            for event in parser.push(&chunk) {
+                match parse_event(dialect, &event)
+                    .map_err(|e| format!("{} stream error: {}", provider, e))?
+                {
+                    Step::Text(text) => {
+                        on_delta(&text);
+                        full.push_str(&text);
+                    }
+                    Step::Done => return Ok(full),
+                    Step::Skip => {}
+                }
+            }
+        }
+
+        if full.is_empty() {
+            return Err(format!("{} returned an empty response", provider));
+        }
+        Ok(full)
     }
 
-    async fn prompt_openai(
+    fn request_openai(
         &self,
         api_key: &str,
         model: &str,
         system: &str,
-        user_msg: &str,
-    ) -> Result<String, String> {
+        messages: &[ChatMessage],
+    ) -> RequestBuilder {
         let body = json!({
             "model": model,
-            "messages": [
-                { "role": "system", "content": system },
-                { "role": "user", "content": user_msg }
-            ],
+            "messages": openai_messages(system, messages),
             "temperature": 0.2,
             "max_tokens": 4096,
-            "stream": false
+            "stream": true
         });
 
-        let resp = self
-            .http
+        self.http
             .post("https://api.openai.com/v1/chat/completions")
             .header("Authorization", format!("Bearer {}", api_key))
             .header("Content-Type", "application/json")
             .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("OpenAI request failed: {}", e))?;
-
-        let data = Self::parse_response(resp, "OpenAI").await?;
-
-        data["choices"][0]["message"]["content"]
-            .as_str()
-            .map(|s| s.to_string())
-            .ok_or_else(|| format!("Unexpected OpenAI response format: {}", data))
     }
 
-    async fn prompt_anthropic(
+    fn request_anthropic(
         &self,
         api_key: &str,
         model: &str,
         system: &str,
-        user_msg: &str,
-    ) -> Result<String, String> {
+        messages: &[ChatMessage],
+    ) -> RequestBuilder {
         let body = json!({
             "model": model,
             "max_tokens": 4096,
             "system": system,
-            "messages": [
-                { "role": "user", "content": user_msg }
-            ]
+            "messages": messages,
+            "stream": true
         });
 
-        let resp = self
-            .http
+        self.http
             .post("https://api.anthropic.com/v1/messages")
             .header("x-api-key", api_key)
             .header("anthropic-version", "2023-06-01")
             .header("Content-Type", "application/json")
             .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Anthropic request failed: {}", e))?;
-
-        let data = Self::parse_response(resp, "Anthropic").await?;
-
-        data["content"][0]["text"]
-            .as_str()
-            .map(|s| s.to_string())
-            .ok_or_else(|| format!("Unexpected Anthropic response format: {}", data))
     }
 
-    async fn prompt_google(
+    fn request_google(
         &self,
         api_key: &str,
         model: &str,
         system: &str,
-        user_msg: &str,
-    ) -> Result<String, String> {
-        // v1beta supports system_instruction + all current models (gemini-2.0-flash, etc.)
+        messages: &[ChatMessage],
+    ) -> RequestBuilder {
+        // v1beta supports system_instruction + all current models (gemini-2.0-flash, etc.);
+        // alt=sse switches streamGenerateContent from a JSON array to server-sent events
         let url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
+            "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?alt=sse&key={}",
             model, api_key
         );
 
@@ -141,30 +433,17 @@ impl LlmClient {
             "system_instruction": {
                 "parts": [{ "text": system }]
             },
-            "contents": [{
-                "parts": [{ "text": user_msg }]
-            }],
+            "contents": gemini_contents(messages),
             "generationConfig": {
                 "temperature": 0.2,
                 "maxOutputTokens": 4096
             }
         });
 
-        let resp = self
-            .http
+        self.http
             .post(&url)
             .header("Content-Type", "application/json")
             .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Google AI request failed: {}", e))?;
-
-        let data = Self::parse_response(resp, "Google AI").await?;
-
-        data["candidates"][0]["content"]["parts"][0]["text"]
-            .as_str()
-            .map(|s| s.to_string())
-            .ok_or_else(|| format!("Unexpected Google AI response format: {}", data))
     }
 
     /// HuggingFace Inference Providers — OpenAI-compatible router endpoint.
@@ -172,78 +451,497 @@ impl LlmClient {
     /// Model is passed in the body. Append ":fastest"/":cheapest"/":preferred"
     /// or ":provider-name" (e.g. "meta-llama/Llama-3.1-8B-Instruct:together")
     /// to steer provider selection.
-    async fn prompt_huggingface(
+    fn request_huggingface(
         &self,
         api_key: &str,
         model: &str,
         system: &str,
-        user_msg: &str,
-    ) -> Result<String, String> {
+        messages: &[ChatMessage],
+    ) -> RequestBuilder {
         let body = json!({
             "model": model,
-            "messages": [
-                { "role": "system", "content": system },
-                { "role": "user", "content": user_msg }
-            ],
+            "messages": openai_messages(system, messages),
             "temperature": 0.2,
             "max_tokens": 4096,
-            "stream": false
+            "stream": true
         });
 
-        let resp = self
-            .http
+        self.http
             .post("https://router.huggingface.co/v1/chat/completions")
             .header("Authorization", format!("Bearer {}", api_key))
             .header("Content-Type", "application/json")
             .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("HuggingFace request failed: {}", e))?;
-
-        let data = Self::parse_response(resp, "HuggingFace").await?;
-
-        data["choices"][0]["message"]["content"]
-            .as_str()
-            .map(|s| s.to_string())
-            .ok_or_else(|| format!("Unexpected HuggingFace response format: {}", data))
     }
 
     /// OpenRouter — OpenAI-compatible, routes to 200+ models.
-    async fn prompt_openrouter(
+    fn request_openrouter(
         &self,
         api_key: &str,
         model: &str,
         system: &str,
-        user_msg: &str,
-    ) -> Result<String, String> {
+        messages: &[ChatMessage],
+    ) -> RequestBuilder {
         let body = json!({
             "model": model,
-            "messages": [
-                { "role": "system", "content": system },
-                { "role": "user", "content": user_msg }
-            ],
+            "messages": openai_messages(system, messages),
             "temperature": 0.2,
             "max_tokens": 4096,
-            "stream": false
+            "stream": true
         });
 
-        let resp = self
-            .http
+        self.http
             .post("https://openrouter.ai/api/v1/chat/completions")
             .header("Authorization", format!("Bearer {}", api_key))
             .header("Content-Type", "application/json")
             .header("HTTP-Referer", "https://github.com/aarav0180/Trace")
             .header("X-Title", "Trace")
             .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("OpenRouter request failed: {}", e))?;
+    }
 
-        let data = Self::parse_response(resp, "OpenRouter").await?;
+    /// Ollama's native chat API. Runs keyless against a local server.
+    fn request_ollama(
+        &self,
+        base_url: &str,
+        model: &str,
+        system: &str,
+        messages: &[ChatMessage],
+    ) -> RequestBuilder {
+        let body = json!({
+            "model": model,
+            "messages": openai_messages(system, messages),
+            "options": {
+                "temperature": 0.2,
+                "num_ctx": OLLAMA_NUM_CTX
+            },
+            "stream": true
+        });
 
-        data["choices"][0]["message"]["content"]
+        self.http
+            .post(format!(
+                "{}/api/chat",
+                base_url.trim().trim_end_matches('/')
+            ))
+            .header("Content-Type", "application/json")
+            .json(&body)
+    }
+
+    /// Any OpenAI-compatible server (llama.cpp, vLLM, LM Studio, LocalAI, …).
+    /// The key is optional; without one no Authorization header is sent.
+    fn request_custom(
+        &self,
+        base_url: &str,
+        api_key: &str,
+        model: &str,
+        system: &str,
+        messages: &[ChatMessage],
+    ) -> RequestBuilder {
+        let body = json!({
+            "model": model,
+            "messages": openai_messages(system, messages),
+            "temperature": 0.2,
+            "max_tokens": 4096,
+            "stream": true
+        });
+
+        let request = self
+            .http
+            .post(format!(
+                "{}/chat/completions",
+                base_url.trim().trim_end_matches('/')
+            ))
+            .header("Content-Type", "application/json")
+            .json(&body);
+        if api_key.is_empty() {
+            request
+        } else {
+            request.header("Authorization", format!("Bearer {}", api_key))
+        }
+    }
+}
+
+/// OpenAI-style message list: the system prompt travels as the first message.
+fn openai_messages(system: &str, messages: &[ChatMessage]) -> Value {
+    let mut list = vec![json!({ "role": "system", "content": system })];
+    list.extend(messages.iter().map(|m| json!(m)));
+    Value::Array(list)
+}
+
+/// Gemini calls the assistant "model" and wraps text in parts.
+fn gemini_contents(messages: &[ChatMessage]) -> Value {
+    messages
+        .iter()
+        .map(|m| {
+            let role = match m.role {
+                Role::User => "user",
+                Role::Assistant => "model",
+            };
+            json!({ "role": role, "parts": [{ "text": m.content }] })
+        })
+        .collect()
+}
+
+/// Vectors from an embedding response: OpenAI-style `data[].embedding` (ordered
+/// by `index`) or Ollama's `embeddings[]`.
+fn parse_embeddings(body: &Value, list: &str) -> Result<Vec<Vec<f32>>, String> {
+    let items = body[list]
+        .as_array()
+        .ok_or_else(|| format!("Embedding response has no `{}` list", list))?;
+    let mut indexed: Vec<(u64, Vec<f32>)> = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let (index, vector) = match item.get("embedding") {
+                Some(vector) => (item["index"].as_u64().unwrap_or(i as u64), vector),
+                None => (i as u64, item),
+            };
+            let vector = vector
+                .as_array()
+                .ok_or("Embedding is not a list of numbers")?
+                .iter()
+                .map(|x| x.as_f64().map(|x| x as f32))
+                .collect::<Option<Vec<f32>>>()
+                .ok_or("Embedding is not a list of numbers")?;
+            Ok((index, vector))
+        })
+        .collect::<Result<_, String>>()?;
+    indexed.sort_by_key(|(index, _)| *index);
+    Ok(indexed.into_iter().map(|(_, v)| v).collect())
+}
+
+/// Pull `body[list][*][field]` out of a model listing, sorted and deduplicated.
+fn parse_model_list(body: &Value, list: &str, field: &str) -> Vec<String> {
+    let mut models: Vec<String> = body[list]
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|m| m[field].as_str())
+                .map(|s| s.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    models.sort();
+    models.dedup();
+    models
+}
+
+// ─── Server-Sent Events ──────────────────────────────────
+
+/// One dispatched server-sent event.
+#[derive(Debug, Default, PartialEq)]
+struct SseEvent {
+    event: Option<String>,
+    data: String,
+}
+
+/// Incremental SSE decoder; network chunks may split lines anywhere.
+#[derive(Debug, Default)]
+struct SseParser {
+    buf: Vec<u8>,
+    pending: SseEvent,
+    /// Newline-delimited JSON instead of SSE: every non-empty line is one event.
+    ndjson: bool,
+}
+
+impl SseParser {
+    fn ndjson() -> Self {
+        Self {
+            ndjson: true,
+            ..Self::default()
+        }
+    }
+
+    /// Feed raw bytes, returning every event completed by them.
+    fn push(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        self.buf.extend_from_slice(bytes);
+        let mut events = Vec::new();
+
+        while let Some(newline) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if self.ndjson {
+                if !line.trim().is_empty() {
+                    events.push(SseEvent {
+                        event: None,
+                        data: line.to_string(),
+                    });
+                }
+                continue;
+            }
+            if line.is_empty() {
+                // Blank line dispatches the event
+                let event = std::mem::take(&mut self.pending);
+                if !event.data.is_empty() || event.event.is_some() {
+                    events.push(event);
+                }
+                continue;
+            }
+            if line.starts_with(':') {
+                continue;
+            }
+
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => self.pending.event = Some(value.to_string()),
+                "data" => {
+                    if !self.pending.data.is_empty() {
+                        self.pending.data.push('\n');
+                    }
+                    self.pending.data.push_str(value);
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+}
+
+/// What a single event contributes to the answer.
+#[derive(Debug, PartialEq)]
+enum Step {
+    Text(String),
+    Done,
+    Skip,
+}
+
+/// Extract the text delta (or end-of-stream marker) from one event.
+fn parse_event(dialect: Dialect, event: &SseEvent) -> Result<Step, String> {
+    if dialect == Dialect::OpenAi && event.data.trim() == "[DONE]" {
+        return Ok(Step::Done);
+    }
+    if dialect == Dialect::Anthropic && event.event.as_deref() == Some("message_stop") {
+        return Ok(Step::Done);
+    }
+    if event.data.is_empty() {
+        return Ok(Step::Skip);
+    }
+
+    let data: Value =
+        serde_json::from_str(&event.data).map_err(|e| format!("{} — event: {}", e, event.data))?;
+    if !data["error"].is_null() {
+        // Ollama sends `{"error":"…"}`, the cloud APIs `{"error":{"message":"…"}}`
+        return Err(data["error"]
             .as_str()
+            .or_else(|| data["error"]["message"].as_str())
             .map(|s| s.to_string())
-            .ok_or_else(|| format!("Unexpected OpenRouter response format: {}", data))
+            .unwrap_or_else(|| data["error"].to_string()));
+    }
+
+    let text = match dialect {
+        Dialect::OpenAi => data["choices"][0]["delta"]["content"]
+            .as_str()
+            .map(|s| s.to_string()),
+        Dialect::Anthropic => match data["type"].as_str() {
+            Some("content_block_delta") => data["delta"]["text"].as_str().map(|s| s.to_string()),
+            _ => None,
+        },
+        Dialect::Gemini => data["candidates"][0]["content"]["parts"]
+            .as_array()
+            .map(|parts| parts.iter().filter_map(|p| p["text"].as_str()).collect()),
+        Dialect::Ollama => {
+            if data["done"].as_bool() == Some(true) {
+                return Ok(Step::Done);
+            }
+            data["message"]["content"].as_str().map(|s| s.to_string())
+        }
+    };
+
+    Ok(match text {
+        Some(text) if !text.is_empty() => Step::Text(text),
+        _ => Step::Skip,
+    })
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(parser: &mut SseParser, chunks: &[&str]) -> Vec<SseEvent> {
+        chunks
+            .iter()
+            .flat_map(|c| parser.push(c.as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn sse_events_survive_arbitrary_chunking() {
+        let stream = "event: ping\r\ndata: {\"a\":1}\r\n\r\n: keep-alive\n\ndata: line one\ndata: line two\n\n";
+        let expected = vec![
+            SseEvent {
+                event: Some("ping".into()),
+                data: "{\"a\":1}".into(),
+            },
+            SseEvent {
+                event: None,
+                data: "line one\nline two".into(),
+            },
+        ];
+
+        assert_eq!(feed(&mut SseParser::default(), &[stream]), expected);
+        for split in 1..stream.len() {
+            let (a, b) = stream.split_at(split);
+            assert_eq!(
+                feed(&mut SseParser::default(), &[a, b]),
+                expected,
+                "split at {}",
+                split
+            );
+        }
+    }
+
+    #[test]
+    fn sse_handles_multibyte_split() {
+        let bytes = "data: héllo\n\n".as_bytes();
+        let mut parser = SseParser::default();
+        assert!(parser.push(&bytes[..8]).is_empty());
+        assert_eq!(parser.push(&bytes[8..])[0].data, "héllo");
+    }
+
+    #[test]
+    fn ndjson_lines_are_events() {
+        let stream = "{\"a\":1}\n\r\n{\"b\":2}\r\n";
+        let expected = vec![data("{\"a\":1}"), data("{\"b\":2}")];
+
+        for split in 1..stream.len() {
+            let (a, b) = stream.split_at(split);
+            assert_eq!(
+                feed(&mut SseParser::ndjson(), &[a, b]),
+                expected,
+                "split at {}",
+                split
+            );
+        }
+        // "data:" has no special meaning outside SSE
+        assert_eq!(
+            feed(&mut SseParser::ndjson(), &["data: x\n"]),
+            vec![data("data: x")]
+        );
+    }
+
+    #[test]
+    fn model_lists() {
+        let ollama = json!({ "models": [
+            { "name": "qwen2.5-coder:7b", "size": 1 },
+            { "name": "llama3.2:latest" },
+            { "model": "no-name" }
+        ]});
+        assert_eq!(
+            parse_model_list(&ollama, "models", "name"),
+            vec!["llama3.2:latest", "qwen2.5-coder:7b"]
+        );
+
+        let openai =
+            json!({ "object": "list", "data": [{ "id": "b" }, { "id": "a" }, { "id": "a" }] });
+        assert_eq!(parse_model_list(&openai, "data", "id"), vec!["a", "b"]);
+        assert!(parse_model_list(&json!({}), "data", "id").is_empty());
+    }
+
+    #[test]
+    fn embedding_responses() {
+        let openai = json!({ "data": [
+            { "index": 1, "embedding": [0.5, 1.0] },
+            { "index": 0, "embedding": [1.0, 0.0] }
+        ]});
+        assert_eq!(
+            parse_embeddings(&openai, "data").unwrap(),
+            vec![vec![1.0, 0.0], vec![0.5, 1.0]]
+        );
+
+        let ollama = json!({ "model": "nomic-embed-text", "embeddings": [[0.25], [0.75]] });
+        assert_eq!(
+            parse_embeddings(&ollama, "embeddings").unwrap(),
+            vec![vec![0.25], vec![0.75]]
+        );
+
+        assert!(parse_embeddings(&json!({ "data": [{ "embedding": "x" }] }), "data").is_err());
+        assert!(parse_embeddings(&json!({}), "embeddings").is_err());
+    }
+
+    fn data(json: &str) -> SseEvent {
+        SseEvent {
+            event: None,
+            data: json.to_string(),
+        }
+    }
+
+    #[test]
+    fn provider_deltas() {
+        let text = |s: &str| Ok(Step::Text(s.to_string()));
+        let cases = [
+            (Dialect::OpenAi, data(r#"{"choices":[{"delta":{"content":"Hi"}}]}"#), text("Hi")),
+            (Dialect::OpenAi, data(r#"{"choices":[{"delta":{"role":"assistant"}}]}"#), Ok(Step::Skip)),
+            (Dialect::OpenAi, data("[DONE]"), Ok(Step::Done)),
+            (
+                Dialect::Anthropic,
+                SseEvent {
+                    event: Some("content_block_delta".into()),
+                    data: r#"{"type":"content_block_delta","delta":{"type":"text_delta","text":"ls"}}"#.into(),
+                },
+                text("ls"),
+            ),
+            (
+                Dialect::Anthropic,
+                SseEvent {
+                    event: Some("message_start".into()),
+                    data: r#"{"type":"message_start","message":{}}"#.into(),
+                },
+                Ok(Step::Skip),
+            ),
+            (
+                Dialect::Anthropic,
+                SseEvent {
+                    event: Some("message_stop".into()),
+                    data: r#"{"type":"message_stop"}"#.into(),
+                },
+                Ok(Step::Done),
+            ),
+            (
+                Dialect::Gemini,
+                data(r#"{"candidates":[{"content":{"parts":[{"text":"a"},{"text":"b"}]}}]}"#),
+                text("ab"),
+            ),
+            (
+                Dialect::Anthropic,
+                data(r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#),
+                Err("Overloaded".to_string()),
+            ),
+            (
+                Dialect::Ollama,
+                data(r#"{"model":"llama3.2","message":{"role":"assistant","content":"gr"},"done":false}"#),
+                text("gr"),
+            ),
+            (
+                Dialect::Ollama,
+                data(r#"{"model":"llama3.2","message":{"role":"assistant","content":""},"done":true}"#),
+                Ok(Step::Done),
+            ),
+            (
+                Dialect::Ollama,
+                data(r#"{"error":"model 'llama9' not found"}"#),
+                Err("model 'llama9' not found".to_string()),
+            ),
+        ];
+
+        for (dialect, event, expected) in cases {
+            assert_eq!(parse_event(dialect, &event), expected, "{:?}", event);
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_token_wakes_waiters() {
+        let token = CancelToken::default();
+        let waiter = {
+            let token = token.clone();
+            tokio::spawn(async move { token.cancelled().await })
+        };
+        token.cancel();
+        waiter.await.unwrap();
+        // Already-cancelled tokens resolve immediately
+        token.cancelled().await;
     }
 }
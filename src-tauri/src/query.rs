@@ -0,0 +1,520 @@
+/// Search query language.
+///
+/// A query is a whitespace-separated list of terms. Bare words are fuzzy-matched
+/// against names as before; everything else narrows the candidate set:
+///
+///   ext:rs,toml       extension (files only, comma = any of)
+///   kind:dir          file | dir | app
+///   in:~/work         inside a directory (absolute), or under a folder named `work`
+///   size:>10mb        size with < <= > >= = (bare number = exact); b, kb, mb, gb, tb
+///   modified:<7d      age with s, m, h, d, w, mo, y (bare = within)
+///   "two words"       name must contain the phrase (also fuzzy-matched)
+///   -word, -ext:log   negation of any term or filter
+///
+/// Filter values may be quoted (`in:"~/My Documents"`). Any other `word:` prefix,
+/// or a key with nothing after it, is plain text (`TODO:`, `http://host`, `C:\Users`).
+use crate::indexer::{EntryKind, FileEntry};
+use std::path::{Path, PathBuf};
+
+// ─── Types ───────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cmp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// Case-insensitive substring of the name.
+    Name(String),
+    /// Lowercased extensions without the leading dot.
+    Ext(Vec<String>),
+    Kind(EntryKind),
+    /// Absolute directory prefix.
+    InDir(PathBuf),
+    /// Folder name (or `a/b` sequence) anywhere above the entry.
+    InFolder(String),
+    Size(Cmp, u64),
+    /// Age in seconds, compared against `now - modified`.
+    Modified(Cmp, u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clause {
+    pub negated: bool,
+    pub filter: Filter,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    /// Words and phrases handed to the fuzzy matcher.
+    pub terms: Vec<String>,
+    pub clauses: Vec<Clause>,
+}
+
+/// One whitespace-delimited piece of the input, before interpretation.
+struct Token {
+    column: usize,
+    negated: bool,
+    key: Option<String>,
+    value: String,
+    quoted: bool,
+    /// The token as typed, minus a leading `-` and quote marks
+    text: String,
+}
+
+// ─── Parser ──────────────────────────────────────────────
+
+impl Query {
+    /// Parse a raw search string. Errors name the offending term and its column.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut query = Query::default();
+
+        for token in tokenize(input)? {
+            let Token {
+                column,
+                negated,
+                key,
+                value,
+                quoted,
+                text,
+            } = token;
+
+            let filter = match &key {
+                Some(key) if !value.is_empty() => parse_filter(key, &value, column)?,
+                _ => None,
+            };
+            if let Some(filter) = filter {
+                query.clauses.push(Clause { negated, filter });
+                continue;
+            }
+
+            let value = if key.is_some() { text } else { value };
+            if value.is_empty() {
+                continue;
+            }
+            if quoted || negated {
+                query.clauses.push(Clause {
+                    negated,
+                    filter: Filter::Name(value.to_lowercase()),
+                });
+            }
+            if !negated {
+                query.terms.push(value);
+            }
+        }
+
+        Ok(query)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.clauses.is_empty()
+    }
+
+    /// The pattern for the fuzzy matcher (empty for filter-only queries).
+    pub fn text(&self) -> String {
+        self.terms.join(" ")
+    }
+
+    /// Returns true if `entry` passes every clause. `now` is a unix timestamp.
+    pub fn matches(&self, entry: &FileEntry, now: u64) -> bool {
+        self.clauses
+            .iter()
+            .all(|c| c.filter.matches(entry, now) != c.negated)
+    }
+}
+
+/// Split the input into tokens, honouring quotes and a leading `-`.
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().enumerate().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut token = Token {
+            column: start + 1,
+            negated: false,
+            key: None,
+            value: String::new(),
+            quoted: false,
+            text: String::new(),
+        };
+
+        // A lone "-" is an ordinary term
+        if c == '-' {
+            chars.next();
+            match chars.peek() {
+                Some(&(_, next)) if !next.is_whitespace() => token.negated = true,
+                _ => {
+                    token.value.push('-');
+                    token.text.push('-');
+                }
+            }
+        }
+
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            match c {
+                '"' => {
+                    token.quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some((_, '"')) => break,
+                            Some((_, c)) => {
+                                token.value.push(c);
+                                token.text.push(c);
+                            }
+                            None => {
+                                return Err(format!(
+                                    "Unterminated quote starting at column {}",
+                                    i + 1
+                                ))
+                            }
+                        }
+                    }
+                }
+                ':' if token.key.is_none()
+                    && !token.quoted
+                    && !token.value.is_empty()
+                    && token.value.chars().all(|c| c.is_ascii_alphabetic()) =>
+                {
+                    token.key = Some(token.value.to_lowercase());
+                    token.value.clear();
+                    token.text.push(':');
+                }
+                c => {
+                    token.value.push(c);
+                    token.text.push(c);
+                }
+            }
+        }
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// The filter a `key:value` token stands for, or `None` if `key` is not a filter.
+fn parse_filter(key: &str, value: &str, column: usize) -> Result<Option<Filter>, String> {
+    let filter = match key {
+        "ext" => Filter::Ext(
+            value
+                .split(',')
+                .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+                .filter(|e| !e.is_empty())
+                .collect(),
+        ),
+        "kind" | "type" => Filter::Kind(parse_kind(value).ok_or_else(|| {
+            format!(
+                "Unknown kind '{}' (column {}) — use file, dir or app",
+                value, column
+            )
+        })?),
+        "in" | "path" => parse_location(value),
+        "size" => {
+            let (cmp, rest) = split_cmp(value, Cmp::Eq);
+            let bytes = parse_size(rest).ok_or_else(|| {
+                format!(
+                    "Invalid size '{}' (column {}) — try size:>10mb",
+                    value, column
+                )
+            })?;
+            Filter::Size(cmp, bytes)
+        }
+        "modified" | "mtime" => {
+            let (cmp, rest) = split_cmp(value, Cmp::Le);
+            let secs = parse_age(rest).ok_or_else(|| {
+                format!(
+                    "Invalid age '{}' (column {}) — try modified:<7d",
+                    value, column
+                )
+            })?;
+            Filter::Modified(cmp, secs)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(filter))
+}
+
+fn parse_kind(value: &str) -> Option<EntryKind> {
+    match value.to_lowercase().as_str() {
+        "file" | "f" => Some(EntryKind::File),
+        "dir" | "directory" | "folder" | "d" => Some(EntryKind::Directory),
+        "app" | "application" => Some(EntryKind::App),
+        _ => None,
+    }
+}
+
+/// `~/x` and absolute paths become a prefix; anything else a folder name.
+fn parse_location(value: &str) -> Filter {
+    let expanded = if value == "~" {
+        dirs::home_dir()
+    } else {
+        value
+            .strip_prefix("~/")
+            .and_then(|rest| dirs::home_dir().map(|h| h.join(rest)))
+    };
+    let path = expanded.unwrap_or_else(|| PathBuf::from(value));
+
+    if path.is_absolute() {
+        Filter::InDir(path)
+    } else {
+        Filter::InFolder(value.trim_matches('/').to_lowercase())
+    }
+}
+
+/// Strip a leading comparison operator, falling back to `default`.
+fn split_cmp(value: &str, default: Cmp) -> (Cmp, &str) {
+    for (prefix, cmp) in [
+        (">=", Cmp::Ge),
+        ("<=", Cmp::Le),
+        (">", Cmp::Gt),
+        ("<", Cmp::Lt),
+        ("=", Cmp::Eq),
+    ] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            return (cmp, rest);
+        }
+    }
+    (default, value)
+}
+
+/// Split "1.5mb" into (1.5, "mb").
+fn split_number(value: &str) -> Option<(f64, String)> {
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let number: f64 = value[..end].parse().ok()?;
+    Some((number, value[end..].to_lowercase()))
+}
+
+fn parse_size(value: &str) -> Option<u64> {
+    let (number, unit) = split_number(value)?;
+    let scale: u64 = match unit.as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        "t" | "tb" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * scale as f64) as u64)
+}
+
+fn parse_age(value: &str) -> Option<u64> {
+    let (number, unit) = split_number(value)?;
+    let scale: u64 = match unit.as_str() {
+        "s" | "sec" => 1,
+        "m" | "min" => 60,
+        "h" | "hr" => 3600,
+        "d" | "day" | "days" => 86_400,
+        "w" | "wk" => 7 * 86_400,
+        "mo" => 30 * 86_400,
+        "y" | "yr" => 365 * 86_400,
+        _ => return None,
+    };
+    Some((number * scale as f64) as u64)
+}
+
+// ─── Evaluation ──────────────────────────────────────────
+
+impl Cmp {
+    fn test(self, actual: u64, expected: u64) -> bool {
+        match self {
+            Cmp::Lt => actual < expected,
+            Cmp::Le => actual <= expected,
+            Cmp::Gt => actual > expected,
+            Cmp::Ge => actual >= expected,
+            Cmp::Eq => actual == expected,
+        }
+    }
+}
+
+impl Filter {
+    fn matches(&self, entry: &FileEntry, now: u64) -> bool {
+        match self {
+            Filter::Name(needle) => entry.name.to_lowercase().contains(needle),
+            Filter::Ext(exts) => {
+                entry.kind == EntryKind::File
+                    && Path::new(&entry.name)
+                        .extension()
+                        .map(|e| e.to_string_lossy().to_lowercase())
+                        .is_some_and(|e| exts.contains(&e))
+            }
+            Filter::Kind(kind) => entry.kind == *kind,
+            Filter::InDir(dir) => {
+                let path = Path::new(&entry.path);
+                path != dir && path.starts_with(dir)
+            }
+            Filter::InFolder(folder) => {
+                let parent = Path::new(&entry.path)
+                    .parent()
+                    .map(|p| p.to_string_lossy().replace('\\', "/").to_lowercase())
+                    .unwrap_or_default();
+                format!("{}/", parent).contains(&format!("/{}/", folder))
+            }
+            // Apps carry no size or mtime, so they never satisfy these
+            Filter::Size(cmp, bytes) => entry.kind != EntryKind::App && cmp.test(entry.size, *bytes),
+            Filter::Modified(cmp, secs) => {
+                entry.modified > 0 && cmp.test(now.saturating_sub(entry.modified), *secs)
+            }
+        }
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+    const DAY: u64 = 86_400;
+
+    fn entry(path: &str, kind: EntryKind, size: u64, age: u64) -> FileEntry {
+        FileEntry {
+            name: Path::new(path)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .to_string(),
+            path: path.to_string(),
+            kind,
+            size,
+            modified: NOW - age,
+            icon_path: None,
+            keywords: None,
+            generic_name: None,
+        }
+    }
+
+    #[test]
+    fn parses_terms_and_filters() {
+        let cases = [
+            ("main", vec!["main"], vec![]),
+            ("foo bar", vec!["foo", "bar"], vec![]),
+            ("ext:rs", vec![], vec![(false, Filter::Ext(vec!["rs".into()]))]),
+            (
+                "ext:.RS,toml main",
+                vec!["main"],
+                vec![(false, Filter::Ext(vec!["rs".into(), "toml".into()]))],
+            ),
+            ("kind:dir", vec![], vec![(false, Filter::Kind(EntryKind::Directory))]),
+            ("type:app", vec![], vec![(false, Filter::Kind(EntryKind::App))]),
+            ("-kind:file", vec![], vec![(true, Filter::Kind(EntryKind::File))]),
+            ("size:>10mb", vec![], vec![(false, Filter::Size(Cmp::Gt, 10 << 20))]),
+            ("size:<=1.5k", vec![], vec![(false, Filter::Size(Cmp::Le, 1536))]),
+            ("size:512", vec![], vec![(false, Filter::Size(Cmp::Eq, 512))]),
+            ("modified:<7d", vec![], vec![(false, Filter::Modified(Cmp::Lt, 7 * DAY))]),
+            ("modified:>1y", vec![], vec![(false, Filter::Modified(Cmp::Gt, 365 * DAY))]),
+            ("mtime:2h", vec![], vec![(false, Filter::Modified(Cmp::Le, 7200))]),
+            ("in:src", vec![], vec![(false, Filter::InFolder("src".into()))]),
+            ("in:/tmp/x", vec![], vec![(false, Filter::InDir("/tmp/x".into()))]),
+            (
+                "in:\"/tmp/My Docs\"",
+                vec![],
+                vec![(false, Filter::InDir("/tmp/My Docs".into()))],
+            ),
+            (
+                "\"Read Me\"",
+                vec!["Read Me"],
+                vec![(false, Filter::Name("read me".into()))],
+            ),
+            ("-test", vec![], vec![(true, Filter::Name("test".into()))]),
+            (
+                "-\"draft copy\" notes",
+                vec!["notes"],
+                vec![(true, Filter::Name("draft copy".into()))],
+            ),
+            ("a - b", vec!["a", "-", "b"], vec![]),
+            ("12:30", vec!["12:30"], vec![]),
+            // Unknown keys and empty values are ordinary text
+            ("TODO: fix", vec!["TODO:", "fix"], vec![]),
+            ("http://host/x", vec!["http://host/x"], vec![]),
+            ("C:\\Users", vec!["C:\\Users"], vec![]),
+            ("colour:red", vec!["colour:red"], vec![]),
+            ("ext:", vec!["ext:"], vec![]),
+            ("-todo:", vec![], vec![(true, Filter::Name("todo:".into()))]),
+            ("\"\"", vec![], vec![]),
+        ];
+
+        for (input, terms, clauses) in cases {
+            let q = Query::parse(input).unwrap_or_else(|e| panic!("{}: {}", input, e));
+            assert_eq!(q.terms, terms, "terms of {:?}", input);
+            let got: Vec<(bool, Filter)> =
+                q.clauses.into_iter().map(|c| (c.negated, c.filter)).collect();
+            assert_eq!(got, clauses, "clauses of {:?}", input);
+        }
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let cases = [
+            ("foo size:big", "Invalid size 'big' (column 5)"),
+            ("size:>10zb", "Invalid size '>10zb'"),
+            ("modified:<7", "Invalid age '<7'"),
+            ("kind:socket", "Unknown kind 'socket'"),
+            ("\"unclosed", "Unterminated quote starting at column 1"),
+            ("in:\"a b", "Unterminated quote starting at column 4"),
+        ];
+
+        for (input, expected) in cases {
+            let err = Query::parse(input).expect_err(input);
+            assert!(err.contains(expected), "{:?}: got {:?}", input, err);
+        }
+    }
+
+    #[test]
+    fn evaluates_against_entries() {
+        let rs = entry("/home/u/work/src/main.rs", EntryKind::File, 2048, DAY);
+        let log = entry("/home/u/work/build.LOG", EntryKind::File, 50 << 20, 30 * DAY);
+        let dir = entry("/home/u/work/src", EntryKind::Directory, 0, 2 * DAY);
+        let app = FileEntry {
+            modified: 0,
+            ..entry("/usr/share/applications/firefox.desktop", EntryKind::App, 0, 0)
+        };
+        let all = [&rs, &log, &dir, &app];
+
+        let cases: &[(&str, &[&FileEntry])] = &[
+            ("", &[&rs, &log, &dir, &app]),
+            ("ext:rs", &[&rs]),
+            ("ext:log", &[&log]),
+            ("-ext:log", &[&rs, &dir, &app]),
+            ("kind:dir", &[&dir]),
+            ("size:>10mb", &[&log]),
+            ("size:<1k", &[&dir]),
+            ("modified:<7d", &[&rs, &dir]),
+            ("modified:>7d", &[&log]),
+            ("in:/home/u/work", &[&rs, &log, &dir]),
+            ("in:/home/u/work/src", &[&rs]),
+            ("in:src", &[&rs]),
+            ("in:u/work", &[&rs, &log, &dir]),
+            ("\"main.r\"", &[&rs]),
+            ("-main", &[&log, &dir, &app]),
+            ("kind:file -in:src", &[&log]),
+        ];
+
+        for (input, expected) in cases {
+            let q = Query::parse(input).unwrap();
+            let got: Vec<&str> = all
+                .iter()
+                .filter(|e| q.matches(e, NOW))
+                .map(|e| e.path.as_str())
+                .collect();
+            let want: Vec<&str> = expected.iter().map(|e| e.path.as_str()).collect();
+            assert_eq!(got, want, "{:?}", input);
+        }
+    }
+}
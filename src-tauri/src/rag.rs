@@ -0,0 +1,447 @@
+/// Retrieval for document chat.
+///
+/// Documents too long for the model's context are split into line-aligned chunks,
+/// ranked against the question with BM25 (fused with embedding similarity when an
+/// embedding provider is configured), and only the best chunks are sent, each
+/// labelled `[n]` with its line (and PDF page) range so the answer can cite them.
+use crate::content_index::tokenize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Target chunk size. Chunks end on a line break unless one line alone is longer.
+const CHUNK_CHARS: usize = 1_500;
+
+/// BM25 term-frequency saturation and length normalisation.
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+/// Reciprocal-rank-fusion damping: higher values flatten the difference between ranks.
+const RRF_K: f64 = 60.0;
+
+/// Documents with more chunks than this are ranked lexically only, since
+/// embedding them all would take too long.
+pub const MAX_EMBEDDED_CHUNKS: usize = 2_000;
+
+/// Page separator in extracted PDF text (see `doc_chat::extract_text`).
+pub const PAGE_BREAK: char = '\x0c';
+
+/// A line-aligned slice of a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    /// Byte range in the document text
+    pub start: usize,
+    pub end: usize,
+    /// 1-based, inclusive
+    pub first_line: usize,
+    pub last_line: usize,
+    /// 1-based, inclusive; `None` for documents without page breaks
+    pub pages: Option<(usize, usize)>,
+}
+
+/// Embedding vectors for every chunk, tagged with the model that produced them.
+pub struct ChunkEmbeddings {
+    pub model: String,
+    pub vectors: Vec<Vec<f32>>,
+}
+
+/// A document split into chunks, with the statistics BM25 needs.
+pub struct Document {
+    pub path: String,
+    pub text: String,
+    pub chunks: Vec<Chunk>,
+    /// Per chunk: term → occurrences
+    terms: Vec<HashMap<String, u32>>,
+    /// Per chunk: number of terms
+    lengths: Vec<u32>,
+    avg_length: f64,
+    /// term → number of chunks containing it
+    chunk_freq: HashMap<String, u32>,
+    /// Computed on the first question that needs them
+    embeddings: Mutex<Option<Arc<ChunkEmbeddings>>>,
+}
+
+/// One excerpt sent to the model, as reported back to the UI.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Citation {
+    /// The `[n]` the excerpt was labelled with
+    pub label: usize,
+    pub path: String,
+    pub first_line: usize,
+    pub last_line: usize,
+    pub pages: Option<(usize, usize)>,
+    /// Whether the answer actually referred to `[label]`
+    pub cited: bool,
+}
+
+impl Chunk {
+    /// "lines 12–40, page 3" style location.
+    pub fn describe(&self) -> String {
+        let mut out = if self.first_line == self.last_line {
+            format!("line {}", self.first_line)
+        } else {
+            format!("lines {}–{}", self.first_line, self.last_line)
+        };
+        match self.pages {
+            Some((a, b)) if a == b => out.push_str(&format!(", page {}", a)),
+            Some((a, b)) => out.push_str(&format!(", pages {}–{}", a, b)),
+            None => {}
+        }
+        out
+    }
+}
+
+/// Split `text` into pieces of at most `CHUNK_CHARS`, each either ending a line
+/// or being part of a line too long to keep whole.
+fn pieces(text: &str) -> impl Iterator<Item = &str> {
+    text.split_inclusive('\n').flat_map(|line| {
+        let mut rest = line;
+        std::iter::from_fn(move || {
+            if rest.is_empty() {
+                return None;
+            }
+            let mut cut = rest.len().min(CHUNK_CHARS);
+            while !rest.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            let (piece, tail) = rest.split_at(cut);
+            rest = tail;
+            Some(piece)
+        })
+    })
+}
+
+/// Cut `text` into consecutive chunks of about `CHUNK_CHARS`.
+fn split_chunks(text: &str) -> Vec<Chunk> {
+    let paged = text.contains(PAGE_BREAK);
+    let mut chunks = Vec::new();
+    let mut current: Option<Chunk> = None;
+    let (mut pos, mut line, mut page) = (0, 1, 1);
+
+    for piece in pieces(text) {
+        page += piece.matches(PAGE_BREAK).count();
+        let end = pos + piece.len();
+        if let Some(chunk) = current.take_if(|c| end - c.start > CHUNK_CHARS) {
+            chunks.push(chunk);
+        }
+        let chunk = current.get_or_insert(Chunk {
+            start: pos,
+            end: pos,
+            first_line: line,
+            last_line: line,
+            pages: paged.then_some((page, page)),
+        });
+        chunk.end = end;
+        chunk.last_line = line;
+        if let Some(pages) = &mut chunk.pages {
+            pages.1 = page;
+        }
+
+        pos = end;
+        if piece.ends_with('\n') {
+            line += 1;
+        }
+    }
+    chunks.extend(current);
+    chunks
+}
+
+impl Document {
+    pub fn new(path: String, text: String) -> Self {
+        let chunks = split_chunks(&text);
+        let mut terms = Vec::with_capacity(chunks.len());
+        let mut lengths = Vec::with_capacity(chunks.len());
+        let mut chunk_freq: HashMap<String, u32> = HashMap::new();
+
+        for chunk in &chunks {
+            let mut counts: HashMap<String, u32> = HashMap::new();
+            let mut length = 0;
+            for term in tokenize(&text[chunk.start..chunk.end]) {
+                *counts.entry(term).or_insert(0) += 1;
+                length += 1;
+            }
+            for term in counts.keys() {
+                *chunk_freq.entry(term.clone()).or_insert(0) += 1;
+            }
+            terms.push(counts);
+            lengths.push(length);
+        }
+
+        let avg_length = if chunks.is_empty() {
+            0.0
+        } else {
+            lengths.iter().map(|&l| l as f64).sum::<f64>() / chunks.len() as f64
+        };
+
+        Self {
+            path,
+            text,
+            chunks,
+            terms,
+            lengths,
+            avg_length,
+            chunk_freq,
+            embeddings: Mutex::new(None),
+        }
+    }
+
+    pub fn chunk_text(&self, index: usize) -> &str {
+        let chunk = &self.chunks[index];
+        &self.text[chunk.start..chunk.end]
+    }
+
+    /// BM25 score of every chunk for `query`.
+    pub fn bm25(&self, query: &str) -> Vec<f64> {
+        let mut query_terms: Vec<String> = tokenize(query).collect();
+        query_terms.sort();
+        query_terms.dedup();
+
+        let n = self.chunks.len() as f64;
+        let idf: Vec<(&String, f64)> = query_terms
+            .iter()
+            .filter_map(|t| {
+                let df = *self.chunk_freq.get(t)? as f64;
+                Some((t, (1.0 + (n - df + 0.5) / (df + 0.5)).ln()))
+            })
+            .collect();
+
+        self.terms
+            .iter()
+            .zip(&self.lengths)
+            .map(|(counts, &length)| {
+                let norm =
+                    BM25_K1 * (1.0 - BM25_B + BM25_B * length as f64 / self.avg_length.max(1.0));
+                idf.iter()
+                    .map(|(term, idf)| {
+                        let tf = counts.get(*term).copied().unwrap_or(0) as f64;
+                        idf * tf * (BM25_K1 + 1.0) / (tf + norm)
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Cached chunk embeddings, if they were computed with `model`.
+    pub fn embeddings(&self, model: &str) -> Option<Arc<ChunkEmbeddings>> {
+        let cached = self.embeddings.lock().ok()?;
+        cached.as_ref().filter(|e| e.model == model).cloned()
+    }
+
+    pub fn set_embeddings(&self, embeddings: ChunkEmbeddings) -> Arc<ChunkEmbeddings> {
+        let embeddings = Arc::new(embeddings);
+        if let Ok(mut cached) = self.embeddings.lock() {
+            *cached = Some(embeddings.clone());
+        }
+        embeddings
+    }
+
+    /// Chunk indices, most relevant first. Chunks that match nothing are left out.
+    /// With `similarity` (one cosine score per chunk) the lexical and semantic
+    /// rankings are merged by reciprocal rank fusion.
+    pub fn rank(&self, query: &str, similarity: Option<&[f64]>) -> Vec<usize> {
+        let bm25 = self.bm25(query);
+        let lexical = ranked_indices(&bm25);
+        let Some(similarity) = similarity else {
+            return lexical;
+        };
+
+        let mut fused = vec![0.0; self.chunks.len()];
+        for ranking in [lexical, ranked_indices(similarity)] {
+            for (rank, &i) in ranking.iter().enumerate() {
+                fused[i] += 1.0 / (RRF_K + rank as f64 + 1.0);
+            }
+        }
+        ranked_indices(&fused)
+    }
+
+    /// Pick chunks in `ranking` order until `budget` bytes are used, and return them
+    /// in document order. Without any ranking, chunks spread evenly over the
+    /// document are used instead, so broad questions see more than the opening.
+    pub fn select(&self, ranking: &[usize], budget: usize) -> Vec<usize> {
+        let spread;
+        let order = if ranking.is_empty() {
+            spread = self.spread(budget);
+            &spread
+        } else {
+            ranking
+        };
+
+        let mut used = 0;
+        let mut picked = Vec::new();
+        for &i in order {
+            let len = self.chunks[i].end - self.chunks[i].start;
+            if used + len <= budget {
+                used += len;
+                picked.push(i);
+            }
+        }
+        picked.sort_unstable();
+        picked
+    }
+
+    /// Evenly spaced chunk indices, about as many as fit in `budget`.
+    fn spread(&self, budget: usize) -> Vec<usize> {
+        let n = self.chunks.len();
+        if n == 0 {
+            return Vec::new();
+        }
+        let want = (budget / CHUNK_CHARS).clamp(1, n);
+        (0..want).map(|k| k * n / want).collect()
+    }
+}
+
+/// Indices with a positive score, highest score first.
+fn ranked_indices(scores: &[f64]) -> Vec<usize> {
+    let mut ranked: Vec<usize> = (0..scores.len()).filter(|&i| scores[i] > 0.0).collect();
+    ranked.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+    ranked
+}
+
+/// Cosine similarity of `query` against each vector.
+pub fn cosine_scores(query: &[f32], vectors: &[Vec<f32>]) -> Vec<f64> {
+    let norm = |v: &[f32]| v.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+    let query_norm = norm(query);
+    vectors
+        .iter()
+        .map(|v| {
+            let denom = query_norm * norm(v);
+            if denom == 0.0 {
+                return 0.0;
+            }
+            let dot: f64 = query
+                .iter()
+                .zip(v)
+                .map(|(a, b)| *a as f64 * *b as f64)
+                .sum();
+            dot / denom
+        })
+        .collect()
+}
+
+/// Whether `answer` refers to excerpt `[label]`.
+pub fn is_cited(answer: &str, label: usize) -> bool {
+    answer.contains(&format!("[{}]", label))
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered_lines(n: usize) -> String {
+        (1..=n)
+            .map(|i| format!("line number {:04} filler text\n", i))
+            .collect()
+    }
+
+    #[test]
+    fn chunks_cover_the_text_on_line_boundaries() {
+        let text = numbered_lines(500);
+        let doc = Document::new("/log".into(), text.clone());
+
+        assert!(doc.chunks.len() > 1);
+        assert_eq!(doc.chunks[0].start, 0);
+        assert_eq!(doc.chunks.last().unwrap().end, text.len());
+        for pair in doc.chunks.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+            assert_eq!(pair[0].last_line + 1, pair[1].first_line);
+        }
+        for (i, chunk) in doc.chunks.iter().enumerate() {
+            assert!(chunk.end - chunk.start <= CHUNK_CHARS);
+            let first = format!("line number {:04}", chunk.first_line);
+            assert!(doc.chunk_text(i).starts_with(&first));
+            assert!(doc.chunk_text(i).ends_with('\n'));
+            assert_eq!(chunk.pages, None);
+        }
+    }
+
+    #[test]
+    fn long_lines_are_split_on_char_boundaries() {
+        let text = format!("{}\nshort\n", "é".repeat(CHUNK_CHARS));
+        let chunks = split_chunks(&text);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks
+            .iter()
+            .all(|c| c.first_line == 1 || c.first_line == 2));
+        assert_eq!(chunks[2].last_line, 2);
+    }
+
+    #[test]
+    fn pdf_pages_are_tracked() {
+        let text = format!(
+            "intro\n{}second page\n{}third page\n",
+            PAGE_BREAK, PAGE_BREAK
+        );
+        let chunks = split_chunks(&text);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].pages, Some((1, 3)));
+        assert_eq!(chunks[0].describe(), "lines 1–3, pages 1–3");
+    }
+
+    #[test]
+    fn bm25_finds_the_relevant_chunk_at_the_end() {
+        let mut text = numbered_lines(2_000);
+        text.push_str("fn checksum_overflow() { panic!(\"overflow in checksum\") }\n");
+        let doc = Document::new("/big.rs".into(), text);
+
+        let ranking = doc.rank("where does the checksum overflow?", None);
+        let last = doc.chunks.len() - 1;
+        assert_eq!(ranking.first(), Some(&last));
+
+        let picked = doc.select(&ranking, 4_000);
+        assert!(picked.contains(&last));
+        let used: usize = picked
+            .iter()
+            .map(|&i| doc.chunks[i].end - doc.chunks[i].start)
+            .sum();
+        assert!(used <= 4_000);
+        assert!(picked.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn unmatched_questions_sample_the_whole_document() {
+        let doc = Document::new("/log".into(), numbered_lines(2_000));
+        assert!(doc.rank("summarize please", None).is_empty());
+
+        let picked = doc.select(&[], 6_000);
+        assert!(picked.len() >= 2);
+        assert_eq!(picked[0], 0);
+        assert!(*picked.last().unwrap() > doc.chunks.len() / 2);
+    }
+
+    #[test]
+    fn fusion_uses_semantic_matches() {
+        let doc = Document::new(
+            "/notes".into(),
+            format!(
+                "{}\nthe cat sat\n{}",
+                "alpha ".repeat(400),
+                "beta ".repeat(400)
+            ),
+        );
+        let lexical = doc.rank("cat", None);
+        assert_eq!(lexical.len(), 1);
+
+        // A semantic hit that shares no words with the query still gets ranked
+        let mut similarity = vec![0.0; doc.chunks.len()];
+        let semantic = doc.chunks.len() - 1;
+        similarity[semantic] = 0.9;
+        let fused = doc.rank("cat", Some(&similarity));
+        assert_eq!(fused.len(), 2);
+        assert!(fused.contains(&semantic));
+    }
+
+    #[test]
+    fn cosine_and_citations() {
+        let scores = cosine_scores(
+            &[1.0, 0.0],
+            &[vec![2.0, 0.0], vec![0.0, 1.0], vec![0.0, 0.0]],
+        );
+        assert_eq!(scores, vec![1.0, 0.0, 0.0]);
+
+        assert!(is_cited("see [2] and [10]", 2));
+        assert!(is_cited("see [2] and [10]", 10));
+        assert!(!is_cited("see [2] and [10]", 1));
+    }
+}
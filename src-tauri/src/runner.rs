@@ -0,0 +1,456 @@
+/// Runs shell commands as child processes, streaming their output as it arrives.
+///
+/// Output is delivered a line at a time (a trailing partial line, such as a
+/// "Continue? [Y/n]" prompt, is flushed after a short pause). While a command
+/// runs it can be fed stdin, killed, or stopped by a timeout; the whole process
+/// group goes down with it, so `find | grep` pipelines don't outlive a kill.
+use crate::llm::CancelToken;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::mpsc;
+
+/// Output kept for the final result per stream; the rest is only streamed.
+const MAX_CAPTURED_BYTES: usize = 1_000_000;
+
+/// A partial line is emitted once the process has been quiet this long.
+const PARTIAL_LINE_FLUSH: Duration = Duration::from_millis(150);
+
+/// How long to keep reading after exit, for output still in the pipes. Background
+/// jobs that inherited the pipes would otherwise hold the run open forever.
+const DRAIN_GRACE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShellOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// -1 when the process was ended by a signal
+    pub exit_code: i32,
+    pub duration_ms: u64,
+    /// Directory the command ran in (for `cd`, the new directory)
+    pub cwd: String,
+    pub timed_out: bool,
+    pub killed: bool,
+    /// `stdout` / `stderr` hold only the first `MAX_CAPTURED_BYTES` of each
+    pub truncated: bool,
+}
+
+/// Input for a running process; `None` closes its stdin.
+pub type StdinInput = Option<String>;
+
+/// Handles to a running process, kept by run id so other commands can reach it.
+#[derive(Clone)]
+pub struct RunControl {
+    pub stdin: mpsc::UnboundedSender<StdinInput>,
+    pub kill: CancelToken,
+}
+
+/// Processes currently running, keyed by the id the frontend started them with.
+pub type ActiveRuns = Arc<Mutex<HashMap<String, RunControl>>>;
+
+pub struct RunOptions {
+    pub cwd: PathBuf,
+    /// Kill the process after this long; `None` waits forever
+    pub timeout: Option<Duration>,
+}
+
+/// Resolve the directory a command should run in: `~` expands to the home
+/// directory, relative paths are taken from `base`, and it must exist.
+pub fn resolve_dir(dir: &str, base: &Path) -> Result<PathBuf, String> {
+    let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/"));
+    let dir = dir.trim();
+    let path = if dir.is_empty() || dir == "~" {
+        home
+    } else if let Some(rest) = dir.strip_prefix("~/") {
+        home.join(rest)
+    } else {
+        base.join(dir)
+    };
+    match path.canonicalize() {
+        Ok(path) if path.is_dir() => Ok(path),
+        Ok(_) => Err(format!("Not a directory: {}", dir)),
+        Err(_) => Err(format!("No such directory: {}", dir)),
+    }
+}
+
+/// The target of a bare `cd` command, which must change the session's directory
+/// rather than run in a throwaway shell. Chained commands are left alone.
+pub fn parse_cd(command: &str) -> Option<&str> {
+    let command = command.trim();
+    let rest = command
+        .strip_prefix("cd")
+        .filter(|r| r.is_empty() || r.starts_with(' '))?;
+    if rest.contains(['&', ';', '|', '>', '<', '`', '$', '\n']) {
+        return None;
+    }
+    let target = rest.trim();
+    Some(
+        target
+            .strip_prefix('"')
+            .and_then(|t| t.strip_suffix('"'))
+            .or_else(|| target.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')))
+            .unwrap_or(target),
+    )
+}
+
+/// `sh -c` on Linux and macOS, `cmd /C` on Windows.
+fn shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        // Own process group, so a kill reaches everything the shell started
+        #[cfg(unix)]
+        cmd.process_group(0);
+        cmd
+    }
+}
+
+/// Kill the process and everything it started.
+fn kill_tree(child: &mut Child) {
+    if let Some(pid) = child.id() {
+        #[cfg(unix)]
+        // SAFETY: killpg only sends a signal; the group was created for this child.
+        unsafe {
+            libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+        }
+        #[cfg(windows)]
+        let _ = std::process::Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid.to_string()])
+            .output();
+    }
+    let _ = child.start_kill();
+}
+
+/// Forward a pipe's output in whole lines, flushing partial lines after a pause.
+async fn pump(
+    mut reader: impl AsyncRead + Unpin,
+    stream: Stream,
+    tx: mpsc::UnboundedSender<(Stream, String)>,
+) {
+    let mut pending: Vec<u8> = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let read = if pending.is_empty() {
+            reader.read(&mut buf).await
+        } else {
+            match tokio::time::timeout(PARTIAL_LINE_FLUSH, reader.read(&mut buf)).await {
+                Ok(read) => read,
+                Err(_) => {
+                    let text = String::from_utf8_lossy(&pending).into_owned();
+                    pending.clear();
+                    if tx.send((stream, text)).is_err() {
+                        return;
+                    }
+                    continue;
+                }
+            }
+        };
+        let n = match read {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        pending.extend_from_slice(&buf[..n]);
+        while let Some(newline) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=newline).collect();
+            if tx
+                .send((stream, String::from_utf8_lossy(&line).into_owned()))
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+    if !pending.is_empty() {
+        let _ = tx.send((stream, String::from_utf8_lossy(&pending).into_owned()));
+    }
+}
+
+/// Feed typed input to the process until the sender ends it or goes away. Runs
+/// as its own task: a process that never reads can fill the pipe and block the
+/// write, and that must not keep `run` from noticing a kill or the timeout.
+async fn feed(mut pipe: ChildStdin, mut stdin: mpsc::UnboundedReceiver<StdinInput>) {
+    while let Some(Some(data)) = stdin.recv().await {
+        // A process that stopped reading just loses its stdin
+        if pipe.write_all(data.as_bytes()).await.is_err() || pipe.flush().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Collected output of one stream, capped at `MAX_CAPTURED_BYTES`.
+#[derive(Default)]
+struct Capture {
+    text: String,
+    truncated: bool,
+}
+
+impl Capture {
+    fn push(&mut self, chunk: &str) {
+        if self.text.len() + chunk.len() <= MAX_CAPTURED_BYTES {
+            self.text.push_str(chunk);
+            return;
+        }
+        let mut end = MAX_CAPTURED_BYTES - self.text.len();
+        while !chunk.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.text.push_str(&chunk[..end]);
+        self.truncated = true;
+    }
+}
+
+/// Run `command` to completion. Output chunks (whole lines where possible) go to
+/// `on_output` as they arrive; `stdin` feeds the process until it sends `None`
+/// or is dropped, and `kill` ends it early.
+pub async fn run(
+    command: &str,
+    options: RunOptions,
+    stdin: mpsc::UnboundedReceiver<StdinInput>,
+    kill: CancelToken,
+    on_output: &mut (dyn FnMut(Stream, &str) + Send),
+) -> Result<ShellOutput, String> {
+    let started = Instant::now();
+    let mut child = shell_command(command)
+        .current_dir(&options.cwd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to execute command: {}", e))?;
+
+    let (tx, mut output) = mpsc::unbounded_channel();
+    if let Some(stdout) = child.stdout.take() {
+        tokio::spawn(pump(stdout, Stream::Stdout, tx.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(pump(stderr, Stream::Stderr, tx));
+    }
+    let feeder = child
+        .stdin
+        .take()
+        .map(|pipe| tokio::spawn(feed(pipe, stdin)));
+
+    let mut captured = [Capture::default(), Capture::default()];
+    let mut record = |stream: Stream, text: &str| {
+        on_output(stream, text);
+        captured[stream as usize].push(text);
+    };
+
+    let deadline = options.timeout.map(|t| tokio::time::Instant::now() + t);
+    let (mut timed_out, mut killed) = (false, false);
+    let status = loop {
+        tokio::select! {
+            Some((stream, text)) = output.recv() => record(stream, &text),
+            status = child.wait() => break status.map_err(|e| format!("Failed to wait for command: {}", e))?,
+            _ = kill.cancelled(), if !killed && !timed_out => {
+                killed = true;
+                kill_tree(&mut child);
+            }
+            _ = async { tokio::time::sleep_until(deadline.unwrap()).await }, if deadline.is_some() && !killed && !timed_out => {
+                timed_out = true;
+                kill_tree(&mut child);
+            }
+        }
+    };
+
+    if let Some(feeder) = feeder {
+        feeder.abort();
+    }
+    let _ = tokio::time::timeout(DRAIN_GRACE, async {
+        while let Some((stream, text)) = output.recv().await {
+            record(stream, &text);
+        }
+    })
+    .await;
+
+    let [stdout, stderr] = captured;
+    Ok(ShellOutput {
+        truncated: stdout.truncated || stderr.truncated,
+        stdout: stdout.text,
+        stderr: stderr.text,
+        exit_code: status.code().unwrap_or(-1),
+        duration_ms: started.elapsed().as_millis() as u64,
+        cwd: options.cwd.to_string_lossy().to_string(),
+        timed_out,
+        killed,
+    })
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn options(timeout: Option<Duration>) -> RunOptions {
+        RunOptions {
+            cwd: std::env::temp_dir(),
+            timeout,
+        }
+    }
+
+    async fn run_collecting(
+        command: &str,
+        options: RunOptions,
+        stdin: mpsc::UnboundedReceiver<StdinInput>,
+        kill: CancelToken,
+    ) -> (ShellOutput, Vec<(Stream, String)>) {
+        let mut chunks = Vec::new();
+        let mut on_output = |stream: Stream, text: &str| chunks.push((stream, text.to_string()));
+        let output = run(command, options, stdin, kill, &mut on_output)
+            .await
+            .unwrap();
+        (output, chunks)
+    }
+
+    #[tokio::test]
+    async fn streams_lines_from_both_pipes() {
+        let (_tx, rx) = mpsc::unbounded_channel();
+        let (output, chunks) = run_collecting(
+            "echo one; echo oops >&2; printf 'two\\nthree'; exit 3",
+            options(None),
+            rx,
+            CancelToken::default(),
+        )
+        .await;
+
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.stdout, "one\ntwo\nthree");
+        assert_eq!(output.stderr, "oops\n");
+        assert!(!output.timed_out && !output.killed);
+        let stdout: Vec<&str> = chunks
+            .iter()
+            .filter(|(s, _)| *s == Stream::Stdout)
+            .map(|(_, t)| t.as_str())
+            .collect();
+        assert_eq!(stdout, ["one\n", "two\n", "three"]);
+    }
+
+    #[tokio::test]
+    async fn feeds_stdin_until_closed() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tx.send(Some("banana\napple\n".to_string())).unwrap();
+        tx.send(None).unwrap();
+        let (output, _) = run_collecting("sort", options(None), rx, CancelToken::default()).await;
+        assert_eq!(output.stdout, "apple\nbanana\n");
+        assert_eq!(output.exit_code, 0);
+    }
+
+    #[tokio::test]
+    async fn timeout_kills_the_whole_pipeline() {
+        let (_tx, rx) = mpsc::unbounded_channel();
+        let (output, _) = run_collecting(
+            "echo started; sleep 30 | cat",
+            options(Some(Duration::from_millis(300))),
+            rx,
+            CancelToken::default(),
+        )
+        .await;
+        assert!(output.timed_out);
+        assert_eq!(output.stdout, "started\n");
+        assert!(output.duration_ms < 5_000, "took {}ms", output.duration_ms);
+    }
+
+    #[tokio::test]
+    async fn kill_stops_a_running_command() {
+        let (_tx, rx) = mpsc::unbounded_channel();
+        let kill = CancelToken::default();
+        let trigger = kill.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            trigger.cancel();
+        });
+        let (output, _) = run_collecting("sleep 30", options(None), rx, kill).await;
+        assert!(output.killed && !output.timed_out);
+        assert_eq!(output.exit_code, -1);
+        assert!(output.duration_ms < 5_000);
+    }
+
+    #[tokio::test]
+    async fn timeout_fires_while_stdin_is_full() {
+        // `sleep` never reads, so this fills the pipe buffer many times over
+        let (tx, rx) = mpsc::unbounded_channel();
+        tx.send(Some("x".repeat(1 << 20))).unwrap();
+        let (output, _) = run_collecting(
+            "sleep 30",
+            options(Some(Duration::from_millis(300))),
+            rx,
+            CancelToken::default(),
+        )
+        .await;
+        assert!(output.timed_out);
+        assert!(output.duration_ms < 5_000, "took {}ms", output.duration_ms);
+        drop(tx);
+    }
+
+    #[tokio::test]
+    async fn flushes_prompts_without_newline() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut seen_prompt = false;
+        let mut on_output = |_: Stream, text: &str| {
+            if text == "Continue? " {
+                seen_prompt = true;
+                let _ = tx.send(Some("y\n".to_string()));
+            }
+        };
+        let output = run(
+            "printf 'Continue? '; read answer; echo \"got $answer\"",
+            options(Some(Duration::from_secs(10))),
+            rx,
+            CancelToken::default(),
+            &mut on_output,
+        )
+        .await
+        .unwrap();
+        assert!(seen_prompt);
+        assert_eq!(output.stdout, "Continue? got y\n");
+    }
+
+    #[test]
+    fn parses_bare_cd() {
+        assert_eq!(parse_cd("cd /tmp"), Some("/tmp"));
+        assert_eq!(parse_cd("  cd  "), Some(""));
+        assert_eq!(parse_cd("cd \"My Docs\""), Some("My Docs"));
+        assert_eq!(parse_cd("cd /tmp && ls"), None);
+        assert_eq!(parse_cd("cdrecord -v"), None);
+        assert_eq!(parse_cd("echo cd"), None);
+    }
+
+    #[test]
+    fn resolves_directories() {
+        let tmp = std::env::temp_dir().canonicalize().unwrap();
+        assert_eq!(resolve_dir(".", &tmp).unwrap(), tmp);
+        assert_eq!(
+            resolve_dir(tmp.to_str().unwrap(), Path::new("/")).unwrap(),
+            tmp
+        );
+        assert!(resolve_dir("definitely-not-here-42", &tmp).is_err());
+        assert!(resolve_dir("~", &tmp).is_ok());
+    }
+
+    #[test]
+    fn capture_is_capped_on_char_boundaries() {
+        let mut capture = Capture::default();
+        capture.push(&"a".repeat(MAX_CAPTURED_BYTES - 1));
+        capture.push("é");
+        assert!(capture.truncated);
+        assert_eq!(capture.text.len(), MAX_CAPTURED_BYTES - 1);
+    }
+}
@@ -1,13 +1,36 @@
-use crate::indexer::FileIndex;
+use crate::frecency::{self, FrecencyStore, Visits};
+use crate::indexer::{EntryId, EntryKind, FileEntry, IndexStore};
+use crate::query::{Clause, Query};
 use fuzzy_matcher::skim::SkimMatcherV2;
 use fuzzy_matcher::FuzzyMatcher;
+use rayon::iter::Either;
+use rayon::prelude::*;
 use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Points per unit of ln(1 + launches), for launches from any query and from the
+/// current query prefix. Matching what was opened for this very prefix counts most.
+const FRECENCY_WEIGHT: f64 = 15.0;
+const PREFIX_FRECENCY_WEIGHT: f64 = 40.0;
+
+/// Bonus for a file modified just now; it halves every `RECENCY_HALF_LIFE_DAYS`.
+const RECENCY_BONUS: f64 = 10.0;
+const RECENCY_HALF_LIFE_DAYS: f64 = 7.0;
+
+/// Points removed per directory level, so shallow paths win ties.
+const DEPTH_PENALTY: i64 = 1;
+const MAX_DEPTH_PENALTY: i64 = 12;
+
+/// Fuzzy scores below this are dropped.
+const MIN_MATCH_SCORE: i64 = 10;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct SearchResult {
     pub name: String,
     pub path: String,
-    pub kind: crate::indexer::EntryKind,
+    pub kind: EntryKind,
     pub score: i64,
     pub matched_indices: Vec<usize>,
     /// Resolved icon path (Apps only).
@@ -18,73 +41,383 @@ pub struct SearchResult {
     pub generic_name: Option<String>,
 }
 
+// ─── Cancellation & Reuse ────────────────────────────────
+
+/// Hands out search tickets. A search gives up as soon as a newer ticket exists,
+/// so a burst of keystrokes only pays for the last one.
+#[derive(Debug, Default)]
+pub struct SearchGate {
+    latest: AtomicU64,
+}
+
+impl SearchGate {
+    /// Start a new search, superseding any still running.
+    pub fn begin(&self) -> u64 {
+        self.latest.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn is_current(&self, ticket: u64) -> bool {
+        self.latest.load(Ordering::Relaxed) == ticket
+    }
+}
+
+/// Everything the previous search matched, before ranking and truncation.
+/// Fuzzy matching is a subsequence test, so when the next query has the same
+/// filters and its text extends the previous text, its matches are a subset of
+/// these and only they need rescoring.
+#[derive(Debug, Default)]
+pub struct SearchCache {
+    last: Option<PreviousSearch>,
+}
+
+#[derive(Debug)]
+struct PreviousSearch {
+    text: String,
+    clauses: Vec<Clause>,
+    generation: u64,
+    candidates: Vec<EntryId>,
+}
+
+impl SearchCache {
+    /// Candidate ids for `query`, if the previous search can stand in for a full scan.
+    fn candidates_for(&self, query: &Query, text: &str, generation: u64) -> Option<&[EntryId]> {
+        let prev = self.last.as_ref()?;
+        (prev.generation == generation && prev.clauses == query.clauses && text.starts_with(&prev.text))
+            .then_some(prev.candidates.as_slice())
+    }
+}
+
+// ─── Top-k Ranking ───────────────────────────────────────
+
+/// A scored entry. Orders by score, then newer modification time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Ranked {
+    score: i64,
+    modified: u64,
+    id: EntryId,
+}
+
+/// Bounded min-heap keeping the `k` best entries seen.
+struct TopK {
+    k: usize,
+    heap: BinaryHeap<Reverse<Ranked>>,
+}
+
+impl TopK {
+    fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: BinaryHeap::with_capacity(k + 1),
+        }
+    }
+
+    fn push(&mut self, ranked: Ranked) {
+        if self.heap.len() < self.k {
+            self.heap.push(Reverse(ranked));
+        } else if self.heap.peek().is_some_and(|worst| ranked > worst.0) {
+            self.heap.pop();
+            self.heap.push(Reverse(ranked));
+        }
+    }
+
+    fn merge(mut self, other: TopK) -> TopK {
+        for Reverse(ranked) in other.heap {
+            self.push(ranked);
+        }
+        self
+    }
+
+    /// Best first.
+    fn into_sorted(self) -> Vec<Ranked> {
+        let mut ranked: Vec<Ranked> = self.heap.into_iter().map(|r| r.0).collect();
+        ranked.sort_unstable_by(|a, b| b.cmp(a));
+        ranked
+    }
+}
+
+/// Per-thread accumulator: the running top-k plus every id that matched at all.
+struct Partial {
+    top: TopK,
+    candidates: Vec<EntryId>,
+}
+
+// ─── Search ──────────────────────────────────────────────
+
 /// Perform a fuzzy search over the index. Returns top results sorted by score.
-/// Matches against name, keywords, and generic_name; boosts App entries.
-pub async fn fuzzy_search(
-    index: &FileIndex,
-    query: &str,
+/// Entries must pass every filter in `query`; its free text is matched against
+/// name, keywords, and generic_name, boosting App entries. The match score is then
+/// blended with launch frecency, recency of modification and path depth; a
+/// filter-only query is ranked by that blend alone.
+///
+/// Scoring runs in parallel and keeps only the best `max_results`; results are
+/// built for those alone. Returns `None` if `cancelled` reported true midway, in
+/// which case `cache` is left untouched.
+pub fn fuzzy_search(
+    idx: &IndexStore,
+    query: &Query,
+    frecency: &FrecencyStore,
+    cache: &mut SearchCache,
     max_results: usize,
-) -> Vec<SearchResult> {
-    if query.is_empty() {
-        return Vec::new();
+    cancelled: &(dyn Fn() -> bool + Sync),
+) -> Option<Vec<SearchResult>> {
+    if query.is_empty() || idx.is_empty() {
+        return Some(Vec::new());
     }
 
-    let idx = index.read().await;
     let matcher = SkimMatcherV2::default();
+    let text = query.text();
+    let now = frecency::now();
+    let prefix_scores = frecency.prefix_scores(&frecency::query_key(&text));
 
-    let mut results: Vec<SearchResult> = idx
-        .iter()
-        .filter_map(|entry| {
-            // Primary: match on name
-            let name_match = matcher.fuzzy_indices(&entry.name, query);
-
-            // Secondary: match on keywords (semi-colon separated)
-            let kw_score = entry
-                .keywords
-                .as_deref()
-                .and_then(|kw| matcher.fuzzy_match(kw, query))
-                .unwrap_or(0);
-
-            // Secondary: match on generic name
-            let gn_score = entry
-                .generic_name
-                .as_deref()
-                .and_then(|gn| matcher.fuzzy_match(gn, query))
-                .unwrap_or(0);
-
-            // Must match at least one field
-            let (name_score, indices) = match name_match {
-                Some((s, i)) => (s, i),
-                None if kw_score > 0 || gn_score > 0 => (0, vec![]),
-                _ => return None,
-            };
-
-            let mut score = name_score.max(kw_score).max(gn_score);
+    let pool = match cache.candidates_for(query, &text, idx.generation()) {
+        Some(ids) => Either::Left(
+            ids.par_iter()
+                .filter_map(|&id| idx.get(id).map(|entry| (id, entry))),
+        ),
+        None => Either::Right(idx.par_iter()),
+    };
 
-            // Minimum score threshold
-            if score < 10 {
-                return None;
+    let new_partial = || Partial {
+        top: TopK::new(max_results),
+        candidates: Vec::new(),
+    };
+    let partial = pool
+        .try_fold(new_partial, |mut acc, (id, entry)| {
+            if cancelled() {
+                return Err(());
             }
-
-            // Boost applications so they surface above similarly-named files
-            if entry.kind == crate::indexer::EntryKind::App {
-                score = (score as f64 * 1.3) as i64;
+            if !query.matches(entry, now) {
+                return Ok(acc);
             }
-
-            Some(SearchResult {
-                name: entry.name.clone(),
-                path: entry.path.clone(),
-                kind: entry.kind.clone(),
-                score,
-                matched_indices: indices,
-                icon_path: entry.icon_path.clone(),
-                generic_name: entry.generic_name.clone(),
+            let Some(score) = match_score(&matcher, entry, &text) else {
+                return Ok(acc);
+            };
+            acc.candidates.push(id);
+            if score >= MIN_MATCH_SCORE || text.is_empty() {
+                acc.top.push(Ranked {
+                    score: app_boost(entry, score) + rank_bonus(entry, frecency, prefix_scores, now),
+                    modified: entry.modified,
+                    id,
+                });
+            }
+            Ok(acc)
+        })
+        .try_reduce(new_partial, |mut a, b| {
+            a.candidates.extend(b.candidates);
+            Ok(Partial {
+                top: a.top.merge(b.top),
+                candidates: a.candidates,
             })
         })
+        .ok()?;
+
+    let results = partial
+        .top
+        .into_sorted()
+        .into_iter()
+        .filter_map(|ranked| {
+            let entry = idx.get(ranked.id)?;
+            // Highlight positions are only worth computing for what is shown
+            let indices = if text.is_empty() {
+                Vec::new()
+            } else {
+                matcher
+                    .fuzzy_indices(&entry.name, &text)
+                    .map(|(_, i)| i)
+                    .unwrap_or_default()
+            };
+            Some(to_result(entry, ranked.score, indices))
+        })
         .collect();
 
-    // Sort by score descending
-    results.sort_unstable_by(|a, b| b.score.cmp(&a.score));
-    results.truncate(max_results);
-    results
+    cache.last = Some(PreviousSearch {
+        text,
+        clauses: query.clauses.clone(),
+        generation: idx.generation(),
+        candidates: partial.candidates,
+    });
+    Some(results)
+}
+
+/// Fuzzy score of `entry` against `text`, or `None` if no field matches at all.
+/// An empty text matches everything with score 0.
+fn match_score(matcher: &SkimMatcherV2, entry: &FileEntry, text: &str) -> Option<i64> {
+    if text.is_empty() {
+        return Some(0);
+    }
+
+    // Primary: match on name
+    let name_score = matcher.fuzzy_match(&entry.name, text);
+
+    // Secondary: match on keywords (semi-colon separated)
+    let kw_score = entry
+        .keywords
+        .as_deref()
+        .and_then(|kw| matcher.fuzzy_match(kw, text));
+
+    // Secondary: match on generic name
+    let gn_score = entry
+        .generic_name
+        .as_deref()
+        .and_then(|gn| matcher.fuzzy_match(gn, text));
+
+    name_score.max(kw_score).max(gn_score)
+}
+
+/// Boost applications so they surface above similarly-named files.
+fn app_boost(entry: &FileEntry, score: i64) -> i64 {
+    if entry.kind == EntryKind::App {
+        (score as f64 * 1.3) as i64
+    } else {
+        score
+    }
+}
+
+/// Score adjustment from usage and metadata, independent of how well the text matched.
+fn rank_bonus(
+    entry: &FileEntry,
+    frecency: &FrecencyStore,
+    prefix_scores: Option<&HashMap<String, Visits>>,
+    now: u64,
+) -> i64 {
+    let launches = frecency.entry_score(&entry.path, now);
+    let prefix_launches = FrecencyStore::score_in(prefix_scores, &entry.path, now);
+    let mut bonus = FRECENCY_WEIGHT * launches.ln_1p()
+        + PREFIX_FRECENCY_WEIGHT * prefix_launches.ln_1p();
+
+    // Apps have no mtime and live in fixed system directories
+    if entry.kind != EntryKind::App {
+        if entry.modified > 0 {
+            let age_days = now.saturating_sub(entry.modified) as f64 / 86_400.0;
+            bonus += RECENCY_BONUS * 0.5f64.powf(age_days / RECENCY_HALF_LIFE_DAYS);
+        }
+        let depth = entry.path.matches(['/', '\\']).count() as i64;
+        bonus -= (depth * DEPTH_PENALTY).min(MAX_DEPTH_PENALTY) as f64;
+    }
+
+    bonus.round() as i64
+}
+
+fn to_result(entry: &FileEntry, score: i64, matched_indices: Vec<usize>) -> SearchResult {
+    SearchResult {
+        name: entry.name.clone(),
+        path: entry.path.clone(),
+        kind: entry.kind.clone(),
+        score,
+        matched_indices,
+        icon_path: entry.icon_path.clone(),
+        generic_name: entry.generic_name.clone(),
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str) -> FileEntry {
+        FileEntry {
+            name: path.rsplit('/').next().unwrap().to_string(),
+            path: path.to_string(),
+            kind: EntryKind::File,
+            size: 0,
+            modified: 0,
+            icon_path: None,
+            keywords: None,
+            generic_name: None,
+        }
+    }
+
+    fn store(n: usize) -> IndexStore {
+        let mut idx = IndexStore::new();
+        idx.extend((0..n).map(|i| file(&format!("/data/file_{:05}.txt", i))));
+        idx.extend(["/data/report.pdf", "/data/readme.md", "/data/rust/main.rs"].map(file));
+        idx
+    }
+
+    fn search(idx: &IndexStore, cache: &mut SearchCache, q: &str, k: usize) -> Vec<String> {
+        let query = Query::parse(q).unwrap();
+        fuzzy_search(idx, &query, &FrecencyStore::new(), cache, k, &|| false)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.name)
+            .collect()
+    }
+
+    #[test]
+    fn top_k_matches_full_sort() {
+        let idx = store(5_000);
+        let query = Query::parse("file_01").unwrap();
+        let matcher = SkimMatcherV2::default();
+
+        // Reference: score everything, sort, truncate
+        let mut all: Vec<(i64, EntryId)> = idx
+            .iter()
+            .filter_map(|(id, e)| {
+                let s = match_score(&matcher, e, "file_01")?;
+                (s >= MIN_MATCH_SCORE)
+                    .then(|| (s + rank_bonus(e, &FrecencyStore::new(), None, 0), id))
+            })
+            .collect();
+        all.sort_unstable_by(|a, b| b.cmp(a));
+        let expected: Vec<i64> = all.iter().take(20).map(|(s, _)| *s).collect();
+
+        let got = fuzzy_search(
+            &idx,
+            &query,
+            &FrecencyStore::new(),
+            &mut SearchCache::default(),
+            20,
+            &|| false,
+        )
+        .unwrap();
+        assert_eq!(got.len(), 20);
+        assert_eq!(got.iter().map(|r| r.score).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn extending_query_reuses_candidates() {
+        let idx = store(1_000);
+        let mut cache = SearchCache::default();
+
+        assert_eq!(search(&idx, &mut cache, "r", 5).len(), 3);
+        for q in ["re", "rea", "read"] {
+            let fresh = search(&idx, &mut SearchCache::default(), q, 5);
+            let reused = cache.candidates_for(&Query::parse(q).unwrap(), q, idx.generation());
+            assert!(reused.is_some(), "{:?}", q);
+            assert_eq!(search(&idx, &mut cache, q, 5), fresh, "{:?}", q);
+        }
+
+        // Different filters or a changed index force a full scan
+        assert!(cache
+            .candidates_for(&Query::parse("ext:md read").unwrap(), "read", idx.generation())
+            .is_none());
+        assert!(cache
+            .candidates_for(&Query::parse("read").unwrap(), "read", idx.generation() + 1)
+            .is_none());
+        assert!(cache
+            .candidates_for(&Query::parse("ra").unwrap(), "ra", idx.generation())
+            .is_none());
+    }
+
+    #[test]
+    fn index_changes_invalidate_reuse() {
+        let mut idx = store(10);
+        let mut cache = SearchCache::default();
+        assert!(search(&idx, &mut cache, "main", 5).contains(&"main.rs".to_string()));
+
+        idx.upsert(file("/data/mainframe.txt"));
+        assert!(search(&idx, &mut cache, "mainf", 5).contains(&"mainframe.txt".to_string()));
+    }
+
+    #[test]
+    fn cancelled_search_returns_none() {
+        let idx = store(100);
+        let mut cache = SearchCache::default();
+        let query = Query::parse("file").unwrap();
+        let result = fuzzy_search(&idx, &query, &FrecencyStore::new(), &mut cache, 5, &|| true);
+        assert!(result.is_none());
+        assert!(cache.last.is_none());
+    }
 }
@@ -1,36 +1,123 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
+/// Providers whose API keys live in the vault, by the name `Settings::key_slot` takes.
+pub const KEY_PROVIDERS: &[&str] = &[
+    "openai",
+    "anthropic",
+    "google",
+    "huggingface",
+    "openrouter",
+    "custom",
+];
+
+/// API key fields are filled from the vault at startup and are never serialized,
+/// so they reach neither `settings.json` nor the webview. When they arrive from
+/// the settings panel, `None` means "unchanged" and an empty string "remove".
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
     /// OpenAI API key
+    #[serde(default, skip_serializing)]
     pub openai_key: Option<String>,
 
     /// Anthropic API key
+    #[serde(default, skip_serializing)]
     pub anthropic_key: Option<String>,
 
     /// Google AI (Gemini) API key
+    #[serde(default, skip_serializing)]
     pub google_key: Option<String>,
 
     /// HuggingFace API key
-    #[serde(default)]
+    #[serde(default, skip_serializing)]
     pub huggingface_key: Option<String>,
 
     /// OpenRouter API key
-    #[serde(default)]
+    #[serde(default, skip_serializing)]
     pub openrouter_key: Option<String>,
 
-    /// Active cloud provider: "openai", "anthropic", "google", "huggingface", "openrouter"
+    /// Key for the OpenAI-compatible custom endpoint (most local servers need none)
+    #[serde(default, skip_serializing)]
+    pub custom_key: Option<String>,
+
+    /// Base URL of the local Ollama server
+    #[serde(default = "default_ollama_base_url")]
+    pub ollama_base_url: String,
+
+    /// Base URL of an OpenAI-compatible server, up to and including `/v1`
+    #[serde(default = "default_custom_base_url")]
+    pub custom_base_url: String,
+
+    /// Active provider: "openai", "anthropic", "google", "huggingface", "openrouter",
+    /// or the local "ollama" / "custom" (OpenAI-compatible base URL)
     pub active_provider: String,
 
     /// Active model name
     pub active_model: String,
 
+    /// Provider used to embed document chunks for chat retrieval: "openai",
+    /// "ollama" or "custom" (reusing that provider's key / base URL); empty = off
+    #[serde(default)]
+    pub embedding_provider: String,
+
+    /// Embedding model name, e.g. "text-embedding-3-small" or "nomic-embed-text"
+    #[serde(default)]
+    pub embedding_model: String,
+
     /// Directories to index
     pub index_roots: Vec<String>,
 
+    /// Per-root include/exclude globs, keyed by the `index_roots` entry
+    #[serde(default)]
+    pub index_filters: HashMap<String, IndexFilter>,
+
     /// Max search results to display
     pub max_results: usize,
+
+    /// Build a full-text index of document contents (takes effect on next launch)
+    #[serde(default)]
+    pub content_index_enabled: bool,
+
+    /// Shell commands are killed after this many seconds (0 = never)
+    #[serde(default = "default_shell_timeout_secs")]
+    pub shell_timeout_secs: u64,
+
+    /// Alternative commands asked for per shell translation; the best that verifies wins
+    #[serde(default = "default_shell_candidates")]
+    pub shell_candidates: u32,
+
+    /// The key vault is passphrase-protected and not unlocked yet
+    #[serde(skip)]
+    pub keys_locked: bool,
+}
+
+fn default_ollama_base_url() -> String {
+    "http://localhost:11434".to_string()
+}
+
+fn default_custom_base_url() -> String {
+    "http://localhost:8080/v1".to_string()
+}
+
+fn default_shell_timeout_secs() -> u64 {
+    300
+}
+
+fn default_shell_candidates() -> u32 {
+    1
+}
+
+/// Gitignore-style globs applied to one index root (relative to that root).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexFilter {
+    /// If non-empty, only files matching one of these are indexed (directories are still walked)
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Paths matching any of these are never indexed
+    #[serde(default)]
+    pub exclude: Vec<String>,
 }
 
 impl Default for Settings {
@@ -48,10 +135,20 @@ impl Default for Settings {
             google_key: None,
             huggingface_key: None,
             openrouter_key: None,
+            custom_key: None,
+            ollama_base_url: default_ollama_base_url(),
+            custom_base_url: default_custom_base_url(),
             active_provider: "openai".to_string(),
             active_model: "gpt-4o-mini".to_string(),
+            embedding_provider: String::new(),
+            embedding_model: String::new(),
             index_roots: vec![home.to_string_lossy().to_string()],
+            index_filters: HashMap::new(),
             max_results: 20,
+            content_index_enabled: false,
+            shell_timeout_secs: default_shell_timeout_secs(),
+            shell_candidates: default_shell_candidates(),
+            keys_locked: false,
         }
     }
 }
@@ -105,7 +202,33 @@ impl Settings {
             "google" => self.google_key.as_ref(),
             "huggingface" => self.huggingface_key.as_ref(),
             "openrouter" => self.openrouter_key.as_ref(),
+            "custom" => self.custom_key.as_ref(),
+            _ => None,
+        }
+        .filter(|key| !key.is_empty())
+    }
+
+    /// The API key field for a provider in `KEY_PROVIDERS`.
+    pub fn key_slot(&mut self, provider: &str) -> Option<&mut Option<String>> {
+        match provider {
+            "openai" => Some(&mut self.openai_key),
+            "anthropic" => Some(&mut self.anthropic_key),
+            "google" => Some(&mut self.google_key),
+            "huggingface" => Some(&mut self.huggingface_key),
+            "openrouter" => Some(&mut self.openrouter_key),
+            "custom" => Some(&mut self.custom_key),
             _ => None,
         }
     }
+
+    /// Whether semantic retrieval is configured for document chat.
+    pub fn embeddings_enabled(&self) -> bool {
+        !self.embedding_provider.is_empty() && !self.embedding_model.trim().is_empty()
+    }
+
+    /// Whether the active provider refuses requests without an API key.
+    /// Local servers (Ollama, custom base URLs) are used keyless.
+    pub fn requires_key(&self) -> bool {
+        !matches!(self.active_provider.as_str(), "ollama" | "custom")
+    }
 }
@@ -1,8 +1,14 @@
 /// NLP-to-Shell: Translates natural language into shell commands using an LLM.
-use crate::llm::LlmClient;
+use crate::llm::{CancelToken, ChatMessage, LlmClient};
 use crate::settings::Settings;
+use crate::shell_parse::{self, unwrap, Command, Script, Word};
+use crate::shell_risk::{self, RiskReport};
 use serde::Serialize;
-use std::process::Command;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+
+/// Most candidate commands asked for in one request.
+pub const MAX_CANDIDATES: usize = 5;
 
 /// Collect runtime OS/environment context for injecting into the LLM prompt.
 fn os_context() -> String {
@@ -42,10 +48,11 @@ fn os_context() -> String {
     format!("{os_name} ({arch}), user: {username}, shell: {shell}")
 }
 
-/// Returns the OS-appropriate system prompt for the LLM shell translator.
-fn system_prompt() -> String {
+/// Returns the OS-appropriate system prompt for the LLM shell translator, asking
+/// for `candidates` alternatives when more than one.
+fn system_prompt(candidates: usize) -> String {
     let ctx = os_context();
-    if cfg!(windows) {
+    let prompt = if cfg!(windows) {
         format!(
             "You are a Windows command-line translator running on {ctx}. \
              Translate the user's plain English request into ONLY the exact command — \
@@ -59,87 +66,102 @@ fn system_prompt() -> String {
              no explanation, no markdown, no code fences, just the raw command. \
              Use standard Linux utilities. Chain multiple commands with && or ;."
         )
+    };
+    if candidates > 1 {
+        format!(
+            "{prompt} Give {candidates} different commands that each do what was asked, \
+             one per line, best first — no numbering, labels or blank lines."
+        )
+    } else {
+        prompt
     }
 }
 
-/// Each entry is (pattern, human-readable reason).
-/// The pattern is matched case-insensitively against the generated command.
-fn dangerous_patterns() -> Vec<(&'static str, &'static str)> {
-    let mut patterns: Vec<(&'static str, &'static str)> = vec![
-        // Recursive force-delete variants (all common orderings)
-        ("rm -rf",           "Recursively and forcefully deletes files/directories with no undo"),
-        ("rm -fr",           "Recursively and forcefully deletes files/directories with no undo"),
-        ("rm -r -f",         "Recursively and forcefully deletes files/directories with no undo"),
-        ("rm -f -r",         "Recursively and forcefully deletes files/directories with no undo"),
-        ("rm -r /",          "Targets the filesystem root — will destroy the entire system"),
-        ("rm -rf /",         "Targets the filesystem root — will destroy the entire system"),
-        ("rm -fr /",         "Targets the filesystem root — will destroy the entire system"),
-        // Disk/filesystem operations
-        ("mkfs",             "Formats a disk partition, erasing all data on it permanently"),
-        ("dd if=",           "Low-level disk operation that can overwrite entire drives"),
-        ("> /dev/sd",        "Writes raw data directly to a block device, destroying disk contents"),
-        ("> /dev/nvme",      "Writes raw data directly to an NVMe drive, destroying its contents"),
-        // Fork bomb
-        (":(){",             "Fork bomb — will crash the system by exhausting all process slots"),
-        // Permission nukes
-        ("chmod -r 777 /",   "Recursively makes every file on the system world-writable"),
-        ("chmod 777 /",      "Makes the filesystem root world-writable — major security risk"),
-        ("chown -r root /",  "Changes ownership of every file on the system to root"),
-        // Remote code execution
-        ("wget | sh",        "Downloads and immediately executes unknown remote code"),
-        ("curl | sh",        "Downloads and immediately executes unknown remote code"),
-        ("curl | bash",      "Downloads and immediately executes unknown remote code"),
-        ("wget | bash",      "Downloads and immediately executes unknown remote code"),
-        // Dangerous redirects
-        ("> /dev/null && rm","Combines output suppression with a delete, masking the damage"),
-    ];
-    if cfg!(windows) {
-        patterns.extend_from_slice(&[
-            ("del /s /q",                  "Silently deletes files and subdirectories recursively"),
-            ("rd /s /q",                   "Silently removes a directory tree with no confirmation"),
-            ("rmdir /s /q",                "Silently removes a directory tree with no confirmation"),
-            ("format ",                    "Formats a drive, permanently erasing all data on it"),
-            ("diskpart",                   "Low-level disk partition editor — can erase entire drives"),
-            ("reg delete",                 "Deletes Windows registry keys, which can break the OS"),
-            ("shutdown",                   "Shuts down or reboots the system immediately"),
-            ("taskkill /f",                "Forcefully terminates processes, may corrupt running work"),
-            ("remove-item -recurse -force","PowerShell equivalent of rm -rf — permanent deletion"),
-        ]);
-    }
-    patterns
-}
-
-/// Check a command against all dangerous patterns (case-insensitive).
-/// Returns Some(reason) if dangerous, None if safe.
-fn check_dangerous(command: &str) -> Option<String> {
-    let lower = command.to_lowercase();
-    for (pattern, reason) in dangerous_patterns() {
-        if lower.contains(pattern) {
-            return Some(reason.to_string());
-        }
-    }
-    None
-}
-
 #[derive(Debug, Clone, Serialize)]
 pub struct ShellTranslation {
     pub command: String,
     pub is_dangerous: bool,
     pub danger_reason: String,
+    pub risk: RiskReport,
+    /// Shell history entry this translation is remembered as
+    pub history_id: Option<u64>,
+    /// Reused from history instead of asking the LLM
+    pub cached: bool,
+    /// The history entry is pinned as a favorite
+    pub pinned: bool,
+    /// Every candidate the model offered, best first; `command` is the first.
+    /// Empty for commands that didn't come from the model just now.
+    pub candidates: Vec<Candidate>,
+}
+
+/// One command the model offered, with how it fared in verification.
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub command: String,
+    pub risk: RiskReport,
+    /// Why verification failed; `None` if it passed
+    pub rejected: Option<String>,
+}
+
+impl ShellTranslation {
+    /// Wrap a command with its risk analysis.
+    pub fn assess(command: String) -> Self {
+        let risk = shell_risk::analyze(&command);
+        Self {
+            command,
+            is_dangerous: risk.is_dangerous(),
+            danger_reason: risk.summary(),
+            risk,
+            history_id: None,
+            cached: false,
+            pinned: false,
+            candidates: Vec::new(),
+        }
+    }
 }
 
-/// Translate natural language to a shell command via LLM.
+/// Translate natural language to a shell command via LLM, asking for
+/// `settings.shell_candidates` alternatives and keeping the best that verifies.
+/// The raw reply is streamed through `on_delta` before it is cleaned up.
 pub async fn translate_to_command(
     llm: &LlmClient,
     settings: &Settings,
     natural_input: &str,
+    on_delta: &mut (dyn FnMut(&str) + Send),
+    cancel: Option<&CancelToken>,
 ) -> Result<ShellTranslation, String> {
-    let prompt = system_prompt();
-    let command = llm.prompt(settings, &prompt, natural_input).await?;
+    let count = (settings.shell_candidates as usize).clamp(1, MAX_CANDIDATES);
+    let prompt = system_prompt(count);
+    let messages = [ChatMessage::user(natural_input)];
+    let reply = llm
+        .prompt_stream(settings, &prompt, &messages, on_delta, cancel)
+        .await?;
 
-    // Clean up any residual formatting
-    let command = command
-        .trim()
+    let mut commands = split_candidates(&reply, count);
+    if commands.is_empty() {
+        commands.push(String::new());
+    }
+    // `sh -n` runs a process per candidate
+    let candidates = tokio::task::spawn_blocking(move || {
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        rank(commands.into_iter().map(|c| check(c, &path)).collect())
+    })
+    .await
+    .map_err(|e| format!("Verifying commands failed: {}", e))?;
+    eprintln!(
+        "[trace][shell] {} candidate(s), {} rejected",
+        candidates.len(),
+        candidates.iter().filter(|c| c.rejected.is_some()).count()
+    );
+
+    let mut translation = ShellTranslation::assess(candidates[0].command.clone());
+    translation.candidates = candidates;
+    Ok(translation)
+}
+
+/// Strip code fences and language tags from a reply.
+fn strip_fences(text: &str) -> &str {
+    text.trim()
         .trim_start_matches("```bash")
         .trim_start_matches("```sh")
         .trim_start_matches("```powershell")
@@ -147,44 +169,254 @@ pub async fn translate_to_command(
         .trim_start_matches("```")
         .trim_end_matches("```")
         .trim()
-        .to_string();
+}
 
-    let danger_reason = check_dangerous(&command).unwrap_or_default();
-    let is_dangerous = !danger_reason.is_empty();
+/// The commands in a reply. A single command may span lines; several come one
+/// per line, and models number or bullet them despite being asked not to.
+fn split_candidates(reply: &str, count: usize) -> Vec<String> {
+    let reply = strip_fences(reply);
+    if count == 1 {
+        return if reply.is_empty() {
+            vec![]
+        } else {
+            vec![reply.to_string()]
+        };
+    }
+    let mut seen = HashSet::new();
+    reply
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            let line = line.trim_start_matches(|c: char| c.is_ascii_digit());
+            let line = line
+                .strip_prefix(". ")
+                .or_else(|| line.strip_prefix(") "))
+                .or_else(|| line.strip_prefix("- "))
+                .or_else(|| line.strip_prefix("* "))
+                .unwrap_or(line);
+            line.trim().trim_matches('`').trim().to_string()
+        })
+        .filter(|line| !line.is_empty() && !line.starts_with("```"))
+        .filter(|line| seen.insert(line.clone()))
+        .take(count)
+        .collect()
+}
+
+// ─── Verification ────────────────────────────────────────
+
+/// Shell builtins and keywords, which aren't programs on PATH.
+const BUILTINS: &[&str] = &[
+    ".", ":", "[", "[[", "]]", "{", "}", "alias", "bg", "break", "builtin", "case", "cd",
+    "command", "continue", "declare", "done", "echo", "esac", "eval", "exec", "exit", "export",
+    "false", "fg", "fi", "for", "function", "getopts", "hash", "history", "in", "jobs", "let",
+    "local", "popd", "printf", "pushd", "pwd", "read", "readonly", "return", "select", "set",
+    "shift", "source", "test", "time", "times", "trap", "true", "type", "ulimit", "umask",
+    "unalias", "unset", "wait",
+];
+
+/// cmd.exe internal commands.
+const CMD_BUILTINS: &[&str] = &[
+    "assoc", "call", "cd", "chdir", "cls", "color", "copy", "date", "del", "dir", "echo",
+    "endlocal", "erase", "exit", "for", "ftype", "goto", "if", "md", "mkdir", "mklink", "move",
+    "path", "pause", "popd", "prompt", "pushd", "rd", "rem", "ren", "rename", "rmdir", "set",
+    "setlocal", "shift", "start", "time", "title", "type", "ver", "vol",
+];
 
-    Ok(ShellTranslation {
+fn check(command: String, path: &OsStr) -> Candidate {
+    Candidate {
+        risk: shell_risk::analyze(&command),
+        rejected: verify(&command, path).err(),
         command,
-        is_dangerous,
-        danger_reason,
-    })
+    }
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub struct ShellOutput {
-    pub stdout: String,
-    pub stderr: String,
-    pub exit_code: i32,
-}
-
-/// Execute a shell command and return its output.
-/// Uses `sh -c` on Linux and `cmd /C` on Windows.
-pub fn execute_command(cmd: &str) -> Result<ShellOutput, String> {
-    let output = if cfg!(windows) {
-        Command::new("cmd")
-            .args(["/C", cmd])
-            .output()
-            .map_err(|e| format!("Failed to execute command: {}", e))?
+/// Parse the command, let `sh -n` check its syntax, and make sure every program
+/// it runs can be found on `path`.
+fn verify(command: &str, path: &OsStr) -> Result<(), String> {
+    let script = shell_parse::parse(command).map_err(|e| format!("Doesn't parse: {}", e))?;
+    if !cfg!(windows) {
+        syntax_check(command)?;
+    }
+    match missing_programs(&script, path).as_slice() {
+        [] => Ok(()),
+        [one] => Err(format!("`{}` isn't installed", one)),
+        many => Err(format!("Not installed: {}", many.join(", "))),
+    }
+}
+
+/// `sh -n` reads the command without running any of it.
+fn syntax_check(command: &str) -> Result<(), String> {
+    let output = std::process::Command::new("sh")
+        .arg("-n")
+        .arg("-c")
+        .arg(command)
+        .stdin(std::process::Stdio::null())
+        .output();
+    match output {
+        Ok(output) if !output.status.success() => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let message = stderr.lines().next().unwrap_or("syntax error").trim();
+            Err(format!("sh -n rejects it: {}", message))
+        }
+        // No `sh` to ask: the parse above is all we can check
+        _ => Ok(()),
+    }
+}
+
+/// Programs the script runs that aren't builtins, functions it defines, or on `path`.
+fn missing_programs(script: &Script, path: &OsStr) -> Vec<String> {
+    let mut functions = HashSet::new();
+    defined_functions(script, &mut functions);
+    let mut missing = Vec::new();
+    script.walk(&mut |command| {
+        let inv = unwrap(&command.words);
+        // The wrapper itself (`sudo`) has to exist too
+        for word in command.words.first().into_iter().chain(inv.words.first()) {
+            if let Some(name) = program_name(word) {
+                if !functions.contains(&name)
+                    && !missing.contains(&name)
+                    && !is_installed(&name, path)
+                {
+                    missing.push(name);
+                }
+            }
+        }
+    });
+    missing
+}
+
+/// The program a command word names, if it can be looked up: not a variable,
+/// substitution or relative path (which earlier steps may create).
+fn program_name(word: &Word) -> Option<String> {
+    let text = word.text.as_str();
+    if text.is_empty() || text.contains('$') || !word.substitutions.is_empty() || word.glob {
+        return None;
+    }
+    let builtins = if cfg!(windows) {
+        CMD_BUILTINS
     } else {
-        Command::new("sh")
-            .arg("-c")
-            .arg(cmd)
-            .output()
-            .map_err(|e| format!("Failed to execute command: {}", e))?
+        BUILTINS
     };
+    if builtins.contains(&text.to_lowercase().as_str()) {
+        return None;
+    }
+    if text.contains(['/', '\\']) && !std::path::Path::new(text).is_absolute() {
+        return None;
+    }
+    Some(text.to_string())
+}
 
-    Ok(ShellOutput {
-        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-        exit_code: output.status.code().unwrap_or(-1),
+fn is_installed(name: &str, path: &OsStr) -> bool {
+    if std::path::Path::new(name).is_absolute() {
+        return std::path::Path::new(name).is_file();
+    }
+    let extensions: Vec<String> = if cfg!(windows) {
+        let pathext = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into());
+        std::iter::once(String::new())
+            .chain(pathext.split(';').map(str::to_string))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+    std::env::split_paths(path).any(|dir| {
+        extensions
+            .iter()
+            .any(|ext| dir.join(format!("{}{}", name, ext)).is_file())
     })
 }
+
+fn defined_functions(script: &Script, names: &mut HashSet<String>) {
+    for command in script.pipelines.iter().flat_map(|p| &p.commands) {
+        match command {
+            Command::Function { name, .. } => {
+                names.insert(name.clone());
+            }
+            Command::Group { body, .. } => defined_functions(body, names),
+            Command::Simple(_) => {}
+        }
+    }
+}
+
+/// Candidates that verify before those that don't, then the less risky, then
+/// in the order the model gave them.
+fn rank(candidates: Vec<Candidate>) -> Vec<Candidate> {
+    let mut ranked: Vec<(usize, Candidate)> = candidates.into_iter().enumerate().collect();
+    ranked.sort_by_key(|(i, c)| (c.rejected.is_some(), c.risk.severity, *i));
+    ranked.into_iter().map(|(_, c)| c).collect()
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_candidates() {
+        assert_eq!(split_candidates("```bash\nls -la\n```", 1), ["ls -la"]);
+        assert_eq!(
+            split_candidates("for f in *; do\n  echo $f\ndone", 1),
+            ["for f in *; do\n  echo $f\ndone"]
+        );
+        assert_eq!(
+            split_candidates(
+                "1. `du -sh *`\n2) ls -S\n\n- du -sh *\n* find . -size +1G\nextra",
+                3
+            ),
+            ["du -sh *", "ls -S", "find . -size +1G"]
+        );
+        assert!(split_candidates("  ", 2).is_empty());
+    }
+
+    #[test]
+    fn finds_missing_programs() {
+        let dir = std::env::temp_dir().join(format!("trace-shell-path-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ls"), "").unwrap();
+        std::fs::write(dir.join("sudo"), "").unwrap();
+        let path = dir.as_os_str();
+
+        let missing = |cmd: &str| missing_programs(&shell_parse::parse(cmd).unwrap(), path);
+        assert!(missing("ls -la && cd /tmp && echo $(ls)").is_empty());
+        assert_eq!(missing("sudo frobnicate --all | ls"), ["frobnicate"]);
+        assert_eq!(missing("doas ls; $EDITOR x; ./build.sh"), ["doas"]);
+        assert!(missing("greet() { ls; }; greet").is_empty());
+        assert_eq!(missing("ls $(nosuch)"), ["nosuch"]);
+        if !cfg!(windows) {
+            assert_eq!(missing("/definitely/not/here"), ["/definitely/not/here"]);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn verifies_and_ranks() {
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let ranked = rank(vec![
+            check("echo 'oops".to_string(), &path),
+            check("sudo echo hi".to_string(), &path),
+            check("not-a-real-program-xyz".to_string(), &path),
+            check("echo hi".to_string(), &path),
+        ]);
+        let rejected = |cmd: &str| {
+            let candidate = ranked.iter().find(|c| c.command == cmd).unwrap();
+            candidate.rejected.clone().unwrap_or_default()
+        };
+        assert_eq!(ranked[0].command, "echo hi");
+        assert!(ranked[0].rejected.is_none());
+        assert!(rejected("echo 'oops").contains("Unterminated single quote"));
+        assert_eq!(
+            rejected("not-a-real-program-xyz"),
+            "`not-a-real-program-xyz` isn't installed"
+        );
+        // Rejected candidates rank last, whatever their risk
+        assert!(ranked[ranked.len() - 2..]
+            .iter()
+            .all(|c| c.rejected.is_some()));
+        if cfg!(unix) {
+            // Parses for us, but a shell can't read it
+            let rejected = verify("echo hi; fi", &path).unwrap_err();
+            assert!(rejected.starts_with("sh -n rejects it"), "{}", rejected);
+        }
+    }
+}
@@ -0,0 +1,1547 @@
+/// Plain-English breakdown of a shell command: one step per command it runs,
+/// with what each flag, redirection and wrapper does.
+use crate::shell_parse::{self, unwrap, Command, Connector, Redirect, Script, SimpleCommand, Word};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlagNote {
+    /// The flag as written, with its value if it takes one
+    pub flag: String,
+    /// Empty when the flag isn't one we know
+    pub meaning: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Step {
+    /// How this step follows the one before it ("If that succeeds", …); empty for the first
+    pub joined: String,
+    pub command: String,
+    pub summary: String,
+    pub flags: Vec<FlagNote>,
+    /// Files, patterns and other arguments
+    pub operands: Vec<String>,
+    /// Wrappers like `sudo`, variable assignments and redirections
+    pub notes: Vec<String>,
+    /// Nesting inside groups, function bodies, substitutions and `sh -c` strings
+    pub depth: usize,
+}
+
+/// Explain a command line step by step.
+pub fn explain(command: &str) -> Result<Vec<Step>, String> {
+    let script =
+        shell_parse::parse(command).map_err(|e| format!("Can't explain this command: {}", e))?;
+    let mut steps = Vec::new();
+    explain_script(&script, command, 0, "", &mut steps);
+    Ok(steps)
+}
+
+// ─── Command table ───────────────────────────────────────
+
+/// (command, summary, flags). Flags list their spellings separated by spaces; a
+/// spelling ending in `=` takes a value (the next word, or after `=`). Entries
+/// named "prog sub" describe subcommands.
+type Doc = (
+    &'static str,
+    &'static str,
+    &'static [(&'static str, &'static str)],
+);
+
+const DOCS: &[Doc] = &[
+    (
+        "ls",
+        "List directory contents",
+        &[
+            ("-l", "Long format: permissions, owner, size and date"),
+            ("-a --all", "Include hidden files"),
+            ("-A --almost-all", "Include hidden files, except . and .."),
+            ("-h --human-readable", "Sizes like 4.2K and 1.1G"),
+            ("-t", "Newest first"),
+            ("-S", "Largest first"),
+            ("-r --reverse", "Reverse the order"),
+            ("-R --recursive", "List subdirectories too"),
+            (
+                "-d --directory",
+                "List directories themselves, not their contents",
+            ),
+            ("-1", "One entry per line"),
+        ],
+    ),
+    ("cd", "Change the current directory", &[]),
+    ("pwd", "Print the current directory", &[]),
+    (
+        "rm",
+        "Delete files",
+        &[
+            (
+                "-r -R --recursive",
+                "Delete directories and everything inside them",
+            ),
+            ("-f --force", "Never ask, and ignore files that don't exist"),
+            ("-i", "Ask before every deletion"),
+            ("-I", "Ask once before deleting more than three files"),
+            ("-d --dir", "Delete empty directories"),
+            ("-v --verbose", "Print each file as it is deleted"),
+            ("--no-preserve-root", "Allow deleting / itself"),
+            ("--preserve-root", "Refuse to delete / (the default)"),
+        ],
+    ),
+    (
+        "rmdir",
+        "Delete empty directories",
+        &[(
+            "-p --parents",
+            "Also delete parent directories that become empty",
+        )],
+    ),
+    (
+        "mv",
+        "Move or rename files",
+        &[
+            ("-f --force", "Overwrite without asking"),
+            ("-i --interactive", "Ask before overwriting"),
+            ("-n --no-clobber", "Never overwrite existing files"),
+            ("-u --update", "Only move files newer than the destination"),
+            ("-b --backup", "Keep a backup of files it overwrites"),
+            (
+                "-t= --target-directory=",
+                "Move everything into this directory",
+            ),
+            ("-v --verbose", "Print each file as it is moved"),
+        ],
+    ),
+    (
+        "cp",
+        "Copy files",
+        &[
+            (
+                "-r -R --recursive",
+                "Copy directories and everything inside them",
+            ),
+            (
+                "-a --archive",
+                "Copy recursively, keeping permissions, owners and timestamps",
+            ),
+            ("-p", "Keep permissions, owners and timestamps"),
+            (
+                "-f --force",
+                "Replace destination files that can't be opened",
+            ),
+            ("-i --interactive", "Ask before overwriting"),
+            ("-n --no-clobber", "Never overwrite existing files"),
+            ("-u --update", "Only copy files newer than the destination"),
+            ("-l --link", "Make hard links instead of copies"),
+            (
+                "-s --symbolic-link",
+                "Make symbolic links instead of copies",
+            ),
+            (
+                "-t= --target-directory=",
+                "Copy everything into this directory",
+            ),
+            ("-v --verbose", "Print each file as it is copied"),
+        ],
+    ),
+    (
+        "mkdir",
+        "Create directories",
+        &[
+            (
+                "-p --parents",
+                "Create parent directories as needed; no error if it exists",
+            ),
+            ("-m= --mode=", "Permissions for the new directories"),
+            ("-v --verbose", "Print each directory as it is created"),
+        ],
+    ),
+    (
+        "touch",
+        "Create empty files, or update their timestamps",
+        &[
+            ("-c --no-create", "Don't create files that don't exist"),
+            ("-d= --date=", "Use this date instead of now"),
+            ("-t=", "Use this timestamp instead of now"),
+        ],
+    ),
+    (
+        "ln",
+        "Create links",
+        &[
+            ("-s --symbolic", "Symbolic link instead of a hard link"),
+            ("-f --force", "Replace an existing file at the link's path"),
+            (
+                "-n --no-dereference",
+                "Treat a link to a directory as a file",
+            ),
+        ],
+    ),
+    (
+        "chmod",
+        "Change file permissions",
+        &[
+            (
+                "-R --recursive",
+                "Apply to directories and everything inside them",
+            ),
+            ("-v --verbose", "Print every file processed"),
+            ("-c --changes", "Print only files that change"),
+            ("--reference=", "Copy the permissions of this file"),
+        ],
+    ),
+    (
+        "chown",
+        "Change file owner and group",
+        &[
+            (
+                "-R --recursive",
+                "Apply to directories and everything inside them",
+            ),
+            ("-h --no-dereference", "Change symbolic links themselves"),
+            ("-v --verbose", "Print every file processed"),
+        ],
+    ),
+    (
+        "chgrp",
+        "Change file group",
+        &[(
+            "-R --recursive",
+            "Apply to directories and everything inside them",
+        )],
+    ),
+    (
+        "find",
+        "Search a directory tree for files",
+        &[
+            ("-name=", "Match file names against this pattern"),
+            (
+                "-iname=",
+                "Match file names against this pattern, ignoring case",
+            ),
+            (
+                "-path= -wholename=",
+                "Match the whole path against this pattern",
+            ),
+            (
+                "-type=",
+                "Only this kind: f files, d directories, l symlinks",
+            ),
+            ("-maxdepth=", "Descend at most this many levels"),
+            ("-mindepth=", "Skip matches shallower than this many levels"),
+            (
+                "-mtime=",
+                "Modified this many days ago (+N: more than, -N: less than)",
+            ),
+            (
+                "-mmin=",
+                "Modified this many minutes ago (+N: more than, -N: less than)",
+            ),
+            ("-size=", "Size, e.g. +100M for bigger than 100 MB"),
+            ("-newer=", "Modified more recently than this file"),
+            ("-user=", "Owned by this user"),
+            ("-perm=", "Has these permissions"),
+            ("-empty", "Empty files and directories"),
+            ("-delete", "Delete every match"),
+            ("-print", "Print every match"),
+            (
+                "-print0",
+                "Print matches separated by NUL bytes (for xargs -0)",
+            ),
+            ("-prune", "Don't descend into matching directories"),
+            ("-not !", "Negate the next test"),
+            ("-o -or", "Either test may match"),
+            ("-L", "Follow symbolic links"),
+        ],
+    ),
+    (
+        "grep",
+        "Search text for lines matching a pattern",
+        &[
+            ("-r -R --recursive", "Search every file under directories"),
+            ("-i --ignore-case", "Ignore case"),
+            ("-n --line-number", "Show line numbers"),
+            ("-v --invert-match", "Show lines that don't match"),
+            (
+                "-l --files-with-matches",
+                "Only print names of matching files",
+            ),
+            ("-c --count", "Only print how many lines match"),
+            ("-E --extended-regexp", "Extended regular expressions"),
+            ("-F --fixed-strings", "Match the pattern literally"),
+            ("-w --word-regexp", "Match whole words only"),
+            ("-o --only-matching", "Print only the matching part"),
+            (
+                "-q --quiet",
+                "Print nothing; only report through the exit code",
+            ),
+            ("-s --no-messages", "Hide errors about unreadable files"),
+            ("-h --no-filename", "Don't prefix lines with file names"),
+            ("-H --with-filename", "Prefix lines with file names"),
+            ("-A= --after-context=", "Lines of context after each match"),
+            (
+                "-B= --before-context=",
+                "Lines of context before each match",
+            ),
+            ("-C= --context=", "Lines of context around each match"),
+            ("-e= --regexp=", "Pattern to search for"),
+            ("--include=", "Only search files matching this pattern"),
+            ("--exclude=", "Skip files matching this pattern"),
+            ("--exclude-dir=", "Skip directories matching this pattern"),
+            ("--color=", "When to highlight matches"),
+        ],
+    ),
+    (
+        "sed",
+        "Edit text with a stream editor",
+        &[
+            ("-i --in-place", "Edit files in place"),
+            ("-n --quiet", "Only print lines the script prints"),
+            ("-E -r --regexp-extended", "Extended regular expressions"),
+            ("-e= --expression=", "Script to run"),
+        ],
+    ),
+    (
+        "awk",
+        "Process text field by field",
+        &[("-F=", "Field separator"), ("-v=", "Set a variable")],
+    ),
+    (
+        "cat",
+        "Print file contents",
+        &[
+            ("-n --number", "Number every line"),
+            (
+                "-A --show-all",
+                "Show tabs, line ends and control characters",
+            ),
+        ],
+    ),
+    (
+        "head",
+        "Print the first lines",
+        &[
+            ("-n= --lines=", "Number of lines"),
+            ("-c= --bytes=", "Number of bytes"),
+        ],
+    ),
+    (
+        "tail",
+        "Print the last lines",
+        &[
+            ("-n= --lines=", "Number of lines"),
+            ("-c= --bytes=", "Number of bytes"),
+            ("-f --follow", "Keep printing lines as they are added"),
+            ("-F", "Follow the file, even if it is replaced"),
+        ],
+    ),
+    ("less", "Page through text", &[]),
+    (
+        "sort",
+        "Sort lines",
+        &[
+            ("-n --numeric-sort", "Compare as numbers"),
+            ("-h --human-numeric-sort", "Compare sizes like 2K and 1G"),
+            ("-r --reverse", "Reverse the order"),
+            ("-u --unique", "Drop duplicate lines"),
+            ("-k= --key=", "Sort by this field"),
+            ("-t= --field-separator=", "Field separator"),
+        ],
+    ),
+    (
+        "uniq",
+        "Collapse repeated adjacent lines",
+        &[
+            ("-c --count", "Prefix lines with how often they occur"),
+            ("-d --repeated", "Only print repeated lines"),
+            ("-u --unique", "Only print lines that aren't repeated"),
+        ],
+    ),
+    (
+        "wc",
+        "Count lines, words and bytes",
+        &[
+            ("-l --lines", "Count lines"),
+            ("-w --words", "Count words"),
+            ("-c --bytes", "Count bytes"),
+            ("-m --chars", "Count characters"),
+        ],
+    ),
+    (
+        "cut",
+        "Cut fields or columns out of each line",
+        &[
+            ("-d= --delimiter=", "Field separator"),
+            ("-f= --fields=", "Fields to keep"),
+            ("-c= --characters=", "Character positions to keep"),
+        ],
+    ),
+    (
+        "tr",
+        "Translate or delete characters",
+        &[
+            ("-d --delete", "Delete the characters"),
+            (
+                "-s --squeeze-repeats",
+                "Collapse runs of a character into one",
+            ),
+        ],
+    ),
+    (
+        "xargs",
+        "Run a command with arguments read from its input",
+        &[
+            ("-0 --null", "Input is separated by NUL bytes"),
+            ("-n= --max-args=", "Arguments per command"),
+            ("-I=", "Replace this string with each input item"),
+            ("-P= --max-procs=", "Run this many commands in parallel"),
+            (
+                "-r --no-run-if-empty",
+                "Don't run at all when there is no input",
+            ),
+        ],
+    ),
+    (
+        "tee",
+        "Copy its input to files as well as the output",
+        &[("-a --append", "Append instead of overwriting")],
+    ),
+    (
+        "echo",
+        "Print text",
+        &[
+            ("-n", "No trailing newline"),
+            ("-e", "Interpret backslash escapes"),
+        ],
+    ),
+    ("printf", "Print formatted text", &[]),
+    (
+        "du",
+        "Show disk usage",
+        &[
+            ("-s --summarize", "One total per argument"),
+            ("-h --human-readable", "Sizes like 4.2K and 1.1G"),
+            ("-a --all", "Include files, not just directories"),
+            ("-c --total", "Print a grand total"),
+            ("-d= --max-depth=", "Descend at most this many levels"),
+        ],
+    ),
+    (
+        "df",
+        "Show free disk space",
+        &[
+            ("-h --human-readable", "Sizes like 4.2K and 1.1G"),
+            ("-T --print-type", "Show filesystem types"),
+        ],
+    ),
+    (
+        "tar",
+        "Create or extract archives",
+        &[
+            ("-c --create", "Create an archive"),
+            ("-x --extract", "Extract an archive"),
+            ("-t --list", "List an archive's contents"),
+            ("-z --gzip", "gzip compression (.tar.gz)"),
+            ("-j --bzip2", "bzip2 compression (.tar.bz2)"),
+            ("-J --xz", "xz compression (.tar.xz)"),
+            ("-v --verbose", "Print each file"),
+            ("-f= --file=", "Archive file"),
+            ("-C= --directory=", "Work in this directory"),
+        ],
+    ),
+    (
+        "gzip",
+        "Compress files (replacing them with .gz)",
+        &[
+            ("-d --decompress", "Decompress instead"),
+            ("-k --keep", "Keep the original files"),
+            ("-r --recursive", "Compress files in directories too"),
+            ("-9 --best", "Best compression"),
+        ],
+    ),
+    (
+        "gunzip",
+        "Decompress .gz files",
+        &[("-k --keep", "Keep the .gz files")],
+    ),
+    (
+        "zip",
+        "Create zip archives",
+        &[
+            (
+                "-r --recurse-paths",
+                "Include directories and everything inside them",
+            ),
+            ("-q --quiet", "Print nothing"),
+        ],
+    ),
+    (
+        "unzip",
+        "Extract zip archives",
+        &[
+            ("-d=", "Extract into this directory"),
+            ("-l", "List contents without extracting"),
+            ("-o", "Overwrite existing files without asking"),
+            ("-q", "Print nothing"),
+        ],
+    ),
+    (
+        "curl",
+        "Transfer data from or to a URL",
+        &[
+            ("-s --silent", "No progress output"),
+            ("-S --show-error", "Still show errors when silent"),
+            ("-L --location", "Follow redirects"),
+            (
+                "-f --fail",
+                "Fail on HTTP errors instead of printing the error page",
+            ),
+            ("-o= --output=", "Save to this file"),
+            ("-O --remote-name", "Save under the file name from the URL"),
+            ("-X= --request=", "HTTP method"),
+            ("-H= --header=", "Extra request header"),
+            ("-d= --data=", "Request body"),
+            ("-I --head", "Fetch headers only"),
+            ("-k --insecure", "Skip TLS certificate checks"),
+        ],
+    ),
+    (
+        "wget",
+        "Download files",
+        &[
+            ("-q --quiet", "No output"),
+            (
+                "-O= --output-document=",
+                "Save to this file (- prints it instead)",
+            ),
+            ("-c --continue", "Resume a partial download"),
+            ("-r --recursive", "Download linked pages too"),
+            ("-P= --directory-prefix=", "Save into this directory"),
+        ],
+    ),
+    (
+        "ps",
+        "List processes",
+        &[
+            ("-e -A", "All processes"),
+            ("-f", "Full format"),
+            ("-a", "Processes of all users"),
+            ("-u", "User-oriented format"),
+            ("-x", "Include processes without a terminal"),
+        ],
+    ),
+    (
+        "kill",
+        "Send a signal to processes (terminate them, by default)",
+        &[
+            (
+                "-9 -KILL -SIGKILL",
+                "Force-kill immediately; no chance to clean up",
+            ),
+            ("-15 -TERM -SIGTERM", "Ask to terminate (the default)"),
+            (
+                "-1 -HUP -SIGHUP",
+                "Hang up; many daemons reload their config",
+            ),
+            ("-s=", "Signal to send"),
+            ("-l", "List signal names"),
+        ],
+    ),
+    (
+        "killall",
+        "Kill processes by name",
+        &[
+            ("-9", "Force-kill immediately"),
+            ("-u= --user=", "Only this user's processes"),
+            ("-i --interactive", "Ask before each kill"),
+        ],
+    ),
+    (
+        "pkill",
+        "Kill processes matching a pattern",
+        &[
+            ("-9", "Force-kill immediately"),
+            (
+                "-f --full",
+                "Match the whole command line, not just the name",
+            ),
+            ("-u= --euid=", "Only this user's processes"),
+        ],
+    ),
+    (
+        "ssh",
+        "Log in to a remote machine",
+        &[
+            ("-p=", "Port"),
+            ("-i=", "Private key file"),
+            ("-L=", "Forward a local port to the remote side"),
+            ("-N", "Don't run a remote command (forwarding only)"),
+            ("-v", "Verbose"),
+        ],
+    ),
+    (
+        "scp",
+        "Copy files over SSH",
+        &[
+            ("-r", "Copy directories recursively"),
+            ("-P=", "Port"),
+            ("-i=", "Private key file"),
+        ],
+    ),
+    (
+        "rsync",
+        "Synchronize files and directories",
+        &[
+            (
+                "-a --archive",
+                "Recursive, keeping permissions, owners and timestamps",
+            ),
+            ("-r --recursive", "Recurse into directories"),
+            ("-v --verbose", "Print each file"),
+            ("-z --compress", "Compress during transfer"),
+            ("-h --human-readable", "Readable sizes"),
+            ("-P", "Show progress and keep partial files"),
+            (
+                "-n --dry-run",
+                "Show what would happen without changing anything",
+            ),
+            (
+                "--delete",
+                "Delete destination files that aren't in the source",
+            ),
+            ("-e= --rsh=", "Remote shell to use"),
+        ],
+    ),
+    (
+        "dd",
+        "Copy raw data block by block",
+        &[
+            ("if=", "Read from this file or device"),
+            (
+                "of=",
+                "Write to this file or device, replacing its contents",
+            ),
+            ("bs=", "Block size"),
+            ("count=", "Copy only this many blocks"),
+            ("status=", "Progress reporting"),
+        ],
+    ),
+    (
+        "shutdown",
+        "Shut down or restart the computer",
+        &[
+            ("-h", "Power off"),
+            ("-r", "Restart"),
+            ("-c", "Cancel a scheduled shutdown"),
+        ],
+    ),
+    (
+        "crontab",
+        "Manage scheduled cron jobs",
+        &[
+            ("-l", "List your jobs"),
+            ("-e", "Edit your jobs"),
+            ("-r", "Delete all of your jobs"),
+        ],
+    ),
+    ("which", "Show where a command's program is", &[]),
+    ("man", "Show the manual for a command", &[]),
+    ("sleep", "Wait", &[]),
+    ("date", "Print the date and time", &[]),
+    ("whoami", "Print your user name", &[]),
+    (
+        "uname",
+        "Print system information",
+        &[("-a --all", "Everything")],
+    ),
+    (
+        "free",
+        "Show memory usage",
+        &[("-h --human", "Sizes like 1.2G")],
+    ),
+    (
+        "watch",
+        "Rerun a command every few seconds",
+        &[("-n= --interval=", "Seconds between runs")],
+    ),
+    ("open", "Open with the default app", &[]),
+    ("xdg-open", "Open with the default app", &[]),
+    (
+        "sh",
+        "Run a shell",
+        &[
+            ("-c=", "Command string to run"),
+            ("-e", "Stop at the first error"),
+        ],
+    ),
+    (
+        "bash",
+        "Run a bash shell",
+        &[
+            ("-c=", "Command string to run"),
+            ("-e", "Stop at the first error"),
+        ],
+    ),
+    (
+        "python3",
+        "Run Python",
+        &[("-m=", "Run this module"), ("-c=", "Run this code")],
+    ),
+    (
+        "python",
+        "Run Python",
+        &[("-m=", "Run this module"), ("-c=", "Run this code")],
+    ),
+    (
+        "node",
+        "Run JavaScript",
+        &[("-e= --eval=", "Run this code")],
+    ),
+    (
+        "mkfs",
+        "Create a filesystem, erasing the device",
+        &[("-t=", "Filesystem type")],
+    ),
+    ("git", "Run git", &[("-C=", "Run in this directory")]),
+    (
+        "git status",
+        "Show changed files",
+        &[("-s --short", "Short format")],
+    ),
+    (
+        "git add",
+        "Stage changes for the next commit",
+        &[
+            ("-A --all", "Stage everything, including deletions"),
+            ("-p --patch", "Pick changes interactively"),
+        ],
+    ),
+    (
+        "git commit",
+        "Record the staged changes",
+        &[
+            ("-m= --message=", "Commit message"),
+            ("-a --all", "Stage modified tracked files first"),
+            ("--amend", "Replace the last commit"),
+        ],
+    ),
+    (
+        "git push",
+        "Upload commits to the remote",
+        &[
+            (
+                "-f --force",
+                "Overwrite the remote branch, even if that loses commits",
+            ),
+            (
+                "--force-with-lease",
+                "Overwrite the remote branch only if nobody else pushed",
+            ),
+            (
+                "-u --set-upstream",
+                "Remember the remote branch for later pushes",
+            ),
+        ],
+    ),
+    (
+        "git pull",
+        "Fetch and merge remote changes",
+        &[("--rebase", "Rebase instead of merging")],
+    ),
+    (
+        "git fetch",
+        "Download remote changes without merging",
+        &[("--all", "From every remote")],
+    ),
+    (
+        "git clone",
+        "Copy a repository",
+        &[("--depth=", "Only this many recent commits")],
+    ),
+    (
+        "git checkout",
+        "Switch branches or restore files",
+        &[("-b", "Create the branch first")],
+    ),
+    (
+        "git switch",
+        "Switch branches",
+        &[("-c --create", "Create the branch first")],
+    ),
+    (
+        "git branch",
+        "List, create or delete branches",
+        &[
+            ("-d --delete", "Delete a merged branch"),
+            ("-D", "Delete a branch, even if unmerged"),
+            ("-a --all", "Include remote branches"),
+        ],
+    ),
+    (
+        "git log",
+        "Show commit history",
+        &[
+            ("--oneline", "One line per commit"),
+            ("--graph", "Draw the branch graph"),
+            ("-n=", "Only this many commits"),
+        ],
+    ),
+    (
+        "git diff",
+        "Show changes",
+        &[("--staged --cached", "Staged changes instead")],
+    ),
+    (
+        "git reset",
+        "Move the branch and unstage changes",
+        &[
+            ("--hard", "Also discard all uncommitted changes"),
+            ("--soft", "Keep changes staged"),
+        ],
+    ),
+    (
+        "git clean",
+        "Delete untracked files",
+        &[
+            ("-f --force", "Actually delete"),
+            ("-d", "Include untracked directories"),
+            ("-x", "Include ignored files"),
+            ("-n --dry-run", "Only show what would be deleted"),
+        ],
+    ),
+    ("git stash", "Set uncommitted changes aside", &[]),
+    ("git merge", "Merge another branch", &[]),
+    (
+        "git rebase",
+        "Replay commits onto another base",
+        &[("-i --interactive", "Edit the commit list first")],
+    ),
+    (
+        "git restore",
+        "Discard changes to files",
+        &[("--staged", "Unstage instead")],
+    ),
+    (
+        "apt",
+        "Manage packages",
+        &[("-y --yes", "Answer yes to prompts")],
+    ),
+    (
+        "apt install",
+        "Install packages",
+        &[("-y --yes", "Answer yes to prompts")],
+    ),
+    (
+        "apt remove",
+        "Uninstall packages",
+        &[("-y --yes", "Answer yes to prompts")],
+    ),
+    ("apt update", "Refresh the package lists", &[]),
+    (
+        "apt upgrade",
+        "Upgrade installed packages",
+        &[("-y --yes", "Answer yes to prompts")],
+    ),
+    ("systemctl", "Control system services", &[]),
+    ("systemctl start", "Start a service", &[]),
+    ("systemctl stop", "Stop a service", &[]),
+    ("systemctl restart", "Restart a service", &[]),
+    ("systemctl status", "Show a service's status", &[]),
+    (
+        "systemctl enable",
+        "Start a service at boot",
+        &[("--now", "And start it now")],
+    ),
+    (
+        "systemctl disable",
+        "Stop starting a service at boot",
+        &[("--now", "And stop it now")],
+    ),
+    ("docker", "Manage containers", &[]),
+    (
+        "docker run",
+        "Start a new container",
+        &[
+            ("-d --detach", "Run in the background"),
+            ("-i --interactive", "Keep input open"),
+            ("-t --tty", "Give it a terminal"),
+            ("--rm", "Remove the container when it exits"),
+            ("-p= --publish=", "Publish a port (host:container)"),
+            ("-v= --volume=", "Mount a volume (host:container)"),
+            ("-e= --env=", "Set an environment variable"),
+            ("--name=", "Container name"),
+        ],
+    ),
+    (
+        "docker ps",
+        "List containers",
+        &[("-a --all", "Include stopped containers")],
+    ),
+    (
+        "docker rm",
+        "Remove containers",
+        &[("-f --force", "Stop running containers first")],
+    ),
+    (
+        "docker build",
+        "Build an image",
+        &[("-t= --tag=", "Image name")],
+    ),
+    (
+        "docker exec",
+        "Run a command in a container",
+        &[
+            ("-i --interactive", "Keep input open"),
+            ("-t --tty", "Give it a terminal"),
+        ],
+    ),
+    // cmd.exe
+    (
+        "dir",
+        "List directory contents",
+        &[("/s", "Include subfolders"), ("/b", "Bare names only")],
+    ),
+    (
+        "del",
+        "Delete files",
+        &[
+            ("/s", "Also in every subfolder"),
+            ("/q", "Don't ask for confirmation"),
+            ("/f", "Include read-only files"),
+        ],
+    ),
+    (
+        "rd",
+        "Remove a folder",
+        &[
+            ("/s", "With everything inside it"),
+            ("/q", "Don't ask for confirmation"),
+        ],
+    ),
+    ("copy", "Copy files", &[("/y", "Overwrite without asking")]),
+    ("move", "Move files", &[("/y", "Overwrite without asking")]),
+    (
+        "taskkill",
+        "End processes",
+        &[
+            ("/f", "Force them to end"),
+            ("/im=", "By image (program) name"),
+            ("/pid=", "By process id"),
+            ("/t", "Including child processes"),
+        ],
+    ),
+];
+
+/// Commands with subcommands documented as "prog sub".
+const WITH_SUBCOMMANDS: &[&str] = &["git", "apt", "apt-get", "systemctl", "docker"];
+
+const WRAPPERS: &[(&str, &str)] = &[
+    ("sudo", "Runs as root (sudo)"),
+    ("doas", "Runs as root (doas)"),
+    ("env", "Runs with a modified environment (env)"),
+    ("nohup", "Keeps running after the terminal closes (nohup)"),
+    ("nice", "Runs at a lower priority (nice)"),
+    ("ionice", "Runs with a lower disk priority (ionice)"),
+    ("time", "Reports how long it took (time)"),
+    (
+        "timeout",
+        "Stopped if it runs past the time limit (timeout)",
+    ),
+    ("stdbuf", "Runs with different output buffering (stdbuf)"),
+    ("xargs", "Gets more arguments from its input (xargs)"),
+    ("exec", "Replaces the shell (exec)"),
+    ("command", "Skips shell functions and aliases (command)"),
+];
+
+fn find_doc(name: &str) -> Option<&'static Doc> {
+    let name = if name == "apt-get" { "apt" } else { name };
+    let name = if name.starts_with("mkfs.") {
+        "mkfs"
+    } else {
+        name
+    };
+    let name = if name == "erase" { "del" } else { name };
+    DOCS.iter().find(|doc| doc.0 == name)
+}
+
+/// The meaning of a flag spelling, and whether it takes a value.
+fn lookup(doc: &Doc, spelling: &str) -> Option<(&'static str, bool)> {
+    let lower = spelling.to_lowercase();
+    // cmd.exe switches are case-insensitive
+    let spelling = if spelling.starts_with('/') {
+        lower.as_str()
+    } else {
+        spelling
+    };
+    doc.2.iter().find_map(|(spellings, meaning)| {
+        spellings
+            .split(' ')
+            .find_map(|s| match s.strip_suffix('=') {
+                Some(s) if s == spelling => Some((*meaning, true)),
+                None if s == spelling => Some((*meaning, false)),
+                _ => None,
+            })
+    })
+}
+
+// ─── Explaining ──────────────────────────────────────────
+
+fn joined_label(connector: Connector) -> &'static str {
+    match connector {
+        Connector::Then => "Then",
+        Connector::And => "If that succeeds",
+        Connector::Or => "If that fails",
+        Connector::Background => "Meanwhile",
+    }
+}
+
+fn explain_script(script: &Script, src: &str, depth: usize, first: &str, steps: &mut Vec<Step>) {
+    let mut joined = first.to_string();
+    for pipeline in &script.pipelines {
+        for (i, command) in pipeline.commands.iter().enumerate() {
+            if i > 0 {
+                joined = "Piped into".to_string();
+            }
+            explain_command(command, src, depth, &joined, steps);
+        }
+        if pipeline.connector == Connector::Background {
+            if let Some(step) = steps.last_mut() {
+                step.notes.push("Runs in the background".to_string());
+            }
+        }
+        joined = joined_label(pipeline.connector).to_string();
+    }
+}
+
+fn explain_command(
+    command: &Command,
+    src: &str,
+    depth: usize,
+    joined: &str,
+    steps: &mut Vec<Step>,
+) {
+    match command {
+        Command::Simple(simple) => explain_simple(simple, src, depth, joined, steps),
+        Command::Group { body, redirects } => {
+            let start = steps.len();
+            explain_script(body, src, depth + 1, joined, steps);
+            if let Some(step) = steps.get_mut(start) {
+                step.notes.extend(redirects.iter().map(describe_redirect));
+            }
+        }
+        Command::Function { name, body } => {
+            steps.push(Step {
+                joined: joined.to_string(),
+                command: format!("{}()", name),
+                summary: format!("Defines the function `{}`", name),
+                flags: vec![],
+                operands: vec![],
+                notes: vec![],
+                depth,
+            });
+            explain_command(body, src, depth + 1, "When called", steps);
+        }
+    }
+}
+
+fn explain_simple(
+    command: &SimpleCommand,
+    src: &str,
+    depth: usize,
+    joined: &str,
+    steps: &mut Vec<Step>,
+) {
+    let text = src
+        .get(command.span.0..command.span.1)
+        .unwrap_or_default()
+        .to_string();
+    let inv = unwrap(&command.words);
+    let wrappers = &command.words[..command.words.len() - inv.words.len()];
+
+    let mut notes: Vec<String> = command
+        .assignments
+        .iter()
+        .map(|w| assignment_note(&w.text))
+        .collect();
+    for word in wrappers {
+        if let Some((_, note)) = WRAPPERS.iter().find(|(name, _)| *name == word.text) {
+            notes.push(note.to_string());
+        } else if shell_parse::is_assignment(&word.text) {
+            notes.push(assignment_note(&word.text));
+        }
+    }
+    notes.extend(command.redirects.iter().map(describe_redirect));
+
+    let Some(name) = inv.name() else {
+        let summary = if command.redirects.is_empty() {
+            "Sets shell variables"
+        } else {
+            "Only redirects"
+        };
+        steps.push(Step {
+            joined: joined.to_string(),
+            command: text,
+            summary: summary.to_string(),
+            flags: vec![],
+            operands: vec![],
+            notes,
+            depth,
+        });
+        return;
+    };
+
+    // `git commit` is documented apart from `git`
+    let mut args = inv.args();
+    let mut doc = find_doc(&name);
+    let mut summary = doc.map(|d| d.1.to_string());
+    if WITH_SUBCOMMANDS.contains(&name.as_str()) {
+        let skip = args.iter().take_while(|w| w.text.starts_with('-')).count();
+        if let Some(sub) = args.get(skip) {
+            let sub_doc = find_doc(&format!("{} {}", name, sub.text));
+            summary = Some(match sub_doc {
+                Some(d) => d.1.to_string(),
+                None => format!("Runs `{} {}`", name, sub.text),
+            });
+            doc = sub_doc.or(doc);
+            args = &args[skip + 1..];
+        }
+    }
+    let summary = summary.unwrap_or_else(|| format!("Runs `{}`", name));
+    let (flags, operands) = describe_args(&name, doc, args);
+
+    if name == "chmod" {
+        if let Some(note) = operands.first().and_then(|mode| describe_mode(mode)) {
+            notes.push(note);
+        }
+    }
+
+    steps.push(Step {
+        joined: joined.to_string(),
+        command: text,
+        summary,
+        flags,
+        operands,
+        notes,
+        depth,
+    });
+
+    // Command strings run by `sh -c` and `eval`, and `$(…)` substitutions
+    if ["sh", "bash", "zsh", "dash", "eval"].contains(&name.as_str()) {
+        let script = if name == "eval" {
+            Some(
+                inv.args()
+                    .iter()
+                    .map(|w| w.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )
+        } else {
+            inv.args()
+                .iter()
+                .position(|w| w.text == "-c")
+                .and_then(|i| inv.args().get(i + 1))
+                .map(|w| w.text.clone())
+        };
+        if let Some(script) = script.filter(|_| depth < 4) {
+            if let Ok(inner) = shell_parse::parse(&script) {
+                explain_script(&inner, &script, depth + 1, "Which runs", steps);
+            }
+        }
+    }
+    let words = command
+        .assignments
+        .iter()
+        .chain(&command.words)
+        .chain(command.redirects.iter().map(|r| &r.target));
+    for script in words.flat_map(|w| &w.substitutions) {
+        explain_script(
+            script,
+            src,
+            depth + 1,
+            "First runs, to fill in a value",
+            steps,
+        );
+    }
+}
+
+fn assignment_note(text: &str) -> String {
+    let (name, value) = text.split_once('=').unwrap_or((text, ""));
+    format!("Sets {} to \"{}\" for this command", name, value)
+}
+
+fn describe_redirect(redirect: &Redirect) -> String {
+    let target = redirect.target.text.as_str();
+    let errors = redirect.fd == Some(2);
+    let stream = if errors { "errors" } else { "output" };
+    match redirect.op {
+        ">" | ">|" if target == "/dev/null" => format!("Discards {}", stream),
+        ">" | ">|" => format!("Writes {} to {}, replacing it", stream, target),
+        ">>" => format!("Appends {} to {}", stream, target),
+        "&>" | "&>>" if target == "/dev/null" => "Discards output and errors".to_string(),
+        "&>" => format!("Writes output and errors to {}, replacing it", target),
+        "&>>" => format!("Appends output and errors to {}", target),
+        ">&" if errors && target == "1" => "Sends errors to the same place as output".to_string(),
+        ">&" if target == "2" => "Sends output to the same place as errors".to_string(),
+        ">&" => format!("Sends {} to file descriptor {}", stream, target),
+        "<" => format!("Reads input from {}", target),
+        "<<" | "<<-" => format!("Reads input from the lines that follow, up to {}", target),
+        "<<<" => format!("Reads input from the text \"{}\"", target),
+        op => format!("Redirects ({}) {}", op, target),
+    }
+}
+
+/// Split arguments into explained flags and operands.
+fn describe_args(name: &str, doc: Option<&Doc>, args: &[Word]) -> (Vec<FlagNote>, Vec<String>) {
+    let mut flags = Vec::new();
+    let mut operands = Vec::new();
+    let mut i = 0;
+    while let Some(word) = args.get(i) {
+        i += 1;
+        let mut text = word.text.clone();
+        // `tar xzf` and `ps aux` take their flags without a dash
+        if i == 1 && matches!(name, "tar" | "ps") && text.chars().all(|c| c.is_ascii_alphabetic()) {
+            text.insert(0, '-');
+        }
+        if text == "--" {
+            flags.push(FlagNote {
+                flag: text,
+                meaning: "Everything after this is an argument, not an option".to_string(),
+            });
+            operands.extend(args[i..].iter().map(|w| w.text.clone()));
+            break;
+        }
+        // cmd.exe switches only count when we know them, so paths stay operands
+        let is_option = (text.starts_with('-') && text.len() > 1)
+            || (text.starts_with('/') && doc.is_some_and(|d| lookup(d, &text).is_some()));
+        if !is_option {
+            // `if=/dev/sda` style operands of dd
+            if let Some((key, _)) = text.split_once('=') {
+                if let Some((meaning, true)) = doc.and_then(|d| lookup(d, key)) {
+                    flags.push(FlagNote {
+                        flag: text,
+                        meaning: meaning.to_string(),
+                    });
+                    continue;
+                }
+            }
+            operands.push(text);
+            continue;
+        }
+
+        // find's -exec runs a command up to `;` or `+`
+        if name == "find" && matches!(text.as_str(), "-exec" | "-execdir" | "-ok" | "-okdir") {
+            let end = args[i..]
+                .iter()
+                .position(|w| w.text == ";" || w.text == "+")
+                .map_or(args.len(), |p| i + p);
+            let run: Vec<&str> = args[i..end].iter().map(|w| w.text.as_str()).collect();
+            let terminator = args.get(end).map_or("", |w| w.text.as_str());
+            flags.push(FlagNote {
+                flag: format!("{} {} {}", text, run.join(" "), terminator)
+                    .trim_end()
+                    .to_string(),
+                meaning: format!("Run `{}` on every match ({{}} is the match)", run.join(" ")),
+            });
+            i = end + 1;
+            continue;
+        }
+
+        let (key, inline) = match text.split_once('=') {
+            Some((key, value)) if text.starts_with("--") => {
+                (key.to_string(), Some(value.to_string()))
+            }
+            _ => (text.clone(), None),
+        };
+        let Some(doc) = doc else {
+            flags.push(FlagNote {
+                flag: text,
+                meaning: String::new(),
+            });
+            continue;
+        };
+        if let Some((meaning, takes_value)) = lookup(doc, &key) {
+            let mut flag = text.clone();
+            if takes_value && inline.is_none() {
+                if let Some(value) = args.get(i) {
+                    flag = format!("{} {}", text, value.text);
+                    i += 1;
+                }
+            }
+            flags.push(FlagNote {
+                flag,
+                meaning: meaning.to_string(),
+            });
+            continue;
+        }
+
+        // Grouped short flags, like -rf; only when every letter is known
+        let grouped = !text.starts_with("--") && text.starts_with('-');
+        let mut notes = Vec::new();
+        let mut value_taken = false;
+        if grouped {
+            for (at, c) in text.char_indices().skip(1) {
+                let Some((meaning, takes_value)) = lookup(doc, &format!("-{}", c)) else {
+                    notes.clear();
+                    break;
+                };
+                let mut flag = format!("-{}", c);
+                if takes_value {
+                    let rest = &text[at + c.len_utf8()..];
+                    if !rest.is_empty() {
+                        flag.push(' ');
+                        flag.push_str(rest);
+                    } else if let Some(value) = args.get(i) {
+                        flag = format!("{} {}", flag, value.text);
+                        value_taken = true;
+                    }
+                    notes.push(FlagNote {
+                        flag,
+                        meaning: meaning.to_string(),
+                    });
+                    break;
+                }
+                notes.push(FlagNote {
+                    flag,
+                    meaning: meaning.to_string(),
+                });
+            }
+        }
+        if notes.is_empty() {
+            flags.push(FlagNote {
+                flag: text,
+                meaning: String::new(),
+            });
+        } else {
+            flags.extend(notes);
+            if value_taken {
+                i += 1;
+            }
+        }
+    }
+    (flags, operands)
+}
+
+/// `755` → who may read, write and run; `u+x` → what changes.
+fn describe_mode(mode: &str) -> Option<String> {
+    fn perms(bits: u32) -> String {
+        let names: Vec<&str> = [(4, "read"), (2, "write"), (1, "run")]
+            .iter()
+            .filter(|(bit, _)| bits & bit != 0)
+            .map(|(_, name)| *name)
+            .collect();
+        if names.is_empty() {
+            "nothing".to_string()
+        } else {
+            names.join(", ")
+        }
+    }
+    fn letters(perms: &str) -> String {
+        perms
+            .chars()
+            .filter_map(|c| match c {
+                'r' => Some("read"),
+                'w' => Some("write"),
+                'x' => Some("run"),
+                'X' => Some("run (directories)"),
+                's' => Some("setuid/setgid"),
+                't' => Some("sticky"),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    if (3..=4).contains(&mode.len()) && mode.chars().all(|c| ('0'..='7').contains(&c)) {
+        let digits: Vec<u32> = mode.chars().filter_map(|c| c.to_digit(8)).collect();
+        let &[owner, group, others] = &digits[digits.len() - 3..] else {
+            return None;
+        };
+        return Some(format!(
+            "Mode {}: owner can {}; group can {}; others can {}",
+            mode,
+            perms(owner),
+            perms(group),
+            perms(others)
+        ));
+    }
+
+    let clauses: Vec<String> = mode
+        .split(',')
+        .map(|clause| {
+            let op_at = clause.find(['+', '-', '='])?;
+            let who = &clause[..op_at];
+            let whom = if who.is_empty() || who.contains('a') {
+                "everyone".to_string()
+            } else {
+                who.chars()
+                    .filter_map(|c| match c {
+                        'u' => Some("the owner"),
+                        'g' => Some("the group"),
+                        'o' => Some("others"),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            };
+            let what = letters(&clause[op_at + 1..]);
+            Some(match &clause[op_at..op_at + 1] {
+                "+" => format!("lets {} {}", whom, what),
+                "-" => format!("stops {} being able to {}", whom, what),
+                _ => format!("lets {} only {}", whom, what),
+            })
+        })
+        .collect::<Option<_>>()?;
+    let sentence = clauses.join("; ");
+    let mut chars = sentence.chars();
+    let first = chars.next()?.to_uppercase().collect::<String>();
+    Some(format!("Mode {}: {}{}", mode, first, chars.as_str()))
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(step: &Step) -> Vec<(&str, &str)> {
+        step.flags
+            .iter()
+            .map(|f| (f.flag.as_str(), f.meaning.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn grouped_and_long_flags() {
+        let steps = explain("rm -rf --verbose build/ 'my dir'").unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].summary, "Delete files");
+        assert_eq!(
+            flags(&steps[0]),
+            [
+                ("-r", "Delete directories and everything inside them"),
+                ("-f", "Never ask, and ignore files that don't exist"),
+                ("--verbose", "Print each file as it is deleted"),
+            ]
+        );
+        assert_eq!(steps[0].operands, ["build/", "my dir"]);
+    }
+
+    #[test]
+    fn values_and_dashless_flags() {
+        let steps =
+            explain("tar xzf site.tar.gz -C /srv && head -n 5 README && sort -k2 data").unwrap();
+        assert_eq!(
+            flags(&steps[0]),
+            [
+                ("-x", "Extract an archive"),
+                ("-z", "gzip compression (.tar.gz)"),
+                ("-f site.tar.gz", "Archive file"),
+                ("-C /srv", "Work in this directory"),
+            ]
+        );
+        assert!(steps[0].operands.is_empty());
+        assert_eq!(steps[1].joined, "If that succeeds");
+        assert_eq!(flags(&steps[1]), [("-n 5", "Number of lines")]);
+        assert_eq!(flags(&steps[2]), [("-k 2", "Sort by this field")]);
+    }
+
+    #[test]
+    fn pipelines_wrappers_and_redirects() {
+        let steps =
+            explain("LANG=C sudo nohup du -sh /var 2>/dev/null | sort -h > sizes.txt &").unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(
+            steps[0].notes,
+            [
+                "Sets LANG to \"C\" for this command",
+                "Runs as root (sudo)",
+                "Keeps running after the terminal closes (nohup)",
+                "Discards errors",
+            ]
+        );
+        assert_eq!(steps[1].joined, "Piped into");
+        assert_eq!(
+            steps[1].notes,
+            [
+                "Writes output to sizes.txt, replacing it",
+                "Runs in the background"
+            ]
+        );
+    }
+
+    #[test]
+    fn find_exec_and_subcommands() {
+        let steps =
+            explain("find . -name '*.log' -mtime +7 -exec rm {} \\; ; git push -f origin main")
+                .unwrap();
+        assert_eq!(
+            flags(&steps[0]),
+            [
+                ("-name *.log", "Match file names against this pattern"),
+                (
+                    "-mtime +7",
+                    "Modified this many days ago (+N: more than, -N: less than)"
+                ),
+                (
+                    "-exec rm {} ;",
+                    "Run `rm {}` on every match ({} is the match)"
+                ),
+            ]
+        );
+        assert_eq!(steps[0].operands, ["."]);
+        assert_eq!(steps[1].joined, "Then");
+        assert_eq!(steps[1].summary, "Upload commits to the remote");
+        assert_eq!(steps[1].operands, ["origin", "main"]);
+    }
+
+    #[test]
+    fn nested_and_unknown_commands() {
+        let steps = explain("bash -c 'cd /tmp && ls' ; frobnicate --turbo x").unwrap();
+        let summaries: Vec<(&str, usize)> = steps
+            .iter()
+            .map(|s| (s.summary.as_str(), s.depth))
+            .collect();
+        assert_eq!(
+            summaries,
+            [
+                ("Run a bash shell", 0),
+                ("Change the current directory", 1),
+                ("List directory contents", 1),
+                ("Runs `frobnicate`", 0),
+            ]
+        );
+        assert_eq!(steps[1].joined, "Which runs");
+        assert_eq!(flags(&steps[3]), [("--turbo", "")]);
+
+        let steps = explain("echo \"Today is $(date +%A)\"").unwrap();
+        assert_eq!(steps[1].summary, "Print the date and time");
+        assert_eq!(steps[1].joined, "First runs, to fill in a value");
+
+        assert!(explain("echo 'oops")
+            .unwrap_err()
+            .contains("Unterminated single quote"));
+    }
+
+    #[test]
+    fn modes_and_dd_operands() {
+        let steps = explain("chmod 750 deploy.sh && chmod g-w,o+rx shared").unwrap();
+        assert_eq!(
+            steps[0].notes,
+            ["Mode 750: owner can read, write, run; group can read, run; others can nothing"]
+        );
+        assert_eq!(
+            steps[1].notes,
+            ["Mode g-w,o+rx: Stops the group being able to write; lets others read, run"]
+        );
+
+        let steps = explain("dd if=disk.img of=/dev/sdb bs=4M").unwrap();
+        assert_eq!(
+            flags(&steps[0]),
+            [
+                ("if=disk.img", "Read from this file or device"),
+                (
+                    "of=/dev/sdb",
+                    "Write to this file or device, replacing its contents"
+                ),
+                ("bs=4M", "Block size"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,337 @@
+/// Remembered shell translations: natural-language request → command → how its
+/// last run went.
+///
+/// An identical request reuses its earlier translation instead of asking the LLM
+/// again, unless that command failed the last time it ran. Pinned entries are
+/// favorites: they are never trimmed and list first when searching.
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::json_store::{self, JsonStore};
+
+/// Unpinned entries kept; the least recently used are dropped first.
+const MAX_ENTRIES: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    /// The request as typed
+    pub input: String,
+    pub command: String,
+    /// Unix timestamp of the first translation
+    pub created: u64,
+    /// Unix timestamp of the latest translation or run
+    pub used: u64,
+    pub runs: u32,
+    /// Exit code of the latest run
+    pub exit_code: Option<i32>,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ShellHistory {
+    version: u32,
+    next_id: u64,
+    /// Oldest first
+    entries: Vec<HistoryEntry>,
+}
+
+pub type SharedShellHistory = Arc<RwLock<ShellHistory>>;
+
+impl JsonStore for ShellHistory {
+    const VERSION: u32 = 1;
+    const NAME: &'static str = "shell history";
+
+    /// Lives in the data dir next to the launch history.
+    fn path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("trace")
+            .join("shell_history.json")
+    }
+
+    fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+/// Requests that differ only in case or spacing are the same request.
+pub fn request_key(input: &str) -> String {
+    input
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl ShellHistory {
+    pub fn new() -> Self {
+        Self {
+            version: Self::VERSION,
+            next_id: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Load the history from disk, or start empty if it is missing or outdated.
+    pub fn load() -> Self {
+        json_store::load().unwrap_or_else(Self::new)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&HistoryEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    fn get_mut(&mut self, id: u64) -> Result<&mut HistoryEntry, String> {
+        self.entries
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or_else(|| "No such history entry".to_string())
+    }
+
+    /// The latest translation of the same request, unless its last run failed.
+    pub fn cached(&self, input: &str) -> Option<&HistoryEntry> {
+        let key = request_key(input);
+        self.entries
+            .iter()
+            .filter(|e| request_key(&e.input) == key)
+            .max_by_key(|e| e.used)
+            .filter(|e| e.pinned || e.exit_code.is_none_or(|code| code == 0))
+    }
+
+    /// Remember a translation; returns the id of its entry.
+    pub fn record_translation(&mut self, input: &str, command: &str, now: u64) -> u64 {
+        let key = request_key(input);
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|e| e.command == command && request_key(&e.input) == key)
+        {
+            entry.used = entry.used.max(now);
+            return entry.id;
+        }
+
+        let id = self.next_id.max(1);
+        self.next_id = id + 1;
+        self.entries.push(HistoryEntry {
+            id,
+            input: input.trim().to_string(),
+            command: command.to_string(),
+            created: now,
+            used: now,
+            runs: 0,
+            exit_code: None,
+            pinned: false,
+        });
+        self.trim();
+        id
+    }
+
+    pub fn record_run(&mut self, id: u64, exit_code: i32, now: u64) -> Result<(), String> {
+        let entry = self.get_mut(id)?;
+        entry.runs += 1;
+        entry.exit_code = Some(exit_code);
+        entry.used = entry.used.max(now);
+        Ok(())
+    }
+
+    pub fn set_pinned(&mut self, id: u64, pinned: bool) -> Result<(), String> {
+        self.get_mut(id)?.pinned = pinned;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.entries.retain(|e| e.id != id);
+    }
+
+    /// Entries whose request or command contains every word of `query`: pinned
+    /// first, then most recently used. An empty query lists everything.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<HistoryEntry> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let mut found: Vec<&HistoryEntry> = self
+            .entries
+            .iter()
+            .filter(|e| {
+                let text = format!("{}\n{}", e.input, e.command).to_lowercase();
+                words.iter().all(|w| text.contains(w.as_str()))
+            })
+            .collect();
+        found.sort_by_key(|e| (std::cmp::Reverse(e.pinned), std::cmp::Reverse(e.used)));
+        found.into_iter().take(limit).cloned().collect()
+    }
+
+    /// The history as a script, oldest first, each command under its request.
+    pub fn to_script(&self, pinned_only: bool) -> String {
+        let (header, comment, newline) = if cfg!(windows) {
+            ("@echo off", "REM", "\r\n")
+        } else {
+            ("#!/bin/sh", "#", "\n")
+        };
+        let entries: Vec<&HistoryEntry> = self
+            .entries
+            .iter()
+            .filter(|e| e.pinned || !pinned_only)
+            .collect();
+        let mut out = format!(
+            "{header}{newline}{comment} Exported from Trace: {} command{}{newline}",
+            entries.len(),
+            if entries.len() == 1 { "" } else { "s" },
+        );
+        for entry in entries {
+            let input = entry.input.split_whitespace().collect::<Vec<_>>().join(" ");
+            out.push_str(&format!(
+                "{newline}{comment} {input}{newline}{}{newline}",
+                entry.command.replace('\n', newline)
+            ));
+        }
+        out
+    }
+
+    /// Write `to_script` to `destination`, or to a timestamped file in the
+    /// documents (else home) directory. Returns the path written.
+    pub fn export(&self, destination: Option<&str>, pinned_only: bool) -> Result<PathBuf, String> {
+        let path = match destination {
+            Some(dest) => PathBuf::from(dest),
+            None => {
+                let dir = dirs::document_dir()
+                    .or_else(dirs::home_dir)
+                    .unwrap_or_else(std::env::temp_dir);
+                let ext = if cfg!(windows) { "cmd" } else { "sh" };
+                dir.join(format!(
+                    "trace-shell-history-{}.{}",
+                    crate::frecency::now(),
+                    ext
+                ))
+            }
+        };
+        std::fs::write(&path, self.to_script(pinned_only)).map_err(|e| {
+            format!(
+                "Failed to export shell history to {}: {}",
+                path.display(),
+                e
+            )
+        })?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755));
+        }
+        Ok(path)
+    }
+
+    /// Drop the least recently used unpinned entries beyond `MAX_ENTRIES`.
+    fn trim(&mut self) {
+        let mut unpinned: Vec<(u64, u64)> = self
+            .entries
+            .iter()
+            .filter(|e| !e.pinned)
+            .map(|e| (e.used, e.id))
+            .collect();
+        if unpinned.len() <= MAX_ENTRIES {
+            return;
+        }
+        unpinned.sort_unstable();
+        let excess = unpinned.len() - MAX_ENTRIES;
+        let dropped: HashSet<u64> = unpinned[..excess].iter().map(|(_, id)| *id).collect();
+        self.entries.retain(|e| !dropped.contains(&e.id));
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T0: u64 = 1_700_000_000;
+
+    #[test]
+    fn identical_requests_hit_the_cache() {
+        let mut history = ShellHistory::new();
+        let id = history.record_translation("list big files ", "du -sh * | sort -h", T0);
+        assert_eq!(history.cached("List   BIG files").map(|e| e.id), Some(id));
+        assert!(history.cached("list big files here").is_none());
+
+        // Same request and command again reuse the entry
+        assert_eq!(
+            history.record_translation("list big files", "du -sh * | sort -h", T0 + 5),
+            id
+        );
+        assert_eq!(history.search("", 10).len(), 1);
+
+        // A newer translation wins; a failed run stops it being reused
+        let newer = history.record_translation("list big files", "ls -lS", T0 + 10);
+        assert_eq!(history.cached("list big files").map(|e| e.id), Some(newer));
+        history.record_run(newer, 2, T0 + 11).unwrap();
+        assert!(history.cached("list big files").is_none());
+        history.set_pinned(newer, true).unwrap();
+        assert_eq!(history.cached("list big files").map(|e| e.id), Some(newer));
+    }
+
+    #[test]
+    fn runs_are_recorded() {
+        let mut history = ShellHistory::new();
+        let id = history.record_translation("show disk space", "df -h", T0);
+        history.record_run(id, 0, T0 + 60).unwrap();
+        history.record_run(id, 1, T0 + 120).unwrap();
+        let entry = history.get(id).unwrap();
+        assert_eq!(
+            (entry.runs, entry.exit_code, entry.used),
+            (2, Some(1), T0 + 120)
+        );
+        assert!(history.record_run(99, 0, T0).is_err());
+    }
+
+    #[test]
+    fn search_lists_pinned_then_recent() {
+        let mut history = ShellHistory::new();
+        let a = history.record_translation("count lines in rust files", "wc -l **/*.rs", T0);
+        let b = history.record_translation("find rust files", "find . -name '*.rs'", T0 + 1);
+        let c = history.record_translation("show disk space", "df -h", T0 + 2);
+        history.set_pinned(a, true).unwrap();
+
+        let ids = |found: Vec<HistoryEntry>| found.iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(ids(history.search("", 10)), [a, c, b]);
+        assert_eq!(ids(history.search("RUST files", 10)), [a, b]);
+        assert_eq!(ids(history.search("find .", 10)), [b]);
+        assert_eq!(ids(history.search("", 2)), [a, c]);
+
+        history.remove(c);
+        assert_eq!(ids(history.search("", 10)), [a, b]);
+    }
+
+    #[test]
+    fn trims_oldest_unpinned() {
+        let mut history = ShellHistory::new();
+        let first = history.record_translation("request 0", "true", T0);
+        history.set_pinned(first, true).unwrap();
+        for i in 1..=MAX_ENTRIES + 2 {
+            history.record_translation(&format!("request {}", i), "true", T0 + i as u64);
+        }
+        assert_eq!(history.entries.len(), MAX_ENTRIES + 1);
+        assert!(history.get(first).is_some());
+        assert!(history.cached("request 1").is_none());
+        assert!(history.cached("request 2").is_none());
+        assert!(history.cached("request 3").is_some());
+    }
+
+    #[test]
+    fn exports_a_script() {
+        let mut history = ShellHistory::new();
+        let a = history.record_translation("show\ndisk space", "df -h", T0);
+        history.record_translation("who am i", "whoami", T0 + 1);
+        history.set_pinned(a, true).unwrap();
+
+        let script = history.to_script(false);
+        if !cfg!(windows) {
+            assert_eq!(
+                script,
+                "#!/bin/sh\n# Exported from Trace: 2 commands\n\n# show disk space\ndf -h\n\n# who am i\nwhoami\n"
+            );
+        }
+        assert!(!history.to_script(true).contains("whoami"));
+    }
+}
@@ -0,0 +1,1073 @@
+/// A small POSIX shell parser — enough to see what a one-line command would run.
+///
+/// It understands quoting and escapes, pipelines, `&&` / `||` / `;` / `&` lists,
+/// redirections, subshells, `{ …; }` groups, function definitions and `$(…)`,
+/// backtick and `<(…)` substitutions (parsed recursively). Variables and globs
+/// are left unexpanded, and heredoc bodies are skipped.
+use std::fmt;
+
+/// Byte span of a token in the command it was parsed from.
+pub type Span = (usize, usize);
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Word {
+    /// The word with quotes and escapes removed
+    pub text: String,
+    pub span: Span,
+    /// Some part was quoted or escaped
+    pub quoted: bool,
+    /// An unquoted `*`, `?` or `[` makes the shell expand this word
+    pub glob: bool,
+    /// Commands run by `$(…)`, backticks or `<(…)` inside the word
+    pub substitutions: Vec<Script>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    /// Explicit file descriptor, as in `2>`
+    pub fd: Option<u32>,
+    /// `>`, `>>`, `<`, `&>`, `2>&1`'s `>&`, …
+    pub op: &'static str,
+    pub target: Word,
+}
+
+impl Redirect {
+    /// Whether the target is opened for writing.
+    pub fn writes(&self) -> bool {
+        matches!(self.op, ">" | ">>" | ">|" | "&>" | "&>>" | "<>")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SimpleCommand {
+    /// Leading `NAME=value` words
+    pub assignments: Vec<Word>,
+    /// Program and arguments
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+    pub span: Span,
+}
+
+impl SimpleCommand {
+    /// The program's name without its directory (`/bin/rm` → `rm`).
+    pub fn program(&self) -> Option<&str> {
+        let first = &self.words.first()?.text;
+        Some(first.rsplit(['/', '\\']).next().unwrap_or(first))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    /// `( … )` subshell or `{ …; }` group
+    Group {
+        body: Script,
+        redirects: Vec<Redirect>,
+    },
+    /// `name() body`
+    Function {
+        name: String,
+        body: Box<Command>,
+    },
+}
+
+/// How a pipeline is joined to the one after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    /// `;`, a newline or the end of the script
+    Then,
+    And,
+    Or,
+    Background,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+    pub connector: Connector,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Script {
+    pub pipelines: Vec<Pipeline>,
+}
+
+impl Script {
+    /// Visit every simple command: inside groups and function bodies, and in the
+    /// substitutions of every word.
+    pub fn walk(&self, f: &mut dyn FnMut(&SimpleCommand)) {
+        for command in self.pipelines.iter().flat_map(|p| &p.commands) {
+            command.walk(f);
+        }
+    }
+}
+
+impl Command {
+    pub fn walk(&self, f: &mut dyn FnMut(&SimpleCommand)) {
+        match self {
+            Command::Simple(simple) => {
+                f(simple);
+                let words = simple
+                    .assignments
+                    .iter()
+                    .chain(&simple.words)
+                    .chain(simple.redirects.iter().map(|r| &r.target));
+                for script in words.flat_map(|w| &w.substitutions) {
+                    script.walk(f);
+                }
+            }
+            Command::Group { body, .. } => body.walk(f),
+            Command::Function { body, .. } => body.walk(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// Byte offset in the command
+    pub position: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+/// Parse a command line.
+pub fn parse(src: &str) -> Result<Script, ParseError> {
+    let (tokens, _) = Lexer::new(src, 0, false).tokens()?;
+    Parser::new(tokens, src.len()).script()
+}
+
+// ─── Arguments ───────────────────────────────────────────
+
+/// Flags and operands of a POSIX-style argument list.
+#[derive(Default)]
+pub struct Args<'w> {
+    pub short: String,
+    pub long: Vec<&'w str>,
+    pub operands: Vec<&'w Word>,
+}
+
+impl<'w> Args<'w> {
+    /// `with_value` lists options that take the next word as their value.
+    pub fn parse(words: &'w [Word], with_value: &[&str]) -> Self {
+        let mut args = Args::default();
+        let mut iter = words.iter();
+        while let Some(word) = iter.next() {
+            let text = word.text.as_str();
+            if text == "--" {
+                args.operands.extend(iter.by_ref());
+                break;
+            }
+            if let Some(long) = text.strip_prefix("--") {
+                args.long.push(long.split('=').next().unwrap_or(long));
+                if !long.contains('=') && with_value.contains(&text) {
+                    iter.next();
+                }
+            } else if text.len() > 1 && text.starts_with('-') {
+                for (i, c) in text.char_indices().skip(1) {
+                    args.short.push(c);
+                    if with_value.contains(&format!("-{}", c).as_str()) {
+                        if i + c.len_utf8() == text.len() {
+                            iter.next();
+                        }
+                        break;
+                    }
+                }
+            } else {
+                args.operands.push(word);
+            }
+        }
+        args
+    }
+
+    /// A short flag, or a long option given in full or as an unambiguous prefix
+    /// (`--rec` for `--recursive`, as GNU tools accept).
+    pub fn has(&self, short: char, long: &str) -> bool {
+        self.short.contains(short) || self.has_long(long)
+    }
+
+    pub fn has_long(&self, long: &str) -> bool {
+        self.long
+            .iter()
+            .any(|l| l.len() >= 3 && long.starts_with(l))
+    }
+}
+
+/// Words after leading options, skipping the values of `with_value` options.
+pub fn skip_options<'w>(words: &'w [Word], with_value: &[&str]) -> &'w [Word] {
+    let mut i = 0;
+    while let Some(word) = words.get(i) {
+        let text = word.text.as_str();
+        if text == "--" {
+            return &words[i + 1..];
+        }
+        if text.len() < 2 || !text.starts_with('-') {
+            break;
+        }
+        i += if with_value.contains(&text) { 2 } else { 1 };
+    }
+    words.get(i..).unwrap_or(&[])
+}
+
+/// The command actually run once wrappers like `sudo` and `xargs` are peeled off.
+pub struct Invocation<'w> {
+    pub words: &'w [Word],
+    /// Under `sudo` or `doas`
+    pub root: bool,
+    /// Under `xargs`, so more operands arrive on stdin
+    pub xargs: bool,
+}
+
+impl<'w> Invocation<'w> {
+    /// Lowercased program name without directory or `.exe`.
+    pub fn name(&self) -> Option<String> {
+        let first = &self.words.first()?.text;
+        let base = first.rsplit(['/', '\\']).next().unwrap_or(first);
+        let base = base.to_lowercase();
+        Some(
+            base.strip_suffix(".exe")
+                .map(str::to_string)
+                .unwrap_or(base),
+        )
+    }
+
+    pub fn args(&self) -> &'w [Word] {
+        self.words.get(1..).unwrap_or(&[])
+    }
+}
+
+pub fn unwrap(words: &[Word]) -> Invocation<'_> {
+    let mut inv = Invocation {
+        words,
+        root: false,
+        xargs: false,
+    };
+    while let Some(name) = inv.name() {
+        let rest = inv.args();
+        inv.words = match name.as_str() {
+            "sudo" | "doas" => {
+                inv.root = true;
+                skip_options(
+                    rest,
+                    &[
+                        "-u", "-g", "-C", "-D", "-h", "-p", "-r", "-t", "-U", "-T", "--user",
+                        "--group", "--chdir", "--prompt", "--host", "--role", "--type",
+                    ],
+                )
+            }
+            "env" => {
+                let rest = skip_options(rest, &["-u", "-C", "-S", "--unset", "--chdir"]);
+                let assignments = rest.iter().take_while(|w| is_assignment(&w.text)).count();
+                &rest[assignments..]
+            }
+            "command"
+                if rest
+                    .first()
+                    .is_some_and(|w| w.text == "-v" || w.text == "-V") =>
+            {
+                &[]
+            }
+            "nohup" | "exec" | "builtin" | "command" | "time" => skip_options(rest, &[]),
+            "nice" => skip_options(rest, &["-n", "--adjustment"]),
+            "ionice" => skip_options(rest, &["-c", "-n", "-p", "-P", "-u"]),
+            "stdbuf" => skip_options(rest, &["-i", "-o", "-e"]),
+            "timeout" => {
+                let rest = skip_options(rest, &["-s", "-k", "--signal", "--kill-after"]);
+                rest.get(1..).unwrap_or(&[])
+            }
+            "xargs" => {
+                inv.xargs = true;
+                skip_options(
+                    rest,
+                    &[
+                        "-a",
+                        "-d",
+                        "-E",
+                        "-I",
+                        "-L",
+                        "-n",
+                        "-P",
+                        "-s",
+                        "--arg-file",
+                        "--delimiter",
+                        "--max-args",
+                        "--max-procs",
+                        "--max-lines",
+                    ],
+                )
+            }
+            _ => break,
+        };
+    }
+    inv
+}
+
+// ─── Lexer ───────────────────────────────────────────────
+
+#[derive(Debug)]
+enum Token {
+    Word(Word),
+    /// Control operator: `;`, `&`, `&&`, `|`, `||`, `|&`, `;;`, `(`, `)`
+    Op(&'static str, usize),
+    Redirect {
+        fd: Option<u32>,
+        op: &'static str,
+        pos: usize,
+    },
+    Newline(usize),
+}
+
+const CONTROL_OPS: &[&str] = &[";;", "&&", "||", "|&", ";", "&", "|", "(", ")"];
+const REDIRECT_OPS: &[&str] = &[
+    "<<<", "<<-", "&>>", "<<", "<&", "<>", ">>", ">&", ">|", "&>", "<", ">",
+];
+
+/// Words that only introduce the command after them (`then rm x`).
+const KEYWORD_PREFIXES: &[&str] = &["if", "then", "else", "elif", "do", "while", "until"];
+
+struct Lexer<'a> {
+    src: &'a str,
+    pos: usize,
+    /// Offset of `src` in the top-level command, for spans and errors
+    base: usize,
+    /// Inside `$(…)`: stop at the unmatched `)`
+    nested: bool,
+    depth: usize,
+    /// Heredoc delimiters whose bodies start after the next newline
+    heredocs: Vec<(String, bool)>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(src: &'a str, base: usize, nested: bool) -> Self {
+        Self {
+            src,
+            pos: 0,
+            base,
+            nested,
+            depth: 0,
+            heredocs: Vec::new(),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn error(&self, message: &str, at: usize) -> ParseError {
+        ParseError {
+            message: message.to_string(),
+            position: self.base + at,
+        }
+    }
+
+    fn operator(&mut self, ops: &[&'static str]) -> &'static str {
+        let op = ops
+            .iter()
+            .copied()
+            .find(|op| self.rest().starts_with(op))
+            .expect("caller checked the first character");
+        self.pos += op.len();
+        op
+    }
+
+    /// Tokens up to the end of input — or, when nested, up to the unmatched `)`,
+    /// whose offset is returned.
+    fn tokens(mut self) -> Result<(Vec<Token>, usize), ParseError> {
+        let mut tokens = Vec::new();
+        loop {
+            while matches!(self.peek(), Some(' ' | '\t' | '\r')) {
+                self.bump();
+            }
+            if self.rest().starts_with("\\\n") {
+                self.pos += 2;
+                continue;
+            }
+            let start = self.pos;
+            let Some(c) = self.peek() else {
+                break;
+            };
+            match c {
+                '\n' => {
+                    self.bump();
+                    self.skip_heredoc_bodies();
+                    tokens.push(Token::Newline(self.base + start));
+                }
+                '#' => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.bump();
+                    }
+                }
+                ')' if self.nested && self.depth == 0 => return Ok((tokens, self.pos)),
+                '&' if self.rest().starts_with("&>") => self.redirect(None, start, &mut tokens)?,
+                ';' | '&' | '|' | '(' | ')' => {
+                    let op = self.operator(CONTROL_OPS);
+                    match op {
+                        "(" => self.depth += 1,
+                        ")" => self.depth = self.depth.saturating_sub(1),
+                        _ => {}
+                    }
+                    tokens.push(Token::Op(op, self.base + start));
+                }
+                '<' | '>' if !self.rest().starts_with("<(") && !self.rest().starts_with(">(") => {
+                    self.redirect(None, start, &mut tokens)?
+                }
+                _ => {
+                    let word = self.word()?;
+                    let io_number = !word.quoted
+                        && !word.text.is_empty()
+                        && word.text.chars().all(|c| c.is_ascii_digit())
+                        && matches!(self.peek(), Some('<' | '>'))
+                        && !self.rest().starts_with("<(")
+                        && !self.rest().starts_with(">(");
+                    if io_number {
+                        self.redirect(word.text.parse().ok(), start, &mut tokens)?;
+                    } else {
+                        tokens.push(Token::Word(word));
+                    }
+                }
+            }
+        }
+        if self.nested {
+            return Err(self.error("Unterminated $(", 0));
+        }
+        Ok((tokens, self.pos))
+    }
+
+    fn redirect(
+        &mut self,
+        fd: Option<u32>,
+        start: usize,
+        tokens: &mut Vec<Token>,
+    ) -> Result<(), ParseError> {
+        let op = self.operator(REDIRECT_OPS);
+        tokens.push(Token::Redirect {
+            fd,
+            op,
+            pos: self.base + start,
+        });
+        if op == "<<" || op == "<<-" {
+            while matches!(self.peek(), Some(' ' | '\t')) {
+                self.bump();
+            }
+            let delimiter = self.word()?;
+            self.heredocs.push((delimiter.text.clone(), op == "<<-"));
+            tokens.push(Token::Word(delimiter));
+        }
+        Ok(())
+    }
+
+    fn skip_heredoc_bodies(&mut self) {
+        for (delimiter, strip_tabs) in std::mem::take(&mut self.heredocs) {
+            while self.pos < self.src.len() {
+                let line_end = self
+                    .rest()
+                    .find('\n')
+                    .map_or(self.src.len(), |i| self.pos + i);
+                let line = &self.src[self.pos..line_end];
+                let line = if strip_tabs {
+                    line.trim_start_matches('\t')
+                } else {
+                    line
+                };
+                let done = line == delimiter;
+                self.pos = (line_end + 1).min(self.src.len());
+                if done {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn word(&mut self) -> Result<Word, ParseError> {
+        let start = self.pos;
+        let mut word = Word::default();
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' | '\n' | ';' | '&' | '|' | '(' | ')' => break,
+                '<' | '>' => {
+                    if !self.rest().starts_with("<(") && !self.rest().starts_with(">(") {
+                        break;
+                    }
+                    let open = self.pos;
+                    self.pos += 2;
+                    let script = self.substitution(open)?;
+                    word.substitutions.push(script);
+                    word.text.push_str(&self.src[open..self.pos]);
+                }
+                '\\' => match self.rest()[1..].chars().next() {
+                    Some('\n') => self.pos += 2,
+                    // Kept before letters and digits, so Windows paths like C:\Users survive
+                    Some(c) if !c.is_ascii_alphanumeric() => {
+                        self.pos += 1 + c.len_utf8();
+                        word.text.push(c);
+                        word.quoted = true;
+                    }
+                    _ => {
+                        self.bump();
+                        word.text.push('\\');
+                    }
+                },
+                '\'' => {
+                    let open = self.pos;
+                    self.bump();
+                    word.quoted = true;
+                    loop {
+                        match self.bump() {
+                            Some('\'') => break,
+                            Some(c) => word.text.push(c),
+                            None => return Err(self.error("Unterminated single quote", open)),
+                        }
+                    }
+                }
+                '"' => {
+                    self.bump();
+                    word.quoted = true;
+                    self.double_quoted(&mut word, self.pos - 1)?;
+                }
+                '$' => self.dollar(&mut word)?,
+                '`' => self.backtick(&mut word)?,
+                '*' | '?' | '[' => {
+                    word.glob = true;
+                    word.text.push(c);
+                    self.bump();
+                }
+                _ => {
+                    word.text.push(c);
+                    self.bump();
+                }
+            }
+        }
+        word.span = (self.base + start, self.base + self.pos);
+        Ok(word)
+    }
+
+    fn double_quoted(&mut self, word: &mut Word, open: usize) -> Result<(), ParseError> {
+        loop {
+            match self.peek() {
+                None => return Err(self.error("Unterminated double quote", open)),
+                Some('"') => {
+                    self.bump();
+                    return Ok(());
+                }
+                Some('\\') => {
+                    self.bump();
+                    match self.bump() {
+                        Some(c @ ('$' | '`' | '"' | '\\')) => word.text.push(c),
+                        Some('\n') => {}
+                        Some(c) => {
+                            word.text.push('\\');
+                            word.text.push(c);
+                        }
+                        None => return Err(self.error("Unterminated double quote", open)),
+                    }
+                }
+                Some('$') => self.dollar(word)?,
+                Some('`') => self.backtick(word)?,
+                Some(c) => {
+                    word.text.push(c);
+                    self.bump();
+                }
+            }
+        }
+    }
+
+    fn dollar(&mut self, word: &mut Word) -> Result<(), ParseError> {
+        let open = self.pos;
+        if self.rest().starts_with("$((") {
+            // Arithmetic: kept verbatim
+            self.pos += 3;
+            let mut depth = 2;
+            while depth > 0 {
+                match self.bump() {
+                    Some('(') => depth += 1,
+                    Some(')') => depth -= 1,
+                    Some(_) => {}
+                    None => return Err(self.error("Unterminated $((", open)),
+                }
+            }
+        } else if self.rest().starts_with("$(") {
+            self.pos += 2;
+            let script = self.substitution(open)?;
+            word.substitutions.push(script);
+        } else if self.rest().starts_with("${") {
+            match self.rest().find('}') {
+                Some(close) => self.pos += close + 1,
+                None => return Err(self.error("Unterminated ${", open)),
+            }
+        } else {
+            self.bump();
+        }
+        word.text.push_str(&self.src[open..self.pos]);
+        Ok(())
+    }
+
+    /// Parse the body of `$(…)` or `<(…)`, leaving the position after the `)`.
+    fn substitution(&mut self, open: usize) -> Result<Script, ParseError> {
+        let inner = Lexer::new(self.rest(), self.base + self.pos, true);
+        let (tokens, consumed) = inner.tokens().map_err(|e| match e.message.as_str() {
+            "Unterminated $(" => self.error("Unterminated $(", open),
+            _ => e,
+        })?;
+        let end = self.base + self.pos + consumed;
+        self.pos += consumed + 1;
+        Parser::new(tokens, end).script()
+    }
+
+    fn backtick(&mut self, word: &mut Word) -> Result<(), ParseError> {
+        let open = self.pos;
+        self.bump();
+        let mut inner = String::new();
+        loop {
+            match self.bump() {
+                Some('`') => break,
+                Some('\\') => match self.bump() {
+                    Some(c @ ('`' | '\\' | '$')) => inner.push(c),
+                    Some(c) => {
+                        inner.push('\\');
+                        inner.push(c);
+                    }
+                    None => return Err(self.error("Unterminated backquote", open)),
+                },
+                Some(c) => inner.push(c),
+                None => return Err(self.error("Unterminated backquote", open)),
+            }
+        }
+        let (tokens, _) = Lexer::new(&inner, self.base + open + 1, false).tokens()?;
+        word.substitutions
+            .push(Parser::new(tokens, self.base + self.pos).script()?);
+        word.text.push_str(&self.src[open..self.pos]);
+        Ok(())
+    }
+}
+
+// ─── Parser ──────────────────────────────────────────────
+
+struct Parser {
+    tokens: std::iter::Peekable<std::vec::IntoIter<Token>>,
+    /// Offset just past the input, for "expected …" errors at the end
+    end: usize,
+}
+
+fn token_pos(token: &Token) -> usize {
+    match token {
+        Token::Word(w) => w.span.0,
+        Token::Op(_, pos) | Token::Redirect { pos, .. } | Token::Newline(pos) => *pos,
+    }
+}
+
+/// `NAME=value`
+pub fn is_assignment(text: &str) -> bool {
+    match text.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+fn is_word(token: Option<&Token>, text: &str) -> bool {
+    matches!(token, Some(Token::Word(w)) if !w.quoted && w.text == text)
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>, end: usize) -> Self {
+        Self {
+            tokens: tokens.into_iter().peekable(),
+            end,
+        }
+    }
+
+    fn error(&mut self, message: &str) -> ParseError {
+        let position = self.tokens.peek().map_or(self.end, token_pos);
+        ParseError {
+            message: message.to_string(),
+            position,
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while matches!(self.tokens.peek(), Some(Token::Newline(_))) {
+            self.tokens.next();
+        }
+    }
+
+    fn script(mut self) -> Result<Script, ParseError> {
+        let script = self.list(None)?;
+        match self.tokens.peek() {
+            None => Ok(script),
+            Some(_) => Err(self.error("Unexpected token")),
+        }
+    }
+
+    /// Pipelines up to the end of input, or to `closer` (`)` or `}`).
+    fn list(&mut self, closer: Option<&str>) -> Result<Script, ParseError> {
+        let mut script = Script::default();
+        loop {
+            self.skip_newlines();
+            match (self.tokens.peek(), closer) {
+                (None, _) => break,
+                (Some(Token::Op(")", _)), Some(")")) => break,
+                (token, Some("}")) if is_word(token, "}") => break,
+                _ => {}
+            }
+            let mut pipeline = self.pipeline()?;
+            match self.tokens.peek() {
+                Some(Token::Op(op @ ("&&" | "||" | ";" | ";;" | "&"), _)) => {
+                    pipeline.connector = match *op {
+                        "&&" => Connector::And,
+                        "||" => Connector::Or,
+                        "&" => Connector::Background,
+                        _ => Connector::Then,
+                    };
+                    self.tokens.next();
+                    if matches!(pipeline.connector, Connector::And | Connector::Or) {
+                        self.skip_newlines();
+                        if self.tokens.peek().is_none() {
+                            return Err(self.error("Expected a command after && / ||"));
+                        }
+                    }
+                }
+                Some(Token::Newline(_)) | None => {}
+                Some(Token::Op(")", _)) if closer == Some(")") => {}
+                token if closer == Some("}") && is_word(token, "}") => {}
+                Some(_) => return Err(self.error("Unexpected token")),
+            }
+            script.pipelines.push(pipeline);
+        }
+        Ok(script)
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        if is_word(self.tokens.peek(), "!") {
+            self.tokens.next();
+        }
+        let mut commands = vec![self.command()?];
+        while matches!(self.tokens.peek(), Some(Token::Op("|" | "|&", _))) {
+            self.tokens.next();
+            self.skip_newlines();
+            commands.push(self.command()?);
+        }
+        Ok(Pipeline {
+            commands,
+            connector: Connector::Then,
+        })
+    }
+
+    fn command(&mut self) -> Result<Command, ParseError> {
+        if matches!(self.tokens.peek(), Some(Token::Op("(", _))) {
+            self.tokens.next();
+            let body = self.list(Some(")"))?;
+            if !matches!(self.tokens.next(), Some(Token::Op(")", _))) {
+                return Err(self.error("Missing )"));
+            }
+            return Ok(Command::Group {
+                body,
+                redirects: self.redirects()?,
+            });
+        }
+        if is_word(self.tokens.peek(), "{") {
+            self.tokens.next();
+            let body = self.list(Some("}"))?;
+            if !is_word(self.tokens.next().as_ref(), "}") {
+                return Err(self.error("Missing }"));
+            }
+            return Ok(Command::Group {
+                body,
+                redirects: self.redirects()?,
+            });
+        }
+        self.simple()
+    }
+
+    fn redirects(&mut self) -> Result<Vec<Redirect>, ParseError> {
+        let mut redirects = Vec::new();
+        while let Some(Token::Redirect { .. }) = self.tokens.peek() {
+            redirects.push(self.redirect()?);
+        }
+        Ok(redirects)
+    }
+
+    fn redirect(&mut self) -> Result<Redirect, ParseError> {
+        let Some(Token::Redirect { fd, op, .. }) = self.tokens.next() else {
+            unreachable!("caller peeked a redirect");
+        };
+        match self.tokens.next() {
+            Some(Token::Word(target)) => Ok(Redirect { fd, op, target }),
+            _ => Err(self.error(&format!("Missing target after {}", op))),
+        }
+    }
+
+    fn simple(&mut self) -> Result<Command, ParseError> {
+        let mut command = SimpleCommand::default();
+        let start = self.tokens.peek().map_or(self.end, token_pos);
+        let mut end = start;
+        loop {
+            match self.tokens.peek() {
+                Some(Token::Word(_)) => {
+                    let Some(Token::Word(word)) = self.tokens.next() else {
+                        unreachable!();
+                    };
+                    end = word.span.1;
+                    let at_start = command.words.is_empty();
+                    if at_start && is_assignment(&word.text) {
+                        command.assignments.push(word);
+                    } else if at_start
+                        && command.assignments.is_empty()
+                        && !word.quoted
+                        && KEYWORD_PREFIXES.contains(&word.text.as_str())
+                    {
+                        continue;
+                    } else if at_start
+                        && command.assignments.is_empty()
+                        && matches!(self.tokens.peek(), Some(Token::Op("(", _)))
+                    {
+                        return self.function(word);
+                    } else {
+                        command.words.push(word);
+                    }
+                }
+                Some(Token::Redirect { .. }) => {
+                    let redirect = self.redirect()?;
+                    end = redirect.target.span.1;
+                    command.redirects.push(redirect);
+                }
+                _ => break,
+            }
+        }
+        if command.words.is_empty()
+            && command.assignments.is_empty()
+            && command.redirects.is_empty()
+        {
+            return Err(self.error("Expected a command"));
+        }
+        command.span = (start, end);
+        Ok(Command::Simple(command))
+    }
+
+    /// `name() body`, with the `(` next.
+    fn function(&mut self, name: Word) -> Result<Command, ParseError> {
+        self.tokens.next();
+        if !matches!(self.tokens.next(), Some(Token::Op(")", _))) {
+            return Err(self.error("Expected ) in function definition"));
+        }
+        self.skip_newlines();
+        Ok(Command::Function {
+            name: name.text,
+            body: Box::new(self.command()?),
+        })
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simple(command: &Command) -> &SimpleCommand {
+        match command {
+            Command::Simple(simple) => simple,
+            other => panic!("not a simple command: {:?}", other),
+        }
+    }
+
+    fn words(command: &Command) -> Vec<&str> {
+        simple(command)
+            .words
+            .iter()
+            .map(|w| w.text.as_str())
+            .collect()
+    }
+
+    /// Every simple command's words, in order, as "prog arg …" strings.
+    fn flat(src: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        parse(src).unwrap().walk(&mut |c| {
+            out.push(
+                c.words
+                    .iter()
+                    .map(|w| w.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )
+        });
+        out
+    }
+
+    #[test]
+    fn quoting_and_escapes() {
+        let script = parse(r#"echo 'a b' "c $HOME \"d\"" e\ f "\n" ''"#).unwrap();
+        let command = &script.pipelines[0].commands[0];
+        assert_eq!(
+            words(command),
+            ["echo", "a b", "c $HOME \"d\"", "e f", "\\n", ""]
+        );
+        assert!(!simple(command).words[0].quoted);
+        assert!(simple(command).words[1].quoted);
+
+        let script = parse(r"rd /s /q C:\Users\me a\ b C:\").unwrap();
+        assert_eq!(
+            words(&script.pipelines[0].commands[0]),
+            ["rd", "/s", "/q", r"C:\Users\me", "a b", r"C:\"]
+        );
+    }
+
+    #[test]
+    fn lists_and_pipelines() {
+        let script = parse("a | b |& c && ! d || e; f & g\nh").unwrap();
+        let shape: Vec<(usize, Connector)> = script
+            .pipelines
+            .iter()
+            .map(|p| (p.commands.len(), p.connector))
+            .collect();
+        assert_eq!(
+            shape,
+            [
+                (3, Connector::And),
+                (1, Connector::Or),
+                (1, Connector::Then),
+                (1, Connector::Background),
+                (1, Connector::Then),
+                (1, Connector::Then),
+            ]
+        );
+        assert_eq!(
+            flat("! grep -q x f && echo missing"),
+            ["grep -q x f", "echo missing"]
+        );
+    }
+
+    #[test]
+    fn redirects_and_assignments() {
+        let script =
+            parse("LANG=C FOO='x y' sort <in.txt 2>/dev/null >>out.txt 2>&1 &>all").unwrap();
+        let command = simple(&script.pipelines[0].commands[0]);
+        assert_eq!(command.assignments.len(), 2);
+        assert_eq!(command.program(), Some("sort"));
+        let redirects: Vec<(Option<u32>, &str, &str)> = command
+            .redirects
+            .iter()
+            .map(|r| (r.fd, r.op, r.target.text.as_str()))
+            .collect();
+        assert_eq!(
+            redirects,
+            [
+                (None, "<", "in.txt"),
+                (Some(2), ">", "/dev/null"),
+                (None, ">>", "out.txt"),
+                (Some(2), ">&", "1"),
+                (None, "&>", "all"),
+            ]
+        );
+        assert!(command.redirects[2].writes() && !command.redirects[0].writes());
+    }
+
+    #[test]
+    fn substitutions_are_parsed() {
+        assert_eq!(
+            flat("echo \"$(rm -rf ~)\" `id -u` <(curl -s x | sh) $((1 + (2)))"),
+            [
+                "echo \"$(rm -rf ~)\" `id -u` <(curl -s x | sh) $((1 + (2)))".replace(['"'], ""),
+                "rm -rf ~".to_string(),
+                "id -u".to_string(),
+                "curl -s x".to_string(),
+                "sh".to_string(),
+            ]
+        );
+        assert_eq!(flat("x=$(a $(b) c)"), ["", "a $(b) c", "b"]);
+    }
+
+    #[test]
+    fn groups_functions_and_keywords() {
+        assert_eq!(
+            flat("(cd /tmp && rm x) | { cat; }"),
+            ["cd /tmp", "rm x", "cat"]
+        );
+        assert_eq!(
+            flat("if test -f a; then rm a; else echo no; fi"),
+            ["test -f a", "rm a", "echo no", "fi"]
+        );
+        assert_eq!(
+            flat("for f in *.log; do gzip \"$f\"; done"),
+            ["for f in *.log", "gzip $f", "done"]
+        );
+
+        let script = parse(":(){ :|:& };:").unwrap();
+        match &script.pipelines[0].commands[0] {
+            Command::Function { name, body } => {
+                assert_eq!(name, ":");
+                assert!(matches!(**body, Command::Group { .. }));
+            }
+            other => panic!("expected a function, got {:?}", other),
+        }
+        assert_eq!(words(&script.pipelines[1].commands[0]), [":"]);
+    }
+
+    #[test]
+    fn comments_heredocs_and_globs() {
+        assert_eq!(flat("ls # rm -rf /"), ["ls"]);
+        assert_eq!(
+            flat("cat <<EOF >out\nrm -rf /\nEOF\necho done"),
+            ["cat", "echo done"]
+        );
+        let script = parse("rm *.tmp '*.keep' dir/[ab]").unwrap();
+        let globs: Vec<bool> = simple(&script.pipelines[0].commands[0])
+            .words
+            .iter()
+            .map(|w| w.glob)
+            .collect();
+        assert_eq!(globs, [false, true, false, true]);
+    }
+
+    #[test]
+    fn spans_point_into_the_source() {
+        let src = "sudo  rm -rf /tmp/x && ls";
+        let script = parse(src).unwrap();
+        let command = simple(&script.pipelines[0].commands[0]);
+        assert_eq!(&src[command.span.0..command.span.1], "sudo  rm -rf /tmp/x");
+        assert_eq!(
+            &src[command.words[3].span.0..command.words[3].span.1],
+            "/tmp/x"
+        );
+    }
+
+    #[test]
+    fn syntax_errors_have_positions() {
+        let cases = [
+            ("echo 'oops", "Unterminated single quote", 5),
+            ("echo \"oops", "Unterminated double quote", 5),
+            ("echo $(ls", "Unterminated $(", 5),
+            ("echo `ls", "Unterminated backquote", 5),
+            ("| grep x", "Expected a command", 0),
+            ("ls &&", "Expected a command after && / ||", 5),
+            ("ls >", "Missing target after >", 4),
+            ("(ls", "Missing )", 3),
+            ("ls )", "Unexpected token", 3),
+        ];
+        for (src, message, position) in cases {
+            let error = parse(src).unwrap_err();
+            assert_eq!(
+                (error.message.as_str(), error.position),
+                (message, position),
+                "{:?}",
+                src
+            );
+        }
+    }
+}
@@ -0,0 +1,763 @@
+/// Dry-run preview for file-affecting commands: which existing paths `rm`,
+/// `mv`, `cp`, `chmod`/`chown` and `find -delete` would touch, found by
+/// expanding globs against the filesystem. Nothing is executed.
+use crate::shell_parse::{self, unwrap, Args, Command, Script, SimpleCommand, Word};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Paths listed per action; the rest are only counted.
+const LIST_LIMIT: usize = 200;
+/// Entries counted inside directories before giving up.
+const COUNT_LIMIT: u64 = 100_000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Touched {
+    pub path: String,
+    pub is_dir: bool,
+    /// Entries inside a directory that a recursive action also touches
+    pub contents: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewAction {
+    pub command: String,
+    /// "Delete", "Move", "Copy", "Change permissions", …
+    pub action: String,
+    pub paths: Vec<Touched>,
+    pub destination: Option<String>,
+    /// Operands that match nothing on disk
+    pub missing: Vec<String>,
+    /// Everything affected, counting directory contents
+    pub total: u64,
+    /// Hit `LIST_LIMIT` or `COUNT_LIMIT`
+    pub truncated: bool,
+    pub notes: Vec<String>,
+}
+
+/// Preview what a command would do to existing files, relative to `cwd`.
+pub fn preview(command: &str, cwd: &Path) -> Result<Vec<PreviewAction>, String> {
+    let script =
+        shell_parse::parse(command).map_err(|e| format!("Can't preview this command: {}", e))?;
+    let mut previewer = Previewer {
+        src: command,
+        home: dirs::home_dir(),
+        lost_cwd: false,
+        actions: Vec::new(),
+    };
+    previewer.script(&script, &mut cwd.to_path_buf());
+    eprintln!(
+        "[trace][shell_preview] {} action(s) for {:?}",
+        previewer.actions.len(),
+        command
+    );
+    Ok(previewer.actions)
+}
+
+// ─── Globs ───────────────────────────────────────────────
+
+/// Shell pattern match of one path component: `*`, `?`, `[abc]`, `[a-z]`, `[!x]`.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    let (mut pi, mut ni) = (0, 0);
+    // Where to resume after the last `*` if the rest fails to match
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        let step = match p.get(pi) {
+            Some('*') => {
+                star = Some((pi, ni));
+                pi += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => class_match(&p[pi..], n[ni]),
+            Some(&c) if c == n[ni] => Some(1),
+            _ => None,
+        };
+        match step {
+            Some(len) => {
+                pi += len;
+                ni += 1;
+            }
+            None => match star {
+                Some((sp, sn)) => {
+                    pi = sp + 1;
+                    ni = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Match `c` against the class at the start of `p`; the class length on success.
+fn class_match(p: &[char], c: char) -> Option<usize> {
+    let mut i = 1;
+    let negated = matches!(p.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let start = i;
+    while let Some(&ch) = p.get(i) {
+        if ch == ']' && i > start {
+            return (matched != negated).then_some(i + 1);
+        }
+        if p.get(i + 1) == Some(&'-') && p.get(i + 2).is_some_and(|&end| end != ']') {
+            matched |= (ch..=p[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= ch == c;
+            i += 1;
+        }
+    }
+    // No closing bracket: `[` is literal
+    (c == '[').then_some(1)
+}
+
+fn has_glob(component: &str) -> bool {
+    component.contains(['*', '?', '['])
+}
+
+/// Existing paths matching `pattern`, component by component.
+fn expand_glob(pattern: &str, cwd: &Path) -> Vec<PathBuf> {
+    let mut matches = vec![if pattern.starts_with('/') {
+        PathBuf::from("/")
+    } else {
+        cwd.to_path_buf()
+    }];
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        let mut next = Vec::new();
+        for base in &matches {
+            if !has_glob(component) {
+                let path = base.join(component);
+                if path.symlink_metadata().is_ok() {
+                    next.push(path);
+                }
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(base) else {
+                continue;
+            };
+            let mut found: Vec<PathBuf> = entries
+                .flatten()
+                .filter(|entry| {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    (component.starts_with('.') || !name.starts_with('.'))
+                        && glob_match(component, &name)
+                })
+                .map(|entry| entry.path())
+                .collect();
+            found.sort();
+            next.extend(found);
+        }
+        matches = next;
+    }
+    matches
+}
+
+// ─── Walking the command ─────────────────────────────────
+
+struct Previewer<'a> {
+    src: &'a str,
+    home: Option<PathBuf>,
+    /// A `cd` couldn't be followed, so later relative paths may be wrong
+    lost_cwd: bool,
+    actions: Vec<PreviewAction>,
+}
+
+/// What an operand expands to.
+enum Expanded {
+    Paths(Vec<PathBuf>),
+    /// Variables or command substitutions we won't evaluate
+    Unknown,
+}
+
+impl Previewer<'_> {
+    fn script(&mut self, script: &Script, cwd: &mut PathBuf) {
+        for pipeline in &script.pipelines {
+            for command in &pipeline.commands {
+                match command {
+                    // Only a `cd` on its own changes the shell's directory
+                    Command::Simple(simple) if pipeline.commands.len() == 1 => {
+                        self.simple(simple, cwd)
+                    }
+                    Command::Simple(simple) => self.simple(simple, &mut cwd.clone()),
+                    Command::Group { body, .. } => self.script(body, &mut cwd.clone()),
+                    // Function bodies only run when called
+                    Command::Function { .. } => {}
+                }
+            }
+        }
+    }
+
+    fn expand(&self, word: &Word, cwd: &Path) -> Expanded {
+        if !word.substitutions.is_empty() {
+            return Expanded::Unknown;
+        }
+        let mut text = word.text.clone();
+        if let Some(home) = &self.home {
+            let home = home.to_string_lossy();
+            for prefix in ["~", "$HOME", "${HOME}"] {
+                let rest = text.strip_prefix(prefix);
+                if let Some(rest) = rest.filter(|r| r.is_empty() || r.starts_with('/')) {
+                    text = format!("{}{}", home, rest);
+                    break;
+                }
+            }
+        }
+        if text.contains('$') {
+            return Expanded::Unknown;
+        }
+        if word.glob {
+            return Expanded::Paths(expand_glob(&text, cwd).iter().map(|p| clean(p)).collect());
+        }
+        let path = clean(&cwd.join(&text));
+        Expanded::Paths(if path.symlink_metadata().is_ok() {
+            vec![path]
+        } else {
+            vec![]
+        })
+    }
+
+    fn simple(&mut self, command: &SimpleCommand, cwd: &mut PathBuf) {
+        let inv = unwrap(&command.words);
+        let Some(name) = inv.name() else {
+            return;
+        };
+        let text = self
+            .src
+            .get(command.span.0..command.span.1)
+            .unwrap_or_default()
+            .to_string();
+        let mut action = PreviewAction {
+            command: text,
+            action: String::new(),
+            paths: vec![],
+            destination: None,
+            missing: vec![],
+            total: 0,
+            truncated: false,
+            notes: vec![],
+        };
+        let recursive = match name.as_str() {
+            "cd" | "pushd" => {
+                self.cd(inv.args(), cwd);
+                return;
+            }
+            "rm" | "unlink" | "rmdir" => {
+                let args = Args::parse(inv.args(), &[]);
+                action.action = "Delete".to_string();
+                let recursive =
+                    name == "rm" && (args.has('r', "recursive") || args.has('R', "recursive"));
+                self.add_operands(&mut action, &args.operands, cwd);
+                if name == "rm" && !recursive && !args.has('d', "dir") {
+                    skip_dirs(&mut action, "rm without -r won't delete it");
+                }
+                recursive
+            }
+            "mv" | "cp" => {
+                let args = Args::parse(inv.args(), &["-t", "--target-directory", "-S", "--suffix"]);
+                action.action = if name == "mv" { "Move" } else { "Copy" }.to_string();
+                let target = target_directory(inv.args());
+                let mut sources = args.operands.clone();
+                let dest = match target {
+                    Some(dest) => Some(dest),
+                    None if sources.len() > 1 => sources.pop().map(|w| w.text.clone()),
+                    None => None,
+                };
+                self.add_operands(&mut action, &sources, cwd);
+                let recursive = args.has('r', "recursive")
+                    || args.has('R', "recursive")
+                    || args.has('a', "archive");
+                if name == "cp" && !recursive {
+                    skip_dirs(&mut action, "cp without -r won't copy it");
+                }
+                if let Some(dest) = dest {
+                    self.destination(&mut action, &dest, cwd, !args.has('n', "no-clobber"));
+                }
+                // Copies read their sources; only moves take whole trees with them
+                name == "mv"
+            }
+            "chmod" | "chown" | "chgrp" => {
+                let args = Args::parse(inv.args(), &[]);
+                action.action = match name.as_str() {
+                    "chmod" => "Change permissions",
+                    "chown" => "Change owner",
+                    _ => "Change group",
+                }
+                .to_string();
+                // The first operand is the mode or owner unless --reference gives it
+                let skip = usize::from(!args.has_long("reference"));
+                let operands = args.operands.get(skip..).unwrap_or_default();
+                self.add_operands(&mut action, operands, cwd);
+                args.has('R', "recursive")
+            }
+            "find" => {
+                if !self.find(&mut action, inv.args(), cwd) {
+                    return;
+                }
+                false
+            }
+            _ => return,
+        };
+
+        if recursive {
+            count_contents(&mut action);
+        }
+        action.total += action.paths.len() as u64;
+        if inv.root {
+            action
+                .notes
+                .push("Runs as root, so permissions won't stop it".to_string());
+        }
+        if inv.xargs {
+            action.notes.push(
+                "Also gets paths from its input (xargs), which can't be previewed".to_string(),
+            );
+        }
+        if self.lost_cwd {
+            action.notes.push(format!(
+                "Follows a `cd` that couldn't be resolved; paths are relative to {}",
+                cwd.display()
+            ));
+        }
+        self.actions.push(action);
+    }
+
+    fn cd(&mut self, args: &[Word], cwd: &mut PathBuf) {
+        let target = args
+            .iter()
+            .find(|w| !w.text.starts_with('-') || w.text == "-");
+        let resolved = match target {
+            None => self.home.clone(),
+            Some(word) if word.text == "-" => None,
+            Some(word) => match self.expand(word, cwd) {
+                Expanded::Paths(paths) => paths.into_iter().next().filter(|p| p.is_dir()),
+                Expanded::Unknown => None,
+            },
+        };
+        match resolved {
+            Some(dir) => *cwd = dir,
+            None => self.lost_cwd = true,
+        }
+    }
+
+    fn add_operands(&self, action: &mut PreviewAction, operands: &[&Word], cwd: &Path) {
+        for word in operands {
+            match self.expand(word, cwd) {
+                Expanded::Paths(paths) if paths.is_empty() => {
+                    action.missing.push(word.text.clone())
+                }
+                Expanded::Paths(paths) => {
+                    for path in paths {
+                        if action.paths.len() < LIST_LIMIT {
+                            action.paths.push(touched(&path));
+                        } else {
+                            action.truncated = true;
+                            action.total += 1;
+                        }
+                    }
+                }
+                Expanded::Unknown => action.notes.push(format!(
+                    "`{}` depends on a variable or command output; not previewed",
+                    word.text
+                )),
+            }
+        }
+    }
+
+    /// Name the destination and warn about files it would replace.
+    fn destination(&self, action: &mut PreviewAction, dest: &str, cwd: &Path, overwrites: bool) {
+        let word = Word {
+            text: dest.to_string(),
+            glob: has_glob(dest),
+            ..Word::default()
+        };
+        let path = match self.expand(&word, cwd) {
+            Expanded::Paths(paths) => paths.into_iter().next().unwrap_or_else(|| cwd.join(dest)),
+            Expanded::Unknown => {
+                action.destination = Some(dest.to_string());
+                return;
+            }
+        };
+        action.destination = Some(path.to_string_lossy().to_string());
+        if !overwrites {
+            return;
+        }
+        if path.is_dir() {
+            let replaced: Vec<String> = action
+                .paths
+                .iter()
+                .filter_map(|t| Path::new(&t.path).file_name())
+                .map(|name| path.join(name))
+                .filter(|p| p.symlink_metadata().is_ok())
+                .map(|p| p.to_string_lossy().to_string())
+                .collect();
+            action
+                .notes
+                .extend(replaced.into_iter().map(|p| format!("Replaces {}", p)));
+        } else if path.exists() {
+            action.notes.push(format!("Replaces {}", path.display()));
+        } else if action.paths.len() > 1 {
+            action.notes.push(format!(
+                "{} isn't a directory, so this will fail with several sources",
+                dest
+            ));
+        }
+    }
+
+    /// `find … -delete` or `-exec rm`: walk the tree with the tests we understand.
+    fn find(&self, action: &mut PreviewAction, args: &[Word], cwd: &Path) -> bool {
+        let split = args
+            .iter()
+            .position(|w| w.text.starts_with('-') || matches!(w.text.as_str(), "(" | "!"))
+            .unwrap_or(args.len());
+        let (starts, expr) = args.split_at(split);
+
+        let deletes = expr.iter().enumerate().any(|(i, w)| {
+            w.text == "-delete"
+                || (matches!(w.text.as_str(), "-exec" | "-execdir" | "-ok" | "-okdir")
+                    && expr.get(i + 1).is_some_and(|next| next.text == "rm"))
+        });
+        if !deletes {
+            return false;
+        }
+        action.action = "Delete".to_string();
+
+        let mut filter = FindFilter::default();
+        let mut unsupported = Vec::new();
+        let mut i = 0;
+        while let Some(word) = expr.get(i) {
+            let value = expr.get(i + 1).map(|w| w.text.clone()).unwrap_or_default();
+            i += 1;
+            match word.text.as_str() {
+                "-name" => filter.names.push((value, false)),
+                "-iname" => filter.names.push((value, true)),
+                "-path" | "-wholename" => filter.paths.push((value, false)),
+                "-ipath" | "-iwholename" => filter.paths.push((value, true)),
+                "-type" => filter.kind = value.chars().next(),
+                "-maxdepth" => filter.max_depth = value.parse().ok(),
+                "-mindepth" => filter.min_depth = value.parse().unwrap_or(0),
+                "-empty" => {
+                    filter.empty = true;
+                    continue;
+                }
+                "-exec" | "-execdir" | "-ok" | "-okdir" => {
+                    i = expr[i..]
+                        .iter()
+                        .position(|w| w.text == ";" || w.text == "+")
+                        .map_or(expr.len(), |p| i + p + 1);
+                    continue;
+                }
+                "-delete" | "-print" | "-print0" | "-depth" | "-xdev" | "-mount" => continue,
+                "!" | "-not" => {
+                    // Skip the negated test so the listing errs on the side of too much
+                    unsupported.push(word.text.clone());
+                    i += 1;
+                    continue;
+                }
+                other => {
+                    unsupported.push(other.to_string());
+                    // Tests like -mtime take a value; operators like -o don't
+                    if !value.starts_with('-')
+                        && !matches!(other, "-o" | "-or" | "-a" | "-and" | "(" | ")" | ",")
+                    {
+                        i += 1;
+                    }
+                    continue;
+                }
+            }
+            i += 1;
+        }
+        if !unsupported.is_empty() {
+            action.notes.push(format!(
+                "Ignores {} when previewing, so this may list more than find would delete",
+                unsupported.join(" ")
+            ));
+        }
+
+        let starts: Vec<&Word> = starts.iter().collect();
+        self.add_operands(action, &starts, cwd);
+        let mut roots = std::mem::take(&mut action.paths);
+        if starts.is_empty() {
+            roots.push(touched(cwd));
+        }
+
+        let mut budget = COUNT_LIMIT;
+        for root in roots {
+            let mut stack = vec![(PathBuf::from(&root.path), 0usize)];
+            while let Some((path, depth)) = stack.pop() {
+                if budget == 0 {
+                    action.truncated = true;
+                    break;
+                }
+                budget -= 1;
+                let Ok(meta) = path.symlink_metadata() else {
+                    continue;
+                };
+                if meta.is_dir() && filter.max_depth.is_none_or(|max| depth < max) {
+                    if let Ok(entries) = std::fs::read_dir(&path) {
+                        let mut children: Vec<PathBuf> =
+                            entries.flatten().map(|e| e.path()).collect();
+                        children.sort();
+                        stack.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+                    }
+                }
+                if depth >= filter.min_depth && filter.matches(&path, &meta) {
+                    if action.paths.len() < LIST_LIMIT {
+                        action.paths.push(touched(&path));
+                    } else {
+                        action.truncated = true;
+                        action.total += 1;
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+#[derive(Default)]
+struct FindFilter {
+    /// Patterns, and whether they ignore case
+    names: Vec<(String, bool)>,
+    paths: Vec<(String, bool)>,
+    kind: Option<char>,
+    max_depth: Option<usize>,
+    min_depth: usize,
+    empty: bool,
+}
+
+impl FindFilter {
+    fn matches(&self, path: &Path, meta: &std::fs::Metadata) -> bool {
+        let fold = |s: &str, ignore_case: bool| {
+            if ignore_case {
+                s.to_lowercase()
+            } else {
+                s.to_string()
+            }
+        };
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let full = path.to_string_lossy();
+        let kind_ok = match self.kind {
+            Some('f') => meta.is_file(),
+            Some('d') => meta.is_dir(),
+            Some('l') => meta.file_type().is_symlink(),
+            _ => true,
+        };
+        let empty_ok = !self.empty
+            || if meta.is_dir() {
+                std::fs::read_dir(path).is_ok_and(|mut e| e.next().is_none())
+            } else {
+                meta.len() == 0
+            };
+        kind_ok
+            && empty_ok
+            && self.names.iter().all(|(p, ic)| glob_match(&fold(p, *ic), &fold(&name, *ic)))
+            // `*` in -path matches across slashes, so compare the whole path as one name
+            && self.paths.iter().all(|(p, ic)| glob_match(&fold(p, *ic), &fold(&full, *ic)))
+    }
+}
+
+/// Drop `.` components, so `find .` lists `dir/x` rather than `dir/./x`.
+fn clean(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+fn touched(path: &Path) -> Touched {
+    Touched {
+        path: path.to_string_lossy().to_string(),
+        is_dir: path.symlink_metadata().is_ok_and(|m| m.is_dir()),
+        contents: 0,
+    }
+}
+
+/// The value of `-t`/`--target-directory`, in any of its spellings.
+fn target_directory(args: &[Word]) -> Option<String> {
+    args.iter().enumerate().find_map(|(i, w)| {
+        let text = w.text.as_str();
+        if let Some(value) = text.strip_prefix("--target-directory=") {
+            return Some(value.to_string());
+        }
+        if text == "-t" || text == "--target-directory" {
+            return args.get(i + 1).map(|v| v.text.clone());
+        }
+        text.strip_prefix("-t")
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    })
+}
+
+/// Drop directories a non-recursive command would refuse, with a note for each.
+fn skip_dirs(action: &mut PreviewAction, reason: &str) {
+    let (dirs, files): (Vec<Touched>, Vec<Touched>) =
+        action.paths.drain(..).partition(|t| t.is_dir);
+    action.paths = files;
+    action.notes.extend(
+        dirs.iter()
+            .map(|d| format!("{} is a directory; {}", d.path, reason)),
+    );
+}
+
+/// Count what recursive actions reach inside each directory.
+fn count_contents(action: &mut PreviewAction) {
+    let mut budget = COUNT_LIMIT;
+    for entry in action.paths.iter_mut().filter(|t| t.is_dir) {
+        let mut stack = vec![PathBuf::from(&entry.path)];
+        while let Some(dir) = stack.pop() {
+            let Ok(children) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for child in children.flatten() {
+                if budget == 0 {
+                    action.truncated = true;
+                    return;
+                }
+                budget -= 1;
+                entry.contents += 1;
+                action.total += 1;
+                // Symlinked directories are removed, not followed
+                if child.file_type().is_ok_and(|t| t.is_dir()) {
+                    stack.push(child.path());
+                }
+            }
+        }
+    }
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match("*.txt", "notes.txt"));
+        assert!(!glob_match("*.txt", "notes.txt.bak"));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(glob_match("file?.log", "file1.log"));
+        assert!(!glob_match("file?.log", "file10.log"));
+        assert!(glob_match("[abc]*", "beta"));
+        assert!(glob_match("[a-c]x", "bx"));
+        assert!(!glob_match("[!a-c]x", "bx"));
+        assert!(glob_match("[!a-c]x", "dx"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("[oops", "[oops"));
+    }
+
+    fn sandbox(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().canonicalize().unwrap().join(format!(
+            "trace-preview-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub/deep")).unwrap();
+        for file in [
+            "a.txt",
+            "b.txt",
+            ".hidden.txt",
+            "sub/c.txt",
+            "sub/d.log",
+            "sub/deep/e.log",
+        ] {
+            std::fs::write(dir.join(file), "x").unwrap();
+        }
+        dir
+    }
+
+    fn names(action: &PreviewAction, root: &Path) -> Vec<String> {
+        action
+            .paths
+            .iter()
+            .map(|t| {
+                Path::new(&t.path)
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn previews_against_the_filesystem() {
+        let root = sandbox("fs");
+
+        let actions = preview("ls -la && rm -v *.txt nope.txt", &root).unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].action, "Delete");
+        assert_eq!(names(&actions[0], &root), ["a.txt", "b.txt"]);
+        assert_eq!(actions[0].missing, ["nope.txt"]);
+        assert_eq!(actions[0].total, 2);
+
+        let actions = preview("rm -rf sub", &root).unwrap();
+        assert_eq!(actions[0].paths[0].contents, 4);
+        assert_eq!(actions[0].total, 5);
+
+        let actions = preview("rm sub a.txt", &root).unwrap();
+        assert_eq!(names(&actions[0], &root), ["a.txt"]);
+        assert!(actions[0].notes[0].contains("rm without -r"));
+
+        let actions = preview("cd sub && rm */*.log", &root).unwrap();
+        assert_eq!(names(&actions[0], &root), ["sub/deep/e.log"]);
+        let actions = preview("rm .*.txt", &root).unwrap();
+        assert_eq!(names(&actions[0], &root), [".hidden.txt"]);
+
+        let actions = preview("sudo chmod -R 755 sub", &root).unwrap();
+        assert_eq!(actions[0].action, "Change permissions");
+        assert_eq!(actions[0].total, 5);
+        assert!(actions[0].notes[0].contains("root"));
+
+        let actions = preview("rm $TARGET/x", &root).unwrap();
+        assert!(actions[0].paths.is_empty());
+        assert!(actions[0].notes[0].contains("variable"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn moves_copies_and_find() {
+        let root = sandbox("mv");
+        std::fs::write(root.join("sub/a.txt"), "old").unwrap();
+
+        let actions = preview("mv a.txt b.txt sub/", &root).unwrap();
+        assert_eq!(actions[0].action, "Move");
+        assert_eq!(names(&actions[0], &root), ["a.txt", "b.txt"]);
+        assert_eq!(
+            actions[0].destination.as_deref(),
+            Some(root.join("sub").to_str().unwrap())
+        );
+        assert_eq!(
+            actions[0].notes,
+            [format!("Replaces {}", root.join("sub/a.txt").display())]
+        );
+
+        let actions = preview("cp -t sub a.txt", &root).unwrap();
+        assert_eq!(actions[0].action, "Copy");
+        assert_eq!(names(&actions[0], &root), ["a.txt"]);
+
+        let actions = preview("find . -name '*.log' -type f -delete", &root).unwrap();
+        let mut found = names(&actions[0], &root);
+        found.sort();
+        assert_eq!(found, ["sub/d.log", "sub/deep/e.log"]);
+
+        let actions = preview("find sub -maxdepth 1 -name '*.log' -exec rm {} +", &root).unwrap();
+        assert_eq!(names(&actions[0], &root), ["sub/d.log"]);
+
+        let actions = preview("find . -name '*.log' -mtime +7 -delete", &root).unwrap();
+        assert_eq!(actions[0].paths.len(), 2);
+        assert!(actions[0].notes[0].contains("-mtime"));
+
+        assert!(preview("find . -name '*.log'", &root).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,1283 @@
+/// Dangerous-command analysis for translated shell commands.
+///
+/// Commands are parsed rather than pattern-matched, so quoted text is harmless
+/// (`echo "rm -rf /"`), wrappers like `sudo`, `env`, `xargs` and `sh -c` are
+/// looked through, and every finding names the paths it would touch.
+use crate::shell_parse::{
+    self, skip_options, unwrap, Args, Command, Invocation, Redirect, Script, SimpleCommand, Span,
+    Word,
+};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// One step up, for commands run as root.
+    fn raised(self) -> Self {
+        match self {
+            Severity::Low => Severity::Medium,
+            Severity::Medium => Severity::High,
+            other => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub reason: String,
+    /// Paths that would be deleted, overwritten or changed
+    pub paths: Vec<String>,
+    /// The part of the command line this is about
+    pub command: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RiskReport {
+    pub severity: Severity,
+    /// Most severe first
+    pub findings: Vec<Finding>,
+}
+
+impl RiskReport {
+    /// High and critical commands are not run from the launcher.
+    pub fn is_dangerous(&self) -> bool {
+        self.severity >= Severity::High
+    }
+
+    /// The most severe finding's reason, or "" when there is none.
+    pub fn summary(&self) -> String {
+        self.findings
+            .first()
+            .map(|f| f.reason.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Analyze a command line.
+pub fn analyze(command: &str) -> RiskReport {
+    let mut analyzer = Analyzer::default();
+    analyzer.source(command, false, 0);
+    let mut findings = analyzer.findings;
+    findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    RiskReport {
+        severity: findings.first().map_or(Severity::None, |f| f.severity),
+        findings,
+    }
+}
+
+// ─── Command tables ──────────────────────────────────────
+
+const SHELLS: &[&str] = &[
+    "sh", "bash", "zsh", "dash", "ksh", "ash", "fish", "csh", "tcsh",
+];
+const INTERPRETERS: &[&str] = &[
+    "python",
+    "python2",
+    "python3",
+    "perl",
+    "ruby",
+    "node",
+    "php",
+    "pwsh",
+    "powershell",
+];
+const DOWNLOADERS: &[&str] = &[
+    "curl",
+    "wget",
+    "fetch",
+    "invoke-webrequest",
+    "iwr",
+    "invoke-restmethod",
+    "irm",
+];
+
+/// Deleting or moving one of these takes the system or the home folder with it.
+const CRITICAL_PATHS: &[&str] = &[
+    "/",
+    "~",
+    "/bin",
+    "/boot",
+    "/dev",
+    "/etc",
+    "/home",
+    "/lib",
+    "/lib64",
+    "/opt",
+    "/proc",
+    "/root",
+    "/sbin",
+    "/srv",
+    "/sys",
+    "/usr",
+    "/var",
+    "/applications",
+    "/library",
+    "/system",
+    "/users",
+    "c:",
+    "c:/windows",
+    "c:/users",
+    "c:/program files",
+];
+const SYSTEM_DIRS: &[&str] = &[
+    "/etc/",
+    "/boot/",
+    "/usr/",
+    "/bin/",
+    "/sbin/",
+    "/lib/",
+    "/lib64/",
+    "c:/windows/",
+];
+const BLOCK_DEVICES: &[&str] = &[
+    "/dev/sd",
+    "/dev/hd",
+    "/dev/vd",
+    "/dev/xvd",
+    "/dev/nvme",
+    "/dev/mmcblk",
+    "/dev/disk",
+    "/dev/rdisk",
+    "/dev/md",
+    "/dev/dm-",
+    "/dev/mapper/",
+    "//./physicaldrive",
+];
+
+/// `~`, `$HOME` and Windows spellings reduced to one form, without trailing `/`, `/*` or `/.`.
+fn normalize(path: &str) -> String {
+    let mut p = path.replace('\\', "/");
+    for home in ["${HOME}", "$HOME", "%USERPROFILE%", "$env:USERPROFILE"] {
+        if let Some(rest) = p.strip_prefix(home) {
+            p = format!("~{}", rest);
+        }
+    }
+    while p.len() > 1 {
+        if let Some(parent) = ["/*", "/.*", "/."].iter().find_map(|s| p.strip_suffix(s)) {
+            p = if parent.is_empty() {
+                "/".to_string()
+            } else {
+                parent.to_string()
+            };
+        } else if let Some(trimmed) = p.strip_suffix('/') {
+            p = trimmed.to_string();
+        } else {
+            break;
+        }
+    }
+    p.to_lowercase()
+}
+
+fn is_critical(path: &str) -> bool {
+    CRITICAL_PATHS.contains(&normalize(path).as_str())
+}
+
+fn is_system_file(path: &str) -> bool {
+    let p = normalize(path);
+    SYSTEM_DIRS.iter().any(|dir| p.starts_with(dir))
+}
+
+fn is_block_device(path: &str) -> bool {
+    let p = normalize(path);
+    BLOCK_DEVICES.iter().any(|dev| p.starts_with(dev))
+}
+
+fn texts(words: &[&Word]) -> Vec<String> {
+    words.iter().map(|w| w.text.clone()).collect()
+}
+
+fn list(paths: &[String]) -> String {
+    paths.join(", ")
+}
+
+/// Whether a script fetches something from the network.
+fn downloads(script: &Script) -> bool {
+    let mut found = false;
+    script.walk(&mut |command| {
+        found |= unwrap(&command.words)
+            .name()
+            .is_some_and(|name| DOWNLOADERS.contains(&name.as_str()));
+    });
+    found
+}
+
+// ─── cmd.exe ─────────────────────────────────────────────
+
+/// cmd.exe switches like `/s`, lowercased.
+fn switches(words: &[Word]) -> Vec<String> {
+    words
+        .iter()
+        .filter(|w| w.text.len() == 2 && w.text.starts_with('/'))
+        .map(|w| w.text.to_lowercase())
+        .collect()
+}
+
+fn non_switches(words: &[Word]) -> Vec<&Word> {
+    words
+        .iter()
+        .filter(|w| !(w.text.len() == 2 && w.text.starts_with('/')))
+        .collect()
+}
+
+/// Whether a program would execute a script piped into it.
+fn runs_stdin(name: &str, args: &[Word]) -> bool {
+    if matches!(name, "iex" | "invoke-expression") {
+        return true;
+    }
+    let shell = SHELLS.contains(&name);
+    if !shell && !INTERPRETERS.contains(&name) {
+        return false;
+    }
+    let parsed = Args::parse(args, &[]);
+    // Code given inline with -c (or -e for interpreters) is not read from stdin
+    if parsed.short.contains('c') || (!shell && parsed.short.contains('e')) {
+        return false;
+    }
+    parsed.short.contains('s') || parsed.operands.first().is_none_or(|w| w.text == "-")
+}
+
+// ─── Analyzer ────────────────────────────────────────────
+
+/// How deep `sh -c` / `eval` strings are followed
+const MAX_DEPTH: usize = 4;
+
+#[derive(Default)]
+struct Analyzer {
+    findings: Vec<Finding>,
+}
+
+/// Where a command is being looked at.
+#[derive(Clone, Copy)]
+struct Scope<'s> {
+    /// Command line the spans refer to
+    src: &'s str,
+    span: Span,
+    /// Running under sudo, doas or su
+    root: bool,
+    /// `sh -c` / `eval` nesting
+    depth: usize,
+}
+
+impl Analyzer {
+    fn flag(&mut self, scope: Scope, severity: Severity, reason: String, paths: Vec<String>) {
+        if self
+            .findings
+            .iter()
+            .any(|f| f.reason == reason && f.paths == paths)
+        {
+            return;
+        }
+        let severity = if scope.root {
+            severity.raised()
+        } else {
+            severity
+        };
+        let command = scope
+            .src
+            .get(scope.span.0..scope.span.1)
+            .unwrap_or(scope.src)
+            .trim()
+            .to_string();
+        self.findings.push(Finding {
+            severity,
+            reason,
+            paths,
+            command,
+        });
+    }
+
+    fn source(&mut self, src: &str, root: bool, depth: usize) {
+        let scope = Scope {
+            src,
+            span: (0, src.len()),
+            root,
+            depth,
+        };
+        match shell_parse::parse(src) {
+            Ok(script) => self.script(&script, scope),
+            Err(e) => self.flag(
+                scope,
+                Severity::Medium,
+                format!(
+                    "Could not be parsed ({}), so what it runs can't be checked",
+                    e
+                ),
+                vec![],
+            ),
+        }
+    }
+
+    fn script(&mut self, script: &Script, scope: Scope) {
+        for pipeline in &script.pipelines {
+            self.pipeline(&pipeline.commands, scope);
+            for command in &pipeline.commands {
+                self.command(command, scope);
+            }
+        }
+    }
+
+    /// `curl … | sh`: a download piped into an interpreter.
+    fn pipeline(&mut self, commands: &[Command], scope: Scope) {
+        let mut download_start = None;
+        for command in commands {
+            let Command::Simple(simple) = command else {
+                continue;
+            };
+            let inv = unwrap(&simple.words);
+            let Some(name) = inv.name() else {
+                continue;
+            };
+            if let Some(start) = download_start {
+                if runs_stdin(&name, inv.args()) {
+                    let span = (start, simple.span.1);
+                    self.flag(
+                        Scope { span, ..scope },
+                        Severity::Critical,
+                        "Downloads a script from the internet and runs it without review".into(),
+                        vec![],
+                    );
+                }
+            }
+            if DOWNLOADERS.contains(&name.as_str()) {
+                download_start.get_or_insert(simple.span.0);
+            }
+        }
+    }
+
+    fn command(&mut self, command: &Command, scope: Scope) {
+        match command {
+            Command::Simple(simple) => self.simple(simple, scope),
+            Command::Group { body, redirects } => {
+                self.script(body, scope);
+                self.redirects(redirects, scope);
+            }
+            Command::Function { name, body } => {
+                let mut calls = 0;
+                body.walk(&mut |c| calls += usize::from(c.program() == Some(name.as_str())));
+                if calls >= 2 {
+                    self.flag(
+                        scope,
+                        Severity::Critical,
+                        format!(
+                            "Fork bomb — `{}` keeps starting copies of itself until the system runs out of processes",
+                            name
+                        ),
+                        vec![],
+                    );
+                }
+                self.command(body, scope);
+            }
+        }
+    }
+
+    fn simple(&mut self, command: &SimpleCommand, scope: Scope) {
+        let scope = Scope {
+            span: command.span,
+            ..scope
+        };
+        // Substitutions run whatever the command turns out to be
+        let words = command
+            .assignments
+            .iter()
+            .chain(&command.words)
+            .chain(command.redirects.iter().map(|r| &r.target));
+        for script in words.flat_map(|w| &w.substitutions) {
+            self.script(script, scope);
+        }
+        self.redirects(&command.redirects, scope);
+
+        let inv = unwrap(&command.words);
+        if inv.root && !scope.root {
+            let reason = match inv.name() {
+                Some(name) => format!("Runs `{}` with root privileges", name),
+                None => "Opens a root shell".to_string(),
+            };
+            self.flag(scope, Severity::Low, reason, vec![]);
+        }
+        let scope = Scope {
+            root: scope.root || inv.root,
+            ..scope
+        };
+        self.invocation(&inv, scope);
+    }
+
+    fn redirects(&mut self, redirects: &[Redirect], scope: Scope) {
+        for redirect in redirects.iter().filter(|r| r.writes()) {
+            self.write_to(&redirect.target.text, redirect.op.ends_with(">>"), scope);
+        }
+    }
+
+    fn write_to(&mut self, path: &str, append: bool, scope: Scope) {
+        let paths = vec![path.to_string()];
+        if is_block_device(path) {
+            let reason = format!(
+                "Writes raw data to the disk {}, destroying what is on it",
+                path
+            );
+            self.flag(scope, Severity::Critical, reason, paths);
+        } else if is_system_file(path) && append {
+            self.flag(
+                scope,
+                Severity::Medium,
+                format!("Appends to the system file {}", path),
+                paths,
+            );
+        } else if is_system_file(path) {
+            self.flag(
+                scope,
+                Severity::High,
+                format!("Overwrites the system file {}", path),
+                paths,
+            );
+        }
+    }
+
+    fn invocation(&mut self, inv: &Invocation, scope: Scope) {
+        let Some(name) = inv.name() else {
+            return;
+        };
+        let args = inv.args();
+        match name.as_str() {
+            "rm" | "unlink" => self.rm(args, inv.xargs, scope),
+            "rd" | "rmdir" => self.rd(args, scope),
+            "del" | "erase" => self.del(args, scope),
+            "remove-item" | "ri" => self.remove_item(args, scope),
+            "shred" | "srm" => {
+                let paths =
+                    texts(&Args::parse(args, &["-n", "-s", "--iterations", "--size"]).operands);
+                let severity = if paths.iter().any(|p| is_block_device(p)) {
+                    Severity::Critical
+                } else {
+                    Severity::High
+                };
+                let reason = format!("Overwrites {} so it can never be recovered", list(&paths));
+                self.flag(scope, severity, reason, paths);
+            }
+            "find" => self.find(args, scope),
+            "chmod" => self.chmod(args, scope),
+            "chown" | "chgrp" => self.chown(args, scope),
+            "dd" => {
+                let Some(target) = args.iter().find_map(|w| w.text.strip_prefix("of=")) else {
+                    return;
+                };
+                let paths = vec![target.to_string()];
+                if is_block_device(target) {
+                    let reason = format!(
+                        "Writes raw data to the disk {}, destroying what is on it",
+                        target
+                    );
+                    self.flag(scope, Severity::Critical, reason, paths);
+                } else {
+                    let reason = format!("Writes raw data to {}, replacing its contents", target);
+                    self.flag(scope, Severity::Medium, reason, paths);
+                }
+            }
+            "tee" => {
+                let parsed = Args::parse(args, &[]);
+                let append = parsed.has('a', "append");
+                for target in parsed.operands {
+                    self.write_to(&target.text, append, scope);
+                }
+            }
+            "cp" | "mv" => self.copy_move(&name, args, scope),
+            "truncate" => {
+                let paths =
+                    texts(&Args::parse(args, &["-s", "-r", "--size", "--reference"]).operands);
+                let reason = format!(
+                    "Resizes {}, discarding data past the new size",
+                    list(&paths)
+                );
+                self.flag(scope, Severity::Medium, reason, paths);
+            }
+            "shutdown" => {
+                let cancel = args
+                    .iter()
+                    .any(|w| matches!(w.text.to_lowercase().as_str(), "-c" | "/a"));
+                if !cancel {
+                    self.flag(
+                        scope,
+                        Severity::High,
+                        "Shuts down or restarts the computer".into(),
+                        vec![],
+                    );
+                }
+            }
+            "reboot" | "poweroff" | "halt" => self.flag(
+                scope,
+                Severity::High,
+                "Shuts down or restarts the computer".into(),
+                vec![],
+            ),
+            "init" | "telinit" if args.first().is_some_and(|w| w.text == "0" || w.text == "6") => {
+                self.flag(
+                    scope,
+                    Severity::High,
+                    "Shuts down or restarts the computer".into(),
+                    vec![],
+                )
+            }
+            "systemctl"
+                if args.iter().any(|w| {
+                    matches!(w.text.as_str(), "poweroff" | "reboot" | "halt" | "kexec")
+                }) =>
+            {
+                self.flag(
+                    scope,
+                    Severity::High,
+                    "Shuts down or restarts the computer".into(),
+                    vec![],
+                )
+            }
+            "kill" if args.len() >= 2 && args.last().is_some_and(|w| w.text == "-1") => self.flag(
+                scope,
+                Severity::Critical,
+                "Kills every process you are allowed to signal, ending your session".into(),
+                vec![],
+            ),
+            "killall" | "pkill" => {
+                let targets =
+                    texts(&Args::parse(args, &["-s", "-u", "-g", "--signal", "--user"]).operands);
+                let reason = format!("Kills every process matching {}", list(&targets));
+                self.flag(scope, Severity::Medium, reason, vec![]);
+            }
+            "taskkill" if switches(args).iter().any(|s| s == "/f") => self.flag(
+                scope,
+                Severity::Medium,
+                "Forcefully terminates processes, losing any unsaved work".into(),
+                vec![],
+            ),
+            "crontab" if Args::parse(args, &["-u"]).short.contains('r') => self.flag(
+                scope,
+                Severity::High,
+                "Deletes all of your scheduled cron jobs".into(),
+                vec![],
+            ),
+            "reg"
+                if args
+                    .first()
+                    .is_some_and(|w| w.text.eq_ignore_ascii_case("delete")) =>
+            {
+                let keys: Vec<String> = args.get(1).map(|w| w.text.clone()).into_iter().collect();
+                let reason = format!(
+                    "Deletes the registry key {}, which can break Windows",
+                    list(&keys)
+                );
+                self.flag(scope, Severity::High, reason, keys);
+            }
+            "git" => self.git(args, scope),
+            "diskpart" => self.flag(
+                scope,
+                Severity::Critical,
+                "Opens the disk partition editor, which can erase whole drives".into(),
+                vec![],
+            ),
+            "format" if args.first().is_some_and(|w| w.text.ends_with(':')) => {
+                let paths = vec![args[0].text.clone()];
+                let reason = format!("Formats the drive {}, erasing everything on it", paths[0]);
+                self.flag(scope, Severity::Critical, reason, paths);
+            }
+            n if n.starts_with("mkfs")
+                || matches!(n, "mke2fs" | "mkswap" | "wipefs" | "blkdiscard" | "newfs") =>
+            {
+                let paths = device_operands(args);
+                let reason = format!("Formats {}, erasing everything on it", list(&paths));
+                self.flag(scope, Severity::Critical, reason, paths);
+            }
+            "fdisk" | "sfdisk" | "gdisk" | "sgdisk" | "cfdisk" | "parted" => {
+                let parsed = Args::parse(args, &[]);
+                let listing = parsed.has('l', "list") || args.iter().any(|w| w.text == "print");
+                if !listing {
+                    let paths = device_operands(args);
+                    let reason = format!("Edits the partition table of {}", list(&paths));
+                    self.flag(scope, Severity::High, reason, paths);
+                }
+            }
+            n if SHELLS.contains(&n) || n == "su" || n == "eval" => self.nested(n, args, scope),
+            _ => {}
+        }
+
+        // `sh -c "$(curl …)"`, `bash <(wget …)`, `eval "$(curl …)"`
+        let runs_code = SHELLS.contains(&name.as_str())
+            || INTERPRETERS.contains(&name.as_str())
+            || matches!(
+                name.as_str(),
+                "eval" | "source" | "." | "iex" | "invoke-expression"
+            );
+        if runs_code && args.iter().flat_map(|w| &w.substitutions).any(downloads) {
+            self.flag(
+                scope,
+                Severity::Critical,
+                "Downloads a script from the internet and runs it without review".into(),
+                vec![],
+            );
+        }
+    }
+
+    /// The command string of `sh -c`, `su -c` or `eval`.
+    fn nested(&mut self, name: &str, args: &[Word], scope: Scope) {
+        if scope.depth >= MAX_DEPTH {
+            return;
+        }
+        let script = if name == "eval" {
+            Some(
+                args.iter()
+                    .map(|w| w.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )
+        } else {
+            args.iter()
+                .take_while(|w| w.text.starts_with('-'))
+                .position(|w| {
+                    w.text == "--command" || (!w.text.starts_with("--") && w.text.contains('c'))
+                })
+                .and_then(|i| args.get(i + 1))
+                .map(|w| w.text.clone())
+        };
+        if let Some(script) = script {
+            self.source(&script, scope.root || name == "su", scope.depth + 1);
+        }
+    }
+
+    fn rm(&mut self, args: &[Word], xargs: bool, scope: Scope) {
+        let parsed = Args::parse(args, &[]);
+        let recursive = parsed.has('r', "recursive") || parsed.short.contains('R');
+        let force = parsed.has('f', "force");
+        let paths = texts(&parsed.operands);
+
+        if parsed.has_long("no-preserve-root") {
+            let reason =
+                "Disables rm's protection against deleting / — everything can be lost".into();
+            self.flag(scope, Severity::Critical, reason, paths);
+        } else if recursive && paths.iter().any(|p| is_critical(p)) {
+            let critical: Vec<String> = paths.into_iter().filter(|p| is_critical(p)).collect();
+            let reason = format!(
+                "Recursively deletes {} and everything under it",
+                list(&critical)
+            );
+            self.flag(scope, Severity::Critical, reason, critical);
+        } else if recursive {
+            let what = if xargs && paths.is_empty() {
+                "every path read from its input".to_string()
+            } else {
+                list(&paths)
+            };
+            let prompt = if force { " without asking" } else { "" };
+            let reason = format!("Recursively deletes {}{} — there is no undo", what, prompt);
+            self.flag(scope, Severity::High, reason, paths);
+        } else if xargs {
+            self.flag(
+                scope,
+                Severity::Medium,
+                "Deletes every file named on its input".into(),
+                paths,
+            );
+        } else if parsed.operands.iter().any(|w| w.glob) {
+            let reason = format!("Deletes every file matching {}", list(&paths));
+            self.flag(scope, Severity::Medium, reason, paths);
+        } else if !paths.is_empty() {
+            self.flag(
+                scope,
+                Severity::Low,
+                format!("Deletes {}", list(&paths)),
+                paths,
+            );
+        }
+    }
+
+    fn rd(&mut self, args: &[Word], scope: Scope) {
+        if !switches(args).iter().any(|s| s == "/s") {
+            return;
+        }
+        let paths = texts(&non_switches(args));
+        let severity = if paths.iter().any(|p| is_critical(p)) {
+            Severity::Critical
+        } else {
+            Severity::High
+        };
+        let reason = format!(
+            "Removes {} and everything in it — there is no undo",
+            list(&paths)
+        );
+        self.flag(scope, severity, reason, paths);
+    }
+
+    fn del(&mut self, args: &[Word], scope: Scope) {
+        let switches = switches(args);
+        let targets = non_switches(args);
+        let paths = texts(&targets);
+        if switches.iter().any(|s| s == "/s") {
+            let severity = if paths.iter().any(|p| is_critical(p)) {
+                Severity::Critical
+            } else {
+                Severity::High
+            };
+            let reason = format!(
+                "Deletes matching files in every subfolder of {}",
+                list(&paths)
+            );
+            self.flag(scope, severity, reason, paths);
+        } else if targets.iter().any(|w| w.glob) {
+            let reason = format!("Deletes every file matching {}", list(&paths));
+            self.flag(scope, Severity::Medium, reason, paths);
+        } else if !paths.is_empty() {
+            self.flag(
+                scope,
+                Severity::Low,
+                format!("Deletes {}", list(&paths)),
+                paths,
+            );
+        }
+    }
+
+    /// PowerShell's `Remove-Item`, whose parameters are case-insensitive prefixes.
+    fn remove_item(&mut self, args: &[Word], scope: Scope) {
+        let param = |full: &str, min: usize| {
+            args.iter().any(|w| {
+                let p = w.text.to_lowercase();
+                p.len() >= min && full.starts_with(&p)
+            })
+        };
+        let recurse = param("-recurse", 2);
+        let paths: Vec<String> = args
+            .iter()
+            .filter(|w| !w.text.starts_with('-'))
+            .map(|w| w.text.clone())
+            .collect();
+        if recurse && paths.iter().any(|p| is_critical(p)) {
+            let reason = format!(
+                "Recursively deletes {} and everything under it",
+                list(&paths)
+            );
+            self.flag(scope, Severity::Critical, reason, paths);
+        } else if recurse {
+            let prompt = if param("-force", 3) {
+                " without asking"
+            } else {
+                ""
+            };
+            let reason = format!(
+                "Recursively deletes {}{} — there is no undo",
+                list(&paths),
+                prompt
+            );
+            self.flag(scope, Severity::High, reason, paths);
+        } else if !paths.is_empty() {
+            self.flag(
+                scope,
+                Severity::Low,
+                format!("Deletes {}", list(&paths)),
+                paths,
+            );
+        }
+    }
+
+    fn find(&mut self, args: &[Word], scope: Scope) {
+        let mut start = 0;
+        while let Some(word) = args.get(start) {
+            match word.text.as_str() {
+                "-H" | "-L" | "-P" => start += 1,
+                "-D" => start += 2,
+                t if t.starts_with("-O") => start += 1,
+                _ => break,
+            }
+        }
+        let args = args.get(start..).unwrap_or(&[]);
+        let roots: Vec<&Word> = args
+            .iter()
+            .take_while(|w| !w.text.starts_with('-') && !matches!(w.text.as_str(), "(" | "!" | ","))
+            .collect();
+        let expression = &args[roots.len()..];
+
+        let mut deletes = expression.iter().any(|w| w.text == "-delete");
+        // -exec and friends run a command for every match, up to `;` or `+`
+        let mut rest = expression;
+        while let Some(at) = rest
+            .iter()
+            .position(|w| matches!(w.text.as_str(), "-exec" | "-execdir" | "-ok" | "-okdir"))
+        {
+            let after = &rest[at + 1..];
+            let end = after
+                .iter()
+                .position(|w| w.text == ";" || w.text == "+")
+                .unwrap_or(after.len());
+            let exec = unwrap(&after[..end]);
+            match exec.name().as_deref() {
+                Some("rm" | "unlink" | "rmdir" | "shred") => deletes = true,
+                _ => {
+                    let scope = Scope {
+                        root: scope.root || exec.root,
+                        ..scope
+                    };
+                    self.invocation(&exec, scope);
+                }
+            }
+            rest = after.get(end + 1..).unwrap_or(&[]);
+        }
+
+        if deletes {
+            let paths = if roots.is_empty() {
+                vec![".".to_string()]
+            } else {
+                texts(&roots)
+            };
+            let severity = if paths.iter().any(|p| is_critical(p)) {
+                Severity::Critical
+            } else {
+                Severity::High
+            };
+            let reason = format!("Deletes everything `find` matches under {}", list(&paths));
+            self.flag(scope, severity, reason, paths);
+        }
+    }
+
+    fn chmod(&mut self, args: &[Word], scope: Scope) {
+        let parsed = Args::parse(args, &[]);
+        let recursive = parsed.has('R', "recursive");
+        let reference = parsed.has_long("reference");
+        let Some((mode, targets)) = (if reference {
+            Some(("", &parsed.operands[..]))
+        } else {
+            parsed
+                .operands
+                .split_first()
+                .map(|(m, t)| (m.text.as_str(), t))
+        }) else {
+            return;
+        };
+        let paths = texts(targets);
+        let open = world_writable(mode);
+        let critical = paths.iter().any(|p| is_critical(p));
+
+        let (severity, reason) = if recursive && critical {
+            (
+                Severity::Critical,
+                format!(
+                    "Recursively changes permissions on everything under {}",
+                    list(&paths)
+                ),
+            )
+        } else if open {
+            let severity = if recursive || critical {
+                Severity::High
+            } else {
+                Severity::Medium
+            };
+            (
+                severity,
+                format!("Makes {} writable by every user", list(&paths)),
+            )
+        } else if recursive || critical {
+            (
+                Severity::Medium,
+                format!("Changes permissions on {}", list(&paths)),
+            )
+        } else {
+            return;
+        };
+        self.flag(scope, severity, reason, paths);
+    }
+
+    fn chown(&mut self, args: &[Word], scope: Scope) {
+        let parsed = Args::parse(args, &[]);
+        let recursive = parsed.has('R', "recursive");
+        let Some((_, targets)) = parsed.operands.split_first() else {
+            return;
+        };
+        let paths = texts(targets);
+        let critical = paths.iter().any(|p| is_critical(p));
+        let (severity, reason) = if recursive && critical {
+            (
+                Severity::Critical,
+                format!(
+                    "Recursively changes the owner of everything under {}",
+                    list(&paths)
+                ),
+            )
+        } else if recursive || critical {
+            (
+                Severity::Medium,
+                format!("Changes the owner of {}", list(&paths)),
+            )
+        } else {
+            return;
+        };
+        self.flag(scope, severity, reason, paths);
+    }
+
+    fn copy_move(&mut self, name: &str, args: &[Word], scope: Scope) {
+        let parsed = Args::parse(args, &["-t", "-S", "--target-directory", "--suffix"]);
+        let Some((dest, sources)) = parsed.operands.split_last() else {
+            return;
+        };
+        let dest = dest.text.as_str();
+        let sources = texts(sources);
+        if sources.is_empty() {
+            return;
+        }
+        if is_block_device(dest) {
+            let reason = format!("Writes over the disk {}, destroying what is on it", dest);
+            self.flag(scope, Severity::Critical, reason, vec![dest.to_string()]);
+        } else if name == "mv" && dest == "/dev/null" {
+            let reason = format!("Moves {} into /dev/null, destroying it", list(&sources));
+            self.flag(scope, Severity::High, reason, sources);
+        } else if name == "mv" && sources.iter().any(|p| is_critical(p)) {
+            let reason = format!(
+                "Moves {} away — the system or your home folder may stop working",
+                list(&sources)
+            );
+            self.flag(scope, Severity::High, reason, sources);
+        } else if is_system_file(dest) {
+            self.write_to(dest, false, scope);
+        }
+    }
+
+    fn git(&mut self, args: &[Word], scope: Scope) {
+        let rest = skip_options(args, &["-C", "-c", "--git-dir", "--work-tree"]);
+        let Some((sub, rest)) = rest.split_first() else {
+            return;
+        };
+        let parsed = Args::parse(rest, &[]);
+        let reason = match sub.text.as_str() {
+            "reset" if parsed.has_long("hard") => "Discards all uncommitted changes",
+            "clean" if parsed.has('f', "force") && parsed.short.contains('x') => {
+                "Deletes untracked and ignored files"
+            }
+            "clean" if parsed.has('f', "force") => "Deletes untracked files",
+            "push" if parsed.short.contains('f') || parsed.long.contains(&"force") => {
+                "Force-pushes, overwriting history on the remote"
+            }
+            _ => return,
+        };
+        self.flag(scope, Severity::Medium, reason.into(), vec![]);
+    }
+}
+
+/// Device operands of disk tools, or the last operand if none look like one.
+fn device_operands(args: &[Word]) -> Vec<String> {
+    let operands = Args::parse(args, &["-t", "-L", "-b", "-n", "-O", "-c", "-U"]).operands;
+    let devices: Vec<String> = operands
+        .iter()
+        .filter(|w| w.text.starts_with("/dev/"))
+        .map(|w| w.text.clone())
+        .collect();
+    if devices.is_empty() {
+        operands
+            .last()
+            .map(|w| w.text.clone())
+            .into_iter()
+            .collect()
+    } else {
+        devices
+    }
+}
+
+/// Whether a chmod mode lets other users write (`777`, `o+w`, `a=rwx`, `+w`).
+fn world_writable(mode: &str) -> bool {
+    if !mode.is_empty() && mode.chars().all(|c| c.is_ascii_digit()) {
+        return mode
+            .chars()
+            .last()
+            .and_then(|c| c.to_digit(8))
+            .is_some_and(|others| others & 2 != 0);
+    }
+    mode.split(',').any(|clause| {
+        let Some(op) = clause.find(['+', '=']) else {
+            return false;
+        };
+        let who = &clause[..op];
+        let perms = &clause[op + 1..];
+        (who.is_empty() || who.contains(['a', 'o'])) && perms.contains('w')
+    })
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Severity::*;
+
+    /// (command, overall severity, paths of the most severe finding)
+    const CASES: &[(&str, Severity, &[&str])] = &[
+        // Deleting the system or the home folder, however it is spelled
+        ("rm -rf /", Critical, &["/"]),
+        ("rm -r -f ~", Critical, &["~"]),
+        ("rm --recursive --force ~", Critical, &["~"]),
+        ("rm --rec --fo ~/", Critical, &["~/"]),
+        ("rm -rf /*", Critical, &["/*"]),
+        ("rm -rf $HOME", Critical, &["$HOME"]),
+        ("rm -rf \"$HOME/\"", Critical, &["$HOME/"]),
+        ("rm -fr --no-preserve-root /", Critical, &["/"]),
+        ("/bin/rm -Rf /etc", Critical, &["/etc"]),
+        ("rm -rf -- ~", Critical, &["~"]),
+        ("rm build -rf ~", Critical, &["~"]),
+        ("sudo rm -rf /", Critical, &["/"]),
+        ("sudo -u root -- rm -rf /usr", Critical, &["/usr"]),
+        ("ls && sudo rm -rf /usr", Critical, &["/usr"]),
+        ("echo ok; rm -rf ~", Critical, &["~"]),
+        ("env LC_ALL=C rm -rf /var", Critical, &["/var"]),
+        ("nohup nice -n 10 rm -rf ~ &", Critical, &["~"]),
+        ("timeout 10 doas rm -rf /home", Critical, &["/home"]),
+        ("bash -c 'rm -rf ~'", Critical, &["~"]),
+        ("sudo sh -c \"rm -rf /\"", Critical, &["/"]),
+        ("su -c 'rm -rf /root'", Critical, &["/root"]),
+        ("eval rm -rf '~'", Critical, &["~"]),
+        ("echo $(rm -rf ~)", Critical, &["~"]),
+        ("echo `rm -rf ~`", Critical, &["~"]),
+        ("(cd / && rm -rf *)", High, &["*"]),
+        ("if true; then rm -rf ~; fi", Critical, &["~"]),
+        ("find / -delete", Critical, &["/"]),
+        ("find -L ~ -name '*.bak' -delete", Critical, &["~"]),
+        ("find ~ -type f -exec rm {} \\;", Critical, &["~"]),
+        (
+            "find / -name '*.log' -exec sudo rm -f {} +",
+            Critical,
+            &["/"],
+        ),
+        ("chmod -R 777 ~", Critical, &["~"]),
+        ("chmod --recursive a+rwx /", Critical, &["/"]),
+        ("sudo chown -R nobody /", Critical, &["/"]),
+        ("rd /s /q C:\\", Critical, &["C:\\"]),
+        (
+            "Remove-Item -Recurse -Force C:\\Users",
+            Critical,
+            &["C:\\Users"],
+        ),
+        ("del /s /q %USERPROFILE%", Critical, &["%USERPROFILE%"]),
+        // Remote code execution
+        ("curl x | sh", Critical, &[]),
+        (
+            "curl -fsSL https://example.com/install.sh | sudo sh",
+            Critical,
+            &[],
+        ),
+        ("wget -qO- https://example.com/x.sh | bash", Critical, &[]),
+        (
+            "curl -s https://x | tee install.sh | bash -s -- --yes",
+            Critical,
+            &[],
+        ),
+        ("curl -s https://x | sudo -E python3 -", Critical, &[]),
+        ("bash <(curl -s https://x)", Critical, &[]),
+        ("sh -c \"$(curl -fsSL https://x)\"", Critical, &[]),
+        ("eval \"$(wget -qO- https://x)\"", Critical, &[]),
+        ("iwr https://x | iex", Critical, &[]),
+        // Disks and the system itself
+        ("dd if=/dev/zero of=/dev/sda bs=1M", Critical, &["/dev/sda"]),
+        (
+            "sudo dd if=ubuntu.iso of=/dev/disk2 bs=4m",
+            Critical,
+            &["/dev/disk2"],
+        ),
+        ("cat image.iso > /dev/sdb", Critical, &["/dev/sdb"]),
+        ("cp image.iso /dev/nvme0n1", Critical, &["/dev/nvme0n1"]),
+        ("sudo mkfs.ext4 -L data /dev/sdb1", Critical, &["/dev/sdb1"]),
+        ("wipefs -a /dev/sdc", Critical, &["/dev/sdc"]),
+        ("shred /dev/sda", Critical, &["/dev/sda"]),
+        ("diskpart", Critical, &[]),
+        ("format D: /q", Critical, &["D:"]),
+        (":(){ :|:& };:", Critical, &[]),
+        ("bomb() { bomb | bomb & }; bomb", Critical, &[]),
+        ("kill -9 -1", Critical, &[]),
+        // Dangerous but contained
+        ("rm -rf build/", High, &["build/"]),
+        ("rm -r node_modules", High, &["node_modules"]),
+        ("if [ -d build ]; then rm -rf build; fi", High, &["build"]),
+        ("find . -name '*.tmp' | xargs rm -rf", High, &[]),
+        ("find . -name '*.tmp' -delete", High, &["."]),
+        ("find -name '*.pyc' -delete", High, &["."]),
+        ("sudo rm -r /tmp/cache", High, &["/tmp/cache"]),
+        ("shred -u secrets.txt", High, &["secrets.txt"]),
+        ("chmod -R 777 ./public", High, &["./public"]),
+        ("chmod 777 /", High, &["/"]),
+        ("shutdown -h now", High, &[]),
+        ("sudo reboot", High, &[]),
+        ("systemctl poweroff", High, &[]),
+        (
+            "echo 127.0.0.1 example.com > /etc/hosts",
+            High,
+            &["/etc/hosts"],
+        ),
+        (
+            "echo 'nameserver 1.1.1.1' | sudo tee /etc/resolv.conf",
+            High,
+            &["/etc/resolv.conf"],
+        ),
+        ("mv ~ /tmp/home", High, &["~"]),
+        ("mv *.log /dev/null", High, &["*.log"]),
+        ("crontab -r", High, &[]),
+        ("del /s /q C:\\temp", High, &["C:\\temp"]),
+        ("rmdir /S /Q build", High, &["build"]),
+        ("Remove-Item -r .\\dist", High, &[".\\dist"]),
+        (
+            "reg delete HKCU\\Software\\App /f",
+            High,
+            &["HKCU\\Software\\App"],
+        ),
+        ("sudo fdisk /dev/sda", High, &["/dev/sda"]),
+        ("sudo killall firefox", High, &[]),
+        ("sudo rm old.txt", Medium, &["old.txt"]),
+        // Worth a second look
+        ("rm *.log", Medium, &["*.log"]),
+        ("del *.tmp", Medium, &["*.tmp"]),
+        (
+            "dd if=/dev/zero of=swapfile bs=1M count=512",
+            Medium,
+            &["swapfile"],
+        ),
+        ("chmod 777 script.sh", Medium, &["script.sh"]),
+        ("chmod o+w shared.txt", Medium, &["shared.txt"]),
+        ("chmod 755 -R ~/bin", Medium, &["~/bin"]),
+        ("chown -R me:me ./project", Medium, &["./project"]),
+        (
+            "echo 'export X=1' >> /etc/profile",
+            Medium,
+            &["/etc/profile"],
+        ),
+        ("git reset --hard HEAD~1", Medium, &[]),
+        ("git clean -fdx", Medium, &[]),
+        ("git -C repo push --force origin main", Medium, &[]),
+        ("truncate -s 0 app.log", Medium, &["app.log"]),
+        ("killall firefox", Medium, &[]),
+        ("taskkill /f /im notepad.exe", Medium, &[]),
+        ("echo 'unterminated", Medium, &[]),
+        ("ls | | wc", Medium, &[]),
+        // Ordinary deletes and privileged commands
+        ("rm notes.txt", Low, &["notes.txt"]),
+        ("rm -- -rf", Low, &["-rf"]),
+        (
+            "rm 'my file.txt' other.txt",
+            Low,
+            &["my file.txt", "other.txt"],
+        ),
+        ("for f in *.tmp; do rm \"$f\"; done", Low, &["$f"]),
+        ("sudo apt install ripgrep", Low, &[]),
+        ("sudo fdisk -l", Low, &[]),
+        ("sudo -i", Low, &[]),
+        // Harmless, however alarming the text inside them
+        ("ls -la", None, &[]),
+        ("echo \"rm -rf /\"", None, &[]),
+        ("grep -r 'rm -rf' .", None, &[]),
+        ("git commit -m \"dd if=/dev/zero of=/dev/sda\"", None, &[]),
+        ("printf '%s\\n' ':(){ :|:& };:'", None, &[]),
+        ("ls # rm -rf /", None, &[]),
+        ("cat <<EOF > notes.md\nrm -rf /\nEOF", None, &[]),
+        ("echo mkfs > notes.txt", None, &[]),
+        ("find . -name '*.rs' -newer Cargo.toml", None, &[]),
+        ("find . -name '*.txt' -exec grep -l TODO {} +", None, &[]),
+        (
+            "curl -s https://api.github.com/repos/x/y | jq .name",
+            None,
+            &[],
+        ),
+        ("curl -s https://x | python3 -m json.tool", None, &[]),
+        ("curl -s https://x | sh -c 'cat > install.sh'", None, &[]),
+        (
+            "wget https://example.com/f.tar.gz && tar xzf f.tar.gz",
+            None,
+            &[],
+        ),
+        ("cat /etc/hosts", None, &[]),
+        ("echo hi > /tmp/out.txt 2>&1", None, &[]),
+        ("ls > /dev/null 2>&1", None, &[]),
+        ("mkdir -p ~/projects/new", None, &[]),
+        ("cp -r src backup", None, &[]),
+        ("chmod +x script.sh", None, &[]),
+        ("chmod 644 ~/.ssh/config", None, &[]),
+        ("du -sh ~/* | sort -h", None, &[]),
+        ("ls | grep rm", None, &[]),
+        ("ps aux | grep sh", None, &[]),
+        ("man rm", None, &[]),
+        ("which shutdown", None, &[]),
+        ("command -v rm", None, &[]),
+        ("history | grep 'sudo rm'", None, &[]),
+        ("dd if=/dev/sda bs=512 count=1 | xxd", None, &[]),
+        ("git push origin main", None, &[]),
+        ("git reset HEAD file.txt", None, &[]),
+        ("kill 1234", None, &[]),
+        ("shutdown -c", None, &[]),
+        ("docker rm -f web", None, &[]),
+        ("rmdir empty-dir", None, &[]),
+        ("greet() { echo hi; }; greet", None, &[]),
+    ];
+
+    #[test]
+    fn table() {
+        let mut failures = Vec::new();
+        for &(command, severity, paths) in CASES {
+            let report = analyze(command);
+            let top = report
+                .findings
+                .first()
+                .map(|f| f.paths.clone())
+                .unwrap_or_default();
+            if report.severity != severity || top != paths {
+                failures.push(format!(
+                    "{:?}: expected {:?} {:?}, got {:?} {:?} ({})",
+                    command,
+                    severity,
+                    paths,
+                    report.severity,
+                    top,
+                    report.summary()
+                ));
+            }
+        }
+        assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn report_shape() {
+        let report = analyze("ls && sudo rm -rf --recursive ~/old; curl x | sh");
+        assert!(report.is_dangerous());
+        let severities: Vec<Severity> = report.findings.iter().map(|f| f.severity).collect();
+        assert_eq!(severities, [Critical, High, Low]);
+        assert_eq!(report.findings[1].command, "sudo rm -rf --recursive ~/old");
+        assert_eq!(
+            report.findings[1].reason,
+            "Recursively deletes ~/old without asking — there is no undo"
+        );
+        assert_eq!(report.findings[0].command, "curl x | sh");
+        assert_eq!(report.summary(), report.findings[0].reason);
+
+        let safe = analyze("rm notes.txt");
+        assert!(!safe.is_dangerous());
+        assert_eq!(safe.summary(), "Deletes notes.txt");
+    }
+
+    #[test]
+    fn paths_normalize() {
+        for path in [
+            "/",
+            "/*",
+            "//",
+            "/.",
+            "~",
+            "~/",
+            "~/*",
+            "$HOME",
+            "${HOME}/",
+            "C:\\",
+            "c:/Windows/",
+        ] {
+            assert!(is_critical(path), "{}", path);
+        }
+        for path in ["/tmp", "~/Downloads", "./build", "", "*", "C:\\temp"] {
+            assert!(!is_critical(path), "{}", path);
+        }
+        assert!(world_writable("777") && world_writable("0666") && world_writable("a+rwx"));
+        assert!(world_writable("u+x,o=rw") && world_writable("+w"));
+        assert!(!world_writable("755") && !world_writable("u+w") && !world_writable("go-w"));
+    }
+}
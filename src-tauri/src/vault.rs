@@ -0,0 +1,661 @@
+/// Encrypted storage for provider API keys.
+///
+/// Keys live in `vault.json`, sealed with XChaCha20-Poly1305 under a key stretched
+/// with Argon2id from one of three secrets: a random secret held by the OS keyring
+/// (Secret Service on Linux, Credential Manager on Windows, Keychain on macOS), a
+/// keyfile next to the vault, or a passphrase typed to unlock it each session.
+/// `settings.json` never holds keys, and the webview only ever sees masked values.
+use crate::settings::{Settings, KEY_PROVIDERS};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// Bump whenever the on-disk layout changes.
+const VAULT_VERSION: u32 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+
+/// Argon2id memory cost in KiB (OWASP's 19 MiB baseline; tiny under test).
+const KDF_MEMORY_KIB: u32 = if cfg!(test) { 64 } else { 19 * 1024 };
+const KDF_ITERATIONS: u32 = 2;
+
+/// Shortest passphrase accepted when switching to passphrase protection.
+const MIN_PASSPHRASE_CHARS: usize = 8;
+
+/// Keyring entry holding the random master secret.
+const KEYRING_SERVICE: &str = "trace";
+const KEYRING_USER: &str = "vault-key";
+
+/// Where the vault's master secret comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protection {
+    Keyring,
+    Keyfile,
+    Passphrase,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct KdfParams {
+    memory_kib: u32,
+    iterations: u32,
+}
+
+/// `vault.json`: everything needed to re-derive the key, plus the sealed keys.
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    protection: Protection,
+    kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// What the settings panel shows about the vault.
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultStatus {
+    pub protection: Protection,
+    /// Keys exist on disk but could not be decrypted yet
+    pub locked: bool,
+    /// Whether the OS keyring answered when the vault was opened
+    pub keyring_available: bool,
+}
+
+pub struct Vault {
+    path: PathBuf,
+    keyfile: PathBuf,
+    protection: Protection,
+    kdf: KdfParams,
+    salt: [u8; SALT_LEN],
+    /// Derived key; `None` until unlocked or first saved
+    key: Option<Zeroizing<[u8; KEY_LEN]>>,
+    /// Ciphertext read from disk and not yet opened. `Some` means locked.
+    sealed: Option<VaultFile>,
+    secrets: BTreeMap<String, Zeroizing<String>>,
+    /// Keys were set since the last successful save
+    unsaved: bool,
+    keyring_available: bool,
+}
+
+fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("trace")
+}
+
+/// Show only enough of a key to tell keys apart.
+pub fn mask(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() < 12 {
+        return "••••••••".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("••••••••{}", tail)
+}
+
+fn random<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn decode(field: &str, value: &str) -> Result<Vec<u8>, String> {
+    BASE64
+        .decode(value)
+        .map_err(|e| format!("Corrupt vault {}: {}", field, e))
+}
+
+fn derive_key(
+    secret: &[u8],
+    salt: &[u8],
+    kdf: KdfParams,
+) -> Result<Zeroizing<[u8; KEY_LEN]>, String> {
+    let params = Params::new(kdf.memory_kib, kdf.iterations, 1, Some(KEY_LEN))
+        .map_err(|e| format!("Invalid vault KDF parameters: {}", e))?;
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(secret, salt, key.as_mut())
+        .map_err(|e| format!("Failed to derive vault key: {}", e))?;
+    Ok(key)
+}
+
+/// Write a file only the current user can read, replacing it atomically.
+fn write_private(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config dir: {}", e))?;
+    }
+    let tmp = path.with_extension("tmp");
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&tmp)
+        .map_err(|e| format!("Failed to create {}: {}", tmp.display(), e))?;
+    file.write_all(bytes)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+// ─── OS keyring ──────────────────────────────────────────
+
+/// Run a keyring call on its own thread: the Secret Service client drives its
+/// own executor and must not block inside the app's async runtime.
+fn with_keyring<T: Send + 'static>(
+    f: impl FnOnce(keyring::Entry) -> keyring::Result<T> + Send + 'static,
+) -> Result<T, keyring::Error> {
+    std::thread::spawn(move || f(keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?))
+        .join()
+        .unwrap_or_else(|_| {
+            Err(keyring::Error::PlatformFailure(
+                "keyring thread panicked".into(),
+            ))
+        })
+}
+
+fn keyring_available() -> bool {
+    matches!(
+        with_keyring(|entry| entry.get_password()),
+        Ok(_) | Err(keyring::Error::NoEntry)
+    )
+}
+
+fn keyring_secret(create: bool) -> Result<Zeroizing<Vec<u8>>, String> {
+    let stored = with_keyring(|entry| entry.get_password());
+    let encoded = match stored {
+        Ok(encoded) => encoded,
+        Err(keyring::Error::NoEntry) if create => {
+            let encoded = BASE64.encode(random::<KEY_LEN>());
+            let value = encoded.clone();
+            with_keyring(move |entry| entry.set_password(&value))
+                .map_err(|e| format!("Failed to store vault secret in the OS keyring: {}", e))?;
+            encoded
+        }
+        Err(e) => return Err(format!("OS keyring unavailable: {}", e)),
+    };
+    Ok(Zeroizing::new(decode("keyring secret", &encoded)?))
+}
+
+fn forget_keyring_secret() {
+    match with_keyring(|entry| entry.delete_credential()) {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => eprintln!("[trace][vault] Could not remove keyring secret: {}", e),
+    }
+}
+
+// ─── Vault ───────────────────────────────────────────────
+
+impl Vault {
+    /// Open the vault in the config dir, unlocking it straight away unless it
+    /// is passphrase-protected.
+    pub fn open() -> Self {
+        let dir = config_dir();
+        Self::open_at(
+            dir.join("vault.json"),
+            dir.join("vault.key"),
+            keyring_available(),
+        )
+    }
+
+    fn open_at(path: PathBuf, keyfile: PathBuf, keyring_available: bool) -> Self {
+        let mut vault = Self {
+            path,
+            keyfile,
+            protection: if keyring_available {
+                Protection::Keyring
+            } else {
+                Protection::Keyfile
+            },
+            kdf: KdfParams {
+                memory_kib: KDF_MEMORY_KIB,
+                iterations: KDF_ITERATIONS,
+            },
+            salt: random(),
+            key: None,
+            sealed: None,
+            secrets: BTreeMap::new(),
+            unsaved: false,
+            keyring_available,
+        };
+
+        let file = match std::fs::read_to_string(&vault.path) {
+            Ok(content) => serde_json::from_str::<VaultFile>(&content)
+                .map_err(|e| format!("unreadable vault: {}", e))
+                .and_then(|file| match file.version {
+                    VAULT_VERSION => Ok(file),
+                    v => Err(format!("unsupported vault version {}", v)),
+                }),
+            Err(_) => return vault,
+        };
+        let salt = file.as_ref().map_err(Clone::clone).and_then(|file| {
+            decode("salt", &file.salt)?
+                .try_into()
+                .map_err(|_| "bad vault salt length".to_string())
+        });
+        let (file, salt) = match (file, salt) {
+            (Ok(file), Ok(salt)) => (file, salt),
+            (Err(e), _) | (_, Err(e)) => {
+                // Keep the unreadable file rather than overwrite it on the next save
+                let aside = vault.path.with_extension("json.corrupt");
+                eprintln!(
+                    "[trace][vault] Starting a new vault ({}); old file kept at {}",
+                    e,
+                    aside.display()
+                );
+                let _ = std::fs::rename(&vault.path, aside);
+                return vault;
+            }
+        };
+
+        vault.protection = file.protection;
+        vault.kdf = file.kdf;
+        vault.salt = salt;
+        vault.sealed = Some(file);
+        if vault.protection != Protection::Passphrase {
+            if let Err(e) = vault.unlock("") {
+                eprintln!("[trace][vault] Vault stays locked: {}", e);
+            }
+        }
+        vault
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.sealed.is_some()
+    }
+
+    pub fn status(&self) -> VaultStatus {
+        VaultStatus {
+            protection: self.protection,
+            locked: self.is_locked(),
+            keyring_available: self.keyring_available,
+        }
+    }
+
+    /// The secret for the current protection. Only keyring and keyfile secrets
+    /// are created on demand; passphrases come from the user.
+    fn master_secret(&self, passphrase: &str, create: bool) -> Result<Zeroizing<Vec<u8>>, String> {
+        match self.protection {
+            Protection::Passphrase if passphrase.is_empty() => {
+                Err("Enter the vault passphrase".to_string())
+            }
+            Protection::Passphrase => Ok(Zeroizing::new(passphrase.as_bytes().to_vec())),
+            Protection::Keyring => keyring_secret(create),
+            Protection::Keyfile => match std::fs::read(&self.keyfile) {
+                Ok(bytes) if !bytes.is_empty() => Ok(Zeroizing::new(bytes)),
+                Ok(_) => Err(format!("Keyfile {} is empty", self.keyfile.display())),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && create => {
+                    let bytes = random::<KEY_LEN>();
+                    write_private(&self.keyfile, &bytes)?;
+                    Ok(Zeroizing::new(bytes.to_vec()))
+                }
+                Err(e) => Err(format!(
+                    "Cannot read keyfile {}: {}",
+                    self.keyfile.display(),
+                    e
+                )),
+            },
+        }
+    }
+
+    /// Decrypt the keys read from disk. `passphrase` is ignored unless the vault
+    /// is passphrase-protected.
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), String> {
+        let Some(file) = &self.sealed else {
+            return Ok(());
+        };
+        let secret = self.master_secret(passphrase, false)?;
+        let key = derive_key(&secret, &self.salt, self.kdf)?;
+        let nonce = decode("nonce", &file.nonce)?;
+        if nonce.len() != NONCE_LEN {
+            return Err("Corrupt vault nonce".to_string());
+        }
+        let plaintext = XChaCha20Poly1305::new(key.as_ref().into())
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                decode("ciphertext", &file.ciphertext)?.as_slice(),
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| match self.protection {
+                Protection::Passphrase => "Wrong passphrase".to_string(),
+                _ => "Vault secret does not match the vault".to_string(),
+            })?;
+        let secrets: BTreeMap<String, String> = serde_json::from_slice(&plaintext)
+            .map_err(|e| format!("Corrupt vault contents: {}", e))?;
+
+        self.secrets = secrets
+            .into_iter()
+            .map(|(k, v)| (k, Zeroizing::new(v)))
+            .collect();
+        self.key = Some(key);
+        self.sealed = None;
+        Ok(())
+    }
+
+    pub fn get(&self, provider: &str) -> Option<&str> {
+        self.secrets.get(provider).map(|s| s.as_str())
+    }
+
+    /// Store a key in memory (an empty one removes it); `save` persists it.
+    pub fn set(&mut self, provider: &str, key: &str) -> Result<(), String> {
+        if self.is_locked() {
+            return Err("Unlock the key vault before changing API keys".to_string());
+        }
+        if key.is_empty() {
+            self.secrets.remove(provider);
+        } else {
+            self.secrets
+                .insert(provider.to_string(), Zeroizing::new(key.to_string()));
+        }
+        self.unsaved = true;
+        Ok(())
+    }
+
+    /// Whether keys exist only in memory because no save has succeeded since.
+    pub fn has_unsaved_keys(&self) -> bool {
+        self.unsaved
+    }
+
+    /// Masked form of every stored key, by provider.
+    pub fn masked(&self) -> BTreeMap<String, String> {
+        self.secrets
+            .iter()
+            .map(|(provider, key)| (provider.clone(), mask(key)))
+            .collect()
+    }
+
+    /// Encrypt the keys to disk, creating the master secret on first save.
+    pub fn save(&mut self) -> Result<(), String> {
+        if self.is_locked() {
+            return Err("The key vault is locked".to_string());
+        }
+        let key = match self.key.take() {
+            Some(key) => key,
+            None => derive_key(&self.master_secret("", true)?, &self.salt, self.kdf)?,
+        };
+        let key = &*self.key.insert(key);
+
+        let plain: BTreeMap<&str, &str> = self
+            .secrets
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        let plaintext = Zeroizing::new(
+            serde_json::to_vec(&plain).map_err(|e| format!("Failed to serialize vault: {}", e))?,
+        );
+        let nonce = random::<NONCE_LEN>();
+        let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+            .encrypt(XNonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| "Failed to encrypt vault".to_string())?;
+
+        let file = VaultFile {
+            version: VAULT_VERSION,
+            protection: self.protection,
+            kdf: self.kdf,
+            salt: BASE64.encode(self.salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        let json = serde_json::to_vec_pretty(&file)
+            .map_err(|e| format!("Failed to serialize vault: {}", e))?;
+        write_private(&self.path, &json)?;
+        self.unsaved = false;
+        Ok(())
+    }
+
+    /// Re-encrypt under a new master secret. The old keyring entry or keyfile
+    /// is removed once the vault no longer needs it.
+    pub fn set_protection(
+        &mut self,
+        protection: Protection,
+        passphrase: Option<&str>,
+    ) -> Result<(), String> {
+        if self.is_locked() {
+            return Err("Unlock the key vault first".to_string());
+        }
+        let passphrase = passphrase.unwrap_or("");
+        if protection == Protection::Passphrase && passphrase.chars().count() < MIN_PASSPHRASE_CHARS
+        {
+            return Err(format!(
+                "Passphrase must be at least {} characters",
+                MIN_PASSPHRASE_CHARS
+            ));
+        }
+        if protection == Protection::Keyring && !self.keyring_available {
+            return Err("No OS keyring is available on this system".to_string());
+        }
+
+        let previous = (self.protection, self.salt, self.key.take());
+        self.protection = protection;
+        self.salt = random();
+        let result = self
+            .master_secret(passphrase, true)
+            .and_then(|secret| derive_key(&secret, &self.salt, self.kdf))
+            .and_then(|key| {
+                self.key = Some(key);
+                self.save()
+            });
+        if let Err(e) = result {
+            (self.protection, self.salt, self.key) = previous;
+            return Err(e);
+        }
+
+        match previous.0 {
+            old if old == protection => {}
+            Protection::Keyring => forget_keyring_secret(),
+            Protection::Keyfile => {
+                let _ = std::fs::remove_file(&self.keyfile);
+            }
+            Protection::Passphrase => {}
+        }
+        Ok(())
+    }
+
+    /// Copy the stored keys into `settings`, which is what the LLM client reads.
+    pub fn apply_to(&self, settings: &mut Settings) {
+        settings.keys_locked = self.is_locked();
+        if self.is_locked() {
+            return;
+        }
+        for provider in KEY_PROVIDERS {
+            if let Some(slot) = settings.key_slot(provider) {
+                *slot = self.get(provider).map(str::to_string);
+            }
+        }
+    }
+}
+
+/// Move plaintext keys left in `settings.json` by older versions into the vault,
+/// then rewrite `settings.json` without them. Returns how many keys moved.
+/// If the vault can't be saved, `settings.json` keeps its keys and the vault
+/// reports [`Vault::has_unsaved_keys`] so the next settings save retries.
+pub fn migrate_plaintext_keys(settings: &mut Settings, vault: &mut Vault) -> Result<usize, String> {
+    let plaintext: Vec<(&str, String)> = KEY_PROVIDERS
+        .iter()
+        .filter_map(|&provider| {
+            let key = settings.key_slot(provider)?.clone()?;
+            (!key.is_empty() && vault.get(provider) != Some(key.as_str()))
+                .then_some((provider, key))
+        })
+        .collect();
+    if plaintext.is_empty() {
+        return Ok(0);
+    }
+    for (provider, key) in &plaintext {
+        vault.set(provider, key)?;
+    }
+    vault.save()?;
+    settings.save()?;
+    Ok(plaintext.len())
+}
+
+// ─── Tests ───────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("trace-vault-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn open(dir: &Path) -> Vault {
+        Vault::open_at(dir.join("vault.json"), dir.join("vault.key"), false)
+    }
+
+    #[test]
+    fn keyfile_vault_round_trips_without_plaintext() {
+        let dir = temp_dir("keyfile");
+        let mut vault = open(&dir);
+        assert_eq!(vault.status().protection, Protection::Keyfile);
+        vault.set("openai", "sk-test-1234567890abcd").unwrap();
+        vault.set("google", "AIza-secret").unwrap();
+        vault.save().unwrap();
+
+        let on_disk = std::fs::read_to_string(dir.join("vault.json")).unwrap();
+        assert!(!on_disk.contains("sk-test") && !on_disk.contains("AIza"));
+        assert!(dir.join("vault.key").exists());
+
+        let mut reopened = open(&dir);
+        assert!(!reopened.is_locked());
+        assert_eq!(reopened.get("openai"), Some("sk-test-1234567890abcd"));
+        reopened.set("google", "").unwrap();
+        reopened.save().unwrap();
+        assert_eq!(open(&dir).get("google"), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn passphrase_vault_needs_unlocking() {
+        let dir = temp_dir("passphrase");
+        let mut vault = open(&dir);
+        vault.set("anthropic", "sk-ant-abcdefghijkl").unwrap();
+        assert!(vault
+            .set_protection(Protection::Passphrase, Some("short"))
+            .is_err());
+        vault
+            .set_protection(Protection::Passphrase, Some("correct horse"))
+            .unwrap();
+        assert!(!dir.join("vault.key").exists(), "old keyfile removed");
+
+        let mut reopened = open(&dir);
+        assert!(reopened.is_locked());
+        assert_eq!(reopened.get("anthropic"), None);
+        assert!(reopened.set("openai", "x").is_err());
+        assert_eq!(
+            reopened.unlock("wrong horse").unwrap_err(),
+            "Wrong passphrase"
+        );
+        reopened.unlock("correct horse").unwrap();
+        assert_eq!(reopened.get("anthropic"), Some("sk-ant-abcdefghijkl"));
+
+        reopened.set_protection(Protection::Keyfile, None).unwrap();
+        assert!(!open(&dir).is_locked());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn tampered_vault_stays_locked() {
+        let dir = temp_dir("tampered");
+        let mut vault = open(&dir);
+        vault.set("openai", "sk-original-key-000").unwrap();
+        vault.save().unwrap();
+        std::fs::write(dir.join("vault.key"), b"another keyfile").unwrap();
+
+        let mut reopened = open(&dir);
+        assert!(reopened.is_locked());
+        assert!(
+            reopened.save().is_err(),
+            "must not overwrite a vault it cannot read"
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unreadable_vault_is_set_aside() {
+        let dir = temp_dir("corrupt");
+        std::fs::write(dir.join("vault.json"), "{not json").unwrap();
+        let vault = open(&dir);
+        assert!(!vault.is_locked());
+        assert!(dir.join("vault.json.corrupt").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn keyring_requires_a_keyring() {
+        let dir = temp_dir("keyring");
+        let mut vault = open(&dir);
+        assert!(vault.set_protection(Protection::Keyring, None).is_err());
+        assert_eq!(vault.status().protection, Protection::Keyfile);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn applies_keys_to_settings() {
+        let dir = temp_dir("apply");
+        let mut vault = open(&dir);
+        vault.set("openrouter", "sk-or-abcdefghijkl").unwrap();
+
+        let mut settings = Settings {
+            openai_key: Some("stale".to_string()),
+            ..Settings::default()
+        };
+        vault.apply_to(&mut settings);
+        assert_eq!(
+            settings.openrouter_key.as_deref(),
+            Some("sk-or-abcdefghijkl")
+        );
+        assert_eq!(settings.openai_key, None);
+        assert!(!settings.keys_locked);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn failed_migration_keeps_keys_unsaved() {
+        let dir = temp_dir("migrate");
+        let mut vault = open(&dir);
+        // A file where the vault's directory should be makes every save fail
+        std::fs::write(dir.join("blocked"), b"").unwrap();
+        vault.path = dir.join("blocked").join("vault.json");
+
+        let mut settings = Settings {
+            openai_key: Some("sk-plaintext-123456".to_string()),
+            ..Settings::default()
+        };
+        assert!(migrate_plaintext_keys(&mut settings, &mut vault).is_err());
+        assert!(vault.has_unsaved_keys());
+        assert_eq!(settings.openai_key.as_deref(), Some("sk-plaintext-123456"));
+
+        // The retry from the next settings save succeeds once the vault is writable
+        vault.path = dir.join("vault.json");
+        vault.save().unwrap();
+        assert!(!vault.has_unsaved_keys());
+        assert_eq!(open(&dir).get("openai"), Some("sk-plaintext-123456"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn masks_keys() {
+        assert_eq!(mask("sk-1234567890abcd"), "••••••••abcd");
+        assert_eq!(mask("short"), "••••••••");
+    }
+}
@@ -1,11 +1,27 @@
-use crate::indexer::{EntryKind, FileEntry, FileIndex};
+use crate::content_index::{self, SharedContentIndex};
+use crate::ignore_rules::IgnoreRules;
+use crate::indexer::{self, EntryKind, FileEntry, FileIndex};
+use notify::event::{ModifyKind, RenameMode};
 use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::time::SystemTime;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long to keep collecting events after the first one before applying a batch.
+const DEBOUNCE_WINDOW: Duration = Duration::from_millis(250);
 
 /// Start a filesystem watcher on the given roots.
-/// Updates the shared index in real-time as files are created, modified, or removed.
-pub async fn start_watcher(index: FileIndex, roots: Vec<PathBuf>) {
+/// Updates the shared index in real-time as files are created, modified, or removed,
+/// dropping anything `rules` excludes just like the initial scan does. When a content
+/// index is supplied, changed documents are re-extracted into it as well.
+pub async fn start_watcher(
+    index: FileIndex,
+    roots: Vec<PathBuf>,
+    rules: Arc<IgnoreRules>,
+    content: Option<SharedContentIndex>,
+) {
     let index_clone = index.clone();
 
     tokio::task::spawn_blocking(move || {
@@ -27,83 +43,187 @@ pub async fn start_watcher(index: FileIndex, roots: Vec<PathBuf>) {
 
         println!("[trace][watcher] Watching {} roots for changes", roots.len());
 
-        // Block this thread and process events
-        for result in rx {
-            match result {
-                Ok(event) => {
-                    let idx = index_clone.clone();
-                    handle_event(idx, event);
+        // Block this thread, collecting events into batches
+        while let Some(batch) = next_batch(&rx) {
+            apply_batch(&index_clone, &rules, content.as_ref(), batch);
+        }
+    });
+}
+
+/// Events collected over one debounce window, coalesced per path.
+#[derive(Default)]
+struct Batch {
+    /// Renames with both ends known, in the order they happened.
+    moves: Vec<(PathBuf, PathBuf)>,
+    /// Paths whose current state must be re-read from disk. The flag marks paths
+    /// that newly appeared, so a directory's contents get scanned as well.
+    dirty: HashMap<PathBuf, bool>,
+}
+
+impl Batch {
+    fn is_empty(&self) -> bool {
+        self.moves.is_empty() && self.dirty.is_empty()
+    }
+
+    fn mark(&mut self, path: PathBuf, appeared: bool) {
+        *self.dirty.entry(path).or_default() |= appeared;
+    }
+
+    fn add(&mut self, event: Event) {
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                let mut paths = event.paths.into_iter();
+                let (from, to) = (paths.next().unwrap(), paths.next().unwrap());
+                // The moved entries are re-keyed in place, so the target only
+                // needs a refresh, not a rescan
+                self.mark(from.clone(), false);
+                self.dirty.insert(to.clone(), false);
+                self.moves.push((from, to));
+            }
+            EventKind::Create(_)
+            | EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Any)) => {
+                for path in event.paths {
+                    self.mark(path, true);
                 }
-                Err(e) => {
-                    eprintln!("[trace][watcher] Error: {}", e);
+            }
+            EventKind::Modify(_) | EventKind::Remove(_) => {
+                for path in event.paths {
+                    self.mark(path, false);
                 }
             }
+            _ => {}
         }
-    });
+    }
+}
+
+/// Block until an event arrives, then keep collecting for `DEBOUNCE_WINDOW`.
+/// Returns `None` once the watcher has shut down.
+fn next_batch(rx: &Receiver<notify::Result<Event>>) -> Option<Batch> {
+    loop {
+        let mut batch = Batch::default();
+        let mut result = rx.recv().ok()?;
+        let deadline = Instant::now() + DEBOUNCE_WINDOW;
+
+        loop {
+            match result {
+                Ok(event) => batch.add(event),
+                Err(e) => eprintln!("[trace][watcher] Error: {}", e),
+            }
+            // Timeout ends the window; disconnect flushes what we have
+            result = match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(r) => r,
+                Err(_) => break,
+            };
+        }
+
+        if !batch.is_empty() {
+            return Some(batch);
+        }
+    }
+}
+
+/// The resolved state of a dirty path.
+enum Update {
+    Upsert(FileEntry, Vec<FileEntry>),
+    Remove(String),
 }
 
-fn handle_event(index: FileIndex, event: Event) {
-    // We use a blocking approach to update the index from the watcher thread
-    let rt = tokio::runtime::Handle::current();
-
-    match event.kind {
-        EventKind::Create(_) | EventKind::Modify(_) => {
-            for path in event.paths {
-                let metadata = match std::fs::metadata(&path) {
-                    Ok(m) => m,
-                    Err(_) => continue,
-                };
-
-                let modified = metadata
-                    .modified()
-                    .unwrap_or(SystemTime::UNIX_EPOCH)
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs();
-
-                let kind = if metadata.is_dir() {
-                    EntryKind::Directory
-                } else {
-                    EntryKind::File
-                };
-
-                let name = path
-                    .file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_default();
-
-                let path_str = path.to_string_lossy().to_string();
-
-                let entry = FileEntry {
-                    name,
-                    path: path_str.clone(),
-                    kind,
-                    size: metadata.len(),
-                    modified,
-                    icon_path: None,
-                    keywords: None,
-                    generic_name: None,
-                };
-
-                let index = index.clone();
-                rt.spawn(async move {
-                    let mut idx = index.write().await;
-                    // Remove old entry if it exists, then insert new
-                    idx.retain(|e| e.path != path_str);
-                    idx.push(entry);
-                });
+/// Read the final state of every dirty path from disk, then apply the whole batch
+/// under a single write lock: renames first (in order), then the refreshed paths.
+/// Dirty paths carry their state as of the end of the window, so a Remove that
+/// raced a Create for the same path resolves to whatever is on disk now.
+fn apply_batch(
+    index: &FileIndex,
+    rules: &IgnoreRules,
+    content: Option<&SharedContentIndex>,
+    mut batch: Batch,
+) {
+    // Edited ignore files must be re-read before anything in this batch is judged
+    for path in batch.dirty.keys() {
+        if IgnoreRules::is_ignore_file(path) {
+            if let Some(dir) = path.parent() {
+                rules.invalidate(dir);
+            }
+        }
+    }
+
+    // A rename whose source was never indexed (e.g. it was ignored) is a new arrival
+    {
+        let idx = index.blocking_read();
+        for (from, to) in &batch.moves {
+            if idx.id_of(&from.to_string_lossy()).is_none() {
+                batch.dirty.insert(to.clone(), true);
+            }
+        }
+    }
+
+    // Filesystem access happens before taking the write lock
+    let updates: Vec<Update> = batch
+        .dirty
+        .into_iter()
+        .map(|(path, appeared)| {
+            let remove = Update::Remove(path.to_string_lossy().to_string());
+            let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+                return remove;
+            };
+            if rules.is_ignored(&path, metadata.is_dir()) {
+                return remove;
+            }
+            match indexer::entry_from_metadata(&path, &metadata) {
+                Some(entry) => {
+                    let children = if appeared && entry.kind == EntryKind::Directory {
+                        indexer::scan_tree(&path, rules)
+                    } else {
+                        Vec::new()
+                    };
+                    Update::Upsert(entry, children)
+                }
+                None => remove,
+            }
+        })
+        .collect();
+
+    let mut moved = Vec::new();
+    let mut removed = Vec::new();
+    let mut changed_files = Vec::new();
+    {
+        let mut idx = index.blocking_write();
+
+        for (from, to) in &batch.moves {
+            moved.extend(idx.move_tree(&from.to_string_lossy(), &to.to_string_lossy()));
+        }
+
+        for update in updates {
+            match update {
+                Update::Upsert(entry, children) => {
+                    for e in std::iter::once(&entry).chain(&children) {
+                        if e.kind == EntryKind::File {
+                            changed_files.push((e.path.clone(), e.modified, e.size));
+                        }
+                    }
+                    idx.upsert(entry);
+                    idx.extend(children);
+                }
+                Update::Remove(path) => {
+                    removed.extend(idx.remove_tree(&path));
+                }
             }
         }
-        EventKind::Remove(_) => {
-            for path in event.paths {
-                let path_str = path.to_string_lossy().to_string();
-                let index = index.clone();
-                rt.spawn(async move {
-                    let mut idx = index.write().await;
-                    idx.retain(|e| e.path != path_str);
-                });
+    }
+
+    // Content extraction can be slow (PDFs), so it runs after the file index is released
+    if let Some(content) = content {
+        {
+            let mut ci = content.blocking_write();
+            for (from, to) in &moved {
+                ci.rename(from, to);
+            }
+            for entry in &removed {
+                ci.remove(&entry.path);
             }
         }
-        _ => {}
+        for (path, modified, size) in changed_files {
+            content_index::update_file(content, &path, modified, size);
+        }
     }
 }
@@ -4,6 +4,7 @@
 // ─────────────────────────────────────────────
 
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { getCurrentWindow, LogicalSize } from "@tauri-apps/api/window";
 
 // ─── Types ───────────────────────────────────
@@ -23,6 +24,20 @@ interface CalcResult {
   result: number;
   display: string;
   has_variable: boolean;
+  unit: string | null;
+  error: string | null;
+  error_position: number | null;
+  bases: Bases | null;
+  fraction: string | null;
+  precise: string | null;
+}
+
+interface Bases {
+  hex: string;
+  dec: string;
+  oct: string;
+  bin: string;
+  bits: number | null;
 }
 
 interface GraphPoint {
@@ -30,28 +45,134 @@ interface GraphPoint {
   y: number;
 }
 
+type RiskSeverity = "none" | "low" | "medium" | "high" | "critical";
+
+interface RiskFinding {
+  severity: RiskSeverity;
+  reason: string;
+  paths: string[];
+  command: string;
+}
+
+interface ShellCandidate {
+  command: string;
+  risk: { severity: RiskSeverity; findings: RiskFinding[] };
+  rejected: string | null;
+}
+
 interface ShellTranslation {
   command: string;
   is_dangerous: boolean;
   danger_reason: string;
+  risk: { severity: RiskSeverity; findings: RiskFinding[] };
+  history_id: number | null;
+  cached: boolean;
+  pinned: boolean;
+  candidates: ShellCandidate[];
+}
+
+interface HistoryEntry {
+  id: number;
+  input: string;
+  command: string;
+  created: number;
+  used: number;
+  runs: number;
+  exit_code: number | null;
+  pinned: boolean;
+}
+
+interface ExplainStep {
+  joined: string;
+  command: string;
+  summary: string;
+  flags: { flag: string; meaning: string }[];
+  operands: string[];
+  notes: string[];
+  depth: number;
+}
+
+interface PreviewAction {
+  command: string;
+  action: string;
+  paths: { path: string; is_dir: boolean; contents: number }[];
+  destination: string | null;
+  missing: string[];
+  total: number;
+  truncated: boolean;
+  notes: string[];
 }
 
 interface ShellOutput {
   stdout: string;
   stderr: string;
   exit_code: number;
+  duration_ms: number;
+  cwd: string;
+  timed_out: boolean;
+  killed: boolean;
+  truncated: boolean;
 }
 
+// API keys are never sent to the webview. On save: null keeps a key, "" removes it.
 interface Settings {
-  openai_key: string | null;
-  anthropic_key: string | null;
-  google_key: string | null;
-  huggingface_key: string | null;
-  openrouter_key: string | null;
+  openai_key?: string | null;
+  anthropic_key?: string | null;
+  google_key?: string | null;
+  huggingface_key?: string | null;
+  openrouter_key?: string | null;
+  custom_key?: string | null;
+  ollama_base_url: string;
+  custom_base_url: string;
   active_provider: string;
   active_model: string;
+  embedding_provider: string;
+  embedding_model: string;
   index_roots: string[];
+  index_filters: Record<string, { include: string[]; exclude: string[] }>;
   max_results: number;
+  content_index_enabled: boolean;
+  shell_timeout_secs: number;
+  shell_candidates: number;
+}
+
+type VaultProtection = "keyring" | "keyfile" | "passphrase";
+
+interface VaultStatus {
+  protection: VaultProtection;
+  locked: boolean;
+  keyring_available: boolean;
+}
+
+interface SettingsView extends Settings {
+  api_keys: Record<string, string>;
+  vault: VaultStatus;
+}
+
+interface ChatMessage {
+  role: "user" | "assistant";
+  content: string;
+}
+
+interface Citation {
+  label: number;
+  path: string;
+  first_line: number;
+  last_line: number;
+  pages: [number, number] | null;
+  cited: boolean;
+}
+
+interface ChatReply {
+  answer: string;
+  sources: Citation[];
+}
+
+interface ContentMatch {
+  name: string;
+  path: string;
+  score: number;
+  snippets: { line: number; text: string }[];
 }
 
 // ─── Provider → Model Map ────────────────────
@@ -85,6 +206,19 @@ const PROVIDER_MODELS: Record<string, { value: string; label: string }[]> = {
   ],
 };
 
+// Local providers list their models from their own endpoint instead
+const LOCAL_PROVIDERS = ["ollama", "custom"];
+
+// Settings input holding each provider's API key
+const KEY_INPUTS: Record<string, string> = {
+  openai: "setting-openai",
+  anthropic: "setting-anthropic",
+  google: "setting-google",
+  huggingface: "setting-huggingface",
+  openrouter: "setting-openrouter",
+  custom: "setting-custom-key",
+};
+
 // ─── State ───────────────────────────────────
 
 type AppMode = "search" | "shell" | "chat";
@@ -94,9 +228,24 @@ let results: SearchResult[] = [];
 let selectedIndex = 0;
 let searchTimeout: ReturnType<typeof setTimeout> | null = null;
 let currentCalcResult: CalcResult | null = null;
+let searchError: string | null = null;
+// Bumped per search; responses from superseded searches are dropped
+let searchSeq = 0;
+// Results marked with Shift+Tab; Tab then opens one chat over all of them
+const chatPicks = new Set<string>();
+
+interface ShellContext { username: string; hostname: string; shell: string; home: string; }
+let shellContext: ShellContext = { username: "user", hostname: "localhost", shell: "bash", home: "" };
+
+// Directory shell commands run in; null until the first command (the home folder)
+let terminalCwd: string | null = null;
 
-interface ShellContext { username: string; hostname: string; shell: string; }
-let shellContext: ShellContext = { username: "user", hostname: "localhost", shell: "bash" };
+// The pending command's request and its shell history entry
+let pendingInput = "";
+let pendingHistoryId: number | null = null;
+let pendingPinned = false;
+// Past requests matching what is typed after ">"
+let historyMatches: HistoryEntry[] = [];
 
 // Icon data-URI cache (icon_path → data:image/... string)
 const iconCache = new Map<string, string>();
@@ -110,7 +259,14 @@ const resultsList = document.getElementById("results-list") as HTMLElement;
 const shellPanel = document.getElementById("shell-panel") as HTMLElement;
 const shellCommand = document.getElementById("shell-command") as HTMLElement;
 const shellWarning = document.getElementById("shell-warning") as HTMLElement;
+const shellCandidates = document.getElementById("shell-candidates") as HTMLElement;
 const shellRun = document.getElementById("shell-run") as HTMLElement;
+const shellExplain = document.getElementById("shell-explain") as HTMLElement;
+const shellExplainPanel = document.getElementById("shell-explain-panel") as HTMLElement;
+const shellHeader = document.getElementById("shell-header") as HTMLElement;
+const shellPin = document.getElementById("shell-pin") as HTMLButtonElement;
+const shellRetranslate = document.getElementById("shell-retranslate") as HTMLElement;
+const shellExport = document.getElementById("shell-export") as HTMLElement;
 const shellCancel = document.getElementById("shell-cancel") as HTMLElement;
 const terminalTitle = document.getElementById("terminal-title") as HTMLElement;
 const terminalHistory = document.getElementById("terminal-history") as HTMLElement;
@@ -146,8 +302,9 @@ function renderResults() {
 
   const hasMath = currentCalcResult !== null;
   const hasResults = results.length > 0;
+  const hasError = searchError !== null;
 
-  if (!hasMath && !hasResults) {
+  if (!hasMath && !hasResults && !hasError) {
     resultsContainer.classList.remove("expanded");
     resizeWindow(BASE_HEIGHT);
     return;
@@ -159,7 +316,23 @@ function renderResults() {
     li.className = "result-item math-result" + (selectedIndex === -1 ? " selected" : "");
     li.dataset.index = "-1";
 
-    if (currentCalcResult.has_variable) {
+    if (currentCalcResult.error) {
+      // Malformed math: point at the offending character
+      const chars = Array.from(currentCalcResult.expression);
+      const at = currentCalcResult.error_position ?? chars.length;
+      const exprHtml =
+        escHtml(chars.slice(0, at).join("")) +
+        `<span class="math-error-at">${escHtml(chars[at] ?? " ")}</span>` +
+        escHtml(chars.slice(at + 1).join(""));
+      li.innerHTML = `
+        <div class="result-icon math-icon">=</div>
+        <div class="result-info">
+          <div class="result-name math-expr">${exprHtml}</div>
+          <div class="result-path">${escHtml(currentCalcResult.error)}</div>
+        </div>
+        <span class="result-kind">CALC</span>
+      `;
+    } else if (currentCalcResult.has_variable) {
       li.innerHTML = `
         <div class="result-icon math-icon">=</div>
         <div class="result-info">
@@ -168,18 +341,31 @@ function renderResults() {
         </div>
         <span class="result-kind">GRAPH</span>
       `;
-      li.addEventListener("click", () => showGraph(currentCalcResult!.expression));
+      li.addEventListener("click", () => {
+        commitCalculation(currentCalcResult!.expression);
+        showGraph(currentCalcResult!.expression);
+      });
     } else {
+      // Programmer results list every base, noting the two's-complement width;
+      // rounded results show their exact fraction and more digits
+      const b = currentCalcResult.bases;
+      const exact = [currentCalcResult.fraction, currentCalcResult.precise].filter(Boolean);
+      const detail = b
+        ? `<div class="result-path math-expr">${escHtml([b.hex, b.dec, b.oct, b.bin].join("  ·  "))}${b.bits ? `  (${b.bits}-bit)` : ""}</div>`
+        : exact.length
+          ? `<div class="result-path math-expr">${escHtml(exact.join("  ·  "))}</div>`
+          : `<div class="result-path">${escHtml(currentCalcResult.expression)}</div>`;
       li.innerHTML = `
         <div class="result-icon math-icon">=</div>
         <div class="result-info">
           <div class="result-name math-value">${escHtml(currentCalcResult.display)}</div>
-          <div class="result-path">${escHtml(currentCalcResult.expression)}</div>
+          ${detail}
         </div>
-        <span class="result-kind">CALC</span>
+        <span class="result-kind">${b ? "PROG" : "CALC"}</span>
       `;
       li.addEventListener("click", () => {
         // Copy result to clipboard
+        commitCalculation(currentCalcResult!.expression);
         navigator.clipboard.writeText(currentCalcResult!.display).catch(() => {});
       });
     }
@@ -187,10 +373,23 @@ function renderResults() {
     resultsList.appendChild(li);
   }
 
+  // ── Search error row (query syntax, content search disabled) ──
+  if (hasError) {
+    const li = document.createElement("li");
+    li.className = "result-item search-error";
+    li.innerHTML = `
+      <div class="result-icon">!</div>
+      <div class="result-info">
+        <div class="result-name">${escHtml(searchError!)}</div>
+      </div>
+    `;
+    resultsList.appendChild(li);
+  }
+
   // ── File / App result rows ─────────────────
   results.forEach((r, i) => {
     const li = document.createElement("li");
-    li.className = `result-item${i === selectedIndex ? " selected" : ""}`;
+    li.className = `result-item${i === selectedIndex ? " selected" : ""}${chatPicks.has(r.path) ? " picked" : ""}`;
     li.dataset.index = String(i);
 
     // Build name with matched character highlights
@@ -228,7 +427,9 @@ function renderResults() {
         <div class="result-path">${subtitle}</div>
       </div>
       <span class="result-kind">${r.kind}</span>
-      <span class="result-tab-hint">Tab to chat</span>
+      <span class="result-tab-hint">${
+        chatPicks.has(r.path) ? "picked" : chatPicks.size > 0 ? `Tab to chat (${chatPicks.size})` : "Tab to chat"
+      }</span>
     `;
 
     li.addEventListener("click", () => openResult(i));
@@ -242,7 +443,7 @@ function renderResults() {
 
   resultsContainer.classList.add("expanded");
 
-  const itemCount = results.length + (hasMath ? 1 : 0);
+  const itemCount = results.length + (hasMath ? 1 : 0) + (hasError ? 1 : 0);
   const contentHeight = BASE_HEIGHT + Math.min(itemCount * 50, 680) + 12;
   resizeWindow(contentHeight);
 }
@@ -422,16 +623,25 @@ function hideGraph() {
 
 async function doSearch(query: string) {
   if (!query || query.startsWith(">") || query.startsWith("?")) return;
+  const seq = ++searchSeq;
+  if (query.startsWith("#")) return doContentSearch(query.slice(1).trim(), seq);
 
   try {
-    // Run math evaluation and fuzzy search in parallel
+    // Run math evaluation and fuzzy search in parallel; query syntax errors come back as strings
     const [mathResult, searchResults] = await Promise.all([
       invoke<CalcResult | null>("evaluate_math", { query }).catch(() => null),
-      invoke<SearchResult[]>("search_files", { query }),
+      invoke<SearchResult[]>("search_files", { query }).catch((e) => String(e)),
     ]);
+    if (seq !== searchSeq) return;
 
     currentCalcResult = mathResult ?? null;
-    results = searchResults;
+    if (typeof searchResults === "string") {
+      results = [];
+      searchError = searchResults;
+    } else {
+      results = searchResults;
+      searchError = null;
+    }
     selectedIndex = 0;
     renderResults();
   } catch (e) {
@@ -439,14 +649,56 @@ async function doSearch(query: string) {
   }
 }
 
+/** Remember a calculation: its assignment or definition, `ans` and a history entry. */
+function commitCalculation(expression: string) {
+  invoke("record_calculation", { query: expression }).catch((e) =>
+    console.error("[trace] Calc history error:", e),
+  );
+}
+
+/** Full-text search over indexed document contents ("#" prefix). */
+async function doContentSearch(query: string, seq: number) {
+  currentCalcResult = null;
+  selectedIndex = 0;
+  if (!query) {
+    results = [];
+    searchError = null;
+    renderResults();
+    return;
+  }
+
+  try {
+    const matches = await invoke<ContentMatch[]>("search_content", { query });
+    if (seq !== searchSeq) return;
+    // Show the best matching line as the subtitle
+    results = matches.map((m) => ({
+      name: m.name,
+      path: m.path,
+      kind: "File",
+      score: m.score,
+      matched_indices: [],
+      generic_name: m.snippets.length > 0 ? `L${m.snippets[0].line}: ${m.snippets[0].text}` : null,
+    }));
+    searchError = null;
+  } catch (e) {
+    if (seq !== searchSeq) return;
+    results = [];
+    searchError = String(e);
+  }
+  renderResults();
+}
+
 // ─── Open / Launch ───────────────────────────
 
 async function openResult(index: number) {
   const r = results[index];
   if (!r) return;
 
+  // Content matches ("#") don't go through the ranked search, so they teach it nothing
+  const query = searchInput.value.startsWith("#") ? null : searchInput.value;
+
   try {
-    await invoke("open_result", { path: r.path, kind: r.kind });
+    await invoke("open_result", { path: r.path, kind: r.kind, query });
     // Hide window after opening
     const appWindow = getCurrentWindow();
     await appWindow.hide();
@@ -455,59 +707,414 @@ async function openResult(index: number) {
   }
 }
 
+/** Forget the launch history of a result so it stops being ranked up. */
+async function forgetResult(index: number) {
+  const r = results[index];
+  if (!r) return;
+
+  try {
+    await invoke("forget_result", { path: r.path });
+    doSearch(searchInput.value);
+  } catch (e) {
+    console.error("[trace] Forget error:", e);
+  }
+}
+
+// ─── LLM Streaming ───────────────────────────
+
+// Streamed replies arrive as "llm-delta" events tagged with the id we invoked with
+const streamHandlers = new Map<string, (delta: string) => void>();
+let streamCounter = 0;
+let activeStreamId: string | null = null;
+
+listen<{ stream_id: string; delta: string }>("llm-delta", (e) => {
+  streamHandlers.get(e.payload.stream_id)?.(e.payload.delta);
+});
+
+/** Run a streaming command, routing its deltas to `onDelta` until it settles. */
+async function withStream<T>(
+  onDelta: (delta: string) => void,
+  run: (streamId: string) => Promise<T>,
+): Promise<T> {
+  const streamId = `stream-${++streamCounter}`;
+  streamHandlers.set(streamId, onDelta);
+  activeStreamId = streamId;
+  try {
+    return await run(streamId);
+  } finally {
+    streamHandlers.delete(streamId);
+    if (activeStreamId === streamId) activeStreamId = null;
+  }
+}
+
+/** Stop the reply currently streaming in, if any. Returns true if there was one. */
+function cancelActiveStream(): boolean {
+  if (!activeStreamId) return false;
+  invoke("cancel_stream", { streamId: activeStreamId }).catch(() => {});
+  return true;
+}
+
 // ─── Shell Mode (NLP-to-Bash) ────────────────
 
 /** Apply or clear the danger state on the pending command panel. */
 function applyDangerState(t: ShellTranslation) {
   const btn = shellRun as HTMLButtonElement;
-  if (t.is_dangerous) {
+  // Low-severity findings (ordinary deletes, sudo) are not worth a warning
+  const findings = t.risk.findings.filter((f) => f.severity !== "low");
+  if (findings.length) {
+    const items = findings
+      .map((f) => {
+        const paths = f.paths.length
+          ? `<span class="warn-paths">${f.paths.map(escHtml).join(", ")}</span>`
+          : "";
+        return `<li><span class="warn-sev sev-${f.severity}">${f.severity}</span>${escHtml(f.reason)}${paths}</li>`;
+      })
+      .join("");
+    const note = t.is_dangerous
+      ? "Run button disabled — copy the command and run it manually if you are certain."
+      : "Review before running.";
     shellWarning.innerHTML =
       `<span class="warn-icon">⚠️</span>` +
-      `<span class="warn-title">Potentially destructive command</span>` +
-      `<span class="warn-body">${escHtml(t.danger_reason)}</span>` +
-      `<span class="warn-note">Run button disabled — copy the command and run it manually if you are certain.</span>`;
+      `<span class="warn-title">${t.is_dangerous ? "Potentially destructive command" : "Check this command"}</span>` +
+      `<ul class="warn-body">${items}</ul>` +
+      `<span class="warn-note">${note}</span>`;
     shellWarning.classList.remove("hidden");
-    btn.disabled = true;
-    btn.classList.add("btn-disabled");
-    shellCommand.classList.add("cmd-dangerous");
+    shellWarning.classList.toggle("warn-caution", !t.is_dangerous);
   } else {
     shellWarning.classList.add("hidden");
-    btn.disabled = false;
-    btn.classList.remove("btn-disabled");
-    shellCommand.classList.remove("cmd-dangerous");
   }
+  btn.disabled = t.is_dangerous;
+  btn.classList.toggle("btn-disabled", t.is_dangerous);
+  shellCommand.classList.toggle("cmd-dangerous", t.is_dangerous);
 }
 
 async function enterShellMode(input: string) {
+  openShellPanel();
+  await requestTranslation(input);
+}
+
+/** Load a remembered command from the launcher, without asking the LLM. */
+async function enterShellModeFromHistory(entry: HistoryEntry) {
+  openShellPanel();
+  pendingInput = entry.input;
+  try {
+    showTranslation(await invoke<ShellTranslation>("use_shell_history", { id: entry.id }));
+  } catch (e: any) {
+    shellCommand.textContent = `Error: ${e}`;
+  }
+}
+
+/** Switch to the terminal panel, waiting on a pending command. */
+function openShellPanel() {
   mode = "shell";
+  historyMatches = [];
   modeIndicator.textContent = "COMMAND";
   modeIndicator.classList.add("visible");
   resultsContainer.classList.remove("expanded");
   shellPanel.classList.remove("hidden");
   shellCommand.textContent = "Thinking...";
   shellWarning.classList.add("hidden");
+  shellCandidates.classList.add("hidden");
+  shellExplainPanel.classList.add("hidden");
   terminalPending.classList.remove("hidden");
   terminalNextRow.classList.add("hidden");
-  terminalTitle.textContent = `${shellContext.shell} — ${shellContext.username}@${shellContext.hostname}`;
+  terminalTitle.textContent = `${shellContext.shell} — ${promptLabel()}`;
   searchInput.value = "";
   searchInput.placeholder = "Press > to run another command, Esc to exit…";
 
   resizeWindow(300);
+}
 
+/**
+ * Stream a translation into the pending command panel, then apply its danger state.
+ * A request seen before comes back from the shell history unless `fresh`.
+ */
+async function requestTranslation(input: string, fresh = false) {
+  pendingInput = input;
+  pendingHistoryId = null;
+  shellHeader.textContent = "Generated Command";
+  shellRetranslate.classList.add("hidden");
+  updatePinButton();
+  let raw = "";
   try {
-    const translation = await invoke<ShellTranslation>("translate_command", { input });
-    shellCommand.textContent = translation.command;
-    applyDangerState(translation);
+    const translation = await withStream(
+      (delta) => {
+        raw += delta;
+        shellCommand.textContent = raw;
+      },
+      (streamId) => invoke<ShellTranslation>("translate_command", { input, streamId, fresh }),
+    );
+    showTranslation(translation);
   } catch (e: any) {
     shellCommand.textContent = `Error: ${e}`;
   }
 }
 
+function showTranslation(t: ShellTranslation) {
+  shellCommand.textContent = t.command;
+  applyDangerState(t);
+  renderCandidates(t.candidates);
+  pendingHistoryId = t.history_id;
+  pendingPinned = t.pinned;
+  shellHeader.textContent = t.cached ? "From History" : "Generated Command";
+  shellRetranslate.classList.toggle("hidden", !t.cached);
+  updatePinButton();
+}
+
+/** Why the chosen command failed its checks, and the model's other candidates. */
+function renderCandidates(candidates: ShellCandidate[]) {
+  const [chosen, ...others] = candidates;
+  if (!chosen || (!chosen.rejected && !others.length)) {
+    shellCandidates.classList.add("hidden");
+    return;
+  }
+  const failed = chosen.rejected
+    ? `<div class="cand-failed">No candidate passed the checks: ${escHtml(chosen.rejected)}</div>`
+    : "";
+  const items = others
+    .map((c, i) => {
+      const sev = c.risk.severity === "none" || c.risk.severity === "low"
+        ? ""
+        : `<span class="warn-sev sev-${c.risk.severity}">${c.risk.severity}</span>`;
+      const why = c.rejected ? `<span class="cand-why">${escHtml(c.rejected)}</span>` : "";
+      return `<li class="${c.rejected ? "cand-rejected" : ""}" data-index="${i + 1}" title="Use this command">` +
+        `${sev}<code>${escHtml(c.command)}</code>${why}</li>`;
+    })
+    .join("");
+  const list = others.length ? `<div class="ex-title">Alternatives</div><ul>${items}</ul>` : "";
+  shellCandidates.innerHTML = failed + list;
+  shellCandidates.querySelectorAll<HTMLElement>("li").forEach((li) => {
+    li.addEventListener("click", () => pickCandidate(candidates, Number(li.dataset.index)));
+  });
+  shellCandidates.classList.remove("hidden");
+}
+
+/** Make another candidate the pending command, keeping the rest as alternatives. */
+async function pickCandidate(candidates: ShellCandidate[], index: number) {
+  const picked = candidates[index];
+  if (!picked || activeRunId) return;
+  try {
+    const t = await invoke<ShellTranslation>("use_shell_candidate", { input: pendingInput, command: picked.command });
+    t.candidates = [picked, ...candidates.filter((_, i) => i !== index)];
+    shellExplainPanel.classList.add("hidden");
+    showTranslation(t);
+  } catch (e) {
+    console.error("[trace] Candidate error:", e);
+  }
+}
+
+function updatePinButton() {
+  shellPin.disabled = pendingHistoryId === null;
+  shellPin.textContent = pendingPinned ? "★ Pinned" : "☆ Pin";
+}
+
+async function togglePin() {
+  if (pendingHistoryId === null) return;
+  try {
+    await invoke("pin_shell_history", { id: pendingHistoryId, pinned: !pendingPinned });
+    pendingPinned = !pendingPinned;
+    updatePinButton();
+  } catch (e) {
+    console.error("[trace] Pin error:", e);
+  }
+}
+
+/** Ask the LLM again for a request that was answered from history. */
+function retranslate() {
+  if (activeStreamId || !pendingInput) return;
+  shellCommand.textContent = "Thinking...";
+  shellWarning.classList.add("hidden");
+  shellCandidates.classList.add("hidden");
+  shellExplainPanel.classList.add("hidden");
+  requestTranslation(pendingInput, true);
+}
+
+/** Save the shell history as a script; Shift-click saves only favorites. */
+async function exportShellHistory(e: MouseEvent) {
+  const note = document.createElement("div");
+  note.className = "t-status";
+  try {
+    const path = await invoke<string>("export_shell_history", { pinnedOnly: e.shiftKey });
+    note.textContent = `History saved to ${path}`;
+  } catch (err: any) {
+    note.textContent = `Export failed: ${err}`;
+  }
+  terminalHistory.appendChild(note);
+  terminalHistory.scrollTop = terminalHistory.scrollHeight;
+}
+
+// ─── Shell History in the Launcher ───────────
+
+/** Past requests matching what is typed after ">"; Enter on one reuses it. */
+async function searchShellHistory(query: string) {
+  const seq = ++searchSeq;
+  let matches: HistoryEntry[] = [];
+  try {
+    matches = await invoke<HistoryEntry[]>("search_shell_history", { query });
+  } catch (e) {
+    console.error("[trace] History search error:", e);
+  }
+  if (seq !== searchSeq) return;
+  historyMatches = matches;
+  // Nothing selected: Enter translates what was typed
+  selectedIndex = -1;
+  renderShellHistory();
+}
+
+function renderShellHistory() {
+  resultsList.innerHTML = "";
+  if (!historyMatches.length) {
+    resultsContainer.classList.remove("expanded");
+    resizeWindow(BASE_HEIGHT);
+    return;
+  }
+
+  historyMatches.forEach((h, i) => {
+    const li = document.createElement("li");
+    li.className = `result-item${i === selectedIndex ? " selected" : ""}`;
+    li.dataset.index = String(i);
+    const status = h.exit_code === null ? "" : h.exit_code === 0 ? " · ✓" : ` · exit ${h.exit_code}`;
+    li.innerHTML = `
+      <div class="result-icon">${h.pinned ? "★" : "$"}</div>
+      <div class="result-info">
+        <div class="result-name">${escHtml(h.input)}</div>
+        <div class="result-path history-command">${escHtml(h.command)}${status}</div>
+      </div>
+      <span class="result-kind">${h.pinned ? "Pinned" : "History"}</span>
+    `;
+    li.addEventListener("click", () => enterShellModeFromHistory(h));
+    resultsList.appendChild(li);
+  });
+
+  resultsContainer.classList.add("expanded");
+  resizeWindow(BASE_HEIGHT + Math.min(historyMatches.length * 50, 680) + 12);
+}
+
+async function forgetHistoryEntry(index: number) {
+  const entry = historyMatches[index];
+  if (!entry) return;
+  try {
+    await invoke("delete_shell_history", { id: entry.id });
+    searchShellHistory(searchInput.value.slice(1).trim());
+  } catch (e) {
+    console.error("[trace] Forget error:", e);
+  }
+}
+
+/** Toggle the step-by-step explanation and dry-run preview of the pending command. */
+async function toggleExplain() {
+  if (!shellExplainPanel.classList.contains("hidden")) {
+    shellExplainPanel.classList.add("hidden");
+    return;
+  }
+  const cmd = shellCommand.textContent || "";
+  if (!cmd || cmd.startsWith("Error:") || cmd === "Thinking..." || activeStreamId) return;
+
+  shellExplainPanel.textContent = "Explaining…";
+  shellExplainPanel.classList.remove("hidden");
+  resizeWindow(480);
+  try {
+    const [steps, actions] = await Promise.all([
+      invoke<ExplainStep[]>("explain_command", { command: cmd }),
+      invoke<PreviewAction[]>("preview_command", { command: cmd, cwd: terminalCwd }),
+    ]);
+    // The command may have been replaced while we waited
+    if (shellCommand.textContent !== cmd) return;
+    shellExplainPanel.innerHTML = renderSteps(steps) + renderPreview(actions);
+  } catch (e: any) {
+    shellExplainPanel.textContent = `${e}`;
+  }
+}
+
+function renderSteps(steps: ExplainStep[]): string {
+  return steps
+    .map((s) => {
+      const joined = s.joined ? `<div class="ex-joined">${escHtml(s.joined)}</div>` : "";
+      const flags = s.flags
+        .map((f) => `<li><code>${escHtml(f.flag)}</code>${f.meaning ? ` — ${escHtml(f.meaning)}` : ""}</li>`)
+        .join("");
+      const operands = s.operands.length
+        ? `<li class="ex-operands">on ${s.operands.map((o) => `<code>${escHtml(o)}</code>`).join(" ")}</li>`
+        : "";
+      const notes = s.notes.map((n) => `<li class="ex-note">${escHtml(n)}</li>`).join("");
+      return `<div class="ex-step" style="margin-left:${s.depth * 14}px">${joined}` +
+        `<div class="ex-head"><code>${escHtml(s.command)}</code><span>${escHtml(s.summary)}</span></div>` +
+        `<ul>${flags}${operands}${notes}</ul></div>`;
+    })
+    .join("");
+}
+
+function renderPreview(actions: PreviewAction[]): string {
+  if (!actions.length) return "";
+  const sections = actions.map((a) => {
+    const shown = a.paths
+      .map((p) => {
+        const inside = p.contents ? ` <span class="ex-count">+${p.contents} inside</span>` : "";
+        return `<li>${escHtml(p.path)}${p.is_dir ? "/" : ""}${inside}</li>`;
+      })
+      .join("");
+    const more = a.truncated ? `<li class="ex-note">…and more (listing stopped early)</li>` : "";
+    const missing = a.missing.length
+      ? `<li class="ex-note">Nothing matches ${a.missing.map(escHtml).join(", ")}</li>`
+      : "";
+    const notes = a.notes.map((n) => `<li class="ex-note">${escHtml(n)}</li>`).join("");
+    const dest = a.destination ? ` → ${escHtml(a.destination)}` : "";
+    const count = `${a.total} item${a.total === 1 ? "" : "s"}`;
+    return `<div class="ex-action"><div class="ex-head"><span>${escHtml(a.action)} ${count}${dest}</span>` +
+      `<code>${escHtml(a.command)}</code></div><ul>${shown}${more}${missing}${notes}</ul></div>`;
+  });
+  return `<div class="ex-preview"><div class="ex-title">Would touch</div>${sections.join("")}</div>`;
+}
+
+// Running commands stream their output as "shell-output" events tagged with the run id
+const runHandlers = new Map<string, (stream: "stdout" | "stderr", text: string) => void>();
+let runCounter = 0;
+let activeRunId: string | null = null;
+
+listen<{ run_id: string; stream: "stdout" | "stderr"; text: string }>("shell-output", (e) => {
+  runHandlers.get(e.payload.run_id)?.(e.payload.stream, e.payload.text);
+});
+
+/** user@host:dir, with the home folder shown as ~. */
+function promptLabel(): string {
+  const cwd = terminalCwd ?? shellContext.home;
+  const home = shellContext.home;
+  const dir = home && (cwd === home || cwd.startsWith(home + "/") || cwd.startsWith(home + "\\"))
+    ? "~" + cwd.slice(home.length)
+    : cwd || "~";
+  return `${shellContext.username}@${shellContext.hostname}:${dir}`;
+}
+
+function describeExit(output: ShellOutput): string {
+  const secs = output.duration_ms / 1000;
+  const took = secs < 1 ? `${output.duration_ms}ms` : `${secs.toFixed(1)}s`;
+  let text = output.timed_out
+    ? `Timed out after ${took} — killed`
+    : output.killed
+      ? `Killed after ${took}`
+      : `exit ${output.exit_code} · ${took}`;
+  if (output.truncated) text += " · output truncated (showing the first 1 MB)";
+  return text;
+}
+
+/** While a command runs, the next-command row feeds its stdin instead. */
+function setStdinMode(running: boolean) {
+  terminalNextInput.value = "";
+  terminalNextInput.placeholder = running
+    ? "Input for the running command — Ctrl+C kills it, Ctrl+D ends input"
+    : "Describe next command…";
+  (terminalNextRow.querySelector(".t-next-prompt") as HTMLElement).textContent = running ? "›" : "$";
+  terminalNextRow.classList.remove("hidden");
+  terminalNextInput.focus();
+}
+
 async function runShellCommand() {
   const cmd = shellCommand.textContent || "";
-  if (!cmd || cmd.startsWith("Error:") || cmd === "Thinking...") return;
+  // Never run a command that is still streaming in
+  if (!cmd || cmd.startsWith("Error:") || cmd === "Thinking..." || activeStreamId || activeRunId) return;
 
   terminalPending.classList.add("hidden");
+  shellExplainPanel.classList.add("hidden");
 
   const entryDiv = createTerminalEntryDiv(cmd);
   terminalHistory.appendChild(entryDiv);
@@ -516,31 +1123,77 @@ async function runShellCommand() {
   resizeWindow(480);
   terminalHistory.scrollTop = terminalHistory.scrollHeight;
 
+  const runId = `run-${++runCounter}`;
+  let streamed = false;
+  runHandlers.set(runId, (stream, text) => {
+    if (!streamed) outputEl.textContent = "";
+    streamed = true;
+    const span = document.createElement("span");
+    if (stream === "stderr") span.className = "t-stderr";
+    span.textContent = text;
+    outputEl.appendChild(span);
+    terminalHistory.scrollTop = terminalHistory.scrollHeight;
+  });
+  activeRunId = runId;
+  setStdinMode(true);
+
   try {
-    const output = await invoke<ShellOutput>("execute_shell", { command: cmd });
-    let text = "";
-    if (output.stdout) text += output.stdout;
-    if (output.stderr) text += (text ? "\n" : "") + output.stderr;
-    if (!text) text = `Process exited with code ${output.exit_code}`;
-    outputEl.textContent = text;
-    outputEl.classList.add(output.exit_code !== 0 ? "t-output-err" : "t-output-ok");
+    const output = await invoke<ShellOutput>("execute_shell", {
+      command: cmd,
+      runId,
+      cwd: terminalCwd,
+      historyId: pendingHistoryId,
+    });
+    terminalCwd = output.cwd;
+    if (!streamed) {
+      const text = output.stdout + output.stderr;
+      if (text) outputEl.textContent = text;
+      else outputEl.remove();
+    }
+    outputEl.classList.add(output.exit_code !== 0 || output.timed_out || output.killed ? "t-output-err" : "t-output-ok");
+    const status = document.createElement("div");
+    status.className = "t-status";
+    status.textContent = describeExit(output);
+    entryDiv.appendChild(status);
   } catch (e: any) {
     outputEl.textContent = `Error: ${e}`;
     outputEl.classList.add("t-output-err");
+  } finally {
+    runHandlers.delete(runId);
+    activeRunId = null;
   }
 
-  terminalNextRow.classList.remove("hidden");
-  terminalNextInput.value = "";
-  terminalNextInput.focus();
+  terminalTitle.textContent = `${shellContext.shell} — ${promptLabel()}`;
+  setStdinMode(false);
   terminalHistory.scrollTop = terminalHistory.scrollHeight;
 }
 
+/** Send a line typed into the terminal to the running command (echoed like a tty). */
+function sendStdin(line: string, close = false) {
+  if (!activeRunId) return;
+  const data = close ? line : line + "\n";
+  const outputEl = terminalHistory.lastElementChild?.querySelector(".t-output");
+  if (outputEl && data) {
+    const echo = document.createElement("span");
+    echo.className = "t-stdin";
+    echo.textContent = data;
+    outputEl.appendChild(echo);
+  }
+  invoke("write_shell_stdin", { runId: activeRunId, data, close }).catch((e) => console.error("[trace] stdin error:", e));
+}
+
+function killActiveRun(): boolean {
+  if (!activeRunId) return false;
+  invoke("kill_shell", { runId: activeRunId }).catch(() => {});
+  return true;
+}
+
 function createTerminalEntryDiv(cmd: string): HTMLElement {
   const div = document.createElement("div");
   div.className = "t-entry";
   div.innerHTML = `
     <div class="t-entry-cmd">
-      <span class="t-prompt-label">${escHtml(shellContext.username)}@${escHtml(shellContext.hostname)}</span><span class="t-prompt-sep"> $ </span><span class="t-cmd-text">${escHtml(cmd)}</span>
+      <span class="t-prompt-label">${escHtml(promptLabel())}</span><span class="t-prompt-sep"> $ </span><span class="t-cmd-text">${escHtml(cmd)}</span>
     </div>
     <pre class="t-output">Running…</pre>
   `;
@@ -552,17 +1205,15 @@ async function translateNextCommand(input: string) {
   terminalPending.classList.remove("hidden");
   shellCommand.textContent = "Thinking...";
   shellWarning.classList.add("hidden");
+  shellCandidates.classList.add("hidden");
+  shellExplainPanel.classList.add("hidden");
 
-  try {
-    const translation = await invoke<ShellTranslation>("translate_command", { input });
-    shellCommand.textContent = translation.command;
-    applyDangerState(translation);
-  } catch (e: any) {
-    shellCommand.textContent = `Error: ${e}`;
-  }
+  await requestTranslation(input);
 }
 
 function exitShellMode() {
+  cancelActiveStream();
+  killActiveRun();
   mode = "search";
   modeIndicator.classList.remove("visible");
   shellPanel.classList.add("hidden");
@@ -575,50 +1226,123 @@ function exitShellMode() {
 
 // ─── Chat Mode ───────────────────────────────
 
-async function enterChatMode(index: number) {
+/** Shift+Tab: add the result to (or drop it from) the next multi-file chat. */
+function toggleChatPick(index: number) {
   const r = results[index];
   if (!r || r.kind === "App") return;
+  if (!chatPicks.delete(r.path)) chatPicks.add(r.path);
+  renderResults();
+}
+
+async function enterChatMode(index: number) {
+  const r = results[index];
+  const paths = chatPicks.size > 0 ? [...chatPicks] : r && r.kind !== "App" ? [r.path] : [];
+  if (paths.length === 0) return;
+  chatPicks.clear();
 
-  const fileName = r.path.split(/[\/\\]/).pop() || r.name;
+  const title = paths.length === 1 ? paths[0].split(/[\/\\]/).pop() || paths[0] : `${paths.length} items`;
 
   // Open the chat panel immediately so errors are visible
   mode = "chat";
-  chatFilename.textContent = fileName;
+  chatFilename.textContent = title;
   modeIndicator.textContent = "CHAT";
   modeIndicator.classList.add("visible");
   resultsContainer.classList.remove("expanded");
   chatPanel.classList.remove("hidden");
   chatMessages.innerHTML = "";
   searchInput.value = "";
-  searchInput.placeholder = `Ask about ${fileName}...`;
+  searchInput.placeholder = `Ask about ${title}...`;
   resizeWindow(580);
 
   try {
-    const preview = await invoke<string>("enter_chat_mode", { path: r.path });
+    const preview = await invoke<string>("enter_chat_mode", { paths });
     addChatMessage("assistant", preview);
+    // Reopening the last chatted-about selection resumes its conversation
+    const history = await invoke<ChatMessage[]>("get_chat_history");
+    history.forEach((m) => addChatMessage(m.role, m.content));
   } catch (e: any) {
-    addChatMessage("assistant", `⚠️ Could not load file: ${e}`);
+    addChatMessage("assistant", `⚠️ Could not load ${title}: ${e}`);
   }
 }
 
 async function sendChatMessage(question: string) {
-  if (!question.trim()) return;
+  if (!question.trim() || activeStreamId) return;
 
   addChatMessage("user", question);
   searchInput.value = "";
   chatLoading.classList.remove("hidden");
 
+  // The answer bubble appears with the first streamed token
+  let partial = "";
+  let bubble: HTMLElement | null = null;
+  const show = (text: string): HTMLElement => {
+    chatLoading.classList.add("hidden");
+    if (bubble) {
+      bubble.innerHTML = renderMarkdown(text);
+      chatMessages.scrollTop = chatMessages.scrollHeight;
+    } else {
+      bubble = addChatMessage("assistant", text);
+    }
+    return bubble;
+  };
+
   try {
-    const answer = await invoke<string>("chat_message", { question });
-    addChatMessage("assistant", answer);
+    const answer = await withStream(
+      (delta) => {
+        partial += delta;
+        show(partial);
+      },
+      (streamId) => invoke<ChatReply>("chat_message", { question, streamId }),
+    );
+    const shown = show(answer.answer);
+    if (answer.sources.length > 0) renderSources(shown, answer.sources);
   } catch (e: any) {
-    addChatMessage("assistant", `Error: ${e}`);
+    show(String(e) === "Cancelled" && partial ? `${partial}\n\n*(stopped)*` : `Error: ${e}`);
   } finally {
     chatLoading.classList.add("hidden");
   }
 }
 
-function addChatMessage(role: "user" | "assistant", text: string) {
+/** List the excerpts a long-file answer was given, highlighting the ones it cited. */
+function renderSources(bubble: HTMLElement, sources: Citation[]) {
+  const div = document.createElement("div");
+  div.className = "chat-sources";
+  div.append("Sources: ");
+  sources.forEach((c, i) => {
+    const span = document.createElement("span");
+    if (c.cited) span.className = "cited";
+    let where = c.first_line === c.last_line ? `line ${c.first_line}` : `lines ${c.first_line}–${c.last_line}`;
+    if (c.pages) where += c.pages[0] === c.pages[1] ? `, p. ${c.pages[0]}` : `, pp. ${c.pages[0]}–${c.pages[1]}`;
+    span.textContent = `[${c.label}] ${where}`;
+    if (i > 0) div.append(" · ");
+    div.appendChild(span);
+  });
+  bubble.appendChild(div);
+}
+
+async function clearChatHistory() {
+  if (activeStreamId) return;
+  try {
+    await invoke("clear_chat_history");
+    // Keep the file preview, drop the exchanges
+    Array.from(chatMessages.children)
+      .slice(1)
+      .forEach((el) => el.remove());
+  } catch (e: any) {
+    addChatMessage("assistant", `Error: ${e}`);
+  }
+}
+
+async function exportChatHistory() {
+  try {
+    const path = await invoke<string>("export_chat_history", {});
+    addChatMessage("assistant", `Conversation saved to \`${path}\``);
+  } catch (e: any) {
+    addChatMessage("assistant", `⚠️ ${e}`);
+  }
+}
+
+function addChatMessage(role: "user" | "assistant", text: string): HTMLElement {
   const div = document.createElement("div");
   div.className = `chat-msg ${role}`;
   if (role === "assistant") {
@@ -628,6 +1352,7 @@ function addChatMessage(role: "user" | "assistant", text: string) {
   }
   chatMessages.appendChild(div);
   chatMessages.scrollTop = chatMessages.scrollHeight;
+  return div;
 }
 
 /** Convert a subset of Markdown to safe HTML for LLM response display.
@@ -731,6 +1456,7 @@ function renderMarkdown(raw: string): string {
 }
 
 async function exitChatMode() {
+  cancelActiveStream();
   mode = "search";
   modeIndicator.classList.remove("visible");
   chatPanel.classList.add("hidden");
@@ -747,10 +1473,9 @@ async function exitChatMode() {
 
 // ─── Settings ────────────────────────────────
 
-function populateModels(provider: string, currentModel?: string) {
+function fillModelSelect(models: { value: string; label: string }[], currentModel?: string) {
   const modelSelect = document.getElementById("setting-model") as HTMLSelectElement;
   modelSelect.innerHTML = "";
-  const models = PROVIDER_MODELS[provider] || [];
   models.forEach((m) => {
     const opt = document.createElement("option");
     opt.value = m.value;
@@ -765,36 +1490,178 @@ function populateModels(provider: string, currentModel?: string) {
   }
 }
 
+// Bumped per model listing; a slow endpoint can't overwrite a newer list
+let modelListSeq = 0;
+
+async function populateModels(provider: string, currentModel?: string) {
+  if (!LOCAL_PROVIDERS.includes(provider)) {
+    fillModelSelect(PROVIDER_MODELS[provider] || [], currentModel);
+    return;
+  }
+
+  const seq = ++modelListSeq;
+  const saved = currentModel ? [{ value: currentModel, label: currentModel }] : [];
+  fillModelSelect(saved, currentModel);
+
+  const baseUrl = (
+    document.getElementById(provider === "ollama" ? "setting-ollama-url" : "setting-custom-url") as HTMLInputElement
+  ).value;
+  const apiKey =
+    provider === "custom" ? (document.getElementById("setting-custom-key") as HTMLInputElement).value || null : null;
+
+  try {
+    const names = await invoke<string[]>("list_models", { provider, baseUrl, apiKey });
+    if (seq !== modelListSeq) return;
+    if (names.length === 0) {
+      fillModelSelect(saved, currentModel);
+      return;
+    }
+    fillModelSelect(
+      names.map((n) => ({ value: n, label: n })),
+      currentModel
+    );
+  } catch (e) {
+    if (seq !== modelListSeq) return;
+    console.error("[trace] Model list error:", e);
+    // Keep the saved model usable while the server is unreachable
+    fillModelSelect(saved, currentModel);
+    const modelSelect = document.getElementById("setting-model") as HTMLSelectElement;
+    const opt = document.createElement("option");
+    opt.disabled = true;
+    opt.textContent = `Could not list models: ${e}`;
+    modelSelect.appendChild(opt);
+  }
+}
+
+// Last settings loaded from the backend — fields the panel doesn't edit are saved back as-is
+let loadedSettings: Settings | null = null;
+let vaultStatus: VaultStatus | null = null;
+
+// Key inputs start empty; a saved key shows only as its masked placeholder
+function renderKeys(apiKeys: Record<string, string>) {
+  for (const [provider, id] of Object.entries(KEY_INPUTS)) {
+    const input = document.getElementById(id) as HTMLInputElement;
+    input.dataset.placeholder ??= input.placeholder;
+    input.value = "";
+    delete input.dataset.cleared;
+    const masked = apiKeys[provider];
+    input.placeholder = masked ? `${masked} (saved — type to replace)` : input.dataset.placeholder;
+    document.querySelector(`.key-clear[data-key="${id}"]`)?.classList.toggle("hidden", !masked);
+  }
+}
+
+function renderVault(status: VaultStatus, error?: string) {
+  vaultStatus = status;
+  const statusEl = document.getElementById("vault-status") as HTMLElement;
+  const select = document.getElementById("vault-protection") as HTMLSelectElement;
+  const passphrase = document.getElementById("vault-passphrase") as HTMLInputElement;
+  const apply = document.getElementById("vault-apply") as HTMLButtonElement;
+
+  (select.querySelector('option[value="keyring"]') as HTMLOptionElement).disabled = !status.keyring_available;
+  select.value = status.protection;
+  select.disabled = status.locked;
+  passphrase.value = "";
+  passphrase.placeholder = status.locked ? "vault passphrase" : "new passphrase (8+ characters)";
+  apply.textContent = status.locked ? "Unlock" : "Apply";
+  updateVaultInputs();
+
+  const source = {
+    keyring: "a secret in the OS keyring",
+    keyfile: "a keyfile in the config folder",
+    passphrase: "your passphrase",
+  }[status.protection];
+  statusEl.classList.toggle("locked", status.locked);
+  statusEl.textContent =
+    error ??
+    (status.locked
+      ? status.protection === "passphrase"
+        ? "Locked — enter the passphrase to use your saved keys."
+        : `Locked — ${source} no longer opens the vault.`
+      : `Keys are encrypted with ${source}.`);
+}
+
+// The passphrase field is only needed to unlock or to switch to passphrase protection
+function updateVaultInputs() {
+  const select = document.getElementById("vault-protection") as HTMLSelectElement;
+  const needed = vaultStatus?.locked ? vaultStatus.protection === "passphrase" : select.value === "passphrase";
+  document.getElementById("vault-passphrase")!.classList.toggle("hidden", !needed);
+}
+
+async function applyVault() {
+  if (!vaultStatus) return;
+  const passphrase = (document.getElementById("vault-passphrase") as HTMLInputElement).value;
+  const protection = (document.getElementById("vault-protection") as HTMLSelectElement).value as VaultProtection;
+  try {
+    const status = vaultStatus.locked
+      ? await invoke<VaultStatus>("unlock_vault", { passphrase })
+      : await invoke<VaultStatus>("set_vault_protection", { protection, passphrase: passphrase || null });
+    const view = await invoke<SettingsView>("get_settings");
+    renderKeys(view.api_keys);
+    renderVault(status);
+  } catch (e) {
+    renderVault(vaultStatus, String(e));
+  }
+}
+
 async function openSettings() {
   settingsOverlay.classList.remove("hidden");
   resizeWindow(600);
 
   try {
-    const s = await invoke<Settings>("get_settings");
+    const s = await invoke<SettingsView>("get_settings");
+    loadedSettings = s;
     const providerSelect = document.getElementById("setting-provider") as HTMLSelectElement;
     providerSelect.value = s.active_provider;
+    (document.getElementById("setting-ollama-url") as HTMLInputElement).value = s.ollama_base_url;
+    (document.getElementById("setting-custom-url") as HTMLInputElement).value = s.custom_base_url;
+    renderKeys(s.api_keys);
+    renderVault(s.vault);
     populateModels(s.active_provider, s.active_model);
-    (document.getElementById("setting-openai") as HTMLInputElement).value = s.openai_key || "";
-    (document.getElementById("setting-anthropic") as HTMLInputElement).value = s.anthropic_key || "";
-    (document.getElementById("setting-google") as HTMLInputElement).value = s.google_key || "";
-    (document.getElementById("setting-huggingface") as HTMLInputElement).value = s.huggingface_key || "";
-    (document.getElementById("setting-openrouter") as HTMLInputElement).value = s.openrouter_key || "";
+    (document.getElementById("setting-embedding-provider") as HTMLSelectElement).value = s.embedding_provider;
+    (document.getElementById("setting-embedding-model") as HTMLInputElement).value = s.embedding_model;
+    (document.getElementById("setting-content-index") as HTMLSelectElement).value =
+      s.content_index_enabled ? "on" : "off";
+    (document.getElementById("setting-shell-timeout") as HTMLInputElement).value = String(s.shell_timeout_secs);
+    (document.getElementById("setting-shell-candidates") as HTMLInputElement).value = String(s.shell_candidates);
   } catch (e) {
     console.error("[trace] Settings load error:", e);
   }
 }
 
+// A typed key replaces the saved one, a cleared one is removed, otherwise it's kept
+function keyValue(id: string): string | null {
+  const input = document.getElementById(id) as HTMLInputElement;
+  if (input.value) return input.value;
+  return input.dataset.cleared ? "" : null;
+}
+
 async function saveSettings() {
   const newSettings: Settings = {
     active_provider: (document.getElementById("setting-provider") as HTMLSelectElement).value,
     active_model: (document.getElementById("setting-model") as HTMLSelectElement).value,
-    openai_key: (document.getElementById("setting-openai") as HTMLInputElement).value || null,
-    anthropic_key: (document.getElementById("setting-anthropic") as HTMLInputElement).value || null,
-    google_key: (document.getElementById("setting-google") as HTMLInputElement).value || null,
-    huggingface_key: (document.getElementById("setting-huggingface") as HTMLInputElement).value || null,
-    openrouter_key: (document.getElementById("setting-openrouter") as HTMLInputElement).value || null,
-    index_roots: ["~"], // default
-    max_results: 20,
+    embedding_provider: (document.getElementById("setting-embedding-provider") as HTMLSelectElement).value,
+    embedding_model: (document.getElementById("setting-embedding-model") as HTMLInputElement).value.trim(),
+    openai_key: keyValue(KEY_INPUTS.openai),
+    anthropic_key: keyValue(KEY_INPUTS.anthropic),
+    google_key: keyValue(KEY_INPUTS.google),
+    huggingface_key: keyValue(KEY_INPUTS.huggingface),
+    openrouter_key: keyValue(KEY_INPUTS.openrouter),
+    custom_key: keyValue(KEY_INPUTS.custom),
+    ollama_base_url: (document.getElementById("setting-ollama-url") as HTMLInputElement).value.trim() || "http://localhost:11434",
+    custom_base_url: (document.getElementById("setting-custom-url") as HTMLInputElement).value.trim() || "http://localhost:8080/v1",
+    index_roots: loadedSettings?.index_roots ?? ["~"],
+    index_filters: loadedSettings?.index_filters ?? {},
+    max_results: loadedSettings?.max_results ?? 20,
+    content_index_enabled:
+      (document.getElementById("setting-content-index") as HTMLSelectElement).value === "on",
+    shell_timeout_secs: Math.max(
+      0,
+      Math.floor(Number((document.getElementById("setting-shell-timeout") as HTMLInputElement).value) || 0)
+    ),
+    shell_candidates: Math.min(
+      5,
+      Math.max(1, Math.floor(Number((document.getElementById("setting-shell-candidates") as HTMLInputElement).value) || 1))
+    ),
   };
 
   try {
@@ -802,6 +1669,7 @@ async function saveSettings() {
     closeSettings();
   } catch (e) {
     console.error("[trace] Settings save error:", e);
+    if (vaultStatus) renderVault(vaultStatus, String(e));
   }
 }
 
@@ -821,15 +1689,17 @@ searchInput.addEventListener("input", () => {
   if (mode === "chat") return; // Don't search in chat mode
 
   if (val.startsWith(">")) {
-    // Shell mode prefix detected — wait for Enter
+    // Shell mode prefix detected — list matching history until Enter
     modeIndicator.textContent = "COMMAND";
     modeIndicator.classList.add("visible");
-    resultsContainer.classList.remove("expanded");
+    searchTimeout = setTimeout(() => searchShellHistory(val.slice(1).trim()), 30);
     return;
   }
+  historyMatches = [];
 
   if (val === "") {
     results = [];
+    searchError = null;
     currentCalcResult = null;
     selectedIndex = 0;
     renderResults();
@@ -846,7 +1716,11 @@ searchInput.addEventListener("keydown", (e: KeyboardEvent) => {
   switch (e.key) {
     case "ArrowDown":
       e.preventDefault();
-      if (mode === "search" && (results.length > 0 || currentCalcResult)) {
+      if (mode === "search" && searchInput.value.startsWith(">")) {
+        selectedIndex = Math.min(selectedIndex + 1, historyMatches.length - 1);
+        renderShellHistory();
+        scrollSelectedIntoView();
+      } else if (mode === "search" && (results.length > 0 || currentCalcResult)) {
         selectedIndex = Math.min(selectedIndex + 1, results.length - 1);
         renderResults();
         scrollSelectedIntoView();
@@ -855,7 +1729,11 @@ searchInput.addEventListener("keydown", (e: KeyboardEvent) => {
 
     case "ArrowUp":
       e.preventDefault();
-      if (mode === "search" && (results.length > 0 || currentCalcResult)) {
+      if (mode === "search" && searchInput.value.startsWith(">")) {
+        selectedIndex = Math.max(selectedIndex - 1, -1);
+        renderShellHistory();
+        scrollSelectedIntoView();
+      } else if (mode === "search" && (results.length > 0 || currentCalcResult)) {
         const minIdx = currentCalcResult ? -1 : 0;
         selectedIndex = Math.max(selectedIndex - 1, minIdx);
         renderResults();
@@ -867,14 +1745,20 @@ searchInput.addEventListener("keydown", (e: KeyboardEvent) => {
       e.preventDefault();
       if (mode === "search") {
         const val = searchInput.value;
-        if (val.startsWith(">")) {
+        if (val.startsWith(">") && historyMatches[selectedIndex]) {
+          enterShellModeFromHistory(historyMatches[selectedIndex]);
+        } else if (val.startsWith(">")) {
           enterShellMode(val.slice(1).trim());
         } else if (currentCalcResult?.has_variable && selectedIndex === -1) {
           // Graph mode: plot the equation
+          commitCalculation(currentCalcResult.expression);
           showGraph(currentCalcResult.expression);
         } else if (currentCalcResult && !currentCalcResult.has_variable && selectedIndex === -1) {
-          // Copy calc result to clipboard
-          navigator.clipboard.writeText(currentCalcResult.display).catch(() => {});
+          // Copy calc result to clipboard (nothing to copy from an error)
+          if (!currentCalcResult.error) {
+            commitCalculation(currentCalcResult.expression);
+            navigator.clipboard.writeText(currentCalcResult.display).catch(() => {});
+          }
         } else if (results.length > 0) {
           openResult(selectedIndex);
         }
@@ -892,14 +1776,28 @@ searchInput.addEventListener("keydown", (e: KeyboardEvent) => {
     case "Tab":
       e.preventDefault();
       if (mode === "search" && results.length > 0) {
-        enterChatMode(selectedIndex);
+        if (e.shiftKey) toggleChatPick(selectedIndex);
+        else enterChatMode(selectedIndex);
+      }
+      break;
+
+    case "Delete":
+      // Shift+Delete: forget the selected history entry, or stop ranking the
+      // selected result by past launches
+      if (e.shiftKey && mode === "search" && searchInput.value.startsWith(">")) {
+        e.preventDefault();
+        forgetHistoryEntry(selectedIndex);
+      } else if (e.shiftKey && mode === "search" && selectedIndex >= 0 && results.length > 0) {
+        e.preventDefault();
+        forgetResult(selectedIndex);
       }
       break;
 
     case "Escape":
       e.preventDefault();
       if (mode === "chat") {
-        exitChatMode();
+        // First Escape stops a streaming answer; the next one leaves chat
+        if (!cancelActiveStream()) exitChatMode();
       } else if (mode === "shell") {
         exitShellMode();
       } else {
@@ -912,11 +1810,26 @@ searchInput.addEventListener("keydown", (e: KeyboardEvent) => {
 
 // Shell panel buttons
 shellRun.addEventListener("click", runShellCommand);
+shellExplain.addEventListener("click", toggleExplain);
+shellPin.addEventListener("click", togglePin);
+shellRetranslate.addEventListener("click", retranslate);
+shellExport.addEventListener("click", exportShellHistory);
 shellCancel.addEventListener("click", exitShellMode);
 
 // Terminal inline next-command input
 terminalNextInput.addEventListener("keydown", (e: KeyboardEvent) => {
-  if (e.key === "Enter") {
+  if (activeRunId && e.ctrlKey && (e.key === "c" || e.key === "d")) {
+    e.preventDefault();
+    if (e.key === "c") killActiveRun();
+    else {
+      sendStdin(terminalNextInput.value, true);
+      terminalNextInput.value = "";
+    }
+  } else if (activeRunId && e.key === "Enter") {
+    e.preventDefault();
+    sendStdin(terminalNextInput.value);
+    terminalNextInput.value = "";
+  } else if (e.key === "Enter") {
     const input = terminalNextInput.value.trim();
     if (!input) return;
     e.preventDefault();
@@ -929,11 +1842,31 @@ terminalNextInput.addEventListener("keydown", (e: KeyboardEvent) => {
 
 // Chat close button
 chatClose.addEventListener("click", exitChatMode);
+document.getElementById("chat-clear")!.addEventListener("click", clearChatHistory);
+document.getElementById("chat-export")!.addEventListener("click", exportChatHistory);
 
 // Settings buttons
 settingsBtn.addEventListener("click", openSettings);
 settingsSave.addEventListener("click", saveSettings);
 settingsCloseBtn.addEventListener("click", closeSettings);
+document.getElementById("vault-apply")!.addEventListener("click", applyVault);
+document.getElementById("vault-protection")!.addEventListener("change", updateVaultInputs);
+document.querySelectorAll<HTMLButtonElement>(".key-clear").forEach((button) => {
+  button.addEventListener("click", () => {
+    const input = document.getElementById(button.dataset.key!) as HTMLInputElement;
+    input.value = "";
+    input.dataset.cleared = "1";
+    input.placeholder = "removed when you save";
+    button.classList.add("hidden");
+  });
+});
+document.getElementById("settings-reset-ranking")!.addEventListener("click", async () => {
+  try {
+    await invoke("reset_frecency");
+  } catch (e) {
+    console.error("[trace] Reset ranking error:", e);
+  }
+});
 
 // Provider change → update available models
 document.getElementById("setting-provider")!.addEventListener("change", (e) => {
@@ -941,6 +1874,19 @@ document.getElementById("setting-provider")!.addEventListener("change", (e) => {
   populateModels(provider);
 });
 
+// Endpoint edits → re-list models if that local provider is selected
+for (const [inputId, provider] of [
+  ["setting-ollama-url", "ollama"],
+  ["setting-custom-url", "custom"],
+  ["setting-custom-key", "custom"],
+]) {
+  document.getElementById(inputId)!.addEventListener("change", () => {
+    const active = (document.getElementById("setting-provider") as HTMLSelectElement).value;
+    if (active !== provider) return;
+    populateModels(provider, (document.getElementById("setting-model") as HTMLSelectElement).value);
+  });
+}
+
 // ─── Init ────────────────────────────────────
 
 document.addEventListener("DOMContentLoaded", () => {